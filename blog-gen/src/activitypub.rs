@@ -0,0 +1,116 @@
+//! The site as a read-only ActivityPub actor: `@user@host` on the fediverse,
+//! whose outbox holds every published post. jatai serves the three documents
+//! at their ids with the media types they need (see its `activitypub`
+//! module).
+//!
+//! The actor names an inbox, as ActivityPub requires, but nothing is served
+//! there: the site publishes and never follows, replies or accepts follows.
+
+use serde_json::{json, Value};
+
+use crate::{feed::absolutize, slugify, tag_path, Post, Site};
+
+pub(crate) const ACTOR: &str = "activitypub/actor.json";
+pub(crate) const OUTBOX: &str = "activitypub/outbox.json";
+pub(crate) const WEBFINGER: &str = ".well-known/webfinger.json";
+
+const CONTEXT: &str = "https://www.w3.org/ns/activitystreams";
+const PUBLIC: &str = "https://www.w3.org/ns/activitystreams#Public";
+
+fn actor_id(site: &Site) -> String {
+    site.url("/activitypub/actor")
+}
+
+/// The actor, named `username`.
+pub(crate) fn actor(site: &Site, username: &str) -> String {
+    let actor = json!({
+        "@context": CONTEXT,
+        "id": actor_id(site),
+        "type": "Person",
+        "preferredUsername": username,
+        "name": site.author,
+        "summary": site.description,
+        "url": site.url("/"),
+        "icon": { "type": "Image", "url": site.og_image_url() },
+        "inbox": site.url("/activitypub/inbox"),
+        "outbox": site.url("/activitypub/outbox"),
+    });
+    pretty(&actor)
+}
+
+/// The actor's outbox: a `Create` of an `Article` for each of `posts`, in
+/// the order given.
+pub(crate) fn outbox(site: &Site, posts: &[&Post]) -> String {
+    let actor = actor_id(site);
+    let items: Vec<Value> = posts
+        .iter()
+        .map(|p| {
+            let url = site.url(&p.path());
+            let tags: Vec<Value> = p
+                .tags
+                .iter()
+                .map(|name| {
+                    json!({
+                        "type": "Hashtag",
+                        "name": format!("#{}", slugify(name).replace('-', "")),
+                        "href": site.url(&tag_path(&slugify(name))),
+                    })
+                })
+                .collect();
+            let mut article = json!({
+                "id": url,
+                "type": "Article",
+                "attributedTo": actor,
+                "name": p.title,
+                "summary": p.description,
+                "content": absolutize(site, &p.html, &p.path()),
+                "url": url,
+                "published": p.date.rfc3339(),
+                "to": [PUBLIC],
+                "tag": tags,
+            });
+            if let Some(updated) = p.updated {
+                article["updated"] = json!(updated.rfc3339());
+            }
+            json!({
+                "id": format!("{url}#create"),
+                "type": "Create",
+                "actor": actor,
+                "published": p.date.rfc3339(),
+                "to": [PUBLIC],
+                "object": article,
+            })
+        })
+        .collect();
+    let outbox = json!({
+        "@context": CONTEXT,
+        "id": site.url("/activitypub/outbox"),
+        "type": "OrderedCollection",
+        "totalItems": items.len(),
+        "orderedItems": items,
+    });
+    pretty(&outbox)
+}
+
+/// The WebFinger answer for `acct:username@host`, pointing at the actor.
+pub(crate) fn webfinger(site: &Site, username: &str) -> String {
+    let host = site
+        .base_url
+        .split_once("://")
+        .map_or(site.base_url.as_str(), |(_, host)| host);
+    let jrd = json!({
+        "subject": format!("acct:{username}@{host}"),
+        "aliases": [actor_id(site), site.url("/")],
+        "links": [
+            { "rel": "self", "type": "application/activity+json", "href": actor_id(site) },
+            { "rel": "http://webfinger.net/rel/profile-page", "type": "text/html", "href": site.url("/") },
+        ],
+    });
+    pretty(&jrd)
+}
+
+fn pretty(value: &Value) -> String {
+    let mut json = serde_json::to_string_pretty(value).expect("JSON always serializes");
+    json.push('\n');
+    json
+}
@@ -0,0 +1,216 @@
+//! The bookshelf on `/books`, read from `content/books.toml`:
+//!
+//! ```toml
+//! [[book]]
+//! title = "Crafting Interpreters"
+//! authors = ["Robert Nystrom"]
+//! format = "book"
+//! section = "Technical"
+//! cover = "/covers/crafting-interpreters.jpg"
+//! color = "#3a4a3a"
+//! status = "reading"              # optional; "read" when left out
+//! review = "/blog/lox-in-rust/"   # optional
+//! ```
+//!
+//! Books being read go on their own shelf first; the rest are shelved by
+//! section, sections in the order they first appear in the file.
+
+use std::{fs, path::Path};
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+/// Where covers are served from, and found under `static/`.
+const COVERS: &str = "/covers/";
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Shelf {
+    #[serde(default)]
+    book: Vec<Book>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Book {
+    title: String,
+    authors: Vec<String>,
+    format: String,
+    section: String,
+    cover: String,
+    color: String,
+    #[serde(default)]
+    status: Status,
+    review: Option<String>,
+}
+
+#[derive(Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Reading,
+    #[default]
+    Read,
+}
+
+/// The shelves in `path` as `books.html` lists them, each `{name, books}`.
+/// A missing file is an empty bookshelf. Every cover must be a file under
+/// `static_dir`'s `covers/`, and no title may be listed twice.
+pub(crate) fn load(path: &Path, static_dir: &Path) -> Result<Vec<Value>, String> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("read {}: {e}", path.display())),
+    };
+    let shelf: Shelf = toml::from_str(&text).map_err(|e| format!("{}: {e}", path.display()))?;
+    let at = |book: &Book, e: String| format!("{}: {:?}: {e}", path.display(), book.title);
+
+    let mut titles: Vec<String> = Vec::new();
+    for book in &shelf.book {
+        let title = book.title.trim().to_lowercase();
+        if titles.contains(&title) {
+            return Err(at(book, "listed twice".to_string()));
+        }
+        titles.push(title);
+        check(book, static_dir).map_err(|e| at(book, e))?;
+    }
+
+    let mut shelves: Vec<(String, Vec<Value>)> = Vec::new();
+    let reading: Vec<Value> = shelf
+        .book
+        .iter()
+        .filter(|b| b.status == Status::Reading)
+        .map(context)
+        .collect();
+    if !reading.is_empty() {
+        shelves.push(("Currently Reading".to_string(), reading));
+    }
+    for book in shelf.book.iter().filter(|b| b.status == Status::Read) {
+        match shelves.iter_mut().find(|(name, _)| *name == book.section) {
+            Some((_, books)) => books.push(context(book)),
+            None => shelves.push((book.section.clone(), vec![context(book)])),
+        }
+    }
+    Ok(shelves
+        .into_iter()
+        .map(|(name, books)| json!({ "name": name, "books": books }))
+        .collect())
+}
+
+fn check(book: &Book, static_dir: &Path) -> Result<(), String> {
+    if book.authors.is_empty() {
+        return Err("no authors".to_string());
+    }
+    let Some(file) = book.cover.strip_prefix(COVERS) else {
+        return Err(format!("cover {:?} is not under {COVERS}", book.cover));
+    };
+    if file.split('/').any(|s| s.is_empty() || s == "..") {
+        return Err(format!("cover {:?} is not under {COVERS}", book.cover));
+    }
+    if !static_dir
+        .join(COVERS.trim_matches('/'))
+        .join(file)
+        .is_file()
+    {
+        return Err(format!("no cover at static{}", book.cover));
+    }
+    let hex = book.color.strip_prefix('#').unwrap_or_default();
+    if !matches!(hex.len(), 3 | 6) || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("color {:?} is not #rgb or #rrggbb", book.color));
+    }
+    Ok(())
+}
+
+fn context(book: &Book) -> Value {
+    json!({
+        "title": book.title,
+        "authors": credit(&book.authors),
+        "format": book.format,
+        "cover": book.cover,
+        "color": book.color,
+        "review": book.review,
+    })
+}
+
+/// "A", "A & B", "A, B & C".
+fn credit(authors: &[String]) -> String {
+    match authors {
+        [] => String::new(),
+        [only] => only.clone(),
+        [rest @ .., last] => format!("{} & {last}", rest.join(", ")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shelves(toml: &str) -> Result<Vec<Value>, String> {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("static/covers")).unwrap();
+        fs::write(dir.path().join("static/covers/a.jpg"), "").unwrap();
+        fs::write(dir.path().join("books.toml"), toml).unwrap();
+        load(&dir.path().join("books.toml"), &dir.path().join("static"))
+    }
+
+    fn book(title: &str, section: &str, extra: &str) -> String {
+        format!(
+            "[[book]]\ntitle = {title:?}\nauthors = [\"X\"]\nformat = \"book\"\n\
+             section = {section:?}\ncover = \"/covers/a.jpg\"\ncolor = \"#123456\"\n{extra}\n"
+        )
+    }
+
+    #[test]
+    fn books_being_read_come_first_then_sections_in_file_order() {
+        let toml = [
+            book("A", "Technical", ""),
+            book("B", "Fiction", ""),
+            book("C", "Technical", "status = \"reading\""),
+            book("D", "Technical", ""),
+        ]
+        .concat();
+        let shelves = shelves(&toml).unwrap();
+        let names: Vec<(&str, Vec<&str>)> = shelves
+            .iter()
+            .map(|s| {
+                let books = s["books"].as_array().unwrap();
+                let titles = books.iter().map(|b| b["title"].as_str().unwrap()).collect();
+                (s["name"].as_str().unwrap(), titles)
+            })
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("Currently Reading", vec!["C"]),
+                ("Technical", vec!["A", "D"]),
+                ("Fiction", vec!["B"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn entries_are_checked() {
+        let twice = [book("A", "T", ""), book("a ", "T", "")].concat();
+        assert!(shelves(&twice).unwrap_err().contains("listed twice"));
+
+        let missing = book("A", "T", "").replace("a.jpg", "b.jpg");
+        assert!(shelves(&missing)
+            .unwrap_err()
+            .contains("no cover at static/covers/b.jpg"));
+
+        let outside = book("A", "T", "").replace("/covers/a.jpg", "/profile.webp");
+        assert!(shelves(&outside)
+            .unwrap_err()
+            .contains("is not under /covers/"));
+
+        let typo = book("A", "T", "reveiw = \"/blog/a/\"");
+        assert!(shelves(&typo).unwrap_err().contains("reveiw"));
+    }
+
+    #[test]
+    fn authors_are_credited_in_one_line() {
+        let names = |n: &[&str]| n.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(credit(&names(&["A"])), "A");
+        assert_eq!(credit(&names(&["A", "B"])), "A & B");
+        assert_eq!(credit(&names(&["A", "B", "C"])), "A, B & C");
+    }
+}
@@ -0,0 +1,395 @@
+//! Social cards: the 1200×630 PNG link previews show for a post, drawn at
+//! build time from its title, date and the site's name.
+//!
+//! The title is set as large as will fit in three lines, and cut short with
+//! an ellipsis when even the smallest size won't; the date and the site's name
+//! sit along the bottom. Background, text colour and font come from the
+//! `[card]` table of `site.toml` (see `site`).
+
+use std::{fs, io::Cursor, path::Path};
+
+use image::{imageops::FilterType, ImageFormat, Rgb, RgbImage};
+
+use crate::{
+    font::{Font, Point},
+    manifest::Inputs,
+    site::CardStyle,
+};
+
+pub(crate) const WIDTH: u32 = 1200;
+pub(crate) const HEIGHT: u32 = 630;
+
+/// Space kept clear around the text, in pixels.
+const PADDING: f32 = 80.0;
+
+/// Title sizes to try, largest first, in pixels per em.
+const TITLE_SIZES: [f32; 3] = [72.0, 60.0, 52.0];
+const TITLE_LINES: usize = 3;
+const LINE_HEIGHT: f32 = 1.2;
+
+const FOOTER_SIZE: f32 = 32.0;
+/// The footer is drawn fainter than the title.
+const FOOTER_OPACITY: f32 = 0.7;
+
+/// A card style, loaded: what every post's card is drawn with.
+pub(crate) struct Painter {
+    background: RgbImage,
+    color: [u8; 3],
+    font: Font,
+    fingerprint: String,
+}
+
+impl Painter {
+    /// Reads the font, and the background image if there is one, that
+    /// `style` names relative to `root`.
+    pub(crate) fn load(root: &Path, style: &CardStyle) -> Result<Self, String> {
+        let read = |path: &str| {
+            let path = root.join(path);
+            fs::read(&path).map_err(|e| format!("read {}: {e}", path.display()))
+        };
+        let color = parse_color(&style.color)?;
+        let font_bytes = read(&style.font)?;
+        let mut inputs = Inputs::new()
+            .add(&style.background)
+            .add(&style.color)
+            .add(&font_bytes);
+
+        let background = if style.background.starts_with('#') {
+            RgbImage::from_pixel(WIDTH, HEIGHT, Rgb(parse_color(&style.background)?))
+        } else {
+            let bytes = read(&style.background)?;
+            let image = image::load_from_memory(&bytes)
+                .map_err(|e| format!("card background {}: {e}", style.background))?;
+            inputs = inputs.add(bytes);
+            image
+                .resize_to_fill(WIDTH, HEIGHT, FilterType::Lanczos3)
+                .to_rgb8()
+        };
+        let font = Font::parse(font_bytes).map_err(|e| format!("font {}: {e}", style.font))?;
+
+        Ok(Painter {
+            background,
+            color,
+            font,
+            fingerprint: inputs.finish(),
+        })
+    }
+
+    /// Hashes everything a card is drawn with besides its text.
+    pub(crate) fn fingerprint(&self) -> &str {
+        &self.fingerprint
+    }
+
+    /// The card for a post called `title`, dated `date`, as a PNG.
+    pub(crate) fn render(&self, title: &str, date: &str, site: &str) -> Result<Vec<u8>, String> {
+        let mut card = self.background.clone();
+        let width = WIDTH as f32 - 2.0 * PADDING;
+
+        let (size, lines) = self.fit_title(title, width);
+        let mut baseline = PADDING + self.ascent(size);
+        for line in &lines {
+            self.draw(&mut card, line, PADDING, baseline, size, 1.0);
+            baseline += size * LINE_HEIGHT;
+        }
+
+        let bottom = HEIGHT as f32 - PADDING;
+        self.draw(
+            &mut card,
+            date,
+            PADDING,
+            bottom,
+            FOOTER_SIZE,
+            FOOTER_OPACITY,
+        );
+        let x = WIDTH as f32 - PADDING - self.measure(site, FOOTER_SIZE);
+        self.draw(&mut card, site, x, bottom, FOOTER_SIZE, FOOTER_OPACITY);
+
+        let mut png = Vec::new();
+        card.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .map_err(|e| format!("encode card: {e}"))?;
+        Ok(png)
+    }
+
+    /// The largest title size that fits, and the title wrapped at it.
+    fn fit_title(&self, title: &str, width: f32) -> (f32, Vec<String>) {
+        for size in TITLE_SIZES {
+            let lines = self.wrap(title, size, width);
+            if lines.len() <= TITLE_LINES {
+                return (size, lines);
+            }
+        }
+        let size = TITLE_SIZES[TITLE_SIZES.len() - 1];
+        let mut lines = self.wrap(title, size, width);
+        lines.truncate(TITLE_LINES);
+        if let Some(last) = lines.last_mut() {
+            while !last.is_empty() && self.measure(&format!("{last}…"), size) > width {
+                last.pop();
+            }
+            *last = format!("{}…", last.trim_end());
+        }
+        (size, lines)
+    }
+
+    /// Breaks `text` into lines no wider than `width`, between words where it
+    /// can and inside a word too long for a line of its own.
+    fn wrap(&self, text: &str, size: f32, width: f32) -> Vec<String> {
+        let mut lines: Vec<String> = Vec::new();
+        let mut line = String::new();
+        for word in text.split_whitespace() {
+            let joined = if line.is_empty() {
+                word.to_string()
+            } else {
+                format!("{line} {word}")
+            };
+            if self.measure(&joined, size) <= width {
+                line = joined;
+                continue;
+            }
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+            for c in word.chars() {
+                line.push(c);
+                if self.measure(&line, size) > width && line.chars().count() > 1 {
+                    line.pop();
+                    lines.push(std::mem::replace(&mut line, c.to_string()));
+                }
+            }
+        }
+        if !line.is_empty() {
+            lines.push(line);
+        }
+        lines
+    }
+
+    fn scale(&self, size: f32) -> f32 {
+        size / self.font.units_per_em
+    }
+
+    fn ascent(&self, size: f32) -> f32 {
+        self.font.ascender * self.scale(size)
+    }
+
+    /// How wide `text` is set at `size`, in pixels.
+    pub(crate) fn measure(&self, text: &str, size: f32) -> f32 {
+        let advance: f32 = text
+            .chars()
+            .map(|c| self.font.advance(self.font.glyph(c)))
+            .sum();
+        advance * self.scale(size)
+    }
+
+    /// Sets `text` on `card` with its baseline starting at (`x`, `baseline`).
+    fn draw(
+        &self,
+        card: &mut RgbImage,
+        text: &str,
+        x: f32,
+        baseline: f32,
+        size: f32,
+        opacity: f32,
+    ) {
+        let scale = self.scale(size);
+        let mut coverage = Coverage::new(card.width() as usize, card.height() as usize);
+        let mut pen = x;
+        for c in text.chars() {
+            let glyph = self.font.glyph(c);
+            for contour in self.font.outline(glyph) {
+                // Font units are y-up; the card is y-down.
+                let place = |p: &Point| Point {
+                    x: pen + p.x * scale,
+                    y: baseline - p.y * scale,
+                };
+                for pair in contour.windows(2) {
+                    coverage.line(place(&pair[0]), place(&pair[1]));
+                }
+            }
+            pen += self.font.advance(glyph) * scale;
+        }
+
+        for (pixel, alpha) in card.pixels_mut().zip(coverage.alphas()) {
+            let alpha = alpha * opacity;
+            for (channel, &color) in pixel.0.iter_mut().zip(&self.color) {
+                let blended = f32::from(*channel) * (1.0 - alpha) + f32::from(color) * alpha;
+                *channel = blended.round() as u8;
+            }
+        }
+    }
+}
+
+/// Signed area accumulated along outline edges, in the manner of font-rs: a
+/// running sum across each row then gives how much of every pixel the shape
+/// covers, with no need to sort or fill spans.
+struct Coverage {
+    width: usize,
+    height: usize,
+    area: Vec<f32>,
+}
+
+impl Coverage {
+    fn new(width: usize, height: usize) -> Self {
+        Coverage {
+            width,
+            height,
+            // The extra cells let an edge on the last column spill over.
+            area: vec![0.0; width * height + 2],
+        }
+    }
+
+    fn line(&mut self, from: Point, to: Point) {
+        if (from.y - to.y).abs() <= f32::EPSILON {
+            return;
+        }
+        let (dir, p0, p1) = if from.y < to.y {
+            (1.0, from, to)
+        } else {
+            (-1.0, to, from)
+        };
+        // Anything off the sides is pinned to them rather than written into
+        // the neighbouring row.
+        let right = self.width as f32 - 2.0;
+        let dxdy = (p1.x - p0.x) / (p1.y - p0.y);
+        let mut x = p0.x;
+        if p0.y < 0.0 {
+            x -= p0.y * dxdy;
+        }
+        let first = p0.y.max(0.0) as usize;
+        let last = (p1.y.ceil().max(0.0) as usize).min(self.height);
+        for y in first..last {
+            let row = y * self.width;
+            let dy = ((y + 1) as f32).min(p1.y) - (y as f32).max(p0.y);
+            let next = x + dxdy * dy;
+            let d = dy * dir;
+            let (x0, x1) = if x < next { (x, next) } else { (next, x) };
+            let (x0, x1) = (x0.clamp(0.0, right), x1.clamp(0.0, right));
+            let x0_floor = x0.floor();
+            let x0i = x0_floor as usize;
+            let x1_ceil = x1.ceil();
+            let x1i = x1_ceil as usize;
+            if x1i <= x0i + 1 {
+                let middle = 0.5 * (x0 + x1) - x0_floor;
+                self.area[row + x0i] += d - d * middle;
+                self.area[row + x0i + 1] += d * middle;
+            } else {
+                let s = (x1 - x0).recip();
+                let x0f = x0 - x0_floor;
+                let a0 = 0.5 * s * (1.0 - x0f) * (1.0 - x0f);
+                let x1f = x1 - x1_ceil + 1.0;
+                let am = 0.5 * s * x1f * x1f;
+                self.area[row + x0i] += d * a0;
+                if x1i == x0i + 2 {
+                    self.area[row + x0i + 1] += d * (1.0 - a0 - am);
+                } else {
+                    let a1 = s * (1.5 - x0f);
+                    self.area[row + x0i + 1] += d * (a1 - a0);
+                    for xi in x0i + 2..x1i - 1 {
+                        self.area[row + xi] += d * s;
+                    }
+                    let a2 = a1 + (x1i - x0i - 3) as f32 * s;
+                    self.area[row + x1i - 1] += d * (1.0 - a2 - am);
+                }
+                self.area[row + x1i] += d * am;
+            }
+            x = next;
+        }
+    }
+
+    /// How much of each pixel is covered, 0 to 1, row by row.
+    fn alphas(&self) -> impl Iterator<Item = f32> + '_ {
+        self.area[..self.width * self.height]
+            .iter()
+            .scan(0.0, |sum, area| {
+                *sum += area;
+                Some(sum.abs().min(1.0))
+            })
+    }
+}
+
+/// `#rgb` or `#rrggbb`.
+fn parse_color(hex: &str) -> Result<[u8; 3], String> {
+    let error = || format!("color {hex:?} is not #rgb or #rrggbb");
+    let digits = hex.strip_prefix('#').ok_or_else(error)?;
+    if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(error());
+    }
+    let channel = |s: &str| u8::from_str_radix(s, 16).map_err(|_| error());
+    match digits.len() {
+        3 => {
+            let mut rgb = [0; 3];
+            for (out, i) in rgb.iter_mut().zip(0..3) {
+                *out = channel(&digits[i..i + 1])? * 17;
+            }
+            Ok(rgb)
+        }
+        6 => {
+            let mut rgb = [0; 3];
+            for (out, i) in rgb.iter_mut().zip(0..3) {
+                *out = channel(&digits[2 * i..2 * i + 2])?;
+            }
+            Ok(rgb)
+        }
+        _ => Err(error()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn painter() -> Painter {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("..");
+        let style = CardStyle {
+            background: "#000".to_string(),
+            color: "#fff".to_string(),
+            font: "assets/fonts/DejaVuSerif-Bold.ttf".to_string(),
+        };
+        Painter::load(&root, &style).unwrap()
+    }
+
+    #[test]
+    fn a_card_is_a_png_of_the_preview_size_with_text_on_it() {
+        let png = painter()
+            .render(
+                "Fuzzing the Lightning Network",
+                "April 16, 2026",
+                "example.com",
+            )
+            .unwrap();
+        let card = image::load_from_memory(&png).unwrap().to_rgb8();
+        assert_eq!(card.dimensions(), (WIDTH, HEIGHT));
+
+        let lit = |x0: u32, y0: u32, x1: u32, y1: u32| {
+            (y0..y1)
+                .flat_map(|y| (x0..x1).map(move |x| (x, y)))
+                .filter(|&(x, y)| card.get_pixel(x, y).0[0] > 128)
+                .count()
+        };
+        // The title's first line, the footer, and nothing in the margin.
+        assert!(lit(80, 80, 1120, 160) > 1000);
+        assert!(lit(80, 510, 1120, 550) > 500);
+        assert_eq!(lit(0, 0, 1200, 60), 0);
+    }
+
+    #[test]
+    fn titles_shrink_then_cut_short_to_fit() {
+        let painter = painter();
+        let width = WIDTH as f32 - 2.0 * PADDING;
+        let (size, lines) = painter.fit_title("Short", width);
+        assert_eq!((size, lines.len()), (TITLE_SIZES[0], 1));
+
+        let long = "a very long title about nothing in particular ".repeat(6);
+        let (size, lines) = painter.fit_title(&long, width);
+        assert_eq!(size, TITLE_SIZES[TITLE_SIZES.len() - 1]);
+        assert_eq!(lines.len(), TITLE_LINES);
+        assert!(lines[2].ends_with('…'));
+        assert!(lines.iter().all(|l| painter.measure(l, size) <= width));
+    }
+
+    #[test]
+    fn colors_are_hex() {
+        assert_eq!(parse_color("#121110"), Ok([0x12, 0x11, 0x10]));
+        assert_eq!(parse_color("#fa0"), Ok([0xff, 0xaa, 0x00]));
+        assert!(parse_color("121110").is_err());
+        assert!(parse_color("#12111").is_err());
+    }
+}
@@ -0,0 +1,399 @@
+//! Post timestamps as written in frontmatter: either a calendar date
+//! (`2026-04-16`) or a full RFC 3339 timestamp with its time zone
+//! (`2026-04-16T09:30:00-03:00`). Both are checked against the calendar, so
+//! "2026-02-30" is an error instead of a post that sorts oddly.
+
+use std::fmt;
+
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+/// Indexed by days since 1970-01-01 (a Thursday) plus four.
+const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+
+/// A point in time, kept in the zone it was written in so pages show the
+/// author's date. Compare with [`Timestamp::unix_seconds`]: two timestamps in
+/// different zones can be the same instant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Timestamp {
+    year: u16,
+    month: u8,
+    day: u8,
+    /// Hour, minute, second; `None` for a bare date, which means midnight UTC.
+    time: Option<(u8, u8, u8)>,
+    /// Minutes east of UTC.
+    offset: i16,
+}
+
+impl Timestamp {
+    pub(crate) fn parse(s: &str) -> Result<Self, String> {
+        let invalid = || {
+            format!("invalid date {s:?}: expected YYYY-MM-DD or an RFC 3339 timestamp such as 2026-04-16T09:30:00Z")
+        };
+
+        let (date, rest) = s.split_at_checked(10).ok_or_else(invalid)?;
+        let b = date.as_bytes();
+        if b[4] != b'-' || b[7] != b'-' {
+            return Err(invalid());
+        }
+        let year = digits(&date[0..4]).ok_or_else(invalid)?;
+        let month = digits(&date[5..7]).ok_or_else(invalid)?;
+        let day = digits(&date[8..10]).ok_or_else(invalid)?;
+
+        if !(1..=12).contains(&month) {
+            return Err(format!("invalid date {s:?}: no month {month}"));
+        }
+        let month = month as u8;
+        if day == 0 || day > days_in_month(year, month) as u16 {
+            return Err(format!(
+                "invalid date {s:?}: {} {year} has no day {day}",
+                MONTHS[month as usize - 1]
+            ));
+        }
+        let mut stamp = Timestamp {
+            year,
+            month,
+            day: day as u8,
+            time: None,
+            offset: 0,
+        };
+        if rest.is_empty() {
+            return Ok(stamp);
+        }
+
+        // "T" per RFC 3339, or the space its section 5.6 note allows.
+        let rest = rest.strip_prefix(['T', 't', ' ']).ok_or_else(invalid)?;
+        let (clock, zone) = rest.split_at_checked(8).ok_or_else(invalid)?;
+        let c = clock.as_bytes();
+        if c[2] != b':' || c[5] != b':' {
+            return Err(invalid());
+        }
+        let hour = digits(&clock[0..2]).ok_or_else(invalid)?;
+        let minute = digits(&clock[3..5]).ok_or_else(invalid)?;
+        let second = digits(&clock[6..8]).ok_or_else(invalid)?;
+        if hour > 23 || minute > 59 || second > 59 {
+            return Err(format!("invalid date {s:?}: no time {clock}"));
+        }
+        stamp.time = Some((hour as u8, minute as u8, second as u8));
+
+        // Fractions of a second are accepted and dropped: nothing on the site
+        // is finer than a second.
+        let zone = match zone.strip_prefix('.') {
+            Some(frac) => {
+                let end = frac
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(frac.len());
+                if end == 0 {
+                    return Err(invalid());
+                }
+                &frac[end..]
+            }
+            None => zone,
+        };
+
+        stamp.offset = match zone {
+            "Z" | "z" => 0,
+            "" => {
+                return Err(format!(
+                    "invalid date {s:?}: a timestamp needs a time zone, such as Z or -03:00"
+                ))
+            }
+            _ => {
+                let (sign, hhmm) = match zone.split_at_checked(1) {
+                    Some(("+", hhmm)) => (1, hhmm),
+                    Some(("-", hhmm)) => (-1, hhmm),
+                    _ => return Err(invalid()),
+                };
+                let (h, m) = hhmm
+                    .split_once(':')
+                    .filter(|(h, m)| h.len() == 2 && m.len() == 2)
+                    .ok_or_else(invalid)?;
+                let (h, m) = (
+                    digits(h).ok_or_else(invalid)?,
+                    digits(m).ok_or_else(invalid)?,
+                );
+                if h > 23 || m > 59 {
+                    return Err(format!("invalid date {s:?}: no time zone {zone}"));
+                }
+                sign * (h * 60 + m) as i16
+            }
+        };
+        Ok(stamp)
+    }
+
+    /// The instant `seconds` after the Unix epoch, in UTC; before 1970 or
+    /// after 9999 is `None`.
+    pub(crate) fn from_unix_seconds(seconds: i64) -> Option<Self> {
+        let (days, rest) = (seconds.div_euclid(86_400), seconds.rem_euclid(86_400));
+        let (year, month, day) = civil_from_days(days);
+        if !(1970..=9999).contains(&year) {
+            return None;
+        }
+        let time = (rest / 3_600, rest % 3_600 / 60, rest % 60);
+        Some(Self {
+            year: year as u16,
+            month,
+            day,
+            time: Some((time.0 as u8, time.1 as u8, time.2 as u8)),
+            offset: 0,
+        })
+    }
+
+    pub(crate) fn year(&self) -> u16 {
+        self.year
+    }
+
+    /// Seconds since the Unix epoch, for ordering and comparing.
+    pub(crate) fn unix_seconds(&self) -> i64 {
+        let (h, m, s) = self.time.unwrap_or((0, 0, 0));
+        days_from_civil(self.year, self.month, self.day) * 86_400
+            + i64::from(h) * 3_600
+            + i64::from(m) * 60
+            + i64::from(s)
+            - i64::from(self.offset) * 60
+    }
+
+    /// Always a full timestamp, as Atom and sitemaps require; a bare date is
+    /// midnight UTC.
+    pub(crate) fn rfc3339(&self) -> String {
+        let (h, m, s) = self.time.unwrap_or((0, 0, 0));
+        format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}{}",
+            self.year,
+            self.month,
+            self.day,
+            h,
+            m,
+            s,
+            zone(self.offset)
+        )
+    }
+
+    /// "Sat, 04 Jul 2026 09:30:00 -0300", the RFC 2822 form RSS dates use.
+    pub(crate) fn rfc2822(&self) -> String {
+        let (h, m, s) = self.time.unwrap_or((0, 0, 0));
+        let days = days_from_civil(self.year, self.month, self.day);
+        let sign = if self.offset < 0 { '-' } else { '+' };
+        let abs = self.offset.unsigned_abs();
+        format!(
+            "{}, {:02} {} {:04} {:02}:{:02}:{:02} {}{:02}{:02}",
+            WEEKDAYS[(days + 4).rem_euclid(7) as usize],
+            self.day,
+            &MONTHS[self.month as usize - 1][..3],
+            self.year,
+            h,
+            m,
+            s,
+            sign,
+            abs / 60,
+            abs % 60
+        )
+    }
+
+    /// "July 4, 2026", on the calendar of the zone it was written in.
+    pub(crate) fn humanize(&self) -> String {
+        format!(
+            "{} {}, {}",
+            MONTHS[self.month as usize - 1],
+            self.day,
+            self.year
+        )
+    }
+}
+
+/// As written: a bare date stays a date, for `<time datetime>`.
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.time {
+            None => write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day),
+            Some(_) => f.write_str(&self.rfc3339()),
+        }
+    }
+}
+
+fn digits(s: &str) -> Option<u16> {
+    if s.is_empty() || !s.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    s.parse().ok()
+}
+
+fn zone(offset: i16) -> String {
+    if offset == 0 {
+        return "Z".into();
+    }
+    let sign = if offset < 0 { '-' } else { '+' };
+    let abs = offset.unsigned_abs();
+    format!("{}{:02}:{:02}", sign, abs / 60, abs % 60)
+}
+
+fn is_leap(year: u16) -> bool {
+    year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400))
+}
+
+fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if is_leap(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days from 1970-01-01 to the given proleptic Gregorian date (Howard
+/// Hinnant's `days_from_civil`).
+fn days_from_civil(year: u16, month: u8, day: u8) -> i64 {
+    let y = i64::from(year) - i64::from(month <= 2);
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let m = i64::from(month);
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// The date `days` after 1970-01-01 (Hinnant's `civil_from_days`, the
+/// inverse of `days_from_civil`).
+fn civil_from_days(days: i64) -> (i64, u8, u8) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u8;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u8;
+    (yoe + era * 400 + i64::from(month <= 2), month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ts(s: &str) -> Timestamp {
+        Timestamp::parse(s).unwrap()
+    }
+
+    #[test]
+    fn a_bare_date_is_midnight_utc_and_prints_as_written() {
+        let date = ts("2026-04-16");
+        assert_eq!(date.to_string(), "2026-04-16");
+        assert_eq!(date.rfc3339(), "2026-04-16T00:00:00Z");
+        assert_eq!(date.humanize(), "April 16, 2026");
+        assert_eq!(date.year(), 2026);
+    }
+
+    #[test]
+    fn a_timestamp_keeps_its_zone() {
+        let stamp = ts("2026-04-16T21:30:00-03:00");
+        assert_eq!(stamp.to_string(), "2026-04-16T21:30:00-03:00");
+        // Shown on the author's calendar, not UTC's (already the 17th there).
+        assert_eq!(stamp.humanize(), "April 16, 2026");
+    }
+
+    #[test]
+    fn accepts_the_rfc3339_variants() {
+        assert_eq!(ts("2026-04-16t09:30:00z").rfc3339(), "2026-04-16T09:30:00Z");
+        assert_eq!(ts("2026-04-16 09:30:00Z").rfc3339(), "2026-04-16T09:30:00Z");
+        assert_eq!(
+            ts("2026-04-16T09:30:00.123+05:30").rfc3339(),
+            "2026-04-16T09:30:00+05:30"
+        );
+    }
+
+    #[test]
+    fn rfc2822_names_the_weekday_and_keeps_the_zone() {
+        assert_eq!(
+            ts("1970-01-01").rfc2822(),
+            "Thu, 01 Jan 1970 00:00:00 +0000"
+        );
+        assert_eq!(
+            ts("2026-07-04T09:30:00-03:00").rfc2822(),
+            "Sat, 04 Jul 2026 09:30:00 -0300"
+        );
+        assert_eq!(
+            ts("2024-02-29T23:59:59+05:30").rfc2822(),
+            "Thu, 29 Feb 2024 23:59:59 +0530"
+        );
+    }
+
+    #[test]
+    fn unix_seconds_account_for_the_offset() {
+        assert_eq!(ts("1970-01-01").unix_seconds(), 0);
+        assert_eq!(ts("2000-03-01T00:00:00Z").unix_seconds(), 951_868_800);
+        assert_eq!(
+            ts("2026-04-16T21:30:00-03:00").unix_seconds(),
+            ts("2026-04-17T00:30:00Z").unix_seconds()
+        );
+    }
+
+    #[test]
+    fn unix_seconds_read_back_as_utc() {
+        for stamp in [
+            "1970-01-01T00:00:00Z",
+            "2024-02-29T23:59:59Z",
+            "2026-10-18T09:05:00Z",
+        ] {
+            let back = Timestamp::from_unix_seconds(ts(stamp).unix_seconds()).unwrap();
+            assert_eq!(back.rfc3339(), stamp);
+        }
+        assert!(Timestamp::from_unix_seconds(-1).is_none());
+    }
+
+    #[test]
+    fn orders_by_instant_rather_than_by_text() {
+        // Lexically "2026-04-16T23..." sorts after "2026-04-16T22...", but in
+        // its zone it happened first.
+        let earlier = ts("2026-04-16T23:00:00+05:00");
+        let later = ts("2026-04-16T22:00:00Z");
+        assert!(earlier.unix_seconds() < later.unix_seconds());
+        assert!(ts("2025-12-31").unix_seconds() < ts("2026-01-01").unix_seconds());
+    }
+
+    #[test]
+    fn rejects_days_the_calendar_does_not_have() {
+        assert!(Timestamp::parse("2026-02-29").is_err());
+        assert!(Timestamp::parse("2024-02-29").is_ok());
+        assert!(Timestamp::parse("1900-02-29").is_err());
+        assert!(Timestamp::parse("2000-02-29").is_ok());
+        assert!(Timestamp::parse("2026-04-31").is_err());
+        assert!(Timestamp::parse("2026-13-01").is_err());
+        assert!(Timestamp::parse("2026-01-00").is_err());
+    }
+
+    #[test]
+    fn a_time_without_a_zone_is_refused() {
+        let err = Timestamp::parse("2026-04-16T09:30:00").unwrap_err();
+        assert!(err.contains("time zone"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_anything_else() {
+        for bad in [
+            "2026-4-16",
+            "16/04/2026",
+            "2026-04-16x",
+            "",
+            "+026-04-16",
+            "2026-04-16T24:00:00Z",
+            "2026-04-16T09:30Z",
+            "2026-04-16T09:30:00+0300",
+            "2026-04-16T09:30:00.Z",
+            "2026-04-16T09:30:00+24:00",
+        ] {
+            assert!(Timestamp::parse(bad).is_err(), "{:?}", bad);
+        }
+    }
+}
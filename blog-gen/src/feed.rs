@@ -0,0 +1,271 @@
+//! The blog's feeds: Atom (`feed.xml`), RSS 2.0 (`rss.xml`) and JSON Feed 1.1
+//! (`feed.json`), all from the same posts. Entries carry the whole post, with
+//! every link and image made absolute, since a feed reader has no page to
+//! resolve them against.
+
+use serde::Serialize;
+
+use crate::{escape_html, newest_modification, tag_slug, Post, Site};
+
+/// A feed of `posts` for the page at `path`; the feed itself lives at
+/// `<path>/feed.xml`.
+pub(crate) fn render_atom(site: &Site, posts: &[&Post], title: &str, path: &str) -> String {
+    let updated = newest_modification(posts)
+        .map(|t| t.rfc3339())
+        .unwrap_or_else(|| "1970-01-01T00:00:00Z".into());
+
+    let mut entries = String::new();
+    for p in posts {
+        let mut categories = String::new();
+        for tag in &p.tags {
+            categories.push_str(&format!(
+                "    <category term=\"{}\" label=\"{}\"/>\n",
+                tag_slug(tag),
+                escape_html(tag)
+            ));
+        }
+        entries.push_str(&format!(
+            "  <entry>\n    <title>{title}</title>\n    <link href=\"{url}\"/>\n    <id>{url}</id>\n    <published>{published}</published>\n    <updated>{updated}</updated>\n{categories}    <summary>{desc}</summary>\n    <content type=\"html\">{content}</content>\n  </entry>\n",
+            title = escape_html(&p.title),
+            url = site.url(&p.path()),
+            published = p.date.rfc3339(),
+            updated = p.last_modified().rfc3339(),
+            categories = categories,
+            desc = escape_html(&p.description),
+            content = escape_html(&absolutize(site, &p.html, &p.path())),
+        ));
+    }
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<feed xmlns=\"http://www.w3.org/2005/Atom\">\n  <title>{title}</title>\n  <link href=\"{site}{dir}/feed.xml\" rel=\"self\"/>\n  <link href=\"{site}{path}\"/>\n  <id>{site}{path}</id>\n  <updated>{updated}</updated>\n  <author>\n    <name>{author}</name>\n    <uri>{site}/</uri>\n  </author>\n{entries}</feed>\n",
+        title = escape_html(title),
+        site = site.base_url,
+        dir = path.trim_end_matches('/'),
+        path = path,
+        updated = updated,
+        author = escape_html(&site.author),
+        entries = entries,
+    )
+}
+
+/// The same feed as RSS 2.0, at `<path>/rss.xml`.
+pub(crate) fn render_rss(site: &Site, posts: &[&Post], title: &str, path: &str) -> String {
+    let built = newest_modification(posts)
+        .map(|t| t.rfc2822())
+        .unwrap_or_else(|| "Thu, 01 Jan 1970 00:00:00 +0000".into());
+
+    let mut items = String::new();
+    for p in posts {
+        let mut categories = String::new();
+        for tag in &p.tags {
+            categories.push_str(&format!(
+                "      <category>{}</category>\n",
+                escape_html(tag)
+            ));
+        }
+        items.push_str(&format!(
+            "    <item>\n      <title>{title}</title>\n      <link>{url}</link>\n      <guid isPermaLink=\"true\">{url}</guid>\n      <pubDate>{published}</pubDate>\n{categories}      <description>{content}</description>\n    </item>\n",
+            title = escape_html(&p.title),
+            url = site.url(&p.path()),
+            published = p.date.rfc2822(),
+            categories = categories,
+            content = escape_html(&absolutize(site, &p.html, &p.path())),
+        ));
+    }
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\">\n  <channel>\n    <title>{title}</title>\n    <link>{site}{path}</link>\n    <description>{description}</description>\n    <language>{language}</language>\n    <atom:link href=\"{site}{dir}/rss.xml\" rel=\"self\" type=\"application/rss+xml\"/>\n    <lastBuildDate>{built}</lastBuildDate>\n{items}  </channel>\n</rss>\n",
+        title = escape_html(title),
+        site = site.base_url,
+        path = path,
+        dir = path.trim_end_matches('/'),
+        description = escape_html(&site.description),
+        language = escape_html(&site.language),
+        built = built,
+        items = items,
+    )
+}
+
+#[derive(Serialize)]
+struct JsonFeed<'a> {
+    version: &'static str,
+    title: &'a str,
+    home_page_url: String,
+    feed_url: String,
+    description: &'a str,
+    language: &'a str,
+    authors: [JsonAuthor<'a>; 1],
+    items: Vec<JsonItem<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonAuthor<'a> {
+    name: &'a str,
+    url: String,
+}
+
+#[derive(Serialize)]
+struct JsonItem<'a> {
+    id: String,
+    url: String,
+    title: &'a str,
+    content_html: String,
+    #[serde(skip_serializing_if = "str::is_empty")]
+    summary: &'a str,
+    date_published: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date_modified: Option<String>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    tags: &'a [String],
+}
+
+/// The same feed as JSON Feed 1.1, at `<path>/feed.json`.
+pub(crate) fn render_json_feed(site: &Site, posts: &[&Post], title: &str, path: &str) -> String {
+    let feed = JsonFeed {
+        version: "https://jsonfeed.org/version/1.1",
+        title,
+        home_page_url: site.url(path),
+        feed_url: site.url(&format!("{}/feed.json", path.trim_end_matches('/'))),
+        description: &site.description,
+        language: &site.language,
+        authors: [JsonAuthor {
+            name: &site.author,
+            url: site.url("/"),
+        }],
+        items: posts
+            .iter()
+            .map(|p| {
+                let url = site.url(&p.path());
+                JsonItem {
+                    id: url.clone(),
+                    url,
+                    title: &p.title,
+                    content_html: absolutize(site, &p.html, &p.path()),
+                    summary: &p.description,
+                    date_published: p.date.rfc3339(),
+                    date_modified: p.updated.map(|t| t.rfc3339()),
+                    tags: &p.tags,
+                }
+            })
+            .collect(),
+    };
+    let mut json = serde_json::to_string_pretty(&feed).expect("a feed always serializes");
+    json.push('\n');
+    json
+}
+
+/// Rewrites every `href`, `src` and `srcset` in `html` to an absolute URL, as
+/// a browser would resolve it on the page at `page_path` (which ends in `/`).
+pub(crate) fn absolutize(site: &Site, html: &str, page_path: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some((start, attr)) = next_url_attr(rest) {
+        let value_start = start + attr.len();
+        let Some(len) = rest[value_start..].find('"') else {
+            break;
+        };
+        let value = &rest[value_start..value_start + len];
+        out.push_str(&rest[..value_start]);
+        if attr == " srcset=\"" {
+            let candidates: Vec<String> = value
+                .split(',')
+                .map(|candidate| {
+                    let candidate = candidate.trim();
+                    match candidate.split_once(' ') {
+                        Some((url, size)) => format!("{} {}", resolve(site, url, page_path), size),
+                        None => resolve(site, candidate, page_path),
+                    }
+                })
+                .collect();
+            out.push_str(&candidates.join(", "));
+        } else {
+            out.push_str(&resolve(site, value, page_path));
+        }
+        rest = &rest[value_start + len..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn next_url_attr(html: &str) -> Option<(usize, &'static str)> {
+    [" href=\"", " src=\"", " srcset=\""]
+        .into_iter()
+        .filter_map(|attr| html.find(attr).map(|i| (i, attr)))
+        .min_by_key(|(i, _)| *i)
+}
+
+fn resolve(site: &Site, url: &str, page_path: &str) -> String {
+    let has_scheme = url.split_once(':').is_some_and(|(scheme, _)| {
+        !scheme.is_empty()
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+    });
+    if has_scheme || url.starts_with("//") {
+        url.to_string()
+    } else if url.starts_with('/') {
+        site.url(url)
+    } else {
+        site.url(&format!("{page_path}{url}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn site() -> Site {
+        Site {
+            base_url: "https://example.com".to_string(),
+            title: "example.com".to_string(),
+            description: "Notes.".to_string(),
+            author: "Someone".to_string(),
+            language: "en".to_string(),
+            og_image: "/me.webp".to_string(),
+            nav: Vec::new(),
+            card: None,
+            webmentions: false,
+            activitypub: None,
+        }
+    }
+
+    #[test]
+    fn absolutize_resolves_like_a_browser_on_the_post_page() {
+        let html = r##"<a href="/about/">a</a> <a href="#intro">b</a> <img src="x.png" alt=""> <a href="https://example.com/">c</a> <a href="mailto:me@example.com">d</a> <img src="//cdn.example.com/y.png">"##;
+        assert_eq!(
+            absolutize(&site(), html, "/blog/post/"),
+            format!(
+                r##"<a href="{s}/about/">a</a> <a href="{s}/blog/post/#intro">b</a> <img src="{s}/blog/post/x.png" alt=""> <a href="https://example.com/">c</a> <a href="mailto:me@example.com">d</a> <img src="//cdn.example.com/y.png">"##,
+                s = "https://example.com"
+            )
+        );
+    }
+
+    #[test]
+    fn rss_escapes_the_site_language_like_its_other_text() {
+        let site = Site {
+            language: "en<&>".to_string(),
+            ..site()
+        };
+        let rss = render_rss(&site, &[], "example.com", "/blog/");
+        assert!(
+            rss.contains("<language>en&lt;&amp;&gt;</language>"),
+            "{rss}"
+        );
+    }
+
+    #[test]
+    fn absolutize_rewrites_each_srcset_candidate() {
+        assert_eq!(
+            absolutize(
+                &site(),
+                r#"<img srcset="a.webp 1x, /b.webp 2x">"#,
+                "/blog/p/"
+            ),
+            format!(
+                r#"<img srcset="{s}/blog/p/a.webp 1x, {s}/b.webp 2x">"#,
+                s = "https://example.com"
+            )
+        );
+    }
+}
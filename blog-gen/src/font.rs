@@ -0,0 +1,520 @@
+//! Just enough of TrueType to set a line of text: character to glyph
+//! (`cmap` formats 4 and 12), advance widths (`hmtx`) and outlines (`glyf`,
+//! simple and composite). Hinting and kerning are left out; at the sizes
+//! social cards use, neither shows.
+
+/// A point in font units, y up.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Point {
+    pub x: f32,
+    pub y: f32,
+}
+
+/// One closed piece of a glyph's outline, flattened into straight lines.
+pub(crate) type Contour = Vec<Point>;
+
+pub(crate) struct Font {
+    data: Vec<u8>,
+    pub units_per_em: f32,
+    /// How far the tallest glyphs reach above the baseline, in font units.
+    pub ascender: f32,
+    num_glyphs: u16,
+    num_h_metrics: u16,
+    long_loca: bool,
+    cmap: Cmap,
+    glyf: usize,
+    loca: usize,
+    hmtx: usize,
+}
+
+#[derive(Clone, Copy)]
+enum Cmap {
+    Format4(usize),
+    Format12(usize),
+}
+
+/// Deepest a composite glyph may nest; a real font never comes close.
+const MAX_COMPOSITE_DEPTH: u32 = 8;
+
+impl Font {
+    pub(crate) fn parse(data: Vec<u8>) -> Result<Self, String> {
+        let table = |tag: &[u8; 4]| -> Result<usize, String> {
+            let count = read_u16(&data, 4)? as usize;
+            (0..count)
+                .map(|i| 12 + 16 * i)
+                .find(|&record| data.get(record..record + 4) == Some(tag))
+                .map(|record| read_u32(&data, record + 8).map(|offset| offset as usize))
+                .unwrap_or_else(|| Err(format!("no {} table", String::from_utf8_lossy(tag))))
+        };
+        let head = table(b"head")?;
+        let hhea = table(b"hhea")?;
+        let maxp = table(b"maxp")?;
+        let cmap_table = table(b"cmap")?;
+
+        let mut cmap = None;
+        for i in 0..read_u16(&data, cmap_table + 2)? as usize {
+            let record = cmap_table + 4 + 8 * i;
+            let platform = read_u16(&data, record)?;
+            let encoding = read_u16(&data, record + 2)?;
+            let subtable = cmap_table + read_u32(&data, record + 4)? as usize;
+            let unicode = platform == 0 || (platform == 3 && matches!(encoding, 1 | 10));
+            match (unicode, read_u16(&data, subtable)?) {
+                (true, 12) => {
+                    cmap = Some(Cmap::Format12(subtable));
+                    break;
+                }
+                (true, 4) => cmap = cmap.or(Some(Cmap::Format4(subtable))),
+                _ => {}
+            }
+        }
+
+        Ok(Font {
+            units_per_em: f32::from(read_u16(&data, head + 18)?),
+            ascender: f32::from(read_i16(&data, hhea + 4)?),
+            num_glyphs: read_u16(&data, maxp + 4)?,
+            num_h_metrics: read_u16(&data, hhea + 34)?,
+            long_loca: read_i16(&data, head + 50)? == 1,
+            cmap: cmap.ok_or("no Unicode character map")?,
+            glyf: table(b"glyf")?,
+            loca: table(b"loca")?,
+            hmtx: table(b"hmtx")?,
+            data,
+        })
+    }
+
+    /// The glyph for `c`, or 0 (the font's "missing" glyph) when it has none.
+    pub(crate) fn glyph(&self, c: char) -> u16 {
+        self.lookup(c as u32).unwrap_or(0)
+    }
+
+    fn lookup(&self, c: u32) -> Result<u16, String> {
+        let d = &self.data;
+        match self.cmap {
+            Cmap::Format4(table) => {
+                let Ok(c) = u16::try_from(c) else {
+                    return Ok(0);
+                };
+                let segments = read_u16(d, table + 6)? as usize / 2;
+                let ends = table + 14;
+                let starts = ends + 2 * segments + 2;
+                let deltas = starts + 2 * segments;
+                let ranges = deltas + 2 * segments;
+                for i in 0..segments {
+                    if read_u16(d, ends + 2 * i)? < c {
+                        continue;
+                    }
+                    let start = read_u16(d, starts + 2 * i)?;
+                    if start > c {
+                        return Ok(0);
+                    }
+                    let delta = read_u16(d, deltas + 2 * i)?;
+                    let range = read_u16(d, ranges + 2 * i)? as usize;
+                    if range == 0 {
+                        return Ok(c.wrapping_add(delta));
+                    }
+                    let at = ranges + 2 * i + range + 2 * (c - start) as usize;
+                    let glyph = read_u16(d, at)?;
+                    return Ok(if glyph == 0 {
+                        0
+                    } else {
+                        glyph.wrapping_add(delta)
+                    });
+                }
+                Ok(0)
+            }
+            Cmap::Format12(table) => {
+                for i in 0..read_u32(d, table + 12)? as usize {
+                    let group = table + 16 + 12 * i;
+                    let start = read_u32(d, group)?;
+                    if (start..=read_u32(d, group + 4)?).contains(&c) {
+                        let glyph = read_u32(d, group + 8)?.checked_add(c - start);
+                        return Ok(glyph.and_then(|g| u16::try_from(g).ok()).unwrap_or(0));
+                    }
+                }
+                Ok(0)
+            }
+        }
+    }
+
+    /// How far the pen moves after `glyph`, in font units.
+    pub(crate) fn advance(&self, glyph: u16) -> f32 {
+        let metric = glyph.min(self.num_h_metrics.saturating_sub(1)) as usize;
+        read_u16(&self.data, self.hmtx + 4 * metric).map_or(0.0, f32::from)
+    }
+
+    /// The outline of `glyph`, in font units. A glyph that can't be read
+    /// draws as nothing rather than failing the card.
+    pub(crate) fn outline(&self, glyph: u16) -> Vec<Contour> {
+        let mut contours = Vec::new();
+        let _ = self.append_outline(glyph, [1.0, 0.0, 0.0, 1.0, 0.0, 0.0], 0, &mut contours);
+        contours
+    }
+
+    /// Adds `glyph`'s contours, through the affine `transform`
+    /// (`[a, b, c, d, e, f]`: x' = ax + cy + e, y' = bx + dy + f).
+    fn append_outline(
+        &self,
+        glyph: u16,
+        transform: [f32; 6],
+        depth: u32,
+        contours: &mut Vec<Contour>,
+    ) -> Result<(), String> {
+        if glyph >= self.num_glyphs || depth > MAX_COMPOSITE_DEPTH {
+            return Ok(());
+        }
+        let d = &self.data;
+        let (start, end) = if self.long_loca {
+            let at = self.loca + 4 * glyph as usize;
+            (read_u32(d, at)? as usize, read_u32(d, at + 4)? as usize)
+        } else {
+            let at = self.loca + 2 * glyph as usize;
+            (
+                2 * read_u16(d, at)? as usize,
+                2 * read_u16(d, at + 2)? as usize,
+            )
+        };
+        if start >= end {
+            // No outline: a space.
+            return Ok(());
+        }
+        let at = self.glyf + start;
+        let count = read_i16(d, at)?;
+        if count >= 0 {
+            for contour in simple_glyph(d, at, count as usize)? {
+                contours.push(flatten(&contour, transform));
+            }
+            return Ok(());
+        }
+
+        // Composite: other glyphs, each moved and maybe scaled.
+        const ARGS_ARE_WORDS: u16 = 0x0001;
+        const ARGS_ARE_XY: u16 = 0x0002;
+        const HAVE_SCALE: u16 = 0x0008;
+        const MORE_COMPONENTS: u16 = 0x0020;
+        const HAVE_XY_SCALE: u16 = 0x0040;
+        const HAVE_TWO_BY_TWO: u16 = 0x0080;
+        let mut at = at + 10;
+        loop {
+            let flags = read_u16(d, at)?;
+            let component = read_u16(d, at + 2)?;
+            at += 4;
+            let (dx, dy) = if flags & ARGS_ARE_WORDS != 0 {
+                at += 4;
+                (read_i16(d, at - 4)?, read_i16(d, at - 2)?)
+            } else {
+                at += 2;
+                (
+                    i16::from(*d.get(at - 2).ok_or("truncated")? as i8),
+                    i16::from(*d.get(at - 1).ok_or("truncated")? as i8),
+                )
+            };
+            let f2dot14 = |at: usize| read_i16(d, at).map(|v| f32::from(v) / 16384.0);
+            let [mut a, mut b, mut c, mut e] = [1.0, 0.0, 0.0, 1.0];
+            if flags & HAVE_SCALE != 0 {
+                a = f2dot14(at)?;
+                e = a;
+                at += 2;
+            } else if flags & HAVE_XY_SCALE != 0 {
+                a = f2dot14(at)?;
+                e = f2dot14(at + 2)?;
+                at += 4;
+            } else if flags & HAVE_TWO_BY_TWO != 0 {
+                a = f2dot14(at)?;
+                b = f2dot14(at + 2)?;
+                c = f2dot14(at + 4)?;
+                e = f2dot14(at + 6)?;
+                at += 8;
+            }
+            // Matching points instead of offsets is for hinted fonts; those
+            // components are placed at the origin.
+            let (dx, dy) = if flags & ARGS_ARE_XY != 0 {
+                (f32::from(dx), f32::from(dy))
+            } else {
+                (0.0, 0.0)
+            };
+            let [ta, tb, tc, td, tx, ty] = transform;
+            let inner = [
+                ta * a + tc * b,
+                tb * a + td * b,
+                ta * c + tc * e,
+                tb * c + td * e,
+                ta * dx + tc * dy + tx,
+                tb * dx + td * dy + ty,
+            ];
+            self.append_outline(component, inner, depth + 1, contours)?;
+            if flags & MORE_COMPONENTS == 0 {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// The contours of a simple glyph at `at`, as (point, on the curve) pairs.
+fn simple_glyph(d: &[u8], at: usize, count: usize) -> Result<Vec<Vec<(Point, bool)>>, String> {
+    const ON_CURVE: u8 = 0x01;
+    const X_SHORT: u8 = 0x02;
+    const Y_SHORT: u8 = 0x04;
+    const REPEAT: u8 = 0x08;
+    const X_SAME_OR_POSITIVE: u8 = 0x10;
+    const Y_SAME_OR_POSITIVE: u8 = 0x20;
+
+    let ends: Vec<usize> = (0..count)
+        .map(|i| read_u16(d, at + 10 + 2 * i).map(usize::from))
+        .collect::<Result<_, _>>()?;
+    let points = ends.last().map_or(0, |last| last + 1);
+    let instructions = read_u16(d, at + 10 + 2 * count)? as usize;
+    let mut i = at + 12 + 2 * count + instructions;
+
+    let byte = |i: usize| d.get(i).copied().ok_or("truncated glyph");
+    let mut flags = Vec::with_capacity(points);
+    while flags.len() < points {
+        let flag = byte(i)?;
+        i += 1;
+        flags.push(flag);
+        if flag & REPEAT != 0 {
+            let repeat = byte(i)?;
+            i += 1;
+            flags.extend(std::iter::repeat_n(flag, repeat as usize));
+        }
+    }
+    flags.truncate(points);
+
+    let mut read_coords = |short: u8, same_or_positive: u8| -> Result<Vec<f32>, String> {
+        let mut value = 0i32;
+        let mut coords = Vec::with_capacity(points);
+        for &flag in &flags {
+            if flag & short != 0 {
+                let delta = i32::from(byte(i)?);
+                i += 1;
+                value += if flag & same_or_positive != 0 {
+                    delta
+                } else {
+                    -delta
+                };
+            } else if flag & same_or_positive == 0 {
+                value += i32::from(read_i16(d, i)?);
+                i += 2;
+            }
+            coords.push(value as f32);
+        }
+        Ok(coords)
+    };
+    let xs = read_coords(X_SHORT, X_SAME_OR_POSITIVE)?;
+    let ys = read_coords(Y_SHORT, Y_SAME_OR_POSITIVE)?;
+
+    let mut contours = Vec::with_capacity(count);
+    let mut first = 0;
+    for end in ends {
+        if end < first || end >= points {
+            return Err("bad contour end".to_string());
+        }
+        contours.push(
+            (first..=end)
+                .map(|p| {
+                    let point = Point { x: xs[p], y: ys[p] };
+                    (point, flags[p] & ON_CURVE != 0)
+                })
+                .collect(),
+        );
+        first = end + 1;
+    }
+    Ok(contours)
+}
+
+/// A contour of on- and off-curve points as straight lines, through
+/// `transform`. Between two off-curve points there is an implied on-curve
+/// one halfway.
+fn flatten(points: &[(Point, bool)], transform: [f32; 6]) -> Contour {
+    /// Lines per quadratic curve: plenty at card sizes.
+    const STEPS: usize = 8;
+    let [a, b, c, d, dx, dy] = transform;
+    let apply = |p: Point| Point {
+        x: a * p.x + c * p.y + dx,
+        y: b * p.x + d * p.y + dy,
+    };
+    let mid = |p: Point, q: Point| Point {
+        x: (p.x + q.x) / 2.0,
+        y: (p.y + q.y) / 2.0,
+    };
+    let Some(&(first, first_on)) = points.first() else {
+        return Vec::new();
+    };
+
+    // Start on the curve.
+    let start = if first_on {
+        first
+    } else {
+        match points.last() {
+            Some(&(last, true)) => last,
+            Some(&(last, false)) => mid(last, first),
+            None => first,
+        }
+    };
+    let mut out = vec![apply(start)];
+    let mut pen = start;
+    let mut control: Option<Point> = None;
+    let rest = points.iter().skip(usize::from(first_on)).copied();
+    for (point, on) in rest.chain(std::iter::once((start, true))) {
+        let Some(ctrl) = control else {
+            if on {
+                out.push(apply(point));
+                pen = point;
+            } else {
+                control = Some(point);
+            }
+            continue;
+        };
+        let end = if on { point } else { mid(ctrl, point) };
+        for step in 1..=STEPS {
+            let t = step as f32 / STEPS as f32;
+            let u = 1.0 - t;
+            out.push(apply(Point {
+                x: u * u * pen.x + 2.0 * u * t * ctrl.x + t * t * end.x,
+                y: u * u * pen.y + 2.0 * u * t * ctrl.y + t * t * end.y,
+            }));
+        }
+        pen = end;
+        control = (!on).then_some(point);
+    }
+    out
+}
+
+fn read_u16(d: &[u8], at: usize) -> Result<u16, String> {
+    d.get(at..at + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+        .ok_or_else(|| "font data is truncated".to_string())
+}
+
+fn read_i16(d: &[u8], at: usize) -> Result<i16, String> {
+    read_u16(d, at).map(|v| v as i16)
+}
+
+fn read_u32(d: &[u8], at: usize) -> Result<u32, String> {
+    d.get(at..at + 4)
+        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| "font data is truncated".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    fn dejavu() -> Vec<u8> {
+        let path =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("../assets/fonts/DejaVuSerif-Bold.ttf");
+        std::fs::read(path).unwrap()
+    }
+
+    /// The offset of table `tag` in `data`, from its table directory.
+    fn table_offset(data: &[u8], tag: &[u8; 4]) -> usize {
+        (0..read_u16(data, 4).unwrap() as usize)
+            .map(|i| 12 + 16 * i)
+            .find(|&record| &data[record..record + 4] == tag)
+            .map(|record| read_u32(data, record + 8).unwrap() as usize)
+            .unwrap()
+    }
+
+    /// A font with only the tables `parse` needs, whose one format 12 cmap
+    /// group maps `first..=last` to glyphs from `first_glyph` on.
+    fn font_with_cmap_group(first: u32, last: u32, first_glyph: u32) -> Vec<u8> {
+        let mut cmap = Vec::new();
+        cmap.extend([0, 0, 0, 1]); // version, one subtable
+        cmap.extend([0, 3, 0, 10]); // Windows, full Unicode
+        cmap.extend(12u32.to_be_bytes());
+        cmap.extend([0, 12, 0, 0]); // format 12
+        cmap.extend(28u32.to_be_bytes()); // length
+        cmap.extend(0u32.to_be_bytes()); // language
+        cmap.extend(1u32.to_be_bytes()); // groups
+        for n in [first, last, first_glyph] {
+            cmap.extend(n.to_be_bytes());
+        }
+        let tables: [(&[u8; 4], Vec<u8>); 7] = [
+            (b"cmap", cmap),
+            (b"glyf", vec![0; 4]),
+            (b"head", vec![0; 54]),
+            (b"hhea", vec![0; 36]),
+            (b"hmtx", vec![0; 4]),
+            (b"loca", vec![0; 4]),
+            (b"maxp", vec![0; 6]),
+        ];
+        let mut font = vec![0, 1, 0, 0, 0, tables.len() as u8, 0, 0, 0, 0, 0, 0];
+        let mut offset = font.len() + 16 * tables.len();
+        for (tag, data) in &tables {
+            font.extend(*tag);
+            font.extend([0; 4]); // checksum
+            font.extend((offset as u32).to_be_bytes());
+            font.extend((data.len() as u32).to_be_bytes());
+            offset += data.len();
+        }
+        for (_, data) in tables {
+            font.extend(data);
+        }
+        font
+    }
+
+    #[test]
+    fn dejavu_maps_outlines_and_measures_text() {
+        let font = Font::parse(dejavu()).unwrap();
+        assert_eq!(font.units_per_em, 2048.0);
+        assert!(font.ascender > 0.0);
+
+        let a = font.glyph('A');
+        assert_ne!(a, 0);
+        assert_ne!(font.glyph('é'), 0);
+        assert_eq!(font.glyph('\u{10FFFF}'), 0);
+        assert!(font.advance(a) > 0.0);
+
+        let outline = font.outline(a);
+        assert!(!outline.is_empty());
+        let em = font.units_per_em;
+        assert!(outline
+            .iter()
+            .flatten()
+            .all(|p| (-em..=em).contains(&p.x) && (-em..=em).contains(&p.y)));
+        assert!(font.outline(font.glyph(' ')).is_empty());
+    }
+
+    #[test]
+    fn a_truncated_font_is_refused_without_panicking() {
+        let data = dejavu();
+        assert!(Font::parse(Vec::new()).is_err());
+        // The metrics tables come after the outlines in this font.
+        let glyf = table_offset(&data, b"glyf");
+        assert_eq!(
+            Font::parse(data[..glyf + 16].to_vec()).err().unwrap(),
+            "font data is truncated"
+        );
+        for len in (0..data.len()).step_by(997) {
+            if let Ok(font) = Font::parse(data[..len].to_vec()) {
+                let glyph = font.glyph('W');
+                font.advance(glyph);
+                font.outline(glyph);
+            }
+        }
+    }
+
+    #[test]
+    fn a_corrupt_font_does_not_panic() {
+        let mut data = dejavu();
+        let glyf = table_offset(&data, b"glyf");
+        data[glyf..glyf + 4096].fill(0xff);
+        let font = Font::parse(data).unwrap();
+        for glyph in 0..font.num_glyphs {
+            font.outline(glyph);
+        }
+    }
+
+    #[test]
+    fn a_cmap_group_running_past_the_last_glyph_id_maps_to_nothing() {
+        let font = Font::parse(font_with_cmap_group(0x41, 0x5a, u32::MAX)).unwrap();
+        assert_eq!(font.glyph('A'), 0);
+        assert_eq!(font.glyph('B'), 0);
+
+        let font = Font::parse(font_with_cmap_group(0x41, 0x5a, 7)).unwrap();
+        assert_eq!(font.glyph('A'), 7);
+        assert_eq!(font.glyph('C'), 9);
+        assert_eq!(font.glyph('a'), 0);
+    }
+}
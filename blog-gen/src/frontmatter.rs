@@ -0,0 +1,451 @@
+//! Post frontmatter: YAML between `---` fences or TOML between `+++` fences,
+//! deserialized into a [`Frontmatter`]. Errors point at the file, line and
+//! column they come from; keys nothing reads are reported as warnings rather
+//! than silently dropped, since they are usually typos.
+
+use std::{fmt, ops::Range, path::Path};
+
+use serde::{de, Deserialize, Deserializer};
+
+use crate::date::Timestamp;
+
+pub(crate) struct Frontmatter {
+    pub title: String,
+    pub date: Timestamp,
+    /// When the post last changed in a way worth telling readers about.
+    pub updated: Option<Timestamp>,
+    pub description: String,
+    pub slug: Option<String>,
+    pub draft: bool,
+    pub tags: Vec<String>,
+    /// The social card image, in place of the generated one: relative to the
+    /// post, a path on the site, or an absolute URL.
+    pub og_image: Option<String>,
+    /// The series the post is a part of, by name; its parts are read in
+    /// date order.
+    pub series: Option<String>,
+}
+
+/// What a post's header says, the markdown after it, and anything worth a
+/// warning on the way.
+pub(crate) struct Parsed<'a> {
+    pub meta: Frontmatter,
+    pub body: &'a str,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug)]
+pub(crate) struct FrontmatterError {
+    pub path: String,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for FrontmatterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: {}",
+            self.path, self.line, self.column, self.message
+        )
+    }
+}
+
+#[derive(Deserialize)]
+struct Raw {
+    title: String,
+    date: RawDate,
+    updated: Option<RawDate>,
+    #[serde(default)]
+    description: String,
+    slug: Option<String>,
+    #[serde(default)]
+    draft: bool,
+    #[serde(default)]
+    tags: Vec<String>,
+    og_image: Option<String>,
+    series: Option<String>,
+}
+
+/// A date or timestamp as written. YAML has no date type, so it arrives as
+/// text; TOML has one, and a bare `date = 2026-04-16` arrives as that. Either way it is
+/// validated afterwards, where the error can name its line.
+struct RawDate(String);
+
+impl<'de> Deserialize<'de> for RawDate {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> de::Visitor<'de> for Visitor {
+            type Value = RawDate;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a YYYY-MM-DD date or an RFC 3339 timestamp")
+            }
+
+            fn visit_str<E: de::Error>(self, s: &str) -> Result<RawDate, E> {
+                Ok(RawDate(s.to_string()))
+            }
+
+            fn visit_map<A: de::MapAccess<'de>>(self, map: A) -> Result<RawDate, A::Error> {
+                let datetime =
+                    toml::value::Datetime::deserialize(de::value::MapAccessDeserializer::new(map))?;
+                Ok(RawDate(datetime.to_string()))
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Format {
+    Yaml,
+    Toml,
+}
+
+impl Format {
+    fn fence(self) -> &'static str {
+        match self {
+            Format::Yaml => "---",
+            Format::Toml => "+++",
+        }
+    }
+}
+
+/// The frontmatter lives between the fences: the block starts on line 2.
+const FIRST_LINE: usize = 2;
+
+/// `Ok(None)` when the file has no frontmatter at all, which makes it not a
+/// post. Once a header is opened, anything wrong with it is an error.
+pub(crate) fn parse<'a>(path: &Path, raw: &'a str) -> Result<Option<Parsed<'a>>, FrontmatterError> {
+    let error = |line: usize, column: usize, message: String| FrontmatterError {
+        path: path.display().to_string(),
+        line,
+        column,
+        message,
+    };
+
+    let Some((format, meta, body)) = split(raw).map_err(|msg| error(1, 1, msg))? else {
+        return Ok(None);
+    };
+
+    let mut ignored = Vec::new();
+    let record = |key: serde_ignored::Path| ignored.push(key.to_string());
+    let parsed: Result<Raw, (Option<Range<usize>>, String)> = match format {
+        Format::Yaml => {
+            serde_ignored::deserialize(serde_yaml::Deserializer::from_str(meta), record)
+                .map_err(|e| (e.location().map(|l| l.index()..l.index()), e.to_string()))
+        }
+        Format::Toml => serde_ignored::deserialize(toml::Deserializer::new(meta), record)
+            .map_err(|e| (e.span(), e.message().to_string())),
+    };
+    let raw_meta = parsed.map_err(|(span, message)| {
+        let (line, column) = span.map_or((0, 1), |s| line_col(meta, s.start));
+        error(FIRST_LINE + line, column, strip_location(&message))
+    })?;
+
+    let at_key = |key: &str, message: String| {
+        let line = key_line(meta, key).unwrap_or(0);
+        error(FIRST_LINE + line, 1, message)
+    };
+
+    let date = Timestamp::parse(raw_meta.date.0.trim()).map_err(|e| at_key("date", e))?;
+    let updated = match raw_meta.updated {
+        Some(raw) => {
+            let updated = Timestamp::parse(raw.0.trim()).map_err(|e| at_key("updated", e))?;
+            if updated.unix_seconds() < date.unix_seconds() {
+                return Err(at_key(
+                    "updated",
+                    format!("updated ({updated}) is before date ({date})"),
+                ));
+            }
+            Some(updated)
+        }
+        None => None,
+    };
+    if raw_meta.title.trim().is_empty() {
+        return Err(at_key("title", "title is empty".into()));
+    }
+    if let Some(slug) = &raw_meta.slug {
+        if slug.trim().is_empty() {
+            return Err(at_key("slug", "slug is empty".into()));
+        }
+        if !is_path_segment(slug) {
+            return Err(at_key(
+                "slug",
+                format!("slug {slug:?} must be one path segment: no `/`, `\\`, `.` or `..`"),
+            ));
+        }
+    }
+    if raw_meta
+        .og_image
+        .as_deref()
+        .is_some_and(|s| s.trim().is_empty())
+    {
+        return Err(at_key("og_image", "og_image is empty".into()));
+    }
+    if raw_meta
+        .series
+        .as_deref()
+        .is_some_and(|s| s.trim().is_empty())
+    {
+        return Err(at_key("series", "series is empty".into()));
+    }
+
+    let warnings = ignored
+        .iter()
+        .map(|key| {
+            let top = key.split('.').next().unwrap_or(key);
+            let line = FIRST_LINE + key_line(meta, top).unwrap_or(0);
+            format!(
+                "{}:{}: unknown frontmatter key `{}`",
+                path.display(),
+                line,
+                key
+            )
+        })
+        .collect();
+
+    Ok(Some(Parsed {
+        meta: Frontmatter {
+            title: raw_meta.title,
+            date,
+            updated,
+            description: raw_meta.description,
+            slug: raw_meta.slug,
+            draft: raw_meta.draft,
+            tags: raw_meta
+                .tags
+                .into_iter()
+                .map(|t| t.trim().to_string())
+                .filter(|t| !t.is_empty())
+                .collect(),
+            og_image: raw_meta.og_image.map(|s| s.trim().to_string()),
+            series: raw_meta.series.map(|s| s.trim().to_string()),
+        },
+        body,
+        warnings,
+    }))
+}
+
+/// Whether `slug` can name a directory of its own under `blog/`, and
+/// nothing else.
+fn is_path_segment(slug: &str) -> bool {
+    !matches!(slug, "." | "..") && !slug.contains(['/', '\\']) && slug.trim() == slug
+}
+
+/// Splits off a header fenced by a line of exactly `---` or `+++`, opening on
+/// the first line. The closing fence has to match the opening one.
+fn split(raw: &str) -> Result<Option<(Format, &str, &str)>, String> {
+    let text = raw.trim_start_matches('\u{feff}');
+    let (first, rest) = text.split_once('\n').unwrap_or((text, ""));
+    let format = match first.trim_end_matches('\r') {
+        "---" => Format::Yaml,
+        "+++" => Format::Toml,
+        _ => return Ok(None),
+    };
+
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end_matches(['\r', '\n']) == format.fence() {
+            return Ok(Some((
+                format,
+                &rest[..offset],
+                &rest[offset + line.len()..],
+            )));
+        }
+        offset += line.len();
+    }
+    Err(format!(
+        "frontmatter opened with `{}` is never closed",
+        format.fence()
+    ))
+}
+
+/// 0-based line and 1-based column of byte `offset` in `text`.
+fn line_col(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line = before.matches('\n').count();
+    let line_start = before.rfind('\n').map_or(0, |nl| nl + 1);
+    (line, before[line_start..].chars().count() + 1)
+}
+
+/// 0-based line on which the top-level `key` is set, in either syntax.
+fn key_line(meta: &str, key: &str) -> Option<usize> {
+    meta.lines().position(|line| {
+        line.strip_prefix(key)
+            .is_some_and(|rest| rest.trim_start().starts_with([':', '=']))
+    })
+}
+
+/// serde_yaml appends " at line X column Y" relative to the header, which
+/// would contradict the file position this error already carries.
+fn strip_location(message: &str) -> String {
+    match message.find(" at line ") {
+        Some(i) => message[..i].to_string(),
+        None => message.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_ok(raw: &str) -> Parsed<'_> {
+        parse(Path::new("post.md"), raw).unwrap().unwrap()
+    }
+
+    fn parse_err(raw: &str) -> FrontmatterError {
+        match parse(Path::new("post.md"), raw) {
+            Err(e) => e,
+            Ok(_) => panic!("expected an error for {:?}", raw),
+        }
+    }
+
+    #[test]
+    fn reads_yaml_with_lists_colons_and_multiline_strings() {
+        let parsed = parse_ok(
+            "---\n\
+             title: \"bitcoin: the good parts\"\n\
+             date: 2026-04-16\n\
+             description: >\n  one line\n  continued\n\
+             tags:\n  - rust\n  - c++\n\
+             ---\nbody\n",
+        );
+        assert_eq!(parsed.meta.title, "bitcoin: the good parts");
+        assert_eq!(parsed.meta.date.to_string(), "2026-04-16");
+        assert_eq!(parsed.meta.description, "one line continued\n");
+        assert_eq!(parsed.meta.tags, ["rust", "c++"]);
+        assert!(!parsed.meta.draft);
+        assert_eq!(parsed.body, "body\n");
+        assert!(parsed.warnings.is_empty());
+    }
+
+    #[test]
+    fn reads_toml_with_a_bare_date() {
+        let parsed = parse_ok(
+            "+++\n\
+             title = \"a toml post\"\n\
+             date = 2026-04-16\n\
+             draft = true\n\
+             tags = [\"lightning\"]\n\
+             +++\nbody\n",
+        );
+        assert_eq!(parsed.meta.title, "a toml post");
+        assert_eq!(parsed.meta.date.to_string(), "2026-04-16");
+        assert!(parsed.meta.draft);
+        assert_eq!(parsed.meta.tags, ["lightning"]);
+    }
+
+    #[test]
+    fn reads_timestamps_and_an_updated_date() {
+        let yaml =
+            parse_ok("---\ntitle: x\ndate: 2026-04-16T09:30:00-03:00\nupdated: 2026-05-01\n---\n");
+        assert_eq!(yaml.meta.date.to_string(), "2026-04-16T09:30:00-03:00");
+        assert_eq!(yaml.meta.updated.unwrap().to_string(), "2026-05-01");
+
+        let toml = parse_ok(
+            "+++\ntitle = \"x\"\ndate = 2026-04-16T09:30:00-03:00\nupdated = 2026-05-01T10:00:00Z\n+++\n",
+        );
+        assert_eq!(toml.meta.date.to_string(), "2026-04-16T09:30:00-03:00");
+        assert_eq!(
+            toml.meta.updated.unwrap().to_string(),
+            "2026-05-01T10:00:00Z"
+        );
+    }
+
+    #[test]
+    fn an_update_before_the_post_existed_is_an_error() {
+        let err = parse_err("---\ntitle: x\ndate: 2026-04-16\nupdated: 2026-04-15\n---\n");
+        assert_eq!(err.line, 4);
+        assert!(err.message.contains("before date"), "{}", err.message);
+    }
+
+    #[test]
+    fn a_toml_local_datetime_needs_a_zone() {
+        let err = parse_err("+++\ntitle = \"x\"\ndate = 2026-04-16T09:30:00\n+++\n");
+        assert_eq!(err.line, 3);
+        assert!(err.message.contains("time zone"), "{}", err.message);
+    }
+
+    #[test]
+    fn a_file_without_frontmatter_is_not_a_post() {
+        assert!(parse(Path::new("README.md"), "# notes\n")
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn a_closing_fence_must_be_a_whole_line() {
+        let err = parse_err("---\ntitle: x\n----\nbody\n");
+        assert_eq!((err.line, err.column), (1, 1));
+        assert!(err.message.contains("never closed"));
+    }
+
+    #[test]
+    fn yaml_syntax_errors_point_into_the_file() {
+        let err = parse_err("---\ntitle: x\ndate: 2026-04-16\ntags: [a, b\n---\n");
+        assert_eq!(err.path, "post.md");
+        assert!(
+            err.line >= 4,
+            "line {} should be inside the header",
+            err.line
+        );
+        assert!(!err.message.contains(" at line "));
+    }
+
+    #[test]
+    fn toml_syntax_errors_point_into_the_file() {
+        let err = parse_err("+++\ntitle = \"x\"\ndate = 2026-04-16\ndraft = yes\n+++\n");
+        assert_eq!((err.line, err.column), (4, 9));
+    }
+
+    #[test]
+    fn an_impossible_date_is_an_error_on_its_line() {
+        let err = parse_err("---\ntitle: x\ndate: 2026-02-30\n---\n");
+        assert_eq!(err.line, 3);
+        assert!(err.message.contains("no day 30"), "{}", err.message);
+    }
+
+    #[test]
+    fn a_date_of_the_wrong_type_says_what_was_expected() {
+        let err = parse_err("---\ntitle: x\ndate: 2026\n---\n");
+        assert!(err.message.contains("YYYY-MM-DD"), "{}", err.message);
+    }
+
+    #[test]
+    fn a_slug_must_stay_in_its_own_directory() {
+        for slug in ["../../../escape", "a/b", "a\\\\b", ".", "..", " padded"] {
+            let e = parse_err(&format!(
+                "---\ntitle: T\ndate: 2026-01-01\nslug: {slug:?}\n---\n"
+            ));
+            assert_eq!(e.line, 4, "{slug}");
+            assert!(e.message.contains("must be one path segment"), "{slug}");
+        }
+        let ok = parse_ok("---\ntitle: T\ndate: 2026-01-01\nslug: v1.2-notes\n---\n");
+        assert_eq!(ok.meta.slug.as_deref(), Some("v1.2-notes"));
+    }
+
+    #[test]
+    fn a_missing_title_is_an_error() {
+        let err = parse_err("---\ndate: 2026-04-16\n---\n");
+        assert!(err.message.contains("title"), "{}", err.message);
+    }
+
+    #[test]
+    fn unknown_keys_are_warnings_with_their_line() {
+        let parsed = parse_ok("---\ntitle: x\ndate: 2026-04-16\ntgas: [oops]\n---\n");
+        assert_eq!(
+            parsed.warnings,
+            ["post.md:4: unknown frontmatter key `tgas`"]
+        );
+    }
+
+    #[test]
+    fn the_error_renders_as_file_line_column() {
+        let err = parse_err("---\ntitle: x\ndate: nope\n---\n");
+        assert!(err.to_string().starts_with("post.md:3:1: invalid date"));
+    }
+}
@@ -0,0 +1,273 @@
+//! Build-time syntax highlighting for fenced code blocks.
+//!
+//! The fence's info string names the language and may ask for more:
+//!
+//! ```text
+//! ```rust{3,5-7} linenos
+//! ```
+//!
+//! marks lines 3 and 5 to 7 and numbers every line. Tokens come out as
+//! `<span class="hl-…">` with one class per scope atom (`hl-keyword
+//! hl-control`), so style.css themes them for both color schemes and the page
+//! needs no script. Each line is its own `<span class="line">`, which is what
+//! the marks and the numbers hang off.
+
+use std::sync::OnceLock;
+
+use syntect::{
+    html::{line_tokens_to_classed_spans, ClassStyle},
+    parsing::{ParseState, Scope, ScopeStack, SyntaxReference, SyntaxSet},
+    util::LinesWithEndings,
+};
+
+use crate::escape_html;
+
+const STYLE: ClassStyle = ClassStyle::SpacedPrefixed { prefix: "hl-" };
+
+fn syntaxes() -> &'static SyntaxSet {
+    static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+/// What a fence's info string asks for.
+#[derive(Debug, Default, PartialEq)]
+struct Fence<'a> {
+    lang: Option<&'a str>,
+    /// Inclusive, 1-based line ranges to mark.
+    marked: Vec<(usize, usize)>,
+    linenos: bool,
+}
+
+impl<'a> Fence<'a> {
+    fn parse(info: &'a str) -> Result<Self, String> {
+        let mut fence = Fence::default();
+        let mut words = info.split_whitespace();
+        if let Some(first) = words.next() {
+            let (lang, marks) = match first.find('{') {
+                Some(i) => first.split_at(i),
+                None => (first, ""),
+            };
+            // `rust,ignore` and friends, as rustdoc writes them.
+            let lang = lang.split(',').next().unwrap_or_default();
+            if !lang.is_empty() {
+                fence.lang = Some(lang);
+            }
+            if !marks.is_empty() {
+                fence.mark(marks)?;
+            }
+        }
+        for word in words {
+            match word {
+                "linenos" => fence.linenos = true,
+                _ if word.starts_with('{') => fence.mark(word)?,
+                _ => return Err(format!("unknown code block option {word:?} in {info:?}")),
+            }
+        }
+        Ok(fence)
+    }
+
+    fn mark(&mut self, spec: &str) -> Result<(), String> {
+        let invalid = || format!("invalid line marks {spec:?}: expected e.g. {{3,5-7}}");
+        let inner = spec
+            .strip_prefix('{')
+            .and_then(|s| s.strip_suffix('}'))
+            .ok_or_else(invalid)?;
+        for range in inner.split(',').map(str::trim) {
+            let (from, to) = range.split_once('-').unwrap_or((range, range));
+            let from: usize = from.trim().parse().map_err(|_| invalid())?;
+            let to: usize = to.trim().parse().map_err(|_| invalid())?;
+            if from == 0 || to < from {
+                return Err(invalid());
+            }
+            self.marked.push((from, to));
+        }
+        Ok(())
+    }
+
+    fn is_marked(&self, line: usize) -> bool {
+        self.marked
+            .iter()
+            .any(|&(from, to)| (from..=to).contains(&line))
+    }
+}
+
+/// A fenced code block as HTML, in place of pulldown-cmark's
+/// `<pre><code class="language-…">`. Code in a language syntect doesn't know
+/// is still split into lines, just without token spans.
+pub(crate) fn code_block(info: &str, code: &str) -> Result<String, String> {
+    let fence = Fence::parse(info)?;
+    let lines: Vec<&str> = LinesWithEndings::from(code).collect();
+    if let Some(&(_, last)) = fence.marked.iter().max_by_key(|(_, to)| *to) {
+        if last > lines.len() {
+            return Err(format!(
+                "line {last} is marked in {info:?}, but the block has {} lines",
+                lines.len()
+            ));
+        }
+    }
+
+    let mut out = String::from("<pre class=\"code");
+    if fence.linenos {
+        out.push_str(" linenos");
+    }
+    out.push('"');
+    if let Some(lang) = fence.lang {
+        out.push_str(&format!(" data-lang=\"{}\"", escape_html(lang)));
+    }
+    out.push_str("><code");
+    if let Some(lang) = fence.lang {
+        out.push_str(&format!(" class=\"language-{}\"", escape_html(lang)));
+    }
+    out.push('>');
+
+    let mut highlighter = fence.lang.and_then(find_syntax).map(Highlighter::new);
+    for (i, line) in lines.iter().enumerate() {
+        let html = match &mut highlighter {
+            Some(h) => h.line(line)?,
+            None => escape_html(line.trim_end_matches('\n')),
+        };
+        out.push_str("<span class=\"line");
+        if fence.is_marked(i + 1) {
+            out.push_str(" marked");
+        }
+        out.push_str("\">");
+        out.push_str(&html);
+        out.push_str("</span>\n");
+    }
+    out.push_str("</code></pre>\n");
+    Ok(out)
+}
+
+fn find_syntax(lang: &str) -> Option<&'static SyntaxReference> {
+    let lang = match lang.to_ascii_lowercase().as_str() {
+        "shell" | "console" | "zsh" => "sh".to_string(),
+        other => other.to_string(),
+    };
+    syntaxes().find_syntax_by_token(&lang)
+}
+
+/// Carries the parser and the open scopes from one line to the next, so a
+/// string or comment that spans lines stays colored.
+struct Highlighter {
+    parser: ParseState,
+    scopes: ScopeStack,
+}
+
+impl Highlighter {
+    fn new(syntax: &SyntaxReference) -> Self {
+        Highlighter {
+            parser: ParseState::new(syntax),
+            scopes: ScopeStack::new(),
+        }
+    }
+
+    /// One line's spans, balanced: scopes still open from earlier lines are
+    /// reopened first and everything is closed at the end, so the line's
+    /// wrapper nests cleanly.
+    fn line(&mut self, line: &str) -> Result<String, String> {
+        let ops = self
+            .parser
+            .parse_line(line, syntaxes())
+            .map_err(|e| format!("highlighting: {e}"))?;
+        let mut html = String::new();
+        for &scope in self.scopes.as_slice() {
+            html.push_str(&open_span(scope));
+        }
+        let (spans, _) = line_tokens_to_classed_spans(line, &ops, STYLE, &mut self.scopes)
+            .map_err(|e| format!("highlighting: {e}"))?;
+        html.push_str(&spans);
+        for _ in self.scopes.as_slice() {
+            html.push_str("</span>");
+        }
+        // The newline goes between the line wrappers, not inside one.
+        Ok(html.replacen('\n', "", 1))
+    }
+}
+
+fn open_span(scope: Scope) -> String {
+    let classes: Vec<String> = scope
+        .build_string()
+        .split('.')
+        .map(|atom| format!("hl-{atom}"))
+        .collect();
+    format!("<span class=\"{}\">", classes.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_info_string() {
+        assert_eq!(Fence::parse("").unwrap(), Fence::default());
+        assert_eq!(
+            Fence::parse("rust{3,5-7} linenos").unwrap(),
+            Fence {
+                lang: Some("rust"),
+                marked: vec![(3, 3), (5, 7)],
+                linenos: true,
+            }
+        );
+        assert_eq!(
+            Fence::parse("rust,ignore {2}").unwrap(),
+            Fence {
+                lang: Some("rust"),
+                marked: vec![(2, 2)],
+                linenos: false,
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_marks_it_cannot_honor() {
+        for bad in [
+            "rust{}",
+            "rust{0}",
+            "rust{5-3}",
+            "rust{x}",
+            "rust{3",
+            "rust nolines",
+        ] {
+            assert!(Fence::parse(bad).is_err(), "{bad:?}");
+        }
+        let err = code_block("rust{4}", "fn main() {}\n").unwrap_err();
+        assert!(err.contains("1 lines"), "{err}");
+    }
+
+    #[test]
+    fn highlights_with_classes_and_marks_lines() {
+        let html = code_block("rust{2}", "// hi\nfn main() {}\n").unwrap();
+        assert!(html
+            .starts_with("<pre class=\"code\" data-lang=\"rust\"><code class=\"language-rust\">"));
+        assert!(html.contains("<span class=\"line marked\">"), "{html}");
+        assert!(html.contains("hl-comment"), "{html}");
+        assert!(html.contains("hl-storage"), "{html}");
+        assert_eq!(html.matches("<span class=\"line").count(), 2);
+    }
+
+    #[test]
+    fn every_line_closes_what_it_opens() {
+        // The block comment is open across the line break.
+        let html = code_block("c", "/* one\n   two */\nint x;\n").unwrap();
+        for line in html.lines().filter(|l| l.contains("class=\"line")) {
+            assert_eq!(
+                line.matches("<span").count(),
+                line.matches("</span>").count(),
+                "{line}"
+            );
+        }
+        assert!(
+            html.lines().nth(1).unwrap().contains("hl-comment"),
+            "{html}"
+        );
+    }
+
+    #[test]
+    fn unknown_languages_are_escaped_and_still_split_into_lines() {
+        let html = code_block("nosuchlang linenos", "a < b\n").unwrap();
+        assert_eq!(
+            html,
+            "<pre class=\"code linenos\" data-lang=\"nosuchlang\"><code class=\"language-nosuchlang\"><span class=\"line\">a &lt; b</span>\n</code></pre>\n"
+        );
+    }
+}
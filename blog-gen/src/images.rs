@@ -0,0 +1,334 @@
+//! Responsive images for posts.
+//!
+//! Every PNG or JPEG next to a post is re-encoded as AVIF and WebP at a few
+//! widths no larger than the original, and each `<img>` that points at one
+//! becomes a `<picture>` offering them, with the original as the fallback.
+//! The `<img>` also gets its intrinsic `width`/`height`, so the page doesn't
+//! shift when it loads, and every image after the first loads lazily (the
+//! first is usually the hero, which should not wait).
+//!
+//! Variant names carry a hash of the source bytes, `graph-3f2a9c01d4e7-960w.avif`,
+//! so an edited image gets new URLs and an unchanged one is never encoded
+//! twice: a variant already in the output, or in the cache under
+//! `.blog-gen/images`, is reused as is.
+
+use std::{
+    fs,
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+
+use image::{
+    codecs::{avif::AvifEncoder, webp::WebPEncoder},
+    imageops::FilterType,
+    DynamicImage, ImageReader,
+};
+use sha2::{Digest, Sha256};
+
+/// Widths to offer below the original's; the original width is offered too,
+/// up to the widest of these.
+const WIDTHS: [u32; 3] = [480, 960, 1440];
+
+/// The article column is 48rem wide less its padding.
+const SIZES: &str = "(max-width: 48rem) 100vw, 44.5rem";
+
+/// rav1e's speed (1–10) and quality (1–100). Speed 6 keeps a rebuild with a
+/// new image to seconds rather than minutes.
+const AVIF_SPEED: u8 = 6;
+const AVIF_QUALITY: u8 = 70;
+
+#[derive(Clone, Copy)]
+enum Format {
+    Avif,
+    Webp,
+}
+
+impl Format {
+    const ALL: [Format; 2] = [Format::Avif, Format::Webp];
+
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Avif => "avif",
+            Format::Webp => "webp",
+        }
+    }
+}
+
+/// A source image and the variants planned for it.
+struct Source {
+    path: PathBuf,
+    bytes: Vec<u8>,
+    stem: String,
+    hash: String,
+    width: u32,
+    height: u32,
+}
+
+impl Source {
+    fn open(path: &Path) -> Result<Self, String> {
+        let bytes = fs::read(path).map_err(|e| format!("read {}: {e}", path.display()))?;
+        let (width, height) = ImageReader::new(Cursor::new(&bytes))
+            .with_guessed_format()
+            .map_err(|e| format!("{}: {e}", path.display()))?
+            .into_dimensions()
+            .map_err(|e| format!("{}: {e}", path.display()))?;
+        let digest = Sha256::digest(&bytes);
+        let hash = digest[..6].iter().map(|b| format!("{b:02x}")).collect();
+        let stem = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("image")
+            .to_string();
+        Ok(Source {
+            path: path.to_path_buf(),
+            bytes,
+            stem,
+            hash,
+            width,
+            height,
+        })
+    }
+
+    fn widths(&self) -> Vec<u32> {
+        let mut widths: Vec<u32> = WIDTHS.into_iter().filter(|&w| w < self.width).collect();
+        if self.width <= WIDTHS[WIDTHS.len() - 1] {
+            widths.push(self.width);
+        }
+        widths
+    }
+
+    fn variant(&self, width: u32, format: Format) -> String {
+        format!(
+            "{}-{}-{width}w.{}",
+            self.stem,
+            self.hash,
+            format.extension()
+        )
+    }
+
+    fn srcset(&self, format: Format) -> String {
+        self.widths()
+            .into_iter()
+            .map(|w| format!("{} {w}w", self.variant(w, format)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Whether blog-gen makes variants of the file at `path`.
+fn is_raster(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| matches!(e.to_ascii_lowercase().as_str(), "png" | "jpg" | "jpeg"))
+}
+
+/// Writes the variants of the image at `src` into `dst_dir`, returning
+/// their file names.
+pub(crate) fn write_variants(
+    src: &Path,
+    dst_dir: &Path,
+    cache_dir: &Path,
+) -> Result<Vec<String>, String> {
+    let mut names = Vec::new();
+    if !is_raster(src) {
+        return Ok(names);
+    }
+    let source = Source::open(src)?;
+    let mut decoded: Option<DynamicImage> = None;
+    for width in source.widths() {
+        for format in Format::ALL {
+            let name = source.variant(width, format);
+            let out = dst_dir.join(&name);
+            names.push(name.clone());
+            if out.exists() {
+                continue;
+            }
+            let cached = cache_dir.join(&name);
+            if !cached.exists() {
+                let image = match &decoded {
+                    Some(image) => image,
+                    None => decoded.insert(
+                        image::load_from_memory(&source.bytes)
+                            .map_err(|e| format!("decode {}: {e}", source.path.display()))?,
+                    ),
+                };
+                let bytes =
+                    encode(image, width, format).map_err(|e| format!("encode {name}: {e}"))?;
+                fs::create_dir_all(cache_dir)
+                    .map_err(|e| format!("create {}: {e}", cache_dir.display()))?;
+                fs::write(&cached, bytes)
+                    .map_err(|e| format!("write {}: {e}", cached.display()))?;
+            }
+            fs::copy(&cached, &out)
+                .map_err(|e| format!("copy {} -> {}: {e}", cached.display(), out.display()))?;
+        }
+    }
+    Ok(names)
+}
+
+fn encode(image: &DynamicImage, width: u32, format: Format) -> image::ImageResult<Vec<u8>> {
+    let resized;
+    let image = if width == image.width() {
+        image
+    } else {
+        resized = image.resize(width, u32::MAX, FilterType::Lanczos3);
+        &resized
+    };
+    let mut bytes = Vec::new();
+    match format {
+        Format::Avif => image.write_with_encoder(AvifEncoder::new_with_speed_quality(
+            &mut bytes,
+            AVIF_SPEED,
+            AVIF_QUALITY,
+        ))?,
+        // image's WebP encoder is lossless only, which suits diagrams and
+        // screenshots, what posts mostly have.
+        Format::Webp => image.write_with_encoder(WebPEncoder::new_lossless(&mut bytes))?,
+    }
+    Ok(bytes)
+}
+
+/// Rewrites the `<img>` tags in `html` that point at a PNG or JPEG in
+/// `asset_dir` into `<picture>` elements with sizes, as described above.
+/// Other images are left as they are.
+pub(crate) fn responsive(html: &str, asset_dir: &Path) -> Result<String, String> {
+    let mut out = String::with_capacity(html.len());
+    let mut rest = html;
+    let mut first = true;
+    while let Some(start) = rest.find("<img ") {
+        let Some(len) = rest[start..].find('>') else {
+            break;
+        };
+        let tag = &rest[start..start + len + 1];
+        out.push_str(&rest[..start]);
+        rest = &rest[start + tag.len()..];
+
+        let Some(path) = attr(tag, "src").and_then(|src| local(asset_dir, src)) else {
+            out.push_str(tag);
+            first = false;
+            continue;
+        };
+        let source = Source::open(&path)?;
+        let (open, close) = match tag.strip_suffix("/>") {
+            Some(open) => (open.trim_end(), " />"),
+            None => (tag[..tag.len() - 1].trim_end(), ">"),
+        };
+        let mut img = open.to_string();
+        if attr(tag, "width").is_none() && attr(tag, "height").is_none() {
+            img.push_str(&format!(
+                " width=\"{}\" height=\"{}\"",
+                source.width, source.height
+            ));
+        }
+        if attr(tag, "loading").is_none() && !first {
+            img.push_str(" loading=\"lazy\" decoding=\"async\"");
+        }
+        img.push_str(close);
+        first = false;
+
+        out.push_str("<picture>");
+        for format in Format::ALL {
+            out.push_str(&format!(
+                "<source type=\"image/{}\" srcset=\"{}\" sizes=\"{SIZES}\">",
+                format.extension(),
+                source.srcset(format)
+            ));
+        }
+        out.push_str(&img);
+        out.push_str("</picture>");
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+fn attr<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let marker = format!(" {name}=\"");
+    let start = tag.find(&marker)? + marker.len();
+    let end = tag[start..].find('"')?;
+    Some(&tag[start..start + end])
+}
+
+/// The file a relative `src` names in `asset_dir`, if it is one of ours.
+fn local(asset_dir: &Path, src: &str) -> Option<PathBuf> {
+    let name = src.strip_prefix("./").unwrap_or(src);
+    if name.is_empty() || name.contains(['/', ':', '?', '#']) {
+        return None;
+    }
+    let path = asset_dir.join(name);
+    (is_raster(&path) && path.is_file()).then_some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageFormat, RgbImage};
+
+    fn fixture(width: u32, height: u32) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        RgbImage::from_fn(width, height, |x, y| image::Rgb([x as u8, y as u8, 128]))
+            .save_with_format(dir.path().join("graph.png"), ImageFormat::Png)
+            .unwrap();
+        dir
+    }
+
+    #[test]
+    fn small_images_get_one_variant_per_format_at_their_own_width() {
+        let dir = fixture(40, 30);
+        let source = Source::open(&dir.path().join("graph.png")).unwrap();
+        assert_eq!(source.widths(), [40]);
+        assert_eq!(source.hash.len(), 12);
+
+        let large = Source {
+            width: 2000,
+            ..Source::open(&dir.path().join("graph.png")).unwrap()
+        };
+        assert_eq!(large.widths(), [480, 960, 1440]);
+    }
+
+    #[test]
+    fn rewrites_local_images_into_pictures() {
+        let dir = fixture(40, 30);
+        let html = "<p><img src=\"./graph.png\" alt=\"a\" /></p><img src=\"graph.png\" alt=\"b\" /><img src=\"https://example.com/x.png\" alt=\"c\" />";
+        let out = responsive(html, dir.path()).unwrap();
+        let hash = Source::open(&dir.path().join("graph.png")).unwrap().hash;
+        assert_eq!(
+            out,
+            format!(
+                "<p><picture><source type=\"image/avif\" srcset=\"graph-{hash}-40w.avif 40w\" sizes=\"{SIZES}\"><source type=\"image/webp\" srcset=\"graph-{hash}-40w.webp 40w\" sizes=\"{SIZES}\"><img src=\"./graph.png\" alt=\"a\" width=\"40\" height=\"30\" /></picture></p>\
+                 <picture><source type=\"image/avif\" srcset=\"graph-{hash}-40w.avif 40w\" sizes=\"{SIZES}\"><source type=\"image/webp\" srcset=\"graph-{hash}-40w.webp 40w\" sizes=\"{SIZES}\"><img src=\"graph.png\" alt=\"b\" width=\"40\" height=\"30\" loading=\"lazy\" decoding=\"async\" /></picture>\
+                 <img src=\"https://example.com/x.png\" alt=\"c\" />"
+            )
+        );
+    }
+
+    #[test]
+    fn variants_are_encoded_once_and_then_reused() {
+        let dir = fixture(40, 30);
+        let (out, cache) = (dir.path().join("out"), dir.path().join("cache"));
+        fs::create_dir_all(&out).unwrap();
+        let src = dir.path().join("graph.png");
+        write_variants(&src, &out, &cache).unwrap();
+
+        let hash = Source::open(&src).unwrap().hash;
+        for ext in ["avif", "webp"] {
+            let name = format!("graph-{hash}-40w.{ext}");
+            let written = image::open(out.join(&name)).map(|i| i.width());
+            // image reads WebP back; AVIF decoding is a separate feature.
+            if ext == "webp" {
+                assert_eq!(written.unwrap(), 40);
+            }
+            assert!(cache.join(&name).is_file(), "{name}");
+        }
+
+        // A fresh output directory is filled from the cache, not re-encoded.
+        let marker = b"cached".to_vec();
+        fs::write(cache.join(format!("graph-{hash}-40w.webp")), &marker).unwrap();
+        let again = dir.path().join("again");
+        fs::create_dir_all(&again).unwrap();
+        write_variants(&src, &again, &cache).unwrap();
+        assert_eq!(
+            fs::read(again.join(format!("graph-{hash}-40w.webp"))).unwrap(),
+            marker
+        );
+    }
+}
@@ -1,48 +1,434 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     fs,
     path::{Path, PathBuf},
 };
 
-use pulldown_cmark::{html, CowStr, Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+mod activitypub;
+mod books;
+mod card;
+mod date;
+mod feed;
+mod font;
+mod frontmatter;
+mod highlight;
+mod images;
+mod links;
+mod manifest;
+mod math;
+mod redirects;
+mod related;
+mod search;
+mod shortcode;
+mod site;
+mod template;
+mod webmention;
 
-const SITE_URL: &str = "https://erickcestari.dev";
+use serde_json::{json, Value};
+
+use pulldown_cmark::{
+    html, CodeBlockKind, CowStr, Event, HeadingLevel, Options, Parser, Tag, TagEnd,
+};
+
+use crate::{
+    date::Timestamp,
+    manifest::{Build, Inputs, Rendered},
+    redirects::{Published, Redirects},
+    template::Templates,
+};
+
+/// Directories of `static/` that hold nothing but generated pages; any other
+/// file found in one is stale and removed.
+const GENERATED_DIRS: [&str; 2] = ["blog", "drafts"];
 
 /// Pages that are pure layout: no data to interpolate beyond the shared chrome.
-const STATIC_PAGES: [&str; 2] = ["books.html", "404.html"];
+const STATIC_PAGES: [&str; 1] = ["404.html"];
 
 struct Post {
     slug: String,
     title: String,
-    date: String,
+    date: Timestamp,
+    updated: Option<Timestamp>,
     description: String,
     html: String,
-    toc: String,
+    /// The headings the table of contents lists; none for a short post.
+    toc: Vec<Heading>,
     reading_time: u32,
+    /// Display names, in frontmatter order. Each links to `/blog/tags/<slug>/`.
+    tags: Vec<String>,
+    /// Rendered under `/drafts/` and left out of every list, feed and sitemap;
+    /// only a signed preview link (see `jatai::preview`) opens it.
+    draft: bool,
+    /// The frontmatter's social card image, as written; see
+    /// [`Post::og_image`].
+    og_image: Option<String>,
+    /// The series the post is a part of, as the frontmatter names it.
+    series: Option<String>,
+    /// Hash of the markdown and the files beside it; see `manifest`.
+    source: String,
 }
 
+impl Post {
+    /// What feeds and the sitemap call "updated": the last edit, or
+    /// publication when there has been none.
+    fn last_modified(&self) -> Timestamp {
+        self.updated.unwrap_or(self.date)
+    }
+
+    fn path(&self) -> String {
+        let section = if self.draft { "drafts" } else { "blog" };
+        format!("/{}/{}/", section, self.slug)
+    }
+
+    /// The absolute URL of the image link previews show: the frontmatter's
+    /// `og_image` (relative to the post, a path on the site or a URL), else
+    /// the generated card when cards are drawn, else the site's image.
+    fn og_image(&self, site: &Site) -> String {
+        match self.og_image.as_deref() {
+            Some(url) if url.starts_with("http://") || url.starts_with("https://") => {
+                url.to_string()
+            }
+            Some(path) if path.starts_with('/') => site.url(path),
+            Some(file) => site.url(&format!("{}{file}", self.path())),
+            None if site.card.is_some() => site.url(&format!("{}{CARD}", self.path())),
+            None => site.og_image_url(),
+        }
+    }
+}
+
+/// The published posts filed under one tag, newest first. Casings of one
+/// name ("Rust", "rust") share a page, titled by the first seen.
+struct TagPage<'a> {
+    slug: String,
+    name: String,
+    posts: Vec<&'a Post>,
+}
+
+/// A heading in a post, as its table of contents lists it.
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
 struct Heading {
     level: u8,
     id: String,
     text: String,
 }
 
+pub use links::BrokenLink;
+pub use site::{ActivityPub, CardStyle, NavLink, Site};
+
+/// The generated social card, beside each post's page.
+const CARD: &str = "og.png";
+
+/// What a build made, and what it found wrong without failing over.
+#[derive(Debug)]
+pub struct Generated {
+    /// Posts published; drafts are rendered but not counted.
+    pub posts: usize,
+    /// Internal links in the output that lead nowhere, for the caller to
+    /// report: a library cannot know where its stderr ends up.
+    pub broken_links: Vec<BrokenLink>,
+}
+
+/// Knobs for [`generate_with`]; the defaults are what [`generate`] uses.
+#[derive(Debug, Clone, Default)]
+pub struct BuildOptions {
+    /// Fail the build on a broken internal link or anchor instead of only
+    /// warning about it.
+    pub strict_links: bool,
+    /// When a published post moves to a new path, add a redirect from the
+    /// old one to `content/redirects.toml` rather than only warning. Off by
+    /// default, so a build never edits the sources it builds from.
+    pub redirect_moves: bool,
+    /// Build for this origin instead of `site.toml`'s `base_url`, such as a
+    /// staging host.
+    pub base_url: Option<String>,
+}
+
 /// Renders `content/blog` and `templates` into `static`, relative to `root`.
-/// Returns the number of posts generated.
-pub fn generate(root: &Path) -> Result<usize, String> {
+pub fn generate(root: &Path) -> Result<Generated, String> {
+    generate_with(root, &BuildOptions::default())
+}
+
+/// [`generate`] with `options`. Once the output is written every internal
+/// link in `static` is checked (see [`BrokenLink`]); broken ones are
+/// returned, or are an error when `options.strict_links` is set.
+pub fn generate_with(root: &Path, options: &BuildOptions) -> Result<Generated, String> {
     let content_dir = root.join("content/blog");
     let static_dir = root.join("static");
-    let out_dir = static_dir.join("blog");
     let templates_dir = root.join("templates");
+    let image_cache = root.join(".blog-gen/images");
 
-    let layout = Layout::load(&templates_dir)?;
-    let post_tmpl = layout.template(&templates_dir, "post.html")?;
-    let list_tmpl = layout.template(&templates_dir, "list.html")?;
-    let home_tmpl = layout.template(&templates_dir, "home.html")?;
+    let mut site = Site::load(root)?;
+    if let Some(base_url) = &options.base_url {
+        site = site.with_base_url(base_url)?;
+    }
+    let templates = Templates::load(&templates_dir, json!({ "site": site.context() }))?;
+
+    let painter = match &site.card {
+        Some(style) => Some(card::Painter::load(root, style)?),
+        None => None,
+    };
+
+    let mut build = Build::start(root, &static_dir, &GENERATED_DIRS);
+    let posts = load_posts(&content_dir, build.rendered())?;
+
+    let all_posts: Vec<&Post> = posts.iter().map(|(p, _)| p).collect();
+    let links = related::links(&all_posts);
+    let webmentions = webmention::load(&root.join("content/webmentions"))?;
+
+    for (i, (post, asset_dir)) in posts.iter().enumerate() {
+        let dir = post.path().trim_matches('/').to_string();
+        let (series, related) = (&links.series[i], &links.related[i]);
+        let mentions = webmentions.get(&post.path()).map_or(&[][..], Vec::as_slice);
+        let inputs = Inputs::new()
+            .add(templates.fingerprint("post.html"))
+            .add(&post.source)
+            .add(post.og_image(&site))
+            .add(json!([series, related, mentions]).to_string())
+            .finish();
+        build.output(&format!("{dir}/index.html"), &inputs, || {
+            let mut context = post_context(&site, post);
+            context["series"] = series.clone();
+            context["related"] = json!(related);
+            context["webmentions"] = json!(mentions);
+            Ok(templates
+                .render("post.html", &json!({ "post": context }))?
+                .into_bytes())
+        })?;
+        if let (Some(painter), None) = (&painter, &post.og_image) {
+            let date = post.date.humanize();
+            let inputs = Inputs::new()
+                .add(painter.fingerprint())
+                .add(&post.title)
+                .add(&date)
+                .add(&site.title)
+                .finish();
+            build.output(&format!("{dir}/{CARD}"), &inputs, || {
+                painter.render(&post.title, &date, &site.title)
+            })?;
+        }
+        if let Some(src) = asset_dir {
+            copy_assets(
+                &mut build,
+                src,
+                &static_dir,
+                &dir,
+                &post.source,
+                &image_cache,
+            )?;
+        }
+        build.keep_rendered(
+            &post.source,
+            Rendered {
+                html: post.html.clone(),
+                toc: post.toc.clone(),
+                reading_time: post.reading_time,
+            },
+        );
+    }
+
+    let bare_posts: Vec<&Post> = posts.iter().map(|(p, _)| p).filter(|p| !p.draft).collect();
+    let tags = collect_tags(&bare_posts)?;
+    for (page, tmpl) in [
+        ("blog/index.html", "list.html"),
+        ("index.html", "home.html"),
+    ] {
+        let inputs = sources(&templates.fingerprint(tmpl), &bare_posts);
+        build.output(page, &inputs, || {
+            let context = json!({ "years": by_year(&bare_posts) });
+            Ok(templates.render(tmpl, &context)?.into_bytes())
+        })?;
+    }
+    write_feeds(&mut build, &site, &bare_posts, &site.title, "/blog")?;
+
+    let inputs = sources(&templates.fingerprint("tags.html"), &bare_posts);
+    build.output("blog/tags/index.html", &inputs, || {
+        let context =
+            json!({ "tags": tags.iter().map(|t| tag_context(&site, t)).collect::<Vec<_>>() });
+        Ok(templates.render("tags.html", &context)?.into_bytes())
+    })?;
+    for tag in &tags {
+        let path = tag_path(&tag.slug);
+        build.output(
+            &format!("{}index.html", path.trim_start_matches('/')),
+            &sources(&templates.fingerprint("tag.html"), &tag.posts),
+            || {
+                let context =
+                    json!({ "tag": tag_context(&site, tag), "years": by_year(&tag.posts) });
+                Ok(templates.render("tag.html", &context)?.into_bytes())
+            },
+        )?;
+        write_feeds(
+            &mut build,
+            &site,
+            &tag.posts,
+            &format!("{}: {}", site.title, tag.name),
+            &path,
+        )?;
+    }
+
+    build.output(
+        "sitemap.xml",
+        &sources(&site.fingerprint(), &bare_posts),
+        || Ok(render_sitemap(&site, &bare_posts, &tags).into_bytes()),
+    )?;
+
+    let inputs = sources(&templates.fingerprint(search::RESULT_TEMPLATE), &bare_posts);
+    build.output("search/index.bin", &inputs, || {
+        search::index(&templates, &site, &bare_posts)
+    })?;
+    build.output(
+        "search/index.html",
+        &templates.fingerprint("search.html"),
+        || {
+            let slots = json!({
+                "query": site_search::QUERY_SLOT,
+                "summary": site_search::SUMMARY_SLOT,
+                "results": site_search::RESULTS_SLOT,
+            });
+            Ok(templates
+                .render("search.html", &json!({ "search": slots }))?
+                .into_bytes())
+        },
+    )?;
+
+    if let Some(ap) = &site.activitypub {
+        let inputs = sources(&site.fingerprint(), &bare_posts);
+        build.output(activitypub::ACTOR, &site.fingerprint(), || {
+            Ok(activitypub::actor(&site, &ap.username).into_bytes())
+        })?;
+        build.output(activitypub::OUTBOX, &inputs, || {
+            Ok(activitypub::outbox(&site, &bare_posts).into_bytes())
+        })?;
+        build.output(activitypub::WEBFINGER, &site.fingerprint(), || {
+            Ok(activitypub::webfinger(&site, &ap.username).into_bytes())
+        })?;
+    }
 
+    let shelves = books::load(&root.join("content/books.toml"), &static_dir)?;
+    let inputs = Inputs::new()
+        .add(templates.fingerprint("books.html"))
+        .add(serde_json::to_string(&shelves).map_err(|e| e.to_string())?)
+        .finish();
+    build.output("books.html", &inputs, || {
+        let context = json!({ "shelves": shelves });
+        Ok(templates.render("books.html", &context)?.into_bytes())
+    })?;
+
+    for page in STATIC_PAGES {
+        let inputs = templates.fingerprint(page);
+        build.output(page, &inputs, || {
+            Ok(templates.render(page, &json!({}))?.into_bytes())
+        })?;
+    }
+
+    // Last, so that every page the build writes is known by the time a
+    // redirect is checked against it.
+    write_redirects(
+        &mut build,
+        &root.join("content/redirects.toml"),
+        &site,
+        &templates,
+        &bare_posts,
+        options.redirect_moves,
+    )?;
+
+    build.finish()?;
+
+    let broken_links = links::check(&static_dir)?;
+    if options.strict_links && !broken_links.is_empty() {
+        let list: Vec<String> = broken_links.iter().map(ToString::to_string).collect();
+        return Err(format!(
+            "{} broken link(s):\n{}",
+            broken_links.len(),
+            list.join("\n")
+        ));
+    }
+
+    Ok(Generated {
+        posts: bare_posts.len(),
+        broken_links,
+    })
+}
+
+/// Writes a page at each old path in `redirects_file` sending readers on to
+/// the new one. A published post that moved since the last build gets its
+/// entry added first when `record_moves` is set, and a warning otherwise.
+fn write_redirects(
+    build: &mut Build,
+    redirects_file: &Path,
+    site: &Site,
+    templates: &Templates,
+    posts: &[&Post],
+    record_moves: bool,
+) -> Result<(), String> {
+    let mut redirects = Redirects::load(redirects_file)?;
+
+    let published: BTreeMap<String, Published> = posts
+        .iter()
+        .map(|p| {
+            let post = Published {
+                title: p.title.clone(),
+                date: p.date.to_string(),
+            };
+            (p.path(), post)
+        })
+        .collect();
+    for (from, to) in redirects::moves(build.published(), &published) {
+        if record_moves {
+            redirects.append(redirects_file, &from, &to)?;
+            eprintln!(
+                "note: {from} moved to {to}; added a redirect to {}",
+                redirects_file.display()
+            );
+        } else {
+            eprintln!(
+                "warning: {from} moved to {to}; add {from:?} = {to:?} to {} to keep old links working",
+                redirects_file.display()
+            );
+        }
+    }
+    for (path, post) in published {
+        build.publish(&path, post);
+    }
+
+    let resolved = redirects.resolved(|from| build.has_output(&redirects::page_file(from)))?;
+    for (from, to) in resolved {
+        let inputs = Inputs::new()
+            .add(templates.fingerprint("redirect.html"))
+            .add(&to)
+            .finish();
+        build.output(&redirects::page_file(&from), &inputs, || {
+            let context = json!({ "url": to, "absolute_url": site.url(&to) });
+            Ok(templates.render("redirect.html", &context)?.into_bytes())
+        })?;
+    }
+    Ok(())
+}
+
+/// Slugs of the posts marked `draft: true` under `root`, for tools that need
+/// to name one (such as `blog-gen preview`) without rendering the site.
+pub fn draft_slugs(root: &Path) -> Result<Vec<String>, String> {
+    let posts = load_posts(&root.join("content/blog"), &BTreeMap::new())?;
+    Ok(posts
+        .into_iter()
+        .filter(|(p, _)| p.draft)
+        .map(|(p, _)| p.slug)
+        .collect())
+}
+
+/// Every post under `content_dir`, newest first, each with the directory its
+/// assets live in when it has one. Posts whose source hashes to a key of
+/// `rendered` take their body from there instead of rendering it again. Two
+/// posts at one path are an error, rather than one overwriting the other.
+fn load_posts(
+    content_dir: &Path,
+    rendered: &BTreeMap<String, Rendered>,
+) -> Result<Vec<(Post, Option<PathBuf>)>, String> {
     let mut posts: Vec<(Post, Option<PathBuf>)> = Vec::new();
+    // URL path -> the file of the post there, so two cannot share one.
+    let mut sources: BTreeMap<String, PathBuf> = BTreeMap::new();
     let read_dir =
-        fs::read_dir(&content_dir).map_err(|e| format!("read {}: {e}", content_dir.display()))?;
+        fs::read_dir(content_dir).map_err(|e| format!("read {}: {e}", content_dir.display()))?;
     for entry in read_dir.flatten() {
         let path = entry.path();
         let (md_path, asset_dir, default_slug) = if path.is_file() {
@@ -70,130 +456,120 @@ pub fn generate(root: &Path) -> Result<usize, String> {
             continue;
         };
 
-        match parse_post(&md_path, &default_slug) {
-            Ok(p) => posts.push((p, asset_dir)),
-            Err(e) => {
-                eprintln!("Skipping {}: {e}", md_path.display());
+        match parse_post(&md_path, &default_slug, asset_dir.as_deref(), rendered)? {
+            Some(p) => {
+                if let Some(other) = sources.insert(p.path(), md_path.clone()) {
+                    return Err(format!(
+                        "{} and {} are both at {}",
+                        other.display(),
+                        md_path.display(),
+                        p.path()
+                    ));
+                }
+                posts.push((p, asset_dir));
             }
+            None => eprintln!("Skipping {}: no frontmatter", md_path.display()),
         }
     }
-    posts.sort_by(|a, b| b.0.date.cmp(&a.0.date));
-
-    fs::create_dir_all(&out_dir).map_err(|e| format!("create {}: {e}", out_dir.display()))?;
-
-    for (post, asset_dir) in &posts {
-        let dir = out_dir.join(&post.slug);
-        fs::create_dir_all(&dir).map_err(|e| format!("create {}: {e}", dir.display()))?;
-        write(&dir.join("index.html"), render_post(&post_tmpl, post))?;
-        if let Some(src) = asset_dir {
-            copy_assets(src, &dir)?;
-        }
-    }
-
-    let bare_posts: Vec<&Post> = posts.iter().map(|(p, _)| p).collect();
-    write(
-        &out_dir.join("index.html"),
-        render_list(&list_tmpl, &bare_posts),
-    )?;
-    write(
-        &static_dir.join("index.html"),
-        render_list(&home_tmpl, &bare_posts),
-    )?;
-    write(&out_dir.join("feed.xml"), render_atom(&bare_posts))?;
-    write(&static_dir.join("sitemap.xml"), render_sitemap(&bare_posts))?;
-
-    for page in STATIC_PAGES {
-        write(
-            &static_dir.join(page),
-            layout.template(&templates_dir, page)?,
-        )?;
-    }
-
-    Ok(posts.len())
+    posts.sort_by_key(|(p, _)| std::cmp::Reverse(p.date.unix_seconds()));
+    Ok(posts)
 }
 
-/// The chrome shared by every page, injected wherever a template says
-/// `{{header}}` / `{{footer}}`.
-struct Layout {
-    header: String,
-    footer: String,
+fn read(path: &Path) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|e| format!("read {}: {e}", path.display()))
 }
 
-impl Layout {
-    fn load(templates_dir: &Path) -> Result<Self, String> {
-        let partials = templates_dir.join("partials");
-        Ok(Layout {
-            header: read(&partials.join("header.html"))?,
-            footer: read(&partials.join("footer.html"))?,
-        })
-    }
-
-    /// Reads a template and wraps it in the shared chrome. Trailing newlines are
-    /// trimmed so a partial's own newline is the only one at the seam.
-    fn template(&self, templates_dir: &Path, name: &str) -> Result<String, String> {
-        let tmpl = read(&templates_dir.join(name))?;
-        Ok(tmpl
-            .replace("{{header}}", self.header.trim_end())
-            .replace("{{footer}}", self.footer.trim_end()))
+/// `Ok(None)` for a markdown file with no frontmatter, which is not a post.
+/// A post whose frontmatter is broken fails the build: shipping it without
+/// its title or date would be worse than not shipping.
+fn parse_post(
+    path: &Path,
+    default_slug: &str,
+    asset_dir: Option<&Path>,
+    rendered: &BTreeMap<String, Rendered>,
+) -> Result<Option<Post>, String> {
+    let raw = read(path)?;
+    let Some(parsed) = frontmatter::parse(path, &raw).map_err(|e| e.to_string())? else {
+        return Ok(None);
+    };
+    for warning in &parsed.warnings {
+        eprintln!("warning: {warning}");
     }
-}
+    let meta = parsed.meta;
+    let body = parsed.body;
 
-fn read(path: &Path) -> Result<String, String> {
-    fs::read_to_string(path).map_err(|e| format!("read {}: {e}", path.display()))
-}
+    let source = source_hash(&raw, default_slug, asset_dir)?;
+    let Rendered {
+        html,
+        toc,
+        reading_time,
+    } = match rendered.get(&source) {
+        Some(done) => done.clone(),
+        None => render_body(path, &raw, body, asset_dir)?,
+    };
 
-fn write(path: &Path, contents: String) -> Result<(), String> {
-    fs::write(path, contents).map_err(|e| format!("write {}: {e}", path.display()))
+    Ok(Some(Post {
+        slug: meta.slug.unwrap_or_else(|| default_slug.to_string()),
+        title: meta.title,
+        date: meta.date,
+        updated: meta.updated,
+        description: meta.description,
+        html,
+        toc,
+        reading_time,
+        tags: meta.tags,
+        draft: meta.draft,
+        og_image: meta.og_image,
+        series: meta.series,
+        source,
+    }))
 }
 
-fn parse_post(path: &Path, default_slug: &str) -> Result<Post, String> {
-    let raw = fs::read_to_string(path).map_err(|e| e.to_string())?;
-    let (meta, body) = split_frontmatter(&raw);
+/// Renders the markdown `body` of the post read from `path` as `raw`.
+fn render_body(
+    path: &Path,
+    raw: &str,
+    body: &str,
+    asset_dir: Option<&Path>,
+) -> Result<Rendered, String> {
+    let body_start = raw[..raw.len() - body.len()].matches('\n').count();
+    let expanded = shortcode::expand(body)
+        .map_err(|e| format!("{}:{}: {}", path.display(), body_start + e.line, e.message))?;
 
-    let mut title = String::new();
-    let mut date = String::new();
-    let mut description = String::new();
-    let mut slug = default_slug.to_string();
-
-    for line in meta.lines() {
-        let line = line.trim();
-        if line.is_empty() {
-            continue;
-        }
-        let Some((k, v)) = line.split_once(':') else {
-            continue;
-        };
-        let key = k.trim();
-        let val = v.trim().trim_matches('"').trim_matches('\'').to_string();
-        match key {
-            "title" => title = val,
-            "date" => date = val,
-            "description" => description = val,
-            "slug" => slug = val,
-            _ => {}
-        }
+    let (mut html, headings) =
+        render_markdown(&expanded).map_err(|e| format!("{}: {e}", path.display()))?;
+    if let Some(dir) = asset_dir {
+        html = images::responsive(&html, dir)?;
     }
+    Ok(Rendered {
+        html,
+        toc: toc_entries(headings),
+        reading_time: estimate_reading_minutes(body),
+    })
+}
 
-    if title.is_empty() {
-        return Err("missing frontmatter: title".into());
-    }
-    if date.is_empty() {
-        return Err("missing frontmatter: date".into());
+/// Hashes a post's markdown together with the name it is filed under, which
+/// is its slug unless the frontmatter says otherwise, and the files beside
+/// it, which its images are rendered from.
+fn source_hash(raw: &str, default_slug: &str, asset_dir: Option<&Path>) -> Result<String, String> {
+    let mut inputs = Inputs::new().add(raw).add(default_slug);
+    if let Some(dir) = asset_dir {
+        for path in asset_files(dir)? {
+            let bytes = fs::read(&path).map_err(|e| format!("read {}: {e}", path.display()))?;
+            inputs = inputs.add(path.file_name().unwrap_or_default().as_encoded_bytes());
+            inputs = inputs.add(bytes);
+        }
     }
+    Ok(inputs.finish())
+}
 
-    let (html_out, headings) = render_markdown(body);
-    let toc = render_toc(&headings);
-    let reading_time = estimate_reading_minutes(body);
-
-    Ok(Post {
-        slug,
-        title,
-        date,
-        description,
-        html: html_out,
-        toc,
-        reading_time,
-    })
+/// Hashes a listing's template (its fingerprint) with the sources of the
+/// posts it shows.
+fn sources(tmpl: &str, posts: &[&Post]) -> String {
+    posts
+        .iter()
+        .fold(Inputs::new().add(tmpl), |inputs, p| inputs.add(&p.source))
+        .finish()
 }
 
 fn estimate_reading_minutes(body: &str) -> u32 {
@@ -201,11 +577,14 @@ fn estimate_reading_minutes(body: &str) -> u32 {
     ((words as f32) / 220.0).ceil().max(1.0) as u32
 }
 
-fn render_markdown(body: &str) -> (String, Vec<Heading>) {
+/// Fails on a code block whose fence asks for something it can't have, such
+/// as marking a line past its end, and on TeX the math renderer can't read.
+fn render_markdown(body: &str) -> Result<(String, Vec<Heading>), String> {
     let mut options = Options::empty();
     options.insert(Options::ENABLE_STRIKETHROUGH);
     options.insert(Options::ENABLE_TABLES);
     options.insert(Options::ENABLE_FOOTNOTES);
+    options.insert(Options::ENABLE_MATH);
 
     let events: Vec<Event> = Parser::new_ext(body, options).collect();
 
@@ -265,8 +644,46 @@ fn render_markdown(body: &str) -> (String, Vec<Heading>) {
         .collect();
 
     let mut html_out = String::new();
-    html::push_html(&mut html_out, rewritten.into_iter());
-    (wrap_images_in_figures(&html_out), headings)
+    html::push_html(&mut html_out, render_code_and_math(rewritten)?.into_iter());
+    Ok((wrap_images_in_figures(&html_out), headings))
+}
+
+/// Replaces each code block's events with its highlighted HTML, and each
+/// `$…$` and `$$…$$` with MathML.
+fn render_code_and_math(events: Vec<Event>) -> Result<Vec<Event>, String> {
+    let mut out = Vec::with_capacity(events.len());
+    let mut events = events.into_iter();
+    while let Some(ev) = events.next() {
+        let kind = match ev {
+            Event::Start(Tag::CodeBlock(kind)) => kind,
+            Event::InlineMath(tex) => {
+                out.push(Event::InlineHtml(math::render(&tex, false)?.into()));
+                continue;
+            }
+            Event::DisplayMath(tex) => {
+                out.push(Event::InlineHtml(math::render(&tex, true)?.into()));
+                continue;
+            }
+            ev => {
+                out.push(ev);
+                continue;
+            }
+        };
+        let info = match kind {
+            CodeBlockKind::Fenced(info) => info,
+            CodeBlockKind::Indented => CowStr::Borrowed(""),
+        };
+        let mut code = String::new();
+        for ev in events.by_ref() {
+            match ev {
+                Event::End(TagEnd::CodeBlock) => break,
+                Event::Text(t) => code.push_str(&t),
+                _ => {}
+            }
+        }
+        out.push(Event::Html(highlight::code_block(&info, &code)?.into()));
+    }
+    Ok(out)
 }
 
 fn wrap_images_in_figures(html: &str) -> String {
@@ -331,6 +748,22 @@ fn heading_level_u8(level: HeadingLevel) -> u8 {
 }
 
 fn slugify(s: &str) -> String {
+    let out = hyphenate(s);
+    if out.is_empty() {
+        return "heading".to_string();
+    }
+    out
+}
+
+/// The slug of a tag's page: empty when the name has nothing to make a URL
+/// from, which `collect_tags` refuses.
+fn tag_slug(name: &str) -> String {
+    hyphenate(name)
+}
+
+/// `s` lowercased, each run of anything but ASCII letters and digits made a
+/// single hyphen, none at either end.
+fn hyphenate(s: &str) -> String {
     let mut out = String::with_capacity(s.len());
     let mut pending_dash = false;
     for c in s.chars() {
@@ -345,9 +778,6 @@ fn slugify(s: &str) -> String {
             pending_dash = true;
         }
     }
-    if out.is_empty() {
-        out.push_str("heading");
-    }
     out
 }
 
@@ -361,158 +791,186 @@ fn uniquify(seen: &mut HashMap<String, u32>, base: String) -> String {
     }
 }
 
-fn render_toc(headings: &[Heading]) -> String {
-    let items: Vec<&Heading> = headings
-        .iter()
+/// The h2 and h3 headings, which the table of contents lists when there are
+/// at least two of them.
+fn toc_entries(headings: Vec<Heading>) -> Vec<Heading> {
+    let entries: Vec<Heading> = headings
+        .into_iter()
         .filter(|h| h.level == 2 || h.level == 3)
         .collect();
-    if items.len() < 2 {
-        return String::new();
-    }
-    let mut out = String::from(
-        "<nav class=\"toc\" aria-label=\"table of contents\">\n  <details open>\n    <summary>contents</summary>\n    <ul>\n",
-    );
-    for h in &items {
-        out.push_str(&format!(
-            "      <li class=\"toc-h{}\"><a href=\"#{}\">{}</a></li>\n",
-            h.level,
-            h.id,
-            escape_html(&h.text)
-        ));
+    if entries.len() < 2 {
+        return Vec::new();
     }
-    out.push_str("    </ul>\n  </details>\n</nav>\n");
-    out
+    entries
 }
 
-fn split_frontmatter(raw: &str) -> (&str, &str) {
-    let trimmed = raw.trim_start_matches('\u{feff}');
-    let rest = match trimmed.strip_prefix("---\n") {
-        Some(r) => r,
-        None => match trimmed.strip_prefix("---\r\n") {
-            Some(r) => r,
-            None => return ("", raw),
-        },
-    };
-    if let Some(end) = rest.find("\n---") {
-        let meta = &rest[..end];
-        let after = &rest[end + 4..];
-        let body = after
-            .strip_prefix("\r\n")
-            .or_else(|| after.strip_prefix('\n'))
-            .unwrap_or(after);
-        return (meta, body);
-    }
-    ("", raw)
-}
-
-/// "2026-07-04" -> "July 4, 2026"; anything unparseable passes through as-is.
-fn humanize_date(iso: &str) -> String {
-    const MONTHS: [&str; 12] = [
-        "January",
-        "February",
-        "March",
-        "April",
-        "May",
-        "June",
-        "July",
-        "August",
-        "September",
-        "October",
-        "November",
-        "December",
-    ];
-    let mut parts = iso.splitn(3, '-');
-    let (Some(year), Some(month), Some(day)) = (parts.next(), parts.next(), parts.next()) else {
-        return iso.to_string();
-    };
-    let month_name = month
-        .parse::<usize>()
-        .ok()
-        .filter(|m| (1..=12).contains(m))
-        .map(|m| MONTHS[m - 1]);
-    match month_name {
-        Some(m) => format!("{} {}, {}", m, day.trim_start_matches('0'), year),
-        None => iso.to_string(),
-    }
-}
-
-fn render_post(tmpl: &str, p: &Post) -> String {
-    tmpl.replace("{{title}}", &escape_html(&p.title))
-        .replace("{{date_iso}}", &escape_html(&p.date))
-        .replace("{{date}}", &escape_html(&humanize_date(&p.date)))
-        .replace("{{description}}", &escape_html(&p.description))
-        .replace("{{slug}}", &p.slug)
-        .replace("{{url}}", &format!("{}/blog/{}/", SITE_URL, p.slug))
-        .replace("{{toc}}", &p.toc)
-        .replace("{{reading_time}}", &p.reading_time.to_string())
-        .replace("{{content}}", &p.html)
-}
-
-/// Posts are sorted newest-first, so runs of equal years are contiguous;
-/// each run opens with a year marker and its own <ul>.
-fn render_list(tmpl: &str, posts: &[&Post]) -> String {
-    let mut items = String::new();
-    let mut current_year = "";
+/// The newest modification among `posts`, which is when a page listing them
+/// last changed.
+fn newest_modification(posts: &[&Post]) -> Option<Timestamp> {
+    posts
+        .iter()
+        .map(|p| p.last_modified())
+        .max_by_key(|t| t.unix_seconds())
+}
+
+fn date_context(t: Timestamp) -> Value {
+    json!({ "iso": t.to_string(), "human": t.humanize() })
+}
+
+/// What `post.html` knows of a post as `post`, less its `series` and
+/// `related` posts (see `related`) and its `webmentions` (see `webmention`).
+fn post_context(site: &Site, p: &Post) -> Value {
+    let tags: Vec<Value> = p
+        .tags
+        .iter()
+        .map(|name| json!({ "name": name, "path": tag_path(&tag_slug(name)) }))
+        .collect();
+    json!({
+        "title": p.title,
+        "description": p.description,
+        "slug": p.slug,
+        "path": p.path(),
+        "url": site.url(&p.path()),
+        "date": date_context(p.date),
+        "updated": p.updated.map(date_context),
+        "reading_time": p.reading_time,
+        "tags": tags,
+        "toc": p.toc,
+        "og_image": p.og_image(site),
+        "content": p.html,
+    })
+}
+
+/// `posts` as `partials/post-list.html` lists them: newest first, so runs of
+/// equal years are contiguous, grouped under each year.
+fn by_year(posts: &[&Post]) -> Vec<Value> {
+    let mut years: Vec<(u16, Vec<Value>)> = Vec::new();
     for p in posts {
-        let year = p.date.get(..4).unwrap_or("");
-        if year != current_year {
-            if !current_year.is_empty() {
-                items.push_str("            </ul>\n");
-            }
-            items.push_str(&format!(
-                "            <h3 class=\"year\">{}</h3>\n            <ul class=\"post-list\">\n",
-                escape_html(year)
-            ));
-            current_year = year;
+        let year = p.date.year();
+        let item = json!({
+            "title": p.title,
+            "path": p.path(),
+            "date": date_context(p.date),
+        });
+        match years.last_mut() {
+            Some((y, items)) if *y == year => items.push(item),
+            _ => years.push((year, vec![item])),
         }
-        items.push_str(&format!(
-            "                <li><a href=\"/blog/{}/\">{}</a><time datetime=\"{}\">{}</time></li>\n",
-            p.slug,
-            escape_html(&p.title),
-            escape_html(&p.date),
-            escape_html(&humanize_date(&p.date))
-        ));
     }
-    if !current_year.is_empty() {
-        items.push_str("            </ul>");
-    }
-    tmpl.replace("{{items}}", items.trim_end())
+    years
+        .into_iter()
+        .map(|(year, posts)| json!({ "year": year, "posts": posts }))
+        .collect()
 }
 
-fn render_atom(posts: &[&Post]) -> String {
-    let updated = posts
-        .first()
-        .map(|p| format!("{}T00:00:00Z", p.date))
-        .unwrap_or_else(|| "1970-01-01T00:00:00Z".into());
+fn tag_path(slug: &str) -> String {
+    format!("/blog/tags/{}/", slug)
+}
 
-    let mut entries = String::new();
+/// Groups posts by tag slug, tags in slug order and posts in the order given.
+/// A tag that makes no slug, or two that make the same one but are more than
+/// two casings of a name ("C" and "C++"), would leave a page that is not the
+/// tag's, so both are errors.
+fn collect_tags<'a>(posts: &[&'a Post]) -> Result<Vec<TagPage<'a>>, String> {
+    let mut tags: Vec<TagPage<'a>> = Vec::new();
     for p in posts {
-        entries.push_str(&format!(
-            "  <entry>\n    <title>{title}</title>\n    <link href=\"{site}/blog/{slug}/\"/>\n    <id>{site}/blog/{slug}/</id>\n    <updated>{date}T00:00:00Z</updated>\n    <summary>{desc}</summary>\n  </entry>\n",
-            title = escape_html(&p.title),
-            site = SITE_URL,
-            slug = p.slug,
-            date = p.date,
-            desc = escape_html(&p.description),
-        ));
+        for name in &p.tags {
+            let slug = tag_slug(name);
+            if slug.is_empty() {
+                return Err(format!(
+                    "{}: tag {:?} has no ASCII letters or digits to make its page's URL from",
+                    p.slug, name
+                ));
+            }
+            let tag = match tags.iter().position(|t| t.slug == slug) {
+                Some(i) if !tags[i].name.eq_ignore_ascii_case(name) => {
+                    return Err(format!(
+                        "{}: tags {:?} and {:?} would share the page {}",
+                        p.slug,
+                        tags[i].name,
+                        name,
+                        tag_path(&slug)
+                    ));
+                }
+                Some(i) => &mut tags[i],
+                None => {
+                    tags.push(TagPage {
+                        slug,
+                        name: name.clone(),
+                        posts: Vec::new(),
+                    });
+                    tags.last_mut().expect("just pushed")
+                }
+            };
+            // A post that lists two spellings of one tag is still one entry.
+            if !tag.posts.iter().any(|q| std::ptr::eq(*q, *p)) {
+                tag.posts.push(p);
+            }
+        }
     }
+    tags.sort_by(|a, b| a.slug.cmp(&b.slug));
+    Ok(tags)
+}
 
-    format!(
-        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<feed xmlns=\"http://www.w3.org/2005/Atom\">\n  <title>erickcestari.dev</title>\n  <link href=\"{site}/blog/feed.xml\" rel=\"self\"/>\n  <link href=\"{site}/blog\"/>\n  <id>{site}/blog</id>\n  <updated>{updated}</updated>\n{entries}</feed>\n",
-        site = SITE_URL,
-        updated = updated,
-        entries = entries,
-    )
+/// A tag as `tag.html` and `tags.html` know it. The count also drives the
+/// `--weight` the stylesheet sizes the tag cloud by.
+fn tag_context(site: &Site, tag: &TagPage) -> Value {
+    let path = tag_path(&tag.slug);
+    json!({
+        "name": tag.name,
+        "url": site.url(&path),
+        "path": path,
+        "count": tag.posts.len(),
+    })
+}
+
+/// Atom, RSS and JSON Feed for the page at `path`, side by side under it.
+fn write_feeds(
+    build: &mut Build,
+    site: &Site,
+    posts: &[&Post],
+    title: &str,
+    path: &str,
+) -> Result<(), String> {
+    let dir = path.trim_matches('/');
+    let feed = Inputs::new().add(site.fingerprint()).add(title).add(path);
+    let inputs = sources(&feed.finish(), posts);
+    build.output(&format!("{dir}/feed.xml"), &inputs, || {
+        Ok(feed::render_atom(site, posts, title, path).into_bytes())
+    })?;
+    build.output(&format!("{dir}/rss.xml"), &inputs, || {
+        Ok(feed::render_rss(site, posts, title, path).into_bytes())
+    })?;
+    build.output(&format!("{dir}/feed.json"), &inputs, || {
+        Ok(feed::render_json_feed(site, posts, title, path).into_bytes())
+    })
 }
 
-fn render_sitemap(posts: &[&Post]) -> String {
-    let mut urls = String::from(
-        "  <url>\n    <loc>https://erickcestari.dev/</loc>\n    <priority>1.0</priority>\n  </url>\n  <url>\n    <loc>https://erickcestari.dev/blog</loc>\n    <priority>0.8</priority>\n  </url>\n  <url>\n    <loc>https://erickcestari.dev/books</loc>\n    <priority>0.7</priority>\n  </url>\n",
-    );
+/// Pages that list posts change whenever one of their posts does, so they
+/// carry the newest `lastmod` among them.
+fn render_sitemap(site: &Site, posts: &[&Post], tags: &[TagPage]) -> String {
+    let newest = newest_modification(posts);
+    let mut urls = String::new();
+    urls.push_str(&sitemap_url(site, "/", newest, "1.0"));
+    urls.push_str(&sitemap_url(site, "/blog", newest, "0.8"));
+    urls.push_str(&sitemap_url(site, "/books", None, "0.7"));
     for p in posts {
-        urls.push_str(&format!(
-            "  <url>\n    <loc>{}/blog/{}/</loc>\n    <priority>0.6</priority>\n  </url>\n",
-            SITE_URL, p.slug
+        urls.push_str(&sitemap_url(
+            site,
+            &p.path(),
+            Some(p.last_modified()),
+            "0.6",
+        ));
+    }
+    if !tags.is_empty() {
+        urls.push_str(&sitemap_url(site, "/blog/tags/", newest, "0.5"));
+    }
+    for tag in tags {
+        urls.push_str(&sitemap_url(
+            site,
+            &tag_path(&tag.slug),
+            newest_modification(&tag.posts),
+            "0.4",
         ));
     }
     format!(
@@ -521,27 +979,56 @@ fn render_sitemap(posts: &[&Post]) -> String {
     )
 }
 
-fn copy_assets(src: &Path, dst: &Path) -> Result<(), String> {
-    let read_dir = fs::read_dir(src).map_err(|e| format!("read {}: {e}", src.display()))?;
-    for entry in read_dir.flatten() {
-        let path = entry.path();
-        if !path.is_file() {
+fn sitemap_url(site: &Site, path: &str, lastmod: Option<Timestamp>, priority: &str) -> String {
+    let lastmod = lastmod
+        .map(|t| format!("    <lastmod>{}</lastmod>\n", t.rfc3339()))
+        .unwrap_or_default();
+    format!(
+        "  <url>\n    <loc>{}</loc>\n{}    <priority>{}</priority>\n  </url>\n",
+        site.url(path),
+        lastmod,
+        priority
+    )
+}
+
+/// Copies a post's files next to its page in `dir` (relative to `static_dir`),
+/// adding the responsive variants of its images.
+fn copy_assets(
+    build: &mut Build,
+    src: &Path,
+    static_dir: &Path,
+    dir: &str,
+    source: &str,
+    image_cache: &Path,
+) -> Result<(), String> {
+    for path in asset_files(src)? {
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
             continue;
-        }
-        let name = match path.file_name().and_then(|n| n.to_str()) {
-            Some(n) => n,
-            None => continue,
         };
-        if name == "index.md" || name.ends_with(".md") {
-            continue;
+        build.output(&format!("{dir}/{name}"), source, || {
+            fs::read(&path).map_err(|e| format!("read {}: {e}", path.display()))
+        })?;
+        let variants = images::write_variants(&path, &static_dir.join(dir), image_cache)?;
+        for variant in variants {
+            // The name carries a hash of the image it was made from.
+            build.record(&format!("{dir}/{variant}"), &variant);
         }
-        let out = dst.join(name);
-        fs::copy(&path, &out)
-            .map_err(|e| format!("copy {} -> {}: {e}", path.display(), out.display()))?;
     }
     Ok(())
 }
 
+/// The files beside a post's `index.md`, sorted by name.
+fn asset_files(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let read_dir = fs::read_dir(dir).map_err(|e| format!("read {}: {e}", dir.display()))?;
+    let mut files: Vec<PathBuf> = read_dir
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension().and_then(|e| e.to_str()) != Some("md"))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
 fn escape_html(s: &str) -> String {
     let mut out = String::with_capacity(s.len());
     for c in s.chars() {
@@ -0,0 +1,420 @@
+//! Checks the links in the generated site before it ships.
+//!
+//! Every `href`, `src` and `srcset` on every page under `static/` that stays
+//! on the site must name a path jatai's `FileCache` would serve: the file
+//! itself, a directory with an `index.html` (with or without the trailing
+//! slash), or an `.html` page without its extension. A `#fragment` must name
+//! an `id` on the target page, which for headings is the one `uniquify` gave
+//! it.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+/// A link that would 404, or land on a page without its anchor.
+#[derive(Debug, PartialEq)]
+pub struct BrokenLink {
+    pub file: PathBuf,
+    /// 1-based.
+    pub line: usize,
+    pub column: usize,
+    pub url: String,
+    pub reason: String,
+}
+
+impl fmt::Display for BrokenLink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: broken link {:?}: {}",
+            self.file.display(),
+            self.line,
+            self.column,
+            self.url,
+            self.reason
+        )
+    }
+}
+
+/// The attributes that hold URLs, as they appear in a tag.
+const URL_ATTRS: [&str; 3] = [" href=\"", " src=\"", " srcset=\""];
+
+/// Every broken link under `static_dir`, in file order.
+pub fn check(static_dir: &Path) -> Result<Vec<BrokenLink>, String> {
+    let mut files = Vec::new();
+    walk(static_dir, static_dir, &mut files)?;
+
+    // Request path -> file, as FileCache maps them.
+    let mut served: HashMap<String, &str> = HashMap::new();
+    for rel in &files {
+        let url = format!("/{rel}");
+        if let Some(dir) = url.strip_suffix("index.html") {
+            served.insert(dir.to_string(), rel);
+            if dir != "/" {
+                served.insert(dir.trim_end_matches('/').to_string(), rel);
+            }
+        } else if let Some(page) = url.strip_suffix(".html") {
+            served.insert(page.to_string(), rel);
+        }
+        served.insert(url, rel);
+    }
+
+    let mut pages: HashMap<&str, String> = HashMap::new();
+    for rel in files.iter().filter(|f| f.ends_with(".html")) {
+        let path = static_dir.join(rel);
+        let html =
+            fs::read_to_string(&path).map_err(|e| format!("read {}: {e}", path.display()))?;
+        pages.insert(rel, html);
+    }
+    let ids: HashMap<&str, HashSet<&str>> = pages
+        .iter()
+        .map(|(rel, html)| (*rel, collect_ids(html)))
+        .collect();
+
+    let mut broken = Vec::new();
+    let mut sorted: Vec<&&str> = pages.keys().collect();
+    sorted.sort();
+    for rel in sorted {
+        let html = &pages[*rel];
+        for (offset, url) in urls(html) {
+            let Some(reason) = verify(rel, url, &served, &ids) else {
+                continue;
+            };
+            let (line, column) = line_col(html, offset);
+            broken.push(BrokenLink {
+                file: static_dir.join(rel),
+                line,
+                column,
+                url: url.to_string(),
+                reason,
+            });
+        }
+    }
+    Ok(broken)
+}
+
+/// Every file under `dir`, relative to `base`, with `/` separators.
+fn walk(base: &Path, dir: &Path, files: &mut Vec<String>) -> Result<(), String> {
+    let read_dir = fs::read_dir(dir).map_err(|e| format!("read {}: {e}", dir.display()))?;
+    for entry in read_dir.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            walk(base, &path, files)?;
+        } else if let Ok(rel) = path.strip_prefix(base) {
+            let parts: Vec<_> = rel.iter().map(|p| p.to_string_lossy()).collect();
+            files.push(parts.join("/"));
+        }
+    }
+    Ok(())
+}
+
+/// Each URL in an attribute of `html`, with its byte offset. A `srcset`
+/// yields one URL per candidate.
+fn urls(html: &str) -> Vec<(usize, &str)> {
+    let mut found = Vec::new();
+    for attr in URL_ATTRS {
+        let mut from = 0;
+        while let Some(i) = html[from..].find(attr) {
+            let start = from + i + attr.len();
+            let Some(len) = html[start..].find('"') else {
+                break;
+            };
+            let value = &html[start..start + len];
+            if attr == " srcset=\"" {
+                let mut at = start;
+                for candidate in value.split(',') {
+                    let url = candidate.split_whitespace().next().unwrap_or("");
+                    let lead = candidate.len() - candidate.trim_start().len();
+                    if !url.is_empty() {
+                        found.push((at + lead, url));
+                    }
+                    at += candidate.len() + 1;
+                }
+            } else {
+                found.push((start, value));
+            }
+            from = start + len;
+        }
+    }
+    found.sort_by_key(|(offset, _)| *offset);
+    found
+}
+
+fn collect_ids(html: &str) -> HashSet<&str> {
+    let mut ids = HashSet::new();
+    for attr in [" id=\"", " name=\""] {
+        let mut from = 0;
+        while let Some(i) = html[from..].find(attr) {
+            let start = from + i + attr.len();
+            let Some(len) = html[start..].find('"') else {
+                break;
+            };
+            ids.insert(&html[start..start + len]);
+            from = start + len;
+        }
+    }
+    ids
+}
+
+/// Why the link `url` on page `rel` is broken, or `None` if it isn't.
+fn verify(
+    rel: &str,
+    url: &str,
+    served: &HashMap<String, &str>,
+    ids: &HashMap<&str, HashSet<&str>>,
+) -> Option<String> {
+    let url = url.replace("&amp;", "&");
+    if is_external(&url) {
+        return None;
+    }
+    let (rest, fragment) = match url.split_once('#') {
+        Some((rest, fragment)) => (rest, Some(fragment)),
+        None => (url.as_str(), None),
+    };
+    let path = rest.split('?').next().unwrap_or_default();
+
+    let target = if path.is_empty() {
+        rel
+    } else {
+        // 404.html answers for every missing path, so only absolute URLs
+        // mean the same thing wherever it is shown.
+        if rel == "404.html" && !path.starts_with('/') {
+            return Some("relative link on the 404 page, which is served at any depth".into());
+        }
+        let resolved = resolve(&page_url(rel), &percent_decode(path));
+        match served.get(&resolved) {
+            Some(target) => target,
+            None => return Some(format!("nothing is served at {resolved}")),
+        }
+    };
+
+    match fragment {
+        None | Some("") => None,
+        Some(fragment) => {
+            let fragment = percent_decode(fragment);
+            let known = ids.get(target)?;
+            (!known.contains(fragment.as_str()))
+                .then(|| format!("{target} has no element with id {fragment:?}"))
+        }
+    }
+}
+
+fn is_external(url: &str) -> bool {
+    url.starts_with("//")
+        || url.split_once(':').is_some_and(|(scheme, _)| {
+            !scheme.is_empty()
+                && !scheme.contains('/')
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+        })
+}
+
+/// The URL a file is linked at, which relative links resolve against:
+/// `blog/x/index.html` is `/blog/x/`.
+fn page_url(rel: &str) -> String {
+    match rel.strip_suffix("index.html") {
+        Some(dir) => format!("/{dir}"),
+        None => format!("/{rel}"),
+    }
+}
+
+/// Resolves `path` against the page at `base`, removing `.` and `..`.
+fn resolve(base: &str, path: &str) -> String {
+    let joined = if path.starts_with('/') {
+        path.to_string()
+    } else {
+        let dir = &base[..=base.rfind('/').unwrap_or(0)];
+        format!("{dir}{path}")
+    };
+    let mut segments: Vec<&str> = Vec::new();
+    let parts: Vec<&str> = joined.split('/').collect();
+    for (i, part) in parts.iter().enumerate() {
+        match *part {
+            "." => {}
+            ".." => {
+                segments.pop();
+            }
+            "" if i != parts.len() - 1 => {}
+            part => segments.push(part),
+        }
+    }
+    format!("/{}", segments.join("/"))
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(b)) => {
+                out.push(b);
+                i += 3;
+            }
+            (b, _) => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// 1-based line and column of byte `offset` in `text`.
+fn line_col(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |nl| nl + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn site(files: &[(&str, &str)]) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for (rel, contents) in files {
+            let path = dir.path().join(rel);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+        dir
+    }
+
+    fn broken(files: &[(&str, &str)]) -> Vec<String> {
+        let dir = site(files);
+        check(dir.path())
+            .unwrap()
+            .into_iter()
+            .map(|b| {
+                let file = b
+                    .file
+                    .strip_prefix(dir.path())
+                    .unwrap()
+                    .display()
+                    .to_string();
+                format!("{file}:{}:{} {} ({})", b.line, b.column, b.url, b.reason)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn accepts_every_alias_file_cache_serves() {
+        let links = [
+            "/",
+            "/index.html",
+            "/blog",
+            "/blog/",
+            "/blog/index.html",
+            "/books",
+            "/books.html",
+            "/style.css",
+            "blog/",
+            "./books",
+            "blog/post/../post/",
+        ]
+        .map(|l| format!("<a href=\"{l}\">x</a>"))
+        .concat();
+        assert_eq!(
+            broken(&[
+                ("index.html", &links),
+                ("books.html", ""),
+                ("style.css", ""),
+                ("blog/index.html", ""),
+                ("blog/post/index.html", ""),
+            ]),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn reports_missing_pages_with_their_location() {
+        assert_eq!(
+            broken(&[
+                (
+                    "index.html",
+                    "<p>\n  <a href=\"/blog/typo/\">x</a> <img src=\"gone.png\" /></p>"
+                ),
+                ("blog/index.html", ""),
+            ]),
+            [
+                "index.html:2:12 /blog/typo/ (nothing is served at /blog/typo/)",
+                "index.html:2:41 gone.png (nothing is served at /gone.png)",
+            ]
+        );
+    }
+
+    #[test]
+    fn resolves_relative_links_against_the_page_directory() {
+        assert_eq!(
+            broken(&[
+                (
+                    "blog/post/index.html",
+                    "<img src=\"chart.png\" /><a href=\"../other/\">o</a>"
+                ),
+                ("blog/post/chart.png", ""),
+                ("blog/other/index.html", ""),
+            ]),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn checks_fragments_against_ids_on_the_target_page() {
+        assert_eq!(
+            broken(&[
+                (
+                    "index.html",
+                    "<h2 id=\"intro\">I</h2><a href=\"#intro\">a</a><a href=\"#\">top</a><a href=\"#outro\">b</a><a href=\"/blog/#list\">c</a><a href=\"/blog/#nope\">d</a>",
+                ),
+                ("blog/index.html", "<ul id=\"list\"></ul>"),
+            ]),
+            [
+                "index.html:1:72 #outro (index.html has no element with id \"outro\")",
+                "index.html:1:121 /blog/#nope (blog/index.html has no element with id \"nope\")",
+            ]
+        );
+    }
+
+    #[test]
+    fn checks_each_srcset_candidate() {
+        assert_eq!(
+            broken(&[
+                ("index.html", "<source srcset=\"a.webp 480w, b.webp 960w\">"),
+                ("a.webp", ""),
+            ]),
+            ["index.html:1:30 b.webp (nothing is served at /b.webp)"]
+        );
+    }
+
+    #[test]
+    fn ignores_external_links_and_queries() {
+        assert_eq!(
+            broken(&[(
+                "index.html",
+                "<a href=\"https://example.com/x\">a</a><a href=\"mailto:me@example.com\">b</a><a href=\"//cdn.example.com/x\">c</a><a href=\"/?q=1\">d</a><a href=\"/%69ndex.html\">e</a>",
+            )]),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn relative_links_on_the_404_page_are_broken() {
+        let found = broken(&[
+            ("404.html", "<a href=\"style.css\">s</a>"),
+            ("style.css", ""),
+        ]);
+        assert_eq!(found.len(), 1, "{found:?}");
+        assert!(found[0].contains("served at any depth"), "{found:?}");
+    }
+}
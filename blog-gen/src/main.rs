@@ -1,13 +1,32 @@
-use std::{path::Path, process::ExitCode};
+mod serve;
+
+use std::{
+    path::Path,
+    process::ExitCode,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const USAGE: &str =
+    "usage: blog-gen [--strict-links] [--record-redirects] [--base-url <url>] | serve [--port <n>] | preview <slug> [--ttl <n>(s|m|h|d)]";
+
+/// How long a preview link works when `--ttl` is not given.
+const DEFAULT_TTL_SECS: u64 = 7 * 24 * 60 * 60;
 
 fn main() -> ExitCode {
     let root = Path::new(env!("CARGO_MANIFEST_DIR"))
         .parent()
         .expect("manifest has a parent");
 
-    match blog_gen::generate(root) {
-        Ok(count) => {
-            println!("Generated {count} post(s)");
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("serve") => serve::run(root, &args[1..]),
+        Some("preview") => preview(root, &args[1..]),
+        _ => generate(root, &args),
+    };
+
+    match result {
+        Ok(out) => {
+            println!("{out}");
             ExitCode::SUCCESS
         }
         Err(e) => {
@@ -16,3 +35,68 @@ fn main() -> ExitCode {
         }
     }
 }
+
+fn generate(root: &Path, flags: &[String]) -> Result<String, String> {
+    let mut options = blog_gen::BuildOptions::default();
+    let mut flags = flags.iter();
+    while let Some(flag) = flags.next() {
+        match flag.as_str() {
+            "--strict-links" => options.strict_links = true,
+            "--record-redirects" => options.redirect_moves = true,
+            "--base-url" => options.base_url = Some(flags.next().ok_or(USAGE)?.clone()),
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    let generated = blog_gen::generate_with(root, &options)?;
+    for link in &generated.broken_links {
+        eprintln!("warning: {link}");
+    }
+    Ok(format!("Generated {} post(s)", generated.posts))
+}
+
+/// Prints a link that opens the draft `slug` until the TTL runs out, signed
+/// with the `PREVIEW_KEY` the server reads (from the environment or `.env`).
+fn preview(root: &Path, args: &[String]) -> Result<String, String> {
+    let (slug, ttl) = match args {
+        [slug] => (slug, DEFAULT_TTL_SECS),
+        [slug, flag, ttl] if flag == "--ttl" => (slug, parse_ttl(ttl)?),
+        _ => return Err(USAGE.to_string()),
+    };
+
+    let drafts = blog_gen::draft_slugs(root)?;
+    if !drafts.contains(slug) {
+        return Err(format!(
+            "no draft with slug {slug:?} (drafts: {})",
+            drafts.join(", ")
+        ));
+    }
+
+    dotenvy::dotenv().ok();
+    let key = std::env::var("PREVIEW_KEY")
+        .ok()
+        .filter(|k| !k.is_empty())
+        .ok_or("PREVIEW_KEY environment variable not set")?;
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| e.to_string())?
+        .as_secs();
+    let link = jatai::preview::link(key.as_bytes(), slug, now + ttl);
+    Ok(blog_gen::Site::load(root)?.url(&link))
+}
+
+/// "90s", "30m", "12h", "7d" -> seconds.
+fn parse_ttl(s: &str) -> Result<u64, String> {
+    let invalid = || format!("invalid --ttl {s:?}: expected a number followed by s, m, h or d");
+    let split = s.len().checked_sub(1).ok_or_else(invalid)?;
+    let (n, unit) = s.split_at(split);
+    let n: u64 = n.parse().map_err(|_| invalid())?;
+    let scale = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+    n.checked_mul(scale).filter(|&t| t > 0).ok_or_else(invalid)
+}
@@ -0,0 +1,377 @@
+//! Incremental builds.
+//!
+//! `.blog-gen/manifest.json` records every file a build wrote under `static/`,
+//! each with a hash of what it was made from: its template with every
+//! template it extends or includes, and the source of every post it shows
+//! (the markdown and the files beside it). The next build skips an output
+//! whose inputs hash the same, so an unchanged page is neither rendered nor
+//! rewritten and keeps its mtime.
+//! An output the last build wrote and this one doesn't, such as the directory
+//! of a post that was deleted or renamed, is removed; so is any file in a
+//! directory blog-gen owns outright that this build did not write, which
+//! catches what a missing manifest can't tell.
+//!
+//! Rendered post bodies are kept as well, by source hash: the lists and feeds
+//! need every post's HTML even when only one post changed.
+//!
+//! The hashes and renderings belong to the blog-gen that wrote them. Another
+//! build of blog-gen may render the same inputs differently, so it renders
+//! everything again, though files whose bytes come out the same are still
+//! left alone and outputs it no longer makes are still removed.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{redirects::Published, Heading};
+
+/// What a post renders to, apart from its frontmatter.
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct Rendered {
+    pub(crate) html: String,
+    pub(crate) toc: Vec<Heading>,
+    pub(crate) reading_time: u32,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Saved {
+    generator: String,
+    /// Output path, relative to `static/` -> hash of its inputs.
+    outputs: BTreeMap<String, String>,
+    /// Post source hash -> its rendering.
+    posts: BTreeMap<String, Rendered>,
+    /// URL path -> the published post there, to tell when one moves.
+    #[serde(default)]
+    published: BTreeMap<String, Published>,
+}
+
+/// One run of `generate`: what the last run left, and what this one makes.
+pub(crate) struct Build {
+    path: PathBuf,
+    static_dir: PathBuf,
+    /// Directories under `static_dir` holding nothing but this build's output.
+    owned: Vec<PathBuf>,
+    previous: Saved,
+    next: Saved,
+}
+
+impl Build {
+    /// Picks up the manifest under `root`. A missing or unreadable one, or
+    /// one another blog-gen wrote, means rendering everything. Each of
+    /// `owned` (relative to `static_dir`) is emptied of whatever this build
+    /// does not write into it.
+    pub(crate) fn start(root: &Path, static_dir: &Path, owned: &[&str]) -> Self {
+        let path = root.join(".blog-gen/manifest.json");
+        let generator = generator();
+        let mut previous = fs::read(&path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<Saved>(&bytes).ok())
+            .unwrap_or_default();
+        if previous.generator != generator {
+            previous.posts.clear();
+            previous.outputs.values_mut().for_each(String::clear);
+        }
+        Build {
+            path,
+            static_dir: static_dir.to_path_buf(),
+            owned: owned.iter().map(|dir| static_dir.join(dir)).collect(),
+            previous,
+            next: Saved {
+                generator,
+                ..Saved::default()
+            },
+        }
+    }
+
+    /// Post renderings from the last build, by source hash.
+    pub(crate) fn rendered(&self) -> &BTreeMap<String, Rendered> {
+        &self.previous.posts
+    }
+
+    /// Keeps the rendering of the post with source hash `source` for the
+    /// next build.
+    pub(crate) fn keep_rendered(&mut self, source: &str, rendered: Rendered) {
+        self.next.posts.insert(source.to_string(), rendered);
+    }
+
+    /// The published posts of the last build, by URL path.
+    pub(crate) fn published(&self) -> &BTreeMap<String, Published> {
+        &self.previous.published
+    }
+
+    /// Notes the published post at `path` for the next build.
+    pub(crate) fn publish(&mut self, path: &str, post: Published) {
+        self.next.published.insert(path.to_string(), post);
+    }
+
+    /// Writes `render()` to `rel` under `static/`, unless the last build
+    /// wrote it from the same `inputs` and it is still there. A rendering
+    /// identical to the file on disk is not written either.
+    pub(crate) fn output(
+        &mut self,
+        rel: &str,
+        inputs: &str,
+        render: impl FnOnce() -> Result<Vec<u8>, String>,
+    ) -> Result<(), String> {
+        if !is_contained(Path::new(rel)) {
+            return Err(format!("refusing to write {rel:?}, outside static/"));
+        }
+        let path = self.static_dir.join(rel);
+        self.next
+            .outputs
+            .insert(rel.to_string(), inputs.to_string());
+        if self.previous.outputs.get(rel).map(String::as_str) == Some(inputs) && path.is_file() {
+            return Ok(());
+        }
+        let contents = render()?;
+        if fs::read(&path).is_ok_and(|old| old == contents) {
+            return Ok(());
+        }
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("create {}: {e}", dir.display()))?;
+        }
+        fs::write(&path, contents).map_err(|e| format!("write {}: {e}", path.display()))
+    }
+
+    /// Whether this build has written `rel` so far.
+    pub(crate) fn has_output(&self, rel: &str) -> bool {
+        self.next.outputs.contains_key(rel)
+    }
+
+    /// Claims `rel`, written by someone else, as an output of this build so
+    /// it is not pruned.
+    pub(crate) fn record(&mut self, rel: &str, inputs: &str) {
+        self.next
+            .outputs
+            .insert(rel.to_string(), inputs.to_string());
+    }
+
+    /// Removes what the last build wrote and this one did not, and anything
+    /// else in an owned directory, then saves the manifest for the next build.
+    /// A recorded output that would lie outside `static/` is dropped, never
+    /// removed: the manifest is only as trustworthy as the builds behind it.
+    pub(crate) fn finish(self) -> Result<(), String> {
+        let mut stale: Vec<PathBuf> = self
+            .previous
+            .outputs
+            .keys()
+            .filter(|rel| !self.next.outputs.contains_key(*rel))
+            .filter(|rel| is_contained(Path::new(rel)))
+            .map(|rel| self.static_dir.join(rel))
+            .collect();
+        for dir in &self.owned {
+            self.orphans(dir, &mut stale);
+        }
+        for path in stale {
+            match fs::remove_file(&path) {
+                Ok(()) => remove_empty_parents(&path, &self.static_dir),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(format!("remove {}: {e}", path.display())),
+            }
+        }
+
+        let json = serde_json::to_vec(&self.next).map_err(|e| e.to_string())?;
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("create {}: {e}", dir.display()))?;
+        }
+        fs::write(&self.path, json).map_err(|e| format!("write {}: {e}", self.path.display()))
+    }
+
+    /// Files under `dir` that this build did not write.
+    fn orphans(&self, dir: &Path, found: &mut Vec<PathBuf>) {
+        let Ok(read_dir) = fs::read_dir(dir) else {
+            return;
+        };
+        for entry in read_dir.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                self.orphans(&path, found);
+                continue;
+            }
+            let rel = path.strip_prefix(&self.static_dir).ok().map(|rel| {
+                rel.iter()
+                    .map(|part| part.to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/")
+            });
+            if rel.is_none_or(|rel| !self.next.outputs.contains_key(&rel)) {
+                found.push(path);
+            }
+        }
+    }
+}
+
+/// Whether `rel` names something beneath the directory it is relative to:
+/// no root, and no `..` to climb out with.
+fn is_contained(rel: &Path) -> bool {
+    rel.components()
+        .all(|part| matches!(part, Component::Normal(_) | Component::CurDir))
+}
+
+/// Removes the directories above `path` that are left empty, up to `root`.
+fn remove_empty_parents(path: &Path, root: &Path) {
+    if !path.strip_prefix(root).is_ok_and(is_contained) {
+        return;
+    }
+    let mut dir = path.parent();
+    while let Some(d) = dir.filter(|d| *d != root && d.starts_with(root)) {
+        // Fails, and stops the climb, at the first directory with anything left.
+        if fs::remove_dir(d).is_err() {
+            break;
+        }
+        dir = d.parent();
+    }
+}
+
+/// Identifies the running blog-gen. Any rebuild of it relinks the binary, so
+/// the binary's size and mtime change with its code; hashing the binary
+/// itself would cost more than the builds it saves.
+fn generator() -> String {
+    let meta = std::env::current_exe().and_then(fs::metadata);
+    let stamp = meta.map(|m| {
+        let modified = m
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map_or(0, |d| d.as_nanos());
+        format!("{}:{modified}", m.len())
+    });
+    format!(
+        "{} {}",
+        env!("CARGO_PKG_VERSION"),
+        stamp.unwrap_or_default()
+    )
+}
+
+/// Hashes the inputs of an output. Each part is length-prefixed, so moving
+/// bytes from one part to the next changes the hash.
+pub(crate) struct Inputs(Sha256);
+
+impl Inputs {
+    pub(crate) fn new() -> Self {
+        Inputs(Sha256::new())
+    }
+
+    pub(crate) fn add(mut self, part: impl AsRef<[u8]>) -> Self {
+        let part = part.as_ref();
+        self.0.update((part.len() as u64).to_le_bytes());
+        self.0.update(part);
+        self
+    }
+
+    pub(crate) fn finish(self) -> String {
+        self.0
+            .finalize()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inputs_hash_their_parts_separately() {
+        let joined = Inputs::new().add("ab").add("c").finish();
+        assert_ne!(joined, Inputs::new().add("a").add("bc").finish());
+        assert_eq!(joined, Inputs::new().add("ab").add("c").finish());
+    }
+
+    #[test]
+    fn outputs_are_skipped_kept_or_pruned_by_their_inputs() {
+        let root = tempfile::tempdir().unwrap();
+        let static_dir = root.path().join("static");
+
+        let mut build = Build::start(root.path(), &static_dir, &[]);
+        build
+            .output("a/index.html", "1", || Ok(b"a".to_vec()))
+            .unwrap();
+        build
+            .output("b/c/index.html", "1", || Ok(b"b".to_vec()))
+            .unwrap();
+        build.finish().unwrap();
+
+        let mut build = Build::start(root.path(), &static_dir, &[]);
+        build
+            .output("a/index.html", "1", || panic!("inputs are unchanged"))
+            .unwrap();
+        build.finish().unwrap();
+
+        assert!(static_dir.join("a/index.html").is_file());
+        assert!(!static_dir.join("b").exists(), "emptied directories go too");
+        assert!(static_dir.is_dir());
+    }
+
+    #[test]
+    fn a_missing_output_is_rendered_again() {
+        let root = tempfile::tempdir().unwrap();
+        let static_dir = root.path().join("static");
+        let mut build = Build::start(root.path(), &static_dir, &[]);
+        build.output("x.html", "1", || Ok(b"x".to_vec())).unwrap();
+        build.finish().unwrap();
+
+        fs::remove_file(static_dir.join("x.html")).unwrap();
+        let mut build = Build::start(root.path(), &static_dir, &[]);
+        build.output("x.html", "1", || Ok(b"x".to_vec())).unwrap();
+        assert_eq!(fs::read(static_dir.join("x.html")).unwrap(), b"x");
+    }
+
+    #[test]
+    fn nothing_outside_static_is_written_or_pruned() {
+        let root = tempfile::tempdir().unwrap();
+        let static_dir = root.path().join("static");
+        let outside = root.path().join("outside/index.html");
+        fs::create_dir_all(outside.parent().unwrap()).unwrap();
+        fs::write(&outside, "not ours").unwrap();
+
+        let mut build = Build::start(root.path(), &static_dir, &[]);
+        let escape = "../outside/index.html";
+        let err = build.output(escape, "1", || Ok(b"x".to_vec()));
+        assert!(err.unwrap_err().contains("outside static/"));
+        build.finish().unwrap();
+
+        // A manifest from a build that did write there is not believed.
+        let manifest = root.path().join(".blog-gen/manifest.json");
+        let mut saved: Saved = serde_json::from_slice(&fs::read(&manifest).unwrap()).unwrap();
+        saved.outputs.insert(escape.to_string(), "1".to_string());
+        fs::write(&manifest, serde_json::to_vec(&saved).unwrap()).unwrap();
+        Build::start(root.path(), &static_dir, &[])
+            .finish()
+            .unwrap();
+        assert_eq!(fs::read_to_string(&outside).unwrap(), "not ours");
+
+        remove_empty_parents(&static_dir.join("../outside/gone.html"), &static_dir);
+        assert!(outside.parent().unwrap().is_dir());
+    }
+
+    #[test]
+    fn owned_directories_lose_whatever_the_build_did_not_write() {
+        let root = tempfile::tempdir().unwrap();
+        let static_dir = root.path().join("static");
+        for stray in ["blog/old/index.html", "blog/old/chart.png", "style.css"] {
+            let path = static_dir.join(stray);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "stray").unwrap();
+        }
+
+        let mut build = Build::start(root.path(), &static_dir, &["blog"]);
+        build
+            .output("blog/new/index.html", "1", || Ok(b"new".to_vec()))
+            .unwrap();
+        build.finish().unwrap();
+
+        assert!(!static_dir.join("blog/old").exists());
+        assert!(static_dir.join("blog/new/index.html").is_file());
+        assert!(
+            static_dir.join("style.css").is_file(),
+            "not in an owned directory"
+        );
+    }
+}
@@ -0,0 +1,548 @@
+//! TeX math to MathML, at build time, so formulas need no script and no
+//! web font: browsers render MathML Core natively.
+//!
+//! This is the subset posts actually use, not all of TeX: letters, numbers
+//! and operators; `_` and `^`; `{}` groups; `\frac`, `\sqrt`, `\binom`,
+//! `\left…\right`, `\text` and friends; accents; Greek; and the common
+//! relation, arrow and big-operator symbols. Anything else is an error rather
+//! than a formula that silently renders wrong.
+
+use crate::escape_html;
+
+/// `tex` as a `<math>` element, inline or as a display block. The source is
+/// kept as an annotation, which is what copying the formula yields.
+pub(crate) fn render(tex: &str, display: bool) -> Result<String, String> {
+    let mut parser = MathParser {
+        src: tex.chars().collect(),
+        pos: 0,
+        display,
+    };
+    let items = parser
+        .expr(Stop::End)
+        .map_err(|e| format!("math {tex:?}: {e}"))?;
+    Ok(format!(
+        "<math{}><semantics>{}<annotation encoding=\"application/x-tex\">{}</annotation></semantics></math>",
+        if display { " display=\"block\"" } else { "" },
+        row(items),
+        escape_html(tex.trim())
+    ))
+}
+
+/// What ends the expression being parsed.
+#[derive(Clone, Copy, PartialEq)]
+enum Stop {
+    End,
+    Brace,
+    Bracket,
+    Right,
+}
+
+struct MathParser {
+    src: Vec<char>,
+    pos: usize,
+    display: bool,
+}
+
+/// A parsed atom, and whether it is a big operator that takes its limits
+/// above and below in display math.
+struct Atom {
+    mathml: String,
+    big: bool,
+}
+
+impl Atom {
+    fn new(mathml: String) -> Self {
+        Atom { mathml, big: false }
+    }
+}
+
+impl MathParser {
+    fn peek(&self) -> Option<char> {
+        self.src.get(self.pos).copied()
+    }
+
+    fn skip_space(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn expr(&mut self, stop: Stop) -> Result<Vec<String>, String> {
+        let mut items = Vec::new();
+        loop {
+            self.skip_space();
+            match (self.peek(), stop) {
+                (None, Stop::End) => return Ok(items),
+                (None, Stop::Brace) => return Err("unclosed {".into()),
+                (None, Stop::Bracket) => return Err("unclosed [".into()),
+                (None, Stop::Right) => return Err("\\left without \\right".into()),
+                (Some('}'), Stop::Brace) | (Some(']'), Stop::Bracket) => {
+                    self.pos += 1;
+                    return Ok(items);
+                }
+                (Some('}'), _) => return Err("unmatched }".into()),
+                (Some('\\'), Stop::Right) if self.at_command("right") => return Ok(items),
+                _ => {}
+            }
+            let Some(atom) = self.atom()? else { continue };
+            items.push(self.scripts(atom)?);
+        }
+    }
+
+    fn at_command(&self, name: &str) -> bool {
+        let end = self.pos + 1 + name.len();
+        self.src
+            .get(self.pos + 1..end)
+            .is_some_and(|s| s.iter().copied().eq(name.chars()))
+            && !self.src.get(end).is_some_and(char::is_ascii_alphabetic)
+    }
+
+    /// Attaches any `_` and `^` that follow `base`.
+    fn scripts(&mut self, base: Atom) -> Result<String, String> {
+        let (mut sub, mut sup) = (None, None);
+        loop {
+            self.skip_space();
+            let slot = match self.peek() {
+                Some('_') => &mut sub,
+                Some('^') => &mut sup,
+                _ => break,
+            };
+            if slot.is_some() {
+                return Err(format!("double {}", self.src[self.pos]));
+            }
+            let mark = self.src[self.pos];
+            self.pos += 1;
+            *slot = Some(self.argument(&format!("{mark}"))?);
+        }
+        let under = base.big && self.display;
+        Ok(match (sub, sup) {
+            (None, None) => base.mathml,
+            (Some(b), None) if under => format!("<munder>{}{b}</munder>", base.mathml),
+            (None, Some(p)) if under => format!("<mover>{}{p}</mover>", base.mathml),
+            (Some(b), Some(p)) if under => {
+                format!("<munderover>{}{b}{p}</munderover>", base.mathml)
+            }
+            (Some(b), None) => format!("<msub>{}{b}</msub>", base.mathml),
+            (None, Some(p)) => format!("<msup>{}{p}</msup>", base.mathml),
+            (Some(b), Some(p)) => format!("<msubsup>{}{b}{p}</msubsup>", base.mathml),
+        })
+    }
+
+    /// The argument of a command or script: a `{}` group or a single atom.
+    fn argument(&mut self, of: &str) -> Result<String, String> {
+        self.skip_space();
+        match self.peek() {
+            None | Some('}') => Err(format!("{of} is missing its argument")),
+            Some('_' | '^') => Err(format!("{of} is missing its argument")),
+            _ => match self.atom()? {
+                Some(atom) => Ok(atom.mathml),
+                None => Err(format!("{of} is missing its argument")),
+            },
+        }
+    }
+
+    /// A `{}` group's raw text, for `\text` and the like.
+    fn raw_group(&mut self, of: &str) -> Result<String, String> {
+        self.skip_space();
+        if self.peek() != Some('{') {
+            return Err(format!("{of} needs a {{…}} argument"));
+        }
+        self.pos += 1;
+        let start = self.pos;
+        let mut depth = 0;
+        while let Some(c) = self.peek() {
+            match c {
+                '{' => depth += 1,
+                '}' if depth == 0 => {
+                    let text: String = self.src[start..self.pos].iter().collect();
+                    self.pos += 1;
+                    return Ok(text);
+                }
+                '}' => depth -= 1,
+                _ => {}
+            }
+            self.pos += 1;
+        }
+        Err("unclosed {".into())
+    }
+
+    /// The next atom, or `None` for something that renders as nothing.
+    fn atom(&mut self) -> Result<Option<Atom>, String> {
+        let Some(c) = self.peek() else {
+            return Ok(None);
+        };
+        self.pos += 1;
+        let mathml = match c {
+            '{' => row(self.expr(Stop::Brace)?),
+            '\\' => return self.command(),
+            '0'..='9' | '.' if c != '.' || self.peek().is_some_and(|d| d.is_ascii_digit()) => {
+                let start = self.pos - 1;
+                while self
+                    .peek()
+                    .is_some_and(|d| d.is_ascii_digit() || (d == '.' && self.digit_after()))
+                {
+                    self.pos += 1;
+                }
+                let number: String = self.src[start..self.pos].iter().collect();
+                format!("<mn>{number}</mn>")
+            }
+            '~' => "<mspace width=\"0.25em\"/>".into(),
+            '&' | '#' | '%' | '$' => return Err(format!("{c} is not supported here")),
+            '_' | '^' => return Err(format!("{c} with nothing before it")),
+            '\'' => "<mo>′</mo>".into(),
+            '-' => "<mo>−</mo>".into(),
+            '*' => "<mo>∗</mo>".into(),
+            '(' | ')' | '[' | ']' | '|' => format!("<mo stretchy=\"false\">{c}</mo>"),
+            c if c.is_alphabetic() => format!("<mi>{c}</mi>"),
+            c => format!("<mo>{}</mo>", escape_html(&c.to_string())),
+        };
+        Ok(Some(Atom::new(mathml)))
+    }
+
+    fn digit_after(&self) -> bool {
+        self.src.get(self.pos + 1).is_some_and(char::is_ascii_digit)
+    }
+
+    fn command(&mut self) -> Result<Option<Atom>, String> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_alphabetic()) {
+            self.pos += 1;
+        }
+        if self.pos == start {
+            // A control symbol: one character that isn't a letter.
+            let Some(c) = self.peek() else {
+                return Err("\\ at the end".into());
+            };
+            self.pos += 1;
+            let mathml = match c {
+                ',' => "<mspace width=\"0.1667em\"/>".into(),
+                ':' | '>' => "<mspace width=\"0.2222em\"/>".into(),
+                ';' => "<mspace width=\"0.2778em\"/>".into(),
+                ' ' => "<mspace width=\"0.25em\"/>".into(),
+                '!' => return Ok(None),
+                '{' | '}' => format!("<mo stretchy=\"false\">{c}</mo>"),
+                '%' | '$' | '#' | '_' => format!("<mi mathvariant=\"normal\">{c}</mi>"),
+                '&' => "<mi mathvariant=\"normal\">&amp;</mi>".into(),
+                '|' => "<mo stretchy=\"false\">‖</mo>".into(),
+                '\\' => return Err("\\\\ line breaks are not supported".into()),
+                c => return Err(format!("unknown command \\{c}")),
+            };
+            return Ok(Some(Atom::new(mathml)));
+        }
+        let name: String = self.src[start..self.pos].iter().collect();
+        let of = format!("\\{name}");
+
+        if let Some(symbol) = symbol(&name) {
+            return Ok(Some(symbol));
+        }
+        let mathml = match name.as_str() {
+            "frac" | "dfrac" | "tfrac" => {
+                let num = self.argument(&of)?;
+                let den = self.argument(&of)?;
+                format!("<mfrac>{num}{den}</mfrac>")
+            }
+            "binom" => {
+                let n = self.argument(&of)?;
+                let k = self.argument(&of)?;
+                format!(
+                    "<mrow><mo>(</mo><mfrac linethickness=\"0\">{n}{k}</mfrac><mo>)</mo></mrow>"
+                )
+            }
+            "sqrt" => {
+                self.skip_space();
+                if self.peek() == Some('[') {
+                    self.pos += 1;
+                    let index = row(self.expr(Stop::Bracket)?);
+                    let radicand = self.argument(&of)?;
+                    format!("<mroot>{radicand}{index}</mroot>")
+                } else {
+                    format!("<msqrt>{}</msqrt>", self.argument(&of)?)
+                }
+            }
+            "text" | "textrm" | "mbox" => {
+                format!("<mtext>{}</mtext>", escape_html(&self.raw_group(&of)?))
+            }
+            "mathrm" | "operatorname" => format!(
+                "<mi mathvariant=\"normal\">{}</mi>",
+                escape_html(&self.raw_group(&of)?)
+            ),
+            "mathbf" => variant("bold", self.argument(&of)?),
+            "mathit" => variant("italic", self.argument(&of)?),
+            "mathbb" => variant("double-struck", self.argument(&of)?),
+            "mathcal" => variant("script", self.argument(&of)?),
+            "mathtt" => variant("monospace", self.argument(&of)?),
+            "hat" | "widehat" => accent(self.argument(&of)?, "^"),
+            "bar" | "overline" => accent(self.argument(&of)?, "‾"),
+            "vec" => accent(self.argument(&of)?, "→"),
+            "tilde" | "widetilde" => accent(self.argument(&of)?, "~"),
+            "dot" => accent(self.argument(&of)?, "˙"),
+            "underline" => format!(
+                "<munder accentunder=\"true\">{}<mo>_</mo></munder>",
+                self.argument(&of)?
+            ),
+            "left" => {
+                let open = self.delimiter(&of)?;
+                let inner = self.expr(Stop::Right)?;
+                self.pos += "\\right".len();
+                let close = self.delimiter("\\right")?;
+                format!("<mrow>{open}{}{close}</mrow>", inner.concat())
+            }
+            "right" => return Err("\\right without \\left".into()),
+            "quad" => "<mspace width=\"1em\"/>".into(),
+            "qquad" => "<mspace width=\"2em\"/>".into(),
+            _ => return Err(format!("unknown command {of}")),
+        };
+        Ok(Some(Atom::new(mathml)))
+    }
+
+    /// The delimiter after `\left` or `\right`; `.` is an invisible one.
+    fn delimiter(&mut self, of: &str) -> Result<String, String> {
+        self.skip_space();
+        let Some(c) = self.peek() else {
+            return Err(format!("{of} needs a delimiter"));
+        };
+        self.pos += 1;
+        let d = match c {
+            '.' => return Ok(String::new()),
+            '(' | ')' | '[' | ']' | '|' | '/' => c.to_string(),
+            '<' => "⟨".into(),
+            '>' => "⟩".into(),
+            '\\' => {
+                let start = self.pos;
+                while self.peek().is_some_and(|c| c.is_ascii_alphabetic()) {
+                    self.pos += 1;
+                }
+                if self.pos == start && self.peek().is_some() {
+                    self.pos += 1;
+                }
+                let name: String = self.src[start..self.pos].iter().collect();
+                match name.as_str() {
+                    "{" | "lbrace" => "{".into(),
+                    "}" | "rbrace" => "}".into(),
+                    "|" | "Vert" => "‖".into(),
+                    "langle" => "⟨".into(),
+                    "rangle" => "⟩".into(),
+                    "lceil" => "⌈".into(),
+                    "rceil" => "⌉".into(),
+                    "lfloor" => "⌊".into(),
+                    "rfloor" => "⌋".into(),
+                    "vert" => "|".into(),
+                    _ => return Err(format!("\\{name} is not a delimiter for {of}")),
+                }
+            }
+            c => return Err(format!("{c} is not a delimiter for {of}")),
+        };
+        Ok(format!("<mo fence=\"true\">{d}</mo>"))
+    }
+}
+
+fn row(mut items: Vec<String>) -> String {
+    if items.len() == 1 {
+        items.pop().unwrap()
+    } else {
+        format!("<mrow>{}</mrow>", items.concat())
+    }
+}
+
+fn variant(name: &str, inner: String) -> String {
+    format!("<mstyle mathvariant=\"{name}\">{inner}</mstyle>")
+}
+
+fn accent(base: String, mark: &str) -> String {
+    format!("<mover accent=\"true\">{base}<mo>{mark}</mo></mover>")
+}
+
+/// Commands that stand for one character.
+fn symbol(name: &str) -> Option<Atom> {
+    let identifier = |c: &str| Some(Atom::new(format!("<mi>{c}</mi>")));
+    let operator = |c: &str| Some(Atom::new(format!("<mo>{c}</mo>")));
+    let big = |c: &str| {
+        Some(Atom {
+            mathml: format!("<mo largeop=\"true\">{c}</mo>"),
+            big: true,
+        })
+    };
+    let function = |f: &str| Some(Atom::new(format!("<mi mathvariant=\"normal\">{f}</mi>")));
+    match name {
+        "alpha" => identifier("α"),
+        "beta" => identifier("β"),
+        "gamma" => identifier("γ"),
+        "delta" => identifier("δ"),
+        "epsilon" => identifier("ϵ"),
+        "varepsilon" => identifier("ε"),
+        "zeta" => identifier("ζ"),
+        "eta" => identifier("η"),
+        "theta" => identifier("θ"),
+        "iota" => identifier("ι"),
+        "kappa" => identifier("κ"),
+        "lambda" => identifier("λ"),
+        "mu" => identifier("μ"),
+        "nu" => identifier("ν"),
+        "xi" => identifier("ξ"),
+        "pi" => identifier("π"),
+        "rho" => identifier("ρ"),
+        "sigma" => identifier("σ"),
+        "tau" => identifier("τ"),
+        "upsilon" => identifier("υ"),
+        "phi" => identifier("ϕ"),
+        "varphi" => identifier("φ"),
+        "chi" => identifier("χ"),
+        "psi" => identifier("ψ"),
+        "omega" => identifier("ω"),
+        "Gamma" => identifier("Γ"),
+        "Delta" => identifier("Δ"),
+        "Theta" => identifier("Θ"),
+        "Lambda" => identifier("Λ"),
+        "Xi" => identifier("Ξ"),
+        "Pi" => identifier("Π"),
+        "Sigma" => identifier("Σ"),
+        "Phi" => identifier("Φ"),
+        "Psi" => identifier("Ψ"),
+        "Omega" => identifier("Ω"),
+        "infty" => identifier("∞"),
+        "ell" => identifier("ℓ"),
+        "partial" => identifier("∂"),
+        "nabla" => identifier("∇"),
+        "emptyset" => identifier("∅"),
+        "times" => operator("×"),
+        "cdot" => operator("⋅"),
+        "div" => operator("÷"),
+        "pm" => operator("±"),
+        "mp" => operator("∓"),
+        "ast" => operator("∗"),
+        "circ" => operator("∘"),
+        "oplus" => operator("⊕"),
+        "otimes" => operator("⊗"),
+        "le" | "leq" => operator("≤"),
+        "ge" | "geq" => operator("≥"),
+        "ll" => operator("≪"),
+        "gg" => operator("≫"),
+        "ne" | "neq" => operator("≠"),
+        "approx" => operator("≈"),
+        "equiv" => operator("≡"),
+        "sim" => operator("∼"),
+        "simeq" => operator("≃"),
+        "propto" => operator("∝"),
+        "in" => operator("∈"),
+        "notin" => operator("∉"),
+        "subset" => operator("⊂"),
+        "subseteq" => operator("⊆"),
+        "cup" => operator("∪"),
+        "cap" => operator("∩"),
+        "setminus" => operator("∖"),
+        "land" | "wedge" => operator("∧"),
+        "lor" | "vee" => operator("∨"),
+        "neg" | "lnot" => operator("¬"),
+        "forall" => operator("∀"),
+        "exists" => operator("∃"),
+        "to" | "rightarrow" => operator("→"),
+        "leftarrow" | "gets" => operator("←"),
+        "leftrightarrow" => operator("↔"),
+        "Rightarrow" | "implies" => operator("⇒"),
+        "Leftarrow" => operator("⇐"),
+        "Leftrightarrow" | "iff" => operator("⇔"),
+        "mapsto" => operator("↦"),
+        "mid" => operator("∣"),
+        "ldots" | "dots" => operator("…"),
+        "cdots" => operator("⋯"),
+        "langle" => operator("⟨"),
+        "rangle" => operator("⟩"),
+        "lceil" => operator("⌈"),
+        "rceil" => operator("⌉"),
+        "lfloor" => operator("⌊"),
+        "rfloor" => operator("⌋"),
+        "sum" => big("∑"),
+        "prod" => big("∏"),
+        "int" => big("∫"),
+        "bigcup" => big("⋃"),
+        "bigcap" => big("⋂"),
+        "lim" => Some(Atom {
+            mathml: "<mi mathvariant=\"normal\">lim</mi>".into(),
+            big: true,
+        }),
+        "max" | "min" | "sup" | "inf" => Some(Atom {
+            mathml: format!("<mi mathvariant=\"normal\">{name}</mi>"),
+            big: true,
+        }),
+        "log" | "ln" | "lg" | "exp" | "sin" | "cos" | "tan" | "gcd" | "deg" | "det" | "dim"
+        | "bmod" => function(if name == "bmod" { "mod" } else { name }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The MathML inside `<semantics>`, without the wrapper and annotation.
+    fn body(tex: &str) -> String {
+        let html = render(tex, false).unwrap();
+        let start = html.find("<semantics>").unwrap() + "<semantics>".len();
+        let end = html.find("<annotation").unwrap();
+        html[start..end].to_string()
+    }
+
+    #[test]
+    fn wraps_inline_and_display_math_and_keeps_the_source() {
+        assert_eq!(
+            render("x", false).unwrap(),
+            "<math><semantics><mi>x</mi><annotation encoding=\"application/x-tex\">x</annotation></semantics></math>"
+        );
+        let block = render("a < b", true).unwrap();
+        assert!(block.starts_with("<math display=\"block\">"), "{block}");
+        assert!(block.contains("<mo>&lt;</mo>"), "{block}");
+        assert!(block.contains(">a &lt; b</annotation>"), "{block}");
+    }
+
+    #[test]
+    fn numbers_operators_and_scripts() {
+        assert_eq!(
+            body("2^{16} - 1"),
+            "<mrow><msup><mn>2</mn><mn>16</mn></msup><mo>−</mo><mn>1</mn></mrow>"
+        );
+        assert_eq!(
+            body("x_i^2"),
+            "<msubsup><mi>x</mi><mi>i</mi><mn>2</mn></msubsup>"
+        );
+        assert_eq!(body("3.14"), "<mn>3.14</mn>");
+    }
+
+    #[test]
+    fn commands_with_arguments() {
+        assert_eq!(body("\\frac{a}{b}"), "<mfrac><mi>a</mi><mi>b</mi></mfrac>");
+        assert_eq!(body("\\sqrt[3]{x}"), "<mroot><mi>x</mi><mn>3</mn></mroot>");
+        assert_eq!(
+            body("1300 \\times \\text{bytes}"),
+            "<mrow><mn>1300</mn><mo>×</mo><mtext>bytes</mtext></mrow>"
+        );
+        assert_eq!(
+            body("\\left( \\frac{1}{2} \\right)"),
+            "<mrow><mo fence=\"true\">(</mo><mfrac><mn>1</mn><mn>2</mn></mfrac><mo fence=\"true\">)</mo></mrow>"
+        );
+    }
+
+    #[test]
+    fn big_operators_take_limits_below_only_in_display_math() {
+        assert!(body("\\sum_{i=1}^n i").starts_with("<mrow><msubsup><mo largeop"));
+        let display = render("\\sum_{i=1}^n i", true).unwrap();
+        assert!(display.contains("<munderover><mo largeop"), "{display}");
+    }
+
+    #[test]
+    fn malformed_tex_is_an_error_that_quotes_the_formula() {
+        for (tex, why) in [
+            ("\\frac{a}", "missing its argument"),
+            ("{x", "unclosed {"),
+            ("x}", "unmatched }"),
+            ("x^2^3", "double ^"),
+            ("\\left( x", "\\left without \\right"),
+            ("\\foo", "unknown command \\foo"),
+            ("a & b", "not supported"),
+            ("^2", "nothing before it"),
+        ] {
+            let err = render(tex, false).unwrap_err();
+            assert!(err.starts_with(&format!("math {tex:?}: ")), "{err}");
+            assert!(err.contains(why), "{tex:?}: {err}");
+        }
+    }
+}
@@ -0,0 +1,229 @@
+//! Old URLs that should keep working.
+//!
+//! `content/redirects.toml` maps an old path to the one that replaced it:
+//!
+//! ```toml
+//! "/blog/old-slug/" = "/blog/new-slug/"
+//! ```
+//!
+//! and each old path gets a page that sends readers (and crawlers, through
+//! its canonical link) on to the new one. When a published post moves, found
+//! by a post vanishing from one path while another with both its date and
+//! title appears at a new one, blog-gen warns, or with `--record-redirects`
+//! adds the entry itself.
+
+use std::{collections::BTreeMap, fs, path::Path};
+
+/// Where a published post was, as the last build saw it.
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub(crate) struct Published {
+    pub(crate) title: String,
+    /// As RFC 3339.
+    pub(crate) date: String,
+}
+
+pub(crate) struct Redirects {
+    /// Old path -> new path, as written in the file.
+    entries: BTreeMap<String, String>,
+}
+
+impl Redirects {
+    /// The entries in `path`, or none when there is no such file.
+    pub(crate) fn load(path: &Path) -> Result<Self, String> {
+        let entries: BTreeMap<String, String> = match fs::read_to_string(path) {
+            Ok(text) => toml::from_str(&text).map_err(|e| format!("{}: {e}", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(format!("read {}: {e}", path.display())),
+        };
+        for (from, to) in &entries {
+            for url in [from, to] {
+                if !url.starts_with('/') || url.split('/').any(|s| s == "..") {
+                    return Err(format!(
+                        "{}: {url:?} must be an absolute path on this site",
+                        path.display()
+                    ));
+                }
+            }
+        }
+        Ok(Redirects { entries })
+    }
+
+    /// Adds `from -> to` to the file at `path` and to `self`.
+    pub(crate) fn append(&mut self, path: &Path, from: &str, to: &str) -> Result<(), String> {
+        let mut text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::from(
+                "# Old path = new path. blog-gen writes a page at each old path that\n\
+                 # sends readers on to the new one.\n",
+            ),
+            Err(e) => return Err(format!("read {}: {e}", path.display())),
+        };
+        if !text.is_empty() && !text.ends_with('\n') {
+            text.push('\n');
+        }
+        text.push_str(&format!("{from:?} = {to:?}\n"));
+        fs::write(path, text).map_err(|e| format!("write {}: {e}", path.display()))?;
+        self.entries.insert(from.to_string(), to.to_string());
+        Ok(())
+    }
+
+    /// Every old path with where it ends up, following chains (`a -> b`,
+    /// `b -> c` sends `a` to `c`). Old paths that are `live` again are left
+    /// out: the page there wins.
+    pub(crate) fn resolved(
+        &self,
+        live: impl Fn(&str) -> bool,
+    ) -> Result<Vec<(String, String)>, String> {
+        let mut out = Vec::new();
+        for from in self.entries.keys() {
+            if live(from) {
+                eprintln!("warning: redirect from {from} ignored: a page is served there");
+                continue;
+            }
+            let mut to = &self.entries[from];
+            let mut seen = vec![from];
+            while let Some(next) = self.entries.get(to).filter(|_| !live(to)) {
+                if seen.contains(&to) {
+                    return Err(format!("redirects loop through {to}"));
+                }
+                seen.push(to);
+                to = next;
+            }
+            out.push((from.clone(), to.clone()));
+        }
+        Ok(out)
+    }
+}
+
+/// Published posts that moved since the last build: each path that is gone
+/// paired with the new path of a post with the same date and title. Either
+/// alone is not enough: a post deleted the day another is published is not
+/// a move, nor is a new post that reuses an old title.
+pub(crate) fn moves(
+    previous: &BTreeMap<String, Published>,
+    current: &BTreeMap<String, Published>,
+) -> Vec<(String, String)> {
+    let gone: Vec<_> = previous
+        .iter()
+        .filter(|(path, _)| !current.contains_key(*path))
+        .collect();
+    let mut new: Vec<_> = current
+        .iter()
+        .filter(|(path, _)| !previous.contains_key(*path))
+        .collect();
+
+    let mut moves = Vec::new();
+    for (from, was) in gone {
+        let found = new
+            .iter()
+            .position(|(_, now)| now.date == was.date && now.title == was.title);
+        if let Some(i) = found {
+            let (to, _) = new.remove(i);
+            moves.push((from.clone(), to.clone()));
+        }
+    }
+    moves
+}
+
+/// The file an old path's redirect page is written to, relative to `static/`.
+pub(crate) fn page_file(from: &str) -> String {
+    let rel = from.trim_start_matches('/');
+    if rel.is_empty() || rel.ends_with('/') {
+        format!("{rel}index.html")
+    } else if rel.ends_with(".html") {
+        rel.to_string()
+    } else {
+        format!("{rel}/index.html")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn published(entries: &[(&str, &str, &str)]) -> BTreeMap<String, Published> {
+        entries
+            .iter()
+            .map(|(path, title, date)| {
+                (
+                    path.to_string(),
+                    Published {
+                        title: title.to_string(),
+                        date: date.to_string(),
+                    },
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn a_post_that_moved_is_found_by_its_date_and_title() {
+        let before = published(&[
+            ("/blog/a/", "A", "2026-01-01"),
+            ("/blog/b/", "B", "2026-02-01"),
+            ("/blog/c/", "C", "2026-03-01"),
+        ]);
+        let after = published(&[
+            ("/blog/a-renamed/", "A", "2026-01-01"),
+            ("/blog/b-retitled/", "B, retitled", "2026-02-01"),
+            ("/blog/c-redated/", "C", "2026-03-02"),
+        ]);
+        assert_eq!(
+            moves(&before, &after),
+            [("/blog/a/".to_string(), "/blog/a-renamed/".to_string())]
+        );
+    }
+
+    #[test]
+    fn a_post_deleted_the_day_another_is_published_did_not_move() {
+        let before = published(&[("/blog/old/", "Old", "2026-05-01T09:00:00Z")]);
+        let after = published(&[("/blog/new/", "New", "2026-05-01T09:00:00Z")]);
+        assert!(moves(&before, &after).is_empty());
+    }
+
+    #[test]
+    fn chains_resolve_to_their_end_and_live_pages_win() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("redirects.toml");
+        let mut redirects = Redirects::load(&path).unwrap();
+        redirects.append(&path, "/blog/a/", "/blog/b/").unwrap();
+        redirects.append(&path, "/blog/b/", "/blog/c/").unwrap();
+        redirects.append(&path, "/blog/live/", "/blog/c/").unwrap();
+
+        let reloaded = Redirects::load(&path).unwrap();
+        let live = |path: &str| ["/blog/live/", "/blog/c/"].contains(&path);
+        assert_eq!(
+            reloaded.resolved(live).unwrap(),
+            [
+                ("/blog/a/".to_string(), "/blog/c/".to_string()),
+                ("/blog/b/".to_string(), "/blog/c/".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn a_loop_is_an_error() {
+        let redirects = Redirects {
+            entries: BTreeMap::from([
+                ("/a".to_string(), "/b".to_string()),
+                ("/b".to_string(), "/a".to_string()),
+            ]),
+        };
+        assert!(redirects.resolved(|_| false).is_err());
+    }
+
+    #[test]
+    fn paths_must_stay_on_the_site() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("redirects.toml");
+        fs::write(&path, "\"/old/\" = \"https://example.com/\"\n").unwrap();
+        assert!(Redirects::load(&path).is_err());
+    }
+
+    #[test]
+    fn old_paths_map_to_the_files_file_cache_serves_them_from() {
+        assert_eq!(page_file("/blog/old/"), "blog/old/index.html");
+        assert_eq!(page_file("/blog/old"), "blog/old/index.html");
+        assert_eq!(page_file("/about.html"), "about.html");
+    }
+}
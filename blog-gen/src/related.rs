@@ -0,0 +1,154 @@
+//! Links from a post to others: the rest of its series, in order, and the
+//! published posts most like it.
+//!
+//! Likeness is the share of tags two posts have in common plus the cosine
+//! similarity of their rendered text, each post's terms (as search cuts
+//! them) weighted by tf-idf, so that words every post uses count for little.
+
+use std::collections::HashMap;
+
+use serde_json::{json, Value};
+
+use crate::{date_context, search, slugify, tag_slug, Post};
+
+/// Most related posts listed under a post.
+const RELATED: usize = 3;
+
+/// Below this, two posts have only chance words in common.
+const MIN_LIKENESS: f32 = 0.1;
+
+/// What `post.html` sees as `post.series` and `post.related` for each of
+/// `posts`, in the same order.
+pub(crate) struct Links {
+    pub series: Vec<Value>,
+    pub related: Vec<Vec<Value>>,
+}
+
+pub(crate) fn links(posts: &[&Post]) -> Links {
+    let vectors = term_vectors(posts);
+    Links {
+        series: (0..posts.len()).map(|i| series(posts, i)).collect(),
+        related: (0..posts.len())
+            .map(|i| related(posts, &vectors, i))
+            .collect(),
+    }
+}
+
+fn entry(p: &Post) -> Value {
+    json!({ "title": p.title, "path": p.path(), "date": date_context(p.date) })
+}
+
+/// `{name, part, total, posts, prev, next}` for the series `posts[at]` is in,
+/// or null. A draft sees itself in its place among the published parts.
+fn series(posts: &[&Post], at: usize) -> Value {
+    let post = posts[at];
+    let Some(name) = &post.series else {
+        return Value::Null;
+    };
+    let key = slugify(name);
+    let mut parts: Vec<&Post> = posts
+        .iter()
+        .copied()
+        .filter(|q| q.series.as_deref().is_some_and(|s| slugify(s) == key))
+        .filter(|q| !q.draft || std::ptr::eq(*q, post))
+        .collect();
+    parts.sort_by_key(|q| q.date.unix_seconds());
+    let part = parts
+        .iter()
+        .position(|q| std::ptr::eq(*q, post))
+        .expect("a post is in its own series");
+
+    let entries: Vec<Value> = parts
+        .iter()
+        .map(|q| {
+            let mut e = entry(q);
+            e["current"] = json!(std::ptr::eq(*q, post));
+            e
+        })
+        .collect();
+    json!({
+        "name": name,
+        "part": part + 1,
+        "total": parts.len(),
+        "prev": part.checked_sub(1).map_or(Value::Null, |i| entry(parts[i])),
+        "next": parts.get(part + 1).map_or(Value::Null, |q| entry(q)),
+        "posts": entries,
+    })
+}
+
+/// The published posts most like `posts[at]`, most alike first.
+fn related(posts: &[&Post], vectors: &[HashMap<String, f32>], at: usize) -> Vec<Value> {
+    let tags = |p: &Post| -> Vec<String> { p.tags.iter().map(|t| tag_slug(t)).collect() };
+    let own_tags = tags(posts[at]);
+
+    let mut scored: Vec<(f32, usize)> = (0..posts.len())
+        .filter(|&i| i != at && !posts[i].draft)
+        .map(|i| {
+            let other_tags = tags(posts[i]);
+            let shared = own_tags.iter().filter(|t| other_tags.contains(t)).count();
+            let all = own_tags.len() + other_tags.len() - shared;
+            let tag_overlap = if all == 0 {
+                0.0
+            } else {
+                shared as f32 / all as f32
+            };
+            (tag_overlap + cosine(&vectors[at], &vectors[i]), i)
+        })
+        .filter(|(likeness, _)| *likeness >= MIN_LIKENESS)
+        .collect();
+    // Most alike first; among equals, the order posts are listed in.
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+    scored
+        .into_iter()
+        .take(RELATED)
+        .map(|(_, i)| entry(posts[i]))
+        .collect()
+}
+
+/// Each post's terms weighted by tf-idf, scaled to unit length.
+fn term_vectors(posts: &[&Post]) -> Vec<HashMap<String, f32>> {
+    let counts: Vec<HashMap<String, f32>> = posts
+        .iter()
+        .map(|p| {
+            let mut counts = HashMap::new();
+            for term in site_search::terms(&search::text(&p.html)) {
+                *counts.entry(term).or_insert(0.0) += 1.0;
+            }
+            counts
+        })
+        .collect();
+
+    let mut document_frequency: HashMap<&str, f32> = HashMap::new();
+    for c in &counts {
+        for term in c.keys() {
+            *document_frequency.entry(term).or_insert(0.0) += 1.0;
+        }
+    }
+    let n = posts.len() as f32;
+
+    counts
+        .iter()
+        .map(|c| {
+            let mut vector: HashMap<String, f32> = c
+                .iter()
+                .map(|(term, tf)| {
+                    let idf = (n / document_frequency[term.as_str()]).ln();
+                    (term.clone(), tf * idf)
+                })
+                .collect();
+            let norm = vector.values().map(|w| w * w).sum::<f32>().sqrt();
+            if norm > 0.0 {
+                vector.values_mut().for_each(|w| *w /= norm);
+            }
+            vector
+        })
+        .collect()
+}
+
+fn cosine(a: &HashMap<String, f32>, b: &HashMap<String, f32>) -> f32 {
+    let (small, large) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    small
+        .iter()
+        .filter_map(|(term, w)| large.get(term).map(|v| w * v))
+        .sum()
+}
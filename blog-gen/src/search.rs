@@ -0,0 +1,101 @@
+//! The search index jatai answers `/search?q=` from (see the `site-search`
+//! crate): each published post's title, headings and text, with its entry in
+//! the results already rendered through `partials/search-result.html`.
+
+use serde_json::json;
+use site_search::Document;
+
+use crate::{post_context, template::Templates, Post, Site};
+
+pub(crate) const RESULT_TEMPLATE: &str = "partials/search-result.html";
+
+/// The index file for `posts`.
+pub(crate) fn index(
+    templates: &Templates,
+    site: &Site,
+    posts: &[&Post],
+) -> Result<Vec<u8>, String> {
+    let docs = posts
+        .iter()
+        .map(|p| {
+            let context = json!({ "post": post_context(site, p) });
+            Ok(Document {
+                path: p.path(),
+                title: p.title.clone(),
+                headings: headings(&p.html),
+                body: text(&p.html),
+                html: templates.render(RESULT_TEMPLATE, &context)?,
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    Ok(site_search::build(&docs))
+}
+
+/// The text of every `<h2>` to `<h6>` in `html`.
+fn headings(html: &str) -> Vec<String> {
+    let mut found = Vec::new();
+    let mut rest = html;
+    while let Some(at) = rest.find("<h") {
+        rest = &rest[at + 2..];
+        let Some(level) = rest.chars().next().filter(|c| ('2'..='6').contains(c)) else {
+            continue;
+        };
+        let close = format!("</h{level}>");
+        let Some(end) = rest.find(&close) else {
+            break;
+        };
+        let inner = rest[1..end].split_once('>').map_or("", |(_, inner)| inner);
+        let inner = text(inner);
+        found.push(inner.split_whitespace().collect::<Vec<_>>().join(" "));
+        rest = &rest[end + close.len()..];
+    }
+    found
+}
+
+/// `html` with its tags dropped and its entities decoded. Every tag leaves a
+/// space, so words in neighbouring cells or list items stay apart.
+pub(crate) fn text(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(at) = rest.find(['<', '&']) {
+        out.push_str(&rest[..at]);
+        rest = &rest[at..];
+        if rest.starts_with('<') {
+            let end = rest.find('>').map_or(rest.len(), |end| end + 1);
+            out.push(' ');
+            rest = &rest[end..];
+            continue;
+        }
+        let entity = rest
+            .find(';')
+            .filter(|&end| end <= 10)
+            .map(|end| &rest[..=end]);
+        let decoded = match entity {
+            Some("&amp;") => "&",
+            Some("&lt;") => "<",
+            Some("&gt;") => ">",
+            Some("&quot;") => "\"",
+            Some("&#39;" | "&#x27;") => "'",
+            Some(_) => " ",
+            None => "&",
+        };
+        out.push_str(decoded);
+        rest = &rest[entity.map_or(1, str::len)..];
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn markup_is_dropped_and_words_kept_apart() {
+        let html = "<h2 id=\"a\">Why <code>x &amp; y</code></h2><p>One</p><p>two&nbsp;three</p>";
+        assert_eq!(headings(html), ["Why x & y"]);
+        let text = text(html);
+        let words: Vec<&str> = text.split_whitespace().collect();
+        assert_eq!(words, ["Why", "x", "&", "y", "One", "two", "three"]);
+    }
+}
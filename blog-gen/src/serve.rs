@@ -0,0 +1,211 @@
+//! `blog-gen serve`: the site on localhost, rebuilt as you write it.
+//!
+//! `content/`, `templates/` and `site.toml` are polled for changes; each
+//! change rebuilds the site and jatai, serving it with live reload, refreshes
+//! every open tab. A failed build shows its error in those tabs until the
+//! next good one. Files edited by hand in `static/` (the stylesheet, say)
+//! reload the tabs without a rebuild.
+//!
+//! Drafts are served as they are in production, behind signed preview links,
+//! but signed with a key made for this run: each draft's link is printed when
+//! a build first finds it.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use jatai::{JataiBuilder, LiveReload};
+
+const DEFAULT_PORT: u16 = 8080;
+
+/// How often the sources are checked. Editors write a file in several steps,
+/// so a rebuild also waits for one quiet interval.
+const POLL: Duration = Duration::from_millis(250);
+
+/// How long the printed preview links work; the key they are signed with
+/// lasts only as long as the server anyway.
+const PREVIEW_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+pub(crate) fn run(root: &Path, args: &[String]) -> Result<String, String> {
+    let port = match args {
+        [] => DEFAULT_PORT,
+        [flag, port] if flag == "--port" => port
+            .parse()
+            .map_err(|_| format!("invalid --port {port:?}"))?,
+        _ => return Err(crate::USAGE.to_string()),
+    };
+    let addr = format!("127.0.0.1:{port}");
+    let static_dir = root.join("static");
+
+    let live = LiveReload::new();
+    let mut previews = Previews {
+        key: jatai::preview::random_key(),
+        origin: format!("http://{addr}"),
+        shown: BTreeSet::new(),
+    };
+    build(root, &live, &mut previews);
+
+    let runtime = tokio::runtime::Runtime::new().map_err(|e| format!("start runtime: {e}"))?;
+    let server = runtime
+        .block_on(
+            JataiBuilder::new()
+                .with_static_dir(static_dir.to_string_lossy())
+                .bind_http(&addr)
+                .live_reload(live.clone())
+                .preview_key(previews.key.clone())
+                .build(),
+        )
+        .map_err(|e| format!("bind {addr}: {e}"))?;
+    println!("Serving {} at http://{addr}/", static_dir.display());
+
+    let root = root.to_path_buf();
+    thread::spawn(move || watch(&root, &live, &mut previews));
+    runtime.block_on(server.run());
+    Ok("Server stopped".to_string())
+}
+
+fn watch(root: &Path, live: &LiveReload, previews: &mut Previews) {
+    let sources = [
+        root.join("content"),
+        root.join("templates"),
+        root.join("site.toml"),
+    ];
+    let output = [root.join("static")];
+    let mut seen_sources = Snapshot::of(&sources);
+    let mut seen_output = Snapshot::of(&output);
+    loop {
+        thread::sleep(POLL);
+        let mut now = Snapshot::of(&sources);
+        if now != seen_sources {
+            loop {
+                thread::sleep(POLL);
+                let settled = Snapshot::of(&sources);
+                if settled == now {
+                    break;
+                }
+                now = settled;
+            }
+            for path in seen_sources.changes(&now) {
+                let path = path.strip_prefix(root).unwrap_or(path);
+                println!("changed: {}", path.display());
+            }
+            seen_sources = now;
+            build(root, live, previews);
+            seen_output = Snapshot::of(&output);
+        } else {
+            let now = Snapshot::of(&output);
+            if now != seen_output {
+                seen_output = now;
+                live.rebuilt();
+            }
+        }
+    }
+}
+
+/// Regenerates the site and tells the server (and through it, the browser)
+/// how that went.
+fn build(root: &Path, live: &LiveReload, previews: &mut Previews) {
+    let started = Instant::now();
+    match blog_gen::generate(root) {
+        Ok(generated) => {
+            for link in &generated.broken_links {
+                eprintln!("warning: {link}");
+            }
+            println!(
+                "Generated {} post(s) in {} ms",
+                generated.posts,
+                started.elapsed().as_millis()
+            );
+            live.rebuilt();
+            previews.print_new(root);
+        }
+        Err(e) => {
+            eprintln!("blog-gen: {e}");
+            live.failed(e);
+        }
+    }
+}
+
+/// The preview links handed out so far, and what signs them.
+struct Previews {
+    key: Vec<u8>,
+    /// Where the server listens, which the links are relative to.
+    origin: String,
+    shown: BTreeSet<String>,
+}
+
+impl Previews {
+    /// Print a link to each draft under `root` that has not had one yet.
+    fn print_new(&mut self, root: &Path) {
+        let Ok(drafts) = blog_gen::draft_slugs(root) else {
+            return;
+        };
+        let expires = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs())
+            + PREVIEW_TTL.as_secs();
+        for slug in drafts {
+            if self.shown.insert(slug.clone()) {
+                let link = jatai::preview::link(&self.key, &slug, expires);
+                println!("draft {slug}: {}{link}", self.origin);
+            }
+        }
+    }
+}
+
+/// The modification time and size of every file under some directories, and
+/// of any files named alongside them.
+#[derive(PartialEq)]
+struct Snapshot(BTreeMap<PathBuf, (Option<SystemTime>, u64)>);
+
+impl Snapshot {
+    fn of(paths: &[PathBuf]) -> Self {
+        let mut files = BTreeMap::new();
+        for path in paths {
+            Self::walk(path, &mut files);
+        }
+        Snapshot(files)
+    }
+
+    fn walk(dir: &Path, files: &mut BTreeMap<PathBuf, (Option<SystemTime>, u64)>) {
+        let Ok(read_dir) = fs::read_dir(dir) else {
+            // A file of its own, or nothing.
+            if let Ok(meta) = fs::metadata(dir) {
+                files.insert(dir.to_path_buf(), (meta.modified().ok(), meta.len()));
+            }
+            return;
+        };
+        for entry in read_dir.flatten() {
+            let path = entry.path();
+            let Ok(meta) = entry.metadata() else {
+                continue;
+            };
+            if meta.is_dir() {
+                Self::walk(&path, files);
+            } else {
+                files.insert(path, (meta.modified().ok(), meta.len()));
+            }
+        }
+    }
+
+    /// Files added, removed or modified between `self` and `now`.
+    fn changes<'a>(&'a self, now: &'a Snapshot) -> Vec<&'a Path> {
+        let mut changed: Vec<&Path> = now
+            .0
+            .iter()
+            .filter(|(path, stamp)| self.0.get(*path) != Some(stamp))
+            .map(|(path, _)| path.as_path())
+            .collect();
+        changed.extend(
+            self.0
+                .keys()
+                .filter(|path| !now.0.contains_key(*path))
+                .map(PathBuf::as_path),
+        );
+        changed
+    }
+}
@@ -0,0 +1,466 @@
+//! Shortcodes: `{{< name key="value" >}}` on a line of their own, expanded to
+//! HTML before the markdown is parsed.
+//!
+//! ```text
+//! {{< note title="Heads up" >}}
+//! Markdown, as usual.
+//! {{< /note >}}
+//!
+//! {{< figure src="graph.png" alt="…" caption="…" width=800 height=450 >}}
+//! ```
+//!
+//! `note`, `warning`, `details` and `aside` wrap markdown and need a closing
+//! tag; `figure` stands alone. The opening and closing HTML go on lines of
+//! their own with blank lines around the content, which CommonMark reads as
+//! an HTML block followed by ordinary markdown, so the content renders as it
+//! would anywhere else. A shortcode may sit inside a list item or a
+//! blockquote: its indentation and `>` markers are carried onto every line it
+//! expands to.
+
+use std::fmt;
+
+use crate::escape_html;
+
+#[derive(Debug)]
+pub(crate) struct ShortcodeError {
+    /// 1-based, counted from the start of the body.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ShortcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// A shortcode opened and not yet closed.
+struct Open {
+    name: String,
+    line: usize,
+    /// What was in front of the opening tag, for the closing HTML's line.
+    prefix: String,
+}
+
+/// Expands every shortcode in `body`. Code blocks are left alone, so a post
+/// can show a shortcode without running it.
+pub(crate) fn expand(body: &str) -> Result<String, ShortcodeError> {
+    let mut out = String::with_capacity(body.len());
+    let mut open: Vec<Open> = Vec::new();
+    let mut fence: Option<String> = None;
+
+    for (i, line) in body.split_inclusive('\n').enumerate() {
+        let n = i + 1;
+        let error = |message: String| ShortcodeError { line: n, message };
+        let (prefix, rest) = split_prefix(line);
+
+        if let Some(marker) = &fence {
+            if closes_fence(rest, marker) {
+                fence = None;
+            }
+            out.push_str(line);
+            continue;
+        }
+        if let Some(marker) = fence_marker(rest) {
+            fence = Some(marker);
+            out.push_str(line);
+            continue;
+        }
+
+        let trimmed = rest.trim_end();
+        let Some(tag) = trimmed
+            .strip_prefix("{{<")
+            .and_then(|t| t.strip_suffix(">}}"))
+        else {
+            if outside_code(trimmed).contains("{{<") {
+                return Err(error(
+                    "a shortcode goes on a line of its own; to show one, put it in `code`".into(),
+                ));
+            }
+            out.push_str(line);
+            continue;
+        };
+        let tag = tag.trim();
+        let continuation = continuation(prefix);
+
+        if let Some(name) = tag.strip_prefix('/') {
+            let name = name.trim();
+            match open.pop() {
+                Some(o) if o.name == name => {
+                    out.push_str(continuation.trim_end());
+                    out.push('\n');
+                    out.push_str(&o.prefix);
+                    out.push_str(closing(name));
+                    out.push('\n');
+                }
+                Some(o) => {
+                    return Err(error(format!(
+                        "{{{{< /{name} >}}}} closes {{{{< {} >}}}} from line {}",
+                        o.name, o.line
+                    )))
+                }
+                None => return Err(error(format!("{{{{< /{name} >}}}} was never opened"))),
+            }
+            continue;
+        }
+
+        let (name, args) = parse_tag(tag).map_err(error)?;
+        let html = match name {
+            "note" | "warning" => {
+                let [title] = take(name, args, ["title"]).map_err(error)?;
+                let title = title.unwrap_or_else(|| capitalize(name));
+                format!(
+                    "<div class=\"callout {name}\" role=\"note\">\n{continuation}<p class=\"callout-title\">{}</p>\n",
+                    escape_html(&title)
+                )
+            }
+            "details" => {
+                let [summary] = take(name, args, ["summary"]).map_err(error)?;
+                let summary = summary.unwrap_or_else(|| "Details".into());
+                format!(
+                    "<details>\n{continuation}<summary>{}</summary>\n",
+                    escape_html(&summary)
+                )
+            }
+            "aside" => {
+                take(name, args, []).map_err(error)?;
+                "<aside class=\"aside\">\n".into()
+            }
+            "figure" => {
+                out.push_str(prefix);
+                out.push_str(&figure(args).map_err(error)?);
+                out.push_str(continuation.trim_end());
+                out.push('\n');
+                continue;
+            }
+            _ => return Err(error(format!("unknown shortcode {{{{< {name} >}}}}"))),
+        };
+        out.push_str(prefix);
+        out.push_str(&html);
+        out.push_str(continuation.trim_end());
+        out.push('\n');
+        open.push(Open {
+            name: name.to_string(),
+            line: n,
+            prefix: continuation,
+        });
+    }
+
+    match open.pop() {
+        Some(o) => Err(ShortcodeError {
+            line: o.line,
+            message: format!("{{{{< {} >}}}} is never closed", o.name),
+        }),
+        None => Ok(out),
+    }
+}
+
+fn closing(name: &str) -> &'static str {
+    match name {
+        "details" => "</details>",
+        "aside" => "</aside>",
+        _ => "</div>",
+    }
+}
+
+/// Splits a line into its container markers (indentation, `>`, list
+/// bullets) and what follows them.
+fn split_prefix(line: &str) -> (&str, &str) {
+    let mut end = 0;
+    let bytes = line.as_bytes();
+    while end < bytes.len() {
+        match bytes[end] {
+            b' ' | b'\t' | b'>' => end += 1,
+            b'-' | b'*' | b'+' if bytes.get(end + 1) == Some(&b' ') => end += 2,
+            b'0'..=b'9' => {
+                let digits = bytes[end..]
+                    .iter()
+                    .take_while(|b| b.is_ascii_digit())
+                    .count();
+                match bytes.get(end + digits..end + digits + 2) {
+                    Some([b'.' | b')', b' ']) => end += digits + 2,
+                    _ => break,
+                }
+            }
+            _ => break,
+        }
+    }
+    line.split_at(end)
+}
+
+/// The prefix for the lines a shortcode adds after its first: the same
+/// containers, but a list bullet becomes the indentation that continues its
+/// item. Blank lines take it with trailing space trimmed.
+fn continuation(prefix: &str) -> String {
+    prefix
+        .chars()
+        .map(|c| match c {
+            '>' | '\t' => c,
+            _ => ' ',
+        })
+        .collect()
+}
+
+fn fence_marker(rest: &str) -> Option<String> {
+    let rest = rest.trim_start();
+    for c in ['`', '~'] {
+        let run = rest.chars().take_while(|&x| x == c).count();
+        if run >= 3 {
+            return Some(c.to_string().repeat(run));
+        }
+    }
+    None
+}
+
+/// Whether `rest` ends the block `marker` opened: a run of the same
+/// character at least as long, with nothing after it. "```rust" inside a
+/// "```" block is content, not the end of it.
+fn closes_fence(rest: &str, marker: &str) -> bool {
+    let run = rest.trim();
+    let c = marker
+        .chars()
+        .next()
+        .expect("a fence marker is never empty");
+    run.len() >= marker.len() && run.chars().all(|x| x == c)
+}
+
+/// `line` less its inline code spans. A backtick run with no run of the same
+/// length to close it is literal text, as in CommonMark.
+fn outside_code(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(at) = rest.find('`') {
+        out.push_str(&rest[..at]);
+        let run = rest[at..].bytes().take_while(|&b| b == b'`').count();
+        let after = &rest[at + run..];
+        // Slicing only at backticks, which are ASCII, keeps to char boundaries.
+        let bytes = after.as_bytes();
+        let close = (0..bytes.len()).find(|&i| {
+            (i == 0 || bytes[i - 1] != b'`')
+                && bytes[i..].iter().take_while(|&&b| b == b'`').count() == run
+        });
+        match close {
+            Some(i) => rest = &after[i + run..],
+            None => {
+                out.push_str(&rest[at..at + run]);
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// `key=value` pairs, in the order written.
+type Args<'a> = Vec<(&'a str, String)>;
+
+/// A tag's name and its arguments; values may be quoted.
+fn parse_tag(tag: &str) -> Result<(&str, Args<'_>), String> {
+    let (name, mut rest) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+    if name.is_empty() {
+        return Err("empty shortcode".into());
+    }
+    let mut args = Vec::new();
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            return Ok((name, args));
+        }
+        let (key, after) = rest
+            .split_once('=')
+            .ok_or_else(|| format!("{{{{< {name} >}}}}: expected key=value, found {rest:?}"))?;
+        let key = key.trim();
+        let after = after.trim_start();
+        let (value, next) = match after.strip_prefix('"') {
+            Some(quoted) => quoted
+                .split_once('"')
+                .ok_or_else(|| format!("{{{{< {name} >}}}}: unclosed quote in {key}"))?,
+            None => after.split_once(char::is_whitespace).unwrap_or((after, "")),
+        };
+        args.push((key, value.to_string()));
+        rest = next;
+    }
+}
+
+/// The values of `keys`, in order, refusing any argument not among them.
+fn take<const N: usize>(
+    name: &str,
+    args: Args<'_>,
+    keys: [&str; N],
+) -> Result<[Option<String>; N], String> {
+    let mut values: [Option<String>; N] = std::array::from_fn(|_| None);
+    for (key, value) in args {
+        let Some(i) = keys.iter().position(|k| *k == key) else {
+            return Err(format!("{{{{< {name} >}}}} has no argument {key:?}"));
+        };
+        if values[i].replace(value).is_some() {
+            return Err(format!("{{{{< {name} >}}}}: {key} given twice"));
+        }
+    }
+    Ok(values)
+}
+
+fn figure(args: Args<'_>) -> Result<String, String> {
+    let [src, alt, caption, width, height] =
+        take("figure", args, ["src", "alt", "caption", "width", "height"])?;
+    let src = src.ok_or("{{< figure >}} needs a src")?;
+    let alt = alt.or_else(|| caption.clone()).unwrap_or_default();
+
+    let mut img = format!(
+        "<img src=\"{}\" alt=\"{}\"",
+        escape_html(&src),
+        escape_html(&alt)
+    );
+    for (attr, value) in [("width", width), ("height", height)] {
+        if let Some(value) = value {
+            if value.parse::<u32>().is_err() {
+                return Err(format!(
+                    "{{{{< figure >}}}}: {attr} must be a number of pixels, not {value:?}"
+                ));
+            }
+            img.push_str(&format!(" {attr}=\"{value}\""));
+        }
+    }
+    img.push_str(" loading=\"lazy\" decoding=\"async\" />");
+
+    Ok(match caption {
+        Some(caption) => format!(
+            "<figure>{img}<figcaption>{}</figcaption></figure>\n",
+            escape_html(&caption)
+        ),
+        None => format!("<figure>{img}</figure>\n"),
+    })
+}
+
+fn capitalize(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(md: &str) -> String {
+        let mut html = String::new();
+        pulldown_cmark::html::push_html(
+            &mut html,
+            pulldown_cmark::Parser::new(&expand(md).unwrap()),
+        );
+        html
+    }
+
+    #[test]
+    fn callouts_wrap_markdown() {
+        let html = render("{{< note >}}\nSome *emphasis*.\n{{< /note >}}\n");
+        assert_eq!(
+            html,
+            "<div class=\"callout note\" role=\"note\">\n<p class=\"callout-title\">Note</p>\n<p>Some <em>emphasis</em>.</p>\n</div>\n"
+        );
+        let html = render("{{< warning title=\"Mind the fee\" >}}\nx\n{{< /warning >}}\n");
+        assert!(
+            html.contains("<p class=\"callout-title\">Mind the fee</p>"),
+            "{html}"
+        );
+    }
+
+    #[test]
+    fn details_and_asides() {
+        let html = render("{{< details summary=\"The proof\" >}}\n1. a\n2. b\n{{< /details >}}\n");
+        assert!(
+            html.starts_with("<details>\n<summary>The proof</summary>\n<ol>"),
+            "{html}"
+        );
+        assert!(html.ends_with("</ol>\n</details>\n"), "{html}");
+        let html = render("{{< aside >}}\nBy the way.\n{{< /aside >}}\n");
+        assert_eq!(
+            html,
+            "<aside class=\"aside\">\n<p>By the way.</p>\n</aside>\n"
+        );
+    }
+
+    #[test]
+    fn figures_carry_their_size() {
+        let html = render(
+            "{{< figure src=\"g.png\" caption=\"Fees & time\" width=800 height=\"450\" >}}\n",
+        );
+        assert_eq!(
+            html,
+            "<figure><img src=\"g.png\" alt=\"Fees &amp; time\" width=\"800\" height=\"450\" loading=\"lazy\" decoding=\"async\" /><figcaption>Fees &amp; time</figcaption></figure>\n"
+        );
+    }
+
+    #[test]
+    fn works_inside_blockquotes_and_lists() {
+        let html = render("> {{< note >}}\n> Quoted.\n> {{< /note >}}\n");
+        assert_eq!(
+            html,
+            "<blockquote>\n<div class=\"callout note\" role=\"note\">\n<p class=\"callout-title\">Note</p>\n<p>Quoted.</p>\n</div>\n</blockquote>\n"
+        );
+
+        let html = render("- one\n\n  {{< note >}}\n  Listed.\n  {{< /note >}}\n- two\n");
+        assert!(
+            html.contains("<li>\n<p>one</p>\n<div class=\"callout note\""),
+            "{html}"
+        );
+        assert!(html.contains("<p>Listed.</p>\n</div>\n</li>"), "{html}");
+        assert_eq!(html.matches("<li>").count(), 2, "{html}");
+    }
+
+    #[test]
+    fn code_blocks_are_left_alone() {
+        let md = "```\n{{< nonsense >}}\n```\n";
+        assert_eq!(expand(md).unwrap(), md);
+        let md = "Write `{{< note >}}` on a line, or ``{{< `figure` >}}`` — `αA`.\n";
+        assert_eq!(expand(md).unwrap(), md);
+    }
+
+    #[test]
+    fn only_a_bare_fence_closes_a_code_block() {
+        // The "```rust" line is the block's content, so the shortcode after it
+        // is still inside the block.
+        let md = "````\n```rust\n{{< nonsense >}}\n```\n````\n{{< aside >}}\nx\n{{< /aside >}}\n";
+        let out = expand(md).unwrap();
+        assert!(
+            out.starts_with("````\n```rust\n{{< nonsense >}}\n```\n````\n<aside"),
+            "{out}"
+        );
+        let md = "```\n```rust\n{{< nonsense >}}\n```\n";
+        assert_eq!(expand(md).unwrap(), md);
+    }
+
+    #[test]
+    fn mistakes_are_errors_with_a_line() {
+        for (md, line, why) in [
+            ("a\n{{< spoiler >}}\n", 2, "unknown shortcode"),
+            ("See {{< nope >}} here.\n", 1, "a line of its own"),
+            (
+                "{{< figure src=\"a.png\" >}} caption\n",
+                1,
+                "a line of its own",
+            ),
+            ("Unclosed `code {{< note >}}\n", 1, "a line of its own"),
+            ("{{< note >}}\nx\n", 1, "never closed"),
+            ("{{< /note >}}\n", 1, "never opened"),
+            (
+                "{{< note >}}\n{{< /details >}}\n",
+                2,
+                "closes {{< note >}} from line 1",
+            ),
+            (
+                "{{< note colour=red >}}\n{{< /note >}}\n",
+                1,
+                "no argument \"colour\"",
+            ),
+            ("{{< figure alt=x >}}\n", 1, "needs a src"),
+            ("{{< figure src=a width=wide >}}\n", 1, "number of pixels"),
+        ] {
+            let err = expand(md).unwrap_err();
+            assert_eq!(err.line, line, "{md:?}: {err}");
+            assert!(err.message.contains(why), "{md:?}: {err}");
+        }
+    }
+}
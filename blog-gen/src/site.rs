@@ -0,0 +1,206 @@
+//! Site-wide settings, from `site.toml` beside `content/`:
+//!
+//! ```toml
+//! base_url = "https://erickcestari.dev"
+//! title = "erickcestari.dev"
+//! description = "Articles on security research, fuzzing, Bitcoin, and Lightning Network."
+//! author = "Erick Cestari"
+//! language = "en"
+//! og_image = "/profile.webp"
+//! webmentions = true              # optional; with jatai's WEBMENTION_DIR
+//!
+//! [[nav]]
+//! label = "Blog"
+//! href = "/blog"
+//!
+//! [card]                          # optional; no social cards without it
+//! background = "#121110"          # or an image, relative to site.toml
+//! color = "#ece7df"
+//! font = "assets/fonts/DejaVuSerif-Bold.ttf"
+//!
+//! [activitypub]                   # optional; no fediverse actor without it
+//! username = "erick"
+//! ```
+//!
+//! Every template sees them as `site`, with `og_image` made absolute and each
+//! nav link marked `external` when it leaves the site.
+
+use std::{fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Site {
+    /// Scheme and host, with no trailing slash: what every absolute URL the
+    /// site links to, or is linked at, starts with.
+    pub base_url: String,
+    /// Names the site in page titles and feeds.
+    pub title: String,
+    /// What the site is about, for the feed formats that ask.
+    pub description: String,
+    pub author: String,
+    /// The `lang` of every page and feed, as a BCP 47 tag.
+    pub language: String,
+    /// The Open Graph image of pages without their own: a path on the site or
+    /// an absolute URL.
+    pub og_image: String,
+    #[serde(default)]
+    pub nav: Vec<NavLink>,
+    /// How each post's social card is drawn; see `card`.
+    pub card: Option<CardStyle>,
+    /// Point every page at jatai's Webmention endpoint, so other sites know
+    /// where to say they link here. The endpoint is there only when jatai
+    /// runs with `WEBMENTION_DIR` set, so the two go on together.
+    #[serde(default)]
+    pub webmentions: bool,
+    /// Publish the posts as a fediverse account; see `activitypub`.
+    pub activitypub: Option<ActivityPub>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NavLink {
+    pub label: String,
+    pub href: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CardStyle {
+    /// A `#rgb` or `#rrggbb` colour, or the path of an image to crop to fit.
+    pub background: String,
+    /// The text's colour, `#rgb` or `#rrggbb`.
+    pub color: String,
+    /// A TrueType font file.
+    pub font: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ActivityPub {
+    /// The account's name: `username@host`, the host being `base_url`'s.
+    pub username: String,
+}
+
+impl Site {
+    /// Reads `site.toml` under `root`.
+    pub fn load(root: &Path) -> Result<Self, String> {
+        let path = root.join("site.toml");
+        let text =
+            fs::read_to_string(&path).map_err(|e| format!("read {}: {e}", path.display()))?;
+        let site: Site = toml::from_str(&text).map_err(|e| format!("{}: {e}", path.display()))?;
+        if let Some(ap) = &site.activitypub {
+            let valid = |c: char| c.is_ascii_alphanumeric() || c == '_';
+            if ap.username.is_empty() || !ap.username.chars().all(valid) {
+                return Err(format!(
+                    "{}: activitypub username {:?} must be letters, digits and _",
+                    path.display(),
+                    ap.username
+                ));
+            }
+        }
+        let base_url = site.base_url.clone();
+        site.with_base_url(&base_url)
+            .map_err(|e| format!("{}: {e}", path.display()))
+    }
+
+    /// The same site served from `base_url` instead, such as a staging host.
+    pub fn with_base_url(mut self, base_url: &str) -> Result<Self, String> {
+        let base_url = base_url.trim_end_matches('/');
+        let host = base_url
+            .strip_prefix("https://")
+            .or_else(|| base_url.strip_prefix("http://"));
+        if host.is_none_or(|host| host.is_empty() || host.contains(['/', '?', '#'])) {
+            return Err(format!(
+                "base_url {base_url:?} must be http(s)://host, with no path"
+            ));
+        }
+        self.base_url = base_url.to_string();
+        Ok(self)
+    }
+
+    /// `path` (starting with `/`) as an absolute URL.
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{path}", self.base_url)
+    }
+
+    /// Hashes every setting, for the outputs made from them.
+    pub(crate) fn fingerprint(&self) -> String {
+        let json = serde_json::to_string(self).expect("settings serialize");
+        crate::manifest::Inputs::new().add(json).finish()
+    }
+
+    /// `og_image` as an absolute URL.
+    pub(crate) fn og_image_url(&self) -> String {
+        if self.og_image.starts_with('/') {
+            self.url(&self.og_image)
+        } else {
+            self.og_image.clone()
+        }
+    }
+
+    /// What templates see as `site`.
+    pub(crate) fn context(&self) -> Value {
+        let nav: Vec<Value> = self
+            .nav
+            .iter()
+            .map(|link| {
+                let external =
+                    link.href.starts_with("http://") || link.href.starts_with("https://");
+                json!({ "label": link.label, "href": link.href, "external": external })
+            })
+            .collect();
+        let webmention = self
+            .webmentions
+            .then(|| self.url("/.well-known/webmention"));
+        json!({
+            "base_url": self.base_url,
+            "title": self.title,
+            "description": self.description,
+            "author": self.author,
+            "language": self.language,
+            "og_image": self.og_image_url(),
+            "nav": nav,
+            "webmention": webmention,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn site(base_url: &str) -> Result<Site, String> {
+        let dir = tempfile::tempdir().unwrap();
+        let toml = format!(
+            "base_url = {base_url:?}\ntitle = \"t\"\ndescription = \"d\"\nauthor = \"a\"\n\
+             language = \"en\"\nog_image = \"/me.webp\"\n\n\
+             [[nav]]\nlabel = \"GitHub\"\nhref = \"https://github.com/someone\"\n"
+        );
+        fs::write(dir.path().join("site.toml"), toml).unwrap();
+        Site::load(dir.path())
+    }
+
+    #[test]
+    fn the_base_url_is_a_bare_origin() {
+        assert_eq!(
+            site("https://example.com/").unwrap().base_url,
+            "https://example.com"
+        );
+        assert!(site("example.com").is_err());
+        assert!(site("https://example.com/blog").is_err());
+
+        let staging = site("https://example.com").unwrap();
+        let staging = staging.with_base_url("http://localhost:8080").unwrap();
+        assert_eq!(staging.url("/blog/"), "http://localhost:8080/blog/");
+    }
+
+    #[test]
+    fn templates_see_absolute_images_and_which_links_leave_the_site() {
+        let context = site("https://example.com").unwrap().context();
+        assert_eq!(context["og_image"], "https://example.com/me.webp");
+        assert_eq!(context["nav"][0]["external"], true);
+    }
+}
@@ -0,0 +1,978 @@
+//! The template language: a small subset of Jinja.
+//!
+//! - `{{ post.title }}` prints a value, HTML-escaped; `{{ post.content | safe }}`
+//!   prints it as is. `length` is the only other filter.
+//! - `{% if a %}…{% elif not b %}…{% else %}…{% endif %}`, with `and`, `or`,
+//!   `==` and `!=`. Empty strings and lists, `0`, `false` and null are false.
+//! - `{% for x in xs %}…{% else %}…{% endfor %}`; inside, `loop.index` (from
+//!   1), `loop.first` and `loop.last`.
+//! - `{% include "partials/header.html" %}` renders another template in place.
+//! - `{% extends "base.html" %}` renders `base.html` with each of its
+//!   `{% block name %}…{% endblock %}` replaced by the child's block of the
+//!   same name, when it has one.
+//! - `{# comments #}`.
+//!
+//! A variable that isn't defined is an error, not an empty string, and so is
+//! printing a list or an object. A block tag alone on its line takes the line
+//! with it (Jinja's `trim_blocks` and `lstrip_blocks`), and `{%-`/`-%}`
+//! trim all whitespace on that side, as in Jinja.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs,
+    path::Path,
+};
+
+use serde_json::{Map, Value};
+
+use crate::{escape_html, manifest::Inputs};
+
+/// Every template under a directory, parsed, by path relative to it.
+pub(crate) struct Templates {
+    templates: HashMap<String, Template>,
+    /// Variables every template sees, under whatever each render adds.
+    globals: Map<String, Value>,
+}
+
+struct Template {
+    source: String,
+    /// The template this one extends, if any.
+    parent: Option<String>,
+    nodes: Vec<Node>,
+}
+
+#[derive(Debug)]
+enum Node {
+    Text(String),
+    Print(Expr, usize),
+    If(Vec<(Expr, usize, Vec<Node>)>, Vec<Node>),
+    For {
+        var: String,
+        iter: Expr,
+        body: Vec<Node>,
+        otherwise: Vec<Node>,
+        line: usize,
+    },
+    Include(String, usize),
+    Block(String, Vec<Node>),
+}
+
+#[derive(Debug)]
+enum Expr {
+    Var(Vec<String>),
+    Str(String),
+    Int(i64),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Eq(Box<Expr>, Box<Expr>, bool),
+    Filter(Box<Expr>, String),
+}
+
+/// A value on its way to the page: `safe` ones skip escaping.
+struct Evaluated {
+    value: Value,
+    safe: bool,
+}
+
+impl Templates {
+    /// Parses every `.html` file under `dir`; each renders with the
+    /// variables in `globals` as well as its own.
+    pub(crate) fn load(dir: &Path, globals: Value) -> Result<Self, String> {
+        let mut templates = HashMap::new();
+        load_dir(dir, dir, &mut templates)?;
+        let Value::Object(globals) = globals else {
+            return Err("template globals must be an object".to_string());
+        };
+        let loaded = Templates { templates, globals };
+        loaded.check()?;
+        Ok(loaded)
+    }
+
+    #[cfg(test)]
+    fn from_sources(sources: &[(&str, &str)]) -> Result<Self, String> {
+        let mut templates = HashMap::new();
+        for (name, source) in sources {
+            templates.insert(name.to_string(), Template::parse(name, source)?);
+        }
+        let loaded = Templates {
+            templates,
+            globals: Map::new(),
+        };
+        loaded.check()?;
+        Ok(loaded)
+    }
+
+    /// Fails if a template extends or includes one that does not exist, or
+    /// (through any number of others) itself, naming the chain.
+    fn check(&self) -> Result<(), String> {
+        let mut names: Vec<_> = self.templates.keys().collect();
+        names.sort();
+        for name in &names {
+            for dep in self.templates[*name].dependencies() {
+                if !self.templates.contains_key(&dep) {
+                    return Err(format!("{name}: no template named {dep:?}"));
+                }
+            }
+        }
+        let mut done = BTreeSet::new();
+        for name in names {
+            self.find_cycle(name, &mut Vec::new(), &mut done)?;
+        }
+        Ok(())
+    }
+
+    /// Walks what `name` depends on, depth first, with `chain` the
+    /// templates that led here and `done` those already known to end.
+    fn find_cycle(
+        &self,
+        name: &str,
+        chain: &mut Vec<String>,
+        done: &mut BTreeSet<String>,
+    ) -> Result<(), String> {
+        if done.contains(name) {
+            return Ok(());
+        }
+        if let Some(start) = chain.iter().position(|n| n == name) {
+            let mut cycle = chain[start..].to_vec();
+            cycle.push(name.to_string());
+            return Err(format!("template cycle: {}", cycle.join(" -> ")));
+        }
+        chain.push(name.to_string());
+        for dep in self.templates[name].dependencies() {
+            self.find_cycle(&dep, chain, done)?;
+        }
+        chain.pop();
+        done.insert(name.to_string());
+        Ok(())
+    }
+
+    /// Renders the template `name` with the variables in `context`.
+    pub(crate) fn render(&self, name: &str, context: &Value) -> Result<String, String> {
+        let mut out = String::new();
+        let mut scope = Scope {
+            root: context
+                .as_object()
+                .ok_or("template context must be an object")?,
+            globals: &self.globals,
+            frames: Vec::new(),
+        };
+        self.render_template(name, &mut scope, &[], &[], &mut out)?;
+        Ok(out)
+    }
+
+    /// Hashes `name` with every template it extends or includes, however
+    /// deep, and the globals: what decides whether its pages need rendering
+    /// again.
+    pub(crate) fn fingerprint(&self, name: &str) -> String {
+        let mut seen = BTreeSet::new();
+        let mut pending = vec![name.to_string()];
+        while let Some(next) = pending.pop() {
+            if let Some(template) = self.templates.get(&next) {
+                pending.extend(template.dependencies());
+            }
+            seen.insert(next);
+        }
+        let globals = serde_json::to_string(&self.globals).expect("JSON values serialize");
+        seen.iter()
+            .fold(Inputs::new().add(globals), |inputs, name| {
+                let source = self.templates.get(name).map_or("", |t| &t.source);
+                inputs.add(name).add(source)
+            })
+            .finish()
+    }
+
+    fn get(&self, name: &str) -> Result<&Template, String> {
+        self.templates
+            .get(name)
+            .ok_or_else(|| format!("no template named {name:?}"))
+    }
+
+    /// Renders `name`, whose blocks are overridden by `overrides`, most
+    /// derived template first. `rendering` holds the templates that led
+    /// here, so a cycle [`Templates::check`] missed fails rather than
+    /// overflowing the stack.
+    fn render_template<'a>(
+        &'a self,
+        name: &str,
+        scope: &mut Scope,
+        overrides: &[(&'a str, &'a [Node])],
+        rendering: &[&str],
+        out: &mut String,
+    ) -> Result<(), String> {
+        if rendering.contains(&name) {
+            return Err(format!(
+                "template cycle: {} -> {name}",
+                rendering.join(" -> ")
+            ));
+        }
+        let rendering = [rendering, &[name]].concat();
+        let template = self.get(name)?;
+        match &template.parent {
+            Some(parent) => {
+                let mut overrides = overrides.to_vec();
+                collect_blocks(&template.nodes, &mut overrides);
+                self.render_template(parent, scope, &overrides, &rendering, out)
+            }
+            None => self.render_nodes(name, &template.nodes, scope, overrides, &rendering, out),
+        }
+    }
+
+    fn render_nodes(
+        &self,
+        name: &str,
+        nodes: &[Node],
+        scope: &mut Scope,
+        overrides: &[(&str, &[Node])],
+        rendering: &[&str],
+        out: &mut String,
+    ) -> Result<(), String> {
+        for node in nodes {
+            match node {
+                Node::Text(text) => out.push_str(text),
+                Node::Print(expr, line) => {
+                    let at = |e: String| format!("{name}:{line}: {e}");
+                    let evaluated = eval(expr, scope).map_err(at)?;
+                    let text = match &evaluated.value {
+                        Value::String(s) => s.clone(),
+                        Value::Number(n) => n.to_string(),
+                        Value::Bool(b) => b.to_string(),
+                        Value::Null => String::new(),
+                        Value::Array(_) | Value::Object(_) => {
+                            return Err(at(format!("cannot print {}", describe(expr))));
+                        }
+                    };
+                    if evaluated.safe {
+                        out.push_str(&text);
+                    } else {
+                        out.push_str(&escape_html(&text));
+                    }
+                }
+                Node::If(branches, otherwise) => {
+                    let mut taken = None;
+                    for (condition, line, body) in branches {
+                        let at = |e: String| format!("{name}:{line}: {e}");
+                        if truthy(&eval(condition, scope).map_err(at)?.value) {
+                            taken = Some(body);
+                            break;
+                        }
+                    }
+                    let body = taken.unwrap_or(otherwise);
+                    self.render_nodes(name, body, scope, overrides, rendering, out)?;
+                }
+                Node::For {
+                    var,
+                    iter,
+                    body,
+                    otherwise,
+                    line,
+                } => {
+                    let at = |e: String| format!("{name}:{line}: {e}");
+                    let items = match eval(iter, scope).map_err(at)?.value {
+                        Value::Array(items) => items,
+                        _ => return Err(at(format!("{} is not a list", describe(iter)))),
+                    };
+                    if items.is_empty() {
+                        self.render_nodes(name, otherwise, scope, overrides, rendering, out)?;
+                    }
+                    let count = items.len();
+                    for (i, item) in items.into_iter().enumerate() {
+                        let mut frame = Map::new();
+                        frame.insert(var.clone(), item);
+                        frame.insert(
+                            "loop".to_string(),
+                            serde_json::json!({
+                                "index": i + 1,
+                                "first": i == 0,
+                                "last": i + 1 == count,
+                            }),
+                        );
+                        scope.frames.push(frame);
+                        let rendered =
+                            self.render_nodes(name, body, scope, overrides, rendering, out);
+                        scope.frames.pop();
+                        rendered?;
+                    }
+                }
+                Node::Include(included, line) => self
+                    .render_template(included, scope, &[], rendering, out)
+                    .map_err(|e| format!("{e}\n  included from {name}:{line}"))?,
+                Node::Block(block, body) => {
+                    let body = overrides
+                        .iter()
+                        .find(|(n, _)| n == block)
+                        .map_or(body.as_slice(), |(_, nodes)| nodes);
+                    self.render_nodes(name, body, scope, overrides, rendering, out)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn load_dir(
+    base: &Path,
+    dir: &Path,
+    templates: &mut HashMap<String, Template>,
+) -> Result<(), String> {
+    let read_dir = fs::read_dir(dir).map_err(|e| format!("read {}: {e}", dir.display()))?;
+    for entry in read_dir.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            load_dir(base, &path, templates)?;
+        } else if path.extension().and_then(|e| e.to_str()) == Some("html") {
+            let Ok(rel) = path.strip_prefix(base) else {
+                continue;
+            };
+            let name = rel
+                .iter()
+                .map(|p| p.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            let source =
+                fs::read_to_string(&path).map_err(|e| format!("read {}: {e}", path.display()))?;
+            let template = Template::parse(&name, &source)
+                .map_err(|e| format!("{}", base.join(e).display()))?;
+            templates.insert(name, template);
+        }
+    }
+    Ok(())
+}
+
+/// Adds the blocks defined in `nodes` that aren't overridden yet.
+fn collect_blocks<'a>(nodes: &'a [Node], overrides: &mut Vec<(&'a str, &'a [Node])>) {
+    for node in nodes {
+        match node {
+            Node::Block(block, body) => {
+                if !overrides.iter().any(|(n, _)| n == block) {
+                    overrides.push((block, body));
+                }
+                collect_blocks(body, overrides);
+            }
+            Node::If(branches, otherwise) => {
+                for (_, _, body) in branches {
+                    collect_blocks(body, overrides);
+                }
+                collect_blocks(otherwise, overrides);
+            }
+            Node::For {
+                body, otherwise, ..
+            } => {
+                collect_blocks(body, overrides);
+                collect_blocks(otherwise, overrides);
+            }
+            Node::Text(_) | Node::Print(..) | Node::Include(..) => {}
+        }
+    }
+}
+
+impl Template {
+    fn parse(name: &str, source: &str) -> Result<Self, String> {
+        let tokens = lex(source).map_err(|(line, e)| format!("{name}:{line}: {e}"))?;
+        let mut parser = Parser {
+            tokens: tokens.into_iter().peekable(),
+            parent: None,
+        };
+        let (nodes, end) = parser
+            .nodes(&[], 0)
+            .map_err(|(line, e)| format!("{name}:{line}: {e}"))?;
+        if let Some((tag, line)) = end {
+            return Err(format!("{name}:{line}: unexpected {{% {tag} %}}"));
+        }
+        Ok(Template {
+            source: source.to_string(),
+            parent: parser.parent,
+            nodes,
+        })
+    }
+
+    /// The templates this one extends or includes, by name.
+    fn dependencies(&self) -> Vec<String> {
+        fn walk(nodes: &[Node], found: &mut Vec<String>) {
+            for node in nodes {
+                match node {
+                    Node::Include(name, _) => found.push(name.clone()),
+                    Node::Block(_, body) => walk(body, found),
+                    Node::If(branches, otherwise) => {
+                        for (_, _, body) in branches {
+                            walk(body, found);
+                        }
+                        walk(otherwise, found);
+                    }
+                    Node::For {
+                        body, otherwise, ..
+                    } => {
+                        walk(body, found);
+                        walk(otherwise, found);
+                    }
+                    Node::Text(_) | Node::Print(..) => {}
+                }
+            }
+        }
+        let mut found: Vec<String> = self.parent.iter().cloned().collect();
+        walk(&self.nodes, &mut found);
+        found
+    }
+}
+
+// -- lexing -----------------------------------------------------------------
+
+#[derive(Debug)]
+enum Token {
+    Text(String),
+    Print(String, usize),
+    Tag(String, usize),
+}
+
+type Error = (usize, String);
+
+/// Nodes, and the end tag that closed them with its line.
+type Section = (Vec<Node>, Option<(String, usize)>);
+
+/// Splits `source` into text and tags, applying the whitespace rules in the
+/// module docs.
+fn lex(source: &str) -> Result<Vec<Token>, Error> {
+    let mut tokens = Vec::new();
+    let mut rest = source;
+    let mut line = 1;
+    // How the last tag wants the text after it trimmed.
+    let mut trim_next = false;
+    let mut after_block = false;
+    // Whether `rest` starts at the start of a line.
+    let mut line_start = true;
+
+    loop {
+        let open = ["{{", "{%", "{#"]
+            .iter()
+            .filter_map(|open| rest.find(open).map(|i| (i, *open)))
+            .min();
+        let tag_start = open.map_or(rest.len(), |(i, _)| i);
+        let mut text = &rest[..tag_start];
+        line += text.matches('\n').count();
+        if trim_next {
+            text = text.trim_start();
+        } else if after_block {
+            text = text
+                .strip_prefix("\r\n")
+                .or_else(|| text.strip_prefix('\n'))
+                .unwrap_or(text);
+        }
+        let Some((_, open)) = open else {
+            push_text(&mut tokens, text);
+            return Ok(tokens);
+        };
+
+        let close = match open {
+            "{{" => "}}",
+            "{%" => "%}",
+            _ => "#}",
+        };
+        let body_start = tag_start + 2;
+        let Some(len) = rest[body_start..].find(close) else {
+            return Err((line, format!("{open} is never closed")));
+        };
+        let mut body = &rest[body_start..body_start + len];
+        let is_block = open != "{{";
+
+        if body.starts_with('-') {
+            text = text.trim_end();
+        } else if is_block {
+            // A block tag with only indentation before it on its line.
+            let indent_start = text.rfind('\n').map(|i| i + 1);
+            let indent = &text[indent_start.unwrap_or(0)..];
+            let own_line = indent_start.is_some() || line_start;
+            if own_line && indent.chars().all(|c| c == ' ' || c == '\t') {
+                text = &text[..indent_start.unwrap_or(0)];
+            }
+        }
+        push_text(&mut tokens, text);
+
+        let tag_line = line;
+        line += body.matches('\n').count();
+        body = body.strip_prefix('-').unwrap_or(body);
+        trim_next = body.ends_with('-');
+        body = body.strip_suffix('-').unwrap_or(body);
+        after_block = is_block;
+        match open {
+            "{{" => tokens.push(Token::Print(body.trim().to_string(), tag_line)),
+            "{%" => tokens.push(Token::Tag(body.trim().to_string(), tag_line)),
+            _ => {}
+        }
+        line_start = false;
+        rest = &rest[body_start + len + 2..];
+    }
+}
+
+fn push_text(tokens: &mut Vec<Token>, text: &str) {
+    if !text.is_empty() {
+        tokens.push(Token::Text(text.to_string()));
+    }
+}
+
+// -- parsing ----------------------------------------------------------------
+
+struct Parser {
+    tokens: std::iter::Peekable<std::vec::IntoIter<Token>>,
+    parent: Option<String>,
+}
+
+impl Parser {
+    /// Nodes up to one of the tags in `ends` (which is returned with its
+    /// line), or to the end of the template when `ends` is empty. `opened`
+    /// is the line of the tag those end.
+    fn nodes(&mut self, ends: &[&str], opened: usize) -> Result<Section, Error> {
+        let mut nodes = Vec::new();
+        while let Some(token) = self.tokens.next() {
+            match token {
+                Token::Text(text) => nodes.push(Node::Text(text)),
+                Token::Print(expr, line) => nodes.push(Node::Print(parse_expr(&expr, line)?, line)),
+                Token::Tag(tag, line) => {
+                    let keyword = tag.split_whitespace().next().unwrap_or("");
+                    if ends.contains(&keyword) {
+                        return Ok((nodes, Some((tag, line))));
+                    }
+                    nodes.push(self.tag(&tag, keyword, line)?);
+                }
+            }
+        }
+        match ends {
+            [] => Ok((nodes, None)),
+            _ => Err((opened, format!("missing {{% {} %}}", ends[ends.len() - 1]))),
+        }
+    }
+
+    fn tag(&mut self, tag: &str, keyword: &str, line: usize) -> Result<Node, Error> {
+        let args = tag[keyword.len()..].trim();
+        match keyword {
+            "if" => {
+                let mut branches = Vec::new();
+                let mut condition = (parse_expr(args, line)?, line);
+                loop {
+                    let (body, end) = self.nodes(&["elif", "else", "endif"], line)?;
+                    branches.push((condition.0, condition.1, body));
+                    let (end, end_line) = end.expect("nodes returns an end when given some");
+                    match end.split_whitespace().next() {
+                        Some("elif") => {
+                            condition = (parse_expr(end[4..].trim(), end_line)?, end_line);
+                        }
+                        Some("else") => {
+                            let (otherwise, _) = self.nodes(&["endif"], line)?;
+                            return Ok(Node::If(branches, otherwise));
+                        }
+                        _ => return Ok(Node::If(branches, Vec::new())),
+                    }
+                }
+            }
+            "for" => {
+                let Some((var, iter)) = args.split_once(" in ") else {
+                    return Err((line, format!("expected `for x in xs`, found `{tag}`")));
+                };
+                let var = var.trim();
+                if !is_identifier(var) {
+                    return Err((line, format!("{var:?} is not a variable name")));
+                }
+                let iter = parse_expr(iter, line)?;
+                let (body, end) = self.nodes(&["else", "endfor"], line)?;
+                let otherwise = match end {
+                    Some((end, _)) if end == "else" => self.nodes(&["endfor"], line)?.0,
+                    _ => Vec::new(),
+                };
+                Ok(Node::For {
+                    var: var.to_string(),
+                    iter,
+                    body,
+                    otherwise,
+                    line,
+                })
+            }
+            "block" => {
+                if !is_identifier(args) {
+                    return Err((line, format!("{args:?} is not a block name")));
+                }
+                let (body, _) = self.nodes(&["endblock"], line)?;
+                Ok(Node::Block(args.to_string(), body))
+            }
+            "include" => Ok(Node::Include(string_literal(args, line)?, line)),
+            "extends" => {
+                if self.parent.is_some() {
+                    return Err((line, "a template can extend only one other".to_string()));
+                }
+                self.parent = Some(string_literal(args, line)?);
+                Ok(Node::Text(String::new()))
+            }
+            _ => Err((line, format!("unknown tag {{% {keyword} %}}"))),
+        }
+    }
+}
+
+fn string_literal(s: &str, line: usize) -> Result<String, Error> {
+    match parse_expr(s, line)? {
+        Expr::Str(s) => Ok(s),
+        _ => Err((
+            line,
+            format!("expected a quoted template name, found `{s}`"),
+        )),
+    }
+}
+
+fn is_identifier(s: &str) -> bool {
+    s.chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Expression tokens: words, literals and operators.
+fn expr_tokens(s: &str, line: usize) -> Result<Vec<String>, Error> {
+    let mut tokens = Vec::new();
+    let mut chars = s.char_indices().peekable();
+    while let Some(&(i, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' || c == '\'' {
+            chars.next();
+            let end = s[i + 1..]
+                .find(c)
+                .ok_or((line, format!("unclosed string in `{s}`")))?;
+            tokens.push(s[i..i + end + 2].to_string());
+            while chars.peek().is_some_and(|&(j, _)| j < i + end + 2) {
+                chars.next();
+            }
+        } else if s[i..].starts_with("==") || s[i..].starts_with("!=") {
+            tokens.push(s[i..i + 2].to_string());
+            chars.next();
+            chars.next();
+        } else if matches!(c, '|' | '(' | ')') {
+            tokens.push(c.to_string());
+            chars.next();
+        } else if c.is_ascii_alphanumeric() || c == '_' || c == '.' {
+            let mut end = i;
+            while let Some(&(j, c)) = chars.peek() {
+                if !(c.is_ascii_alphanumeric() || c == '_' || c == '.') {
+                    break;
+                }
+                end = j + c.len_utf8();
+                chars.next();
+            }
+            tokens.push(s[i..end].to_string());
+        } else {
+            return Err((line, format!("unexpected {c:?} in `{s}`")));
+        }
+    }
+    Ok(tokens)
+}
+
+fn parse_expr(s: &str, line: usize) -> Result<Expr, Error> {
+    let tokens = expr_tokens(s, line)?;
+    let mut pos = 0;
+    let expr = parse_or(&tokens, &mut pos, line)?;
+    if pos != tokens.len() {
+        return Err((line, format!("unexpected `{}` in `{s}`", tokens[pos])));
+    }
+    Ok(expr)
+}
+
+fn parse_or(tokens: &[String], pos: &mut usize, line: usize) -> Result<Expr, Error> {
+    let mut left = parse_and(tokens, pos, line)?;
+    while tokens.get(*pos).is_some_and(|t| t == "or") {
+        *pos += 1;
+        left = Expr::Or(Box::new(left), Box::new(parse_and(tokens, pos, line)?));
+    }
+    Ok(left)
+}
+
+fn parse_and(tokens: &[String], pos: &mut usize, line: usize) -> Result<Expr, Error> {
+    let mut left = parse_not(tokens, pos, line)?;
+    while tokens.get(*pos).is_some_and(|t| t == "and") {
+        *pos += 1;
+        left = Expr::And(Box::new(left), Box::new(parse_not(tokens, pos, line)?));
+    }
+    Ok(left)
+}
+
+fn parse_not(tokens: &[String], pos: &mut usize, line: usize) -> Result<Expr, Error> {
+    if tokens.get(*pos).is_some_and(|t| t == "not") {
+        *pos += 1;
+        return Ok(Expr::Not(Box::new(parse_not(tokens, pos, line)?)));
+    }
+    let left = parse_filtered(tokens, pos, line)?;
+    match tokens.get(*pos).map(String::as_str) {
+        Some(op @ ("==" | "!=")) => {
+            let equal = op == "==";
+            *pos += 1;
+            let right = parse_filtered(tokens, pos, line)?;
+            Ok(Expr::Eq(Box::new(left), Box::new(right), equal))
+        }
+        _ => Ok(left),
+    }
+}
+
+fn parse_filtered(tokens: &[String], pos: &mut usize, line: usize) -> Result<Expr, Error> {
+    let mut expr = parse_primary(tokens, pos, line)?;
+    while tokens.get(*pos).is_some_and(|t| t == "|") {
+        let Some(filter) = tokens.get(*pos + 1) else {
+            return Err((line, "expected a filter after `|`".to_string()));
+        };
+        if !matches!(filter.as_str(), "safe" | "length") {
+            return Err((line, format!("unknown filter `{filter}`")));
+        }
+        expr = Expr::Filter(Box::new(expr), filter.clone());
+        *pos += 2;
+    }
+    Ok(expr)
+}
+
+fn parse_primary(tokens: &[String], pos: &mut usize, line: usize) -> Result<Expr, Error> {
+    let Some(token) = tokens.get(*pos) else {
+        return Err((line, "expected an expression".to_string()));
+    };
+    *pos += 1;
+    if token == "(" {
+        let inner = parse_or(tokens, pos, line)?;
+        if tokens.get(*pos).is_none_or(|t| t != ")") {
+            return Err((line, "missing `)`".to_string()));
+        }
+        *pos += 1;
+        return Ok(inner);
+    }
+    if let Some(quote) = token.chars().next().filter(|c| *c == '"' || *c == '\'') {
+        return Ok(Expr::Str(token.trim_matches(quote).to_string()));
+    }
+    if let Ok(n) = token.parse() {
+        return Ok(Expr::Int(n));
+    }
+    let path: Vec<String> = token.split('.').map(String::from).collect();
+    if path.iter().all(|p| is_identifier(p)) {
+        Ok(Expr::Var(path))
+    } else {
+        Err((line, format!("unexpected `{token}`")))
+    }
+}
+
+// -- evaluation -------------------------------------------------------------
+
+struct Scope<'a> {
+    root: &'a Map<String, Value>,
+    globals: &'a Map<String, Value>,
+    /// Loop variables, innermost last.
+    frames: Vec<Map<String, Value>>,
+}
+
+impl Scope<'_> {
+    fn lookup(&self, path: &[String]) -> Option<&Value> {
+        let first = &path[0];
+        let mut value = self
+            .frames
+            .iter()
+            .rev()
+            .find_map(|frame| frame.get(first))
+            .or_else(|| self.root.get(first))
+            .or_else(|| self.globals.get(first))?;
+        for key in &path[1..] {
+            value = value.as_object()?.get(key)?;
+        }
+        Some(value)
+    }
+}
+
+fn eval(expr: &Expr, scope: &Scope) -> Result<Evaluated, String> {
+    let plain = |value| Evaluated { value, safe: false };
+    Ok(match expr {
+        Expr::Var(path) => match scope.lookup(path) {
+            Some(value) => plain(value.clone()),
+            None => return Err(format!("undefined variable `{}`", path.join("."))),
+        },
+        Expr::Str(s) => plain(Value::String(s.clone())),
+        Expr::Int(n) => plain(Value::from(*n)),
+        Expr::Not(inner) => plain(Value::Bool(!truthy(&eval(inner, scope)?.value))),
+        Expr::And(a, b) => plain(Value::Bool(
+            truthy(&eval(a, scope)?.value) && truthy(&eval(b, scope)?.value),
+        )),
+        Expr::Or(a, b) => plain(Value::Bool(
+            truthy(&eval(a, scope)?.value) || truthy(&eval(b, scope)?.value),
+        )),
+        Expr::Eq(a, b, equal) => plain(Value::Bool(
+            (eval(a, scope)?.value == eval(b, scope)?.value) == *equal,
+        )),
+        Expr::Filter(inner, filter) => {
+            let evaluated = eval(inner, scope)?;
+            match filter.as_str() {
+                "safe" => Evaluated {
+                    safe: true,
+                    ..evaluated
+                },
+                _ => match &evaluated.value {
+                    Value::Array(items) => plain(Value::from(items.len())),
+                    Value::String(s) => plain(Value::from(s.chars().count())),
+                    Value::Object(map) => plain(Value::from(map.len())),
+                    _ => return Err(format!("{} has no length", describe(inner))),
+                },
+            }
+        }
+    })
+}
+
+fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64().is_some_and(|n| n != 0.0),
+        Value::String(s) => !s.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(map) => !map.is_empty(),
+    }
+}
+
+fn describe(expr: &Expr) -> String {
+    match expr {
+        Expr::Var(path) => format!("`{}`", path.join(".")),
+        _ => "the expression".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn render(sources: &[(&str, &str)], context: Value) -> Result<String, String> {
+        Templates::from_sources(sources)?.render(sources[0].0, &context)
+    }
+
+    #[test]
+    fn prints_escaped_unless_marked_safe() {
+        let out = render(
+            &[("t", "<p>{{ title }}</p>{{ html | safe }}")],
+            json!({"title": "Fish & <chips>", "html": "<em>hi</em>"}),
+        );
+        assert_eq!(out.unwrap(), "<p>Fish &amp; &lt;chips&gt;</p><em>hi</em>");
+    }
+
+    #[test]
+    fn an_undefined_variable_is_an_error_with_its_line() {
+        let err = render(
+            &[("post.html", "\n\n{{ post.titel }}")],
+            json!({"post": {"title": "x"}}),
+        );
+        assert_eq!(
+            err.unwrap_err(),
+            "post.html:3: undefined variable `post.titel`"
+        );
+    }
+
+    #[test]
+    fn loops_and_conditionals() {
+        let tmpl = "{% for t in tags %}{% if not loop.first %}, {% endif %}{{ t.name }}{% if t.name == \"rust\" %}!{% endif %}{% else %}none{% endfor %}";
+        let tags = json!({"tags": [{"name": "rust"}, {"name": "fuzzing"}]});
+        assert_eq!(render(&[("t", tmpl)], tags).unwrap(), "rust!, fuzzing");
+        assert_eq!(render(&[("t", tmpl)], json!({"tags": []})).unwrap(), "none");
+
+        let tmpl = "{% if a and b %}both{% elif a or b %}one{% else %}neither{% endif %} {{ xs | length }}";
+        let out = render(&[("t", tmpl)], json!({"a": "x", "b": "", "xs": [1, 2]}));
+        assert_eq!(out.unwrap(), "one 2");
+    }
+
+    #[test]
+    fn block_tags_alone_on_a_line_take_the_line_with_them() {
+        let tmpl = "<ul>\n    {% for x in xs %}\n    <li>{{ x }}</li>\n    {% endfor %}\n</ul>\n";
+        let out = render(&[("t", tmpl)], json!({"xs": [1, 2]}));
+        assert_eq!(
+            out.unwrap(),
+            "<ul>\n    <li>1</li>\n    <li>2</li>\n</ul>\n"
+        );
+
+        let out = render(
+            &[("t", "a  {%- if x -%}  b  {%- endif -%}  c")],
+            json!({"x": true}),
+        );
+        assert_eq!(out.unwrap(), "abc");
+    }
+
+    #[test]
+    fn includes_and_inheritance() {
+        let sources = [
+            ("page.html", "{% extends \"base.html\" %}\n{% block title %}{{ title }}{% endblock %}\n{% block main %}<p>body</p>{% endblock %}\n"),
+            ("base.html", "<title>{% block title %}untitled{% endblock %}</title>\n{% include \"nav.html\" %}\n{% block main %}{% endblock %}\n<footer>{% block footer %}default{% endblock %}</footer>\n"),
+            ("nav.html", "<nav>{{ title }}</nav>\n"),
+        ];
+        let out = render(&sources, json!({"title": "Hi"})).unwrap();
+        assert_eq!(
+            out,
+            "<title>Hi</title>\n<nav>Hi</nav>\n<p>body</p><footer>default</footer>\n"
+        );
+    }
+
+    #[test]
+    fn a_fingerprint_covers_what_a_template_extends_and_includes() {
+        let sources = |nav: &'static str| {
+            Templates::from_sources(&[
+                ("page.html", "{% extends \"base.html\" %}"),
+                ("base.html", "{% include \"nav.html\" %}"),
+                ("nav.html", nav),
+                ("other.html", "alone"),
+            ])
+            .unwrap()
+        };
+        let (a, b) = (sources("<nav>"), sources("<nav class=x>"));
+        assert_ne!(a.fingerprint("page.html"), b.fingerprint("page.html"));
+        assert_eq!(a.fingerprint("other.html"), b.fingerprint("other.html"));
+    }
+
+    #[test]
+    fn a_template_that_includes_itself_is_refused() {
+        let err = Templates::from_sources(&[(
+            "nav.html",
+            "<nav>{% if more %}{% include \"nav.html\" %}{% endif %}</nav>",
+        )])
+        .err()
+        .unwrap();
+        assert_eq!(err, "template cycle: nav.html -> nav.html");
+    }
+
+    #[test]
+    fn templates_that_extend_each_other_are_refused() {
+        let err = Templates::from_sources(&[
+            ("a.html", "{% extends \"b.html\" %}"),
+            ("b.html", "{% extends \"a.html\" %}"),
+        ])
+        .err()
+        .unwrap();
+        assert_eq!(err, "template cycle: a.html -> b.html -> a.html");
+    }
+
+    #[test]
+    fn rendering_stops_at_a_cycle_it_is_handed() {
+        let templates = Templates {
+            templates: HashMap::from([(
+                "t".to_string(),
+                Template::parse("t", "{% include \"t\" %}").unwrap(),
+            )]),
+            globals: Map::new(),
+        };
+        let err = templates.render("t", &json!({})).unwrap_err();
+        assert!(err.starts_with("template cycle: t -> t\n"), "{err}");
+    }
+
+    #[test]
+    fn syntax_errors_point_at_their_line() {
+        let err = Templates::from_sources(&[("t", "ok\n{% if x %}\nno end")])
+            .err()
+            .unwrap();
+        assert_eq!(err, "t:2: missing {% endif %}");
+        let err = Templates::from_sources(&[("t", "\n{{ x | shout }}")])
+            .err()
+            .unwrap();
+        assert_eq!(err, "t:2: unknown filter `shout`");
+        let err = Templates::from_sources(&[("t", "{% while %}")])
+            .err()
+            .unwrap();
+        assert_eq!(err, "t:1: unknown tag {% while %}");
+    }
+
+    #[test]
+    fn printing_a_list_is_an_error() {
+        let err = render(&[("t", "{{ xs }}")], json!({"xs": []})).unwrap_err();
+        assert_eq!(err, "t:1: cannot print `xs`");
+    }
+}
@@ -0,0 +1,121 @@
+//! Webmentions jatai has verified (see its `webmention` module), one JSON
+//! file each in `content/webmentions/`, listed under the post they target.
+
+use std::{collections::HashMap, fs, path::Path};
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::{date::Timestamp, date_context};
+
+#[derive(Deserialize)]
+struct Mention {
+    source: String,
+    target: String,
+    /// The source's title, when it had one.
+    #[serde(default)]
+    title: Option<String>,
+    /// When jatai last saw the source link to the target, in Unix seconds.
+    verified: i64,
+}
+
+/// The mentions in `dir`, by the path of the page each targets, oldest
+/// first, as `post.html` sees them: `{source, title, date}`. No directory
+/// means no mentions.
+pub(crate) fn load(dir: &Path) -> Result<HashMap<String, Vec<Value>>, String> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(e) => return Err(format!("read {}: {e}", dir.display())),
+    };
+    let mut files: Vec<_> = entries
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<_, _>>()
+        .map_err(|e| format!("read {}: {e}", dir.display()))?;
+    // Partial files jatai is still writing start with a dot.
+    files.retain(|path| {
+        path.extension().is_some_and(|ext| ext == "json")
+            && !path
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+    });
+    files.sort();
+
+    let mut mentions: Vec<Mention> = Vec::new();
+    for path in files {
+        let text = crate::read(&path)?;
+        let mention =
+            serde_json::from_str(&text).map_err(|e| format!("{}: {e}", path.display()))?;
+        mentions.push(mention);
+    }
+    mentions.sort_by_key(|m| m.verified);
+
+    let mut by_page: HashMap<String, Vec<Value>> = HashMap::new();
+    for m in mentions {
+        let Some(date) = Timestamp::from_unix_seconds(m.verified) else {
+            continue;
+        };
+        let title = m.title.unwrap_or_else(|| host(&m.source).to_string());
+        by_page.entry(path_of(&m.target)).or_default().push(json!({
+            "source": m.source,
+            "title": title,
+            "date": date_context(date),
+        }));
+    }
+    Ok(by_page)
+}
+
+/// The path of `url` on its site: what follows the host, without a query or
+/// fragment. The host is not checked, so mentions kept for the live site
+/// also show on a staging build.
+fn path_of(url: &str) -> String {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let rest = rest.split(['?', '#']).next().unwrap_or_default();
+    match rest.find('/') {
+        Some(at) => rest[at..].to_string(),
+        None => "/".to_string(),
+    }
+}
+
+/// The host of `url`, to name a source without a title.
+fn host(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    rest.split(['/', '?', '#']).next().unwrap_or(rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mentions_are_grouped_by_the_path_they_target() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, json: &str| fs::write(dir.path().join(name), json).unwrap();
+        write(
+            "a.json",
+            r#"{"source":"https://b.example/later","target":"https://example.com/blog/p/?utm=x","title":"Later","verified":1760000100}"#,
+        );
+        write(
+            "b.json",
+            r#"{"source":"https://a.example/first","target":"https://example.com/blog/p/","verified":1760000000}"#,
+        );
+        write(".c.json.tmp", "{");
+        write("notes.txt", "not a mention");
+
+        let mentions = load(dir.path()).unwrap();
+        let titles: Vec<&str> = mentions["/blog/p/"]
+            .iter()
+            .map(|m| m["title"].as_str().unwrap())
+            .collect();
+        assert_eq!(titles, ["a.example", "Later"]);
+        assert_eq!(mentions["/blog/p/"][1]["date"]["human"], "October 9, 2025");
+    }
+
+    #[test]
+    fn no_directory_is_no_mentions_and_a_broken_file_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(load(&dir.path().join("missing")).unwrap().is_empty());
+        fs::write(dir.path().join("bad.json"), "{").unwrap();
+        assert!(load(dir.path()).unwrap_err().contains("bad.json"));
+    }
+}
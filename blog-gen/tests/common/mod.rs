@@ -0,0 +1,62 @@
+//! The site every integration test builds on: the real templates and a
+//! minimal `site.toml`, into which each test writes its own posts.
+
+// Each test crate uses its own share of these.
+#![allow(dead_code)]
+
+use std::{fs, path::Path};
+
+use tempfile::TempDir;
+
+/// Where the test site says it lives.
+pub const SITE_URL: &str = "https://example.com";
+
+const SITE_TOML: &str = r#"
+base_url = "https://example.com"
+title = "example.com"
+description = "Notes."
+author = "Someone"
+language = "en"
+og_image = "/me.webp"
+"#;
+
+/// A site with no posts yet, its `site.toml` the minimal one followed by
+/// `extra`: further settings, then any tables.
+pub fn site_root(extra: &str) -> TempDir {
+    let root = tempfile::tempdir().unwrap();
+    copy_dir(
+        &Path::new(env!("CARGO_MANIFEST_DIR")).join("../templates"),
+        &root.path().join("templates"),
+    );
+    write_site_toml(&root, extra);
+    root
+}
+
+/// Rewrite the site's `site.toml` as the minimal one followed by `extra`.
+pub fn write_site_toml(root: &TempDir, extra: &str) {
+    fs::write(root.path().join("site.toml"), format!("{SITE_TOML}{extra}")).unwrap();
+}
+
+pub fn write_post(root: &TempDir, slug: &str, body: &str) {
+    let dir = root.path().join("content/blog").join(slug);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("index.md"), body).unwrap();
+}
+
+pub fn copy_dir(src: &Path, dst: &Path) {
+    fs::create_dir_all(dst).unwrap();
+    for entry in fs::read_dir(src).unwrap() {
+        let entry = entry.unwrap();
+        let to = dst.join(entry.file_name());
+        if entry.file_type().unwrap().is_dir() {
+            copy_dir(&entry.path(), &to);
+        } else {
+            fs::copy(entry.path(), to).unwrap();
+        }
+    }
+}
+
+/// The built file at `path` under `static/`.
+pub fn read(root: &TempDir, path: &str) -> String {
+    fs::read_to_string(root.path().join("static").join(path)).unwrap()
+}
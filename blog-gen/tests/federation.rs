@@ -0,0 +1,75 @@
+//! Generates a site with Webmentions and ActivityPub turned on, and checks
+//! the mentions under each post and the documents the fediverse reads.
+
+mod common;
+
+use std::fs;
+
+use common::{read, site_root, write_post};
+use serde_json::Value;
+
+/// What the test site adds to the minimal `site.toml`.
+const FEDERATION: &str = r#"webmentions = true
+
+[activitypub]
+username = "someone"
+"#;
+
+const FEES: &str = "---\ntitle: Routing fees\ndate: 2026-05-01\ntags: [Lightning]\n---\n\n\
+    Fees are set per [channel](/blog/jamming/).\n";
+
+const JAMMING: &str = "---\ntitle: Channel jamming\ndate: 2026-06-01\n---\n\nJams.\n";
+
+const MENTION: &str = r#"{"source":"https://other.example/reply","target":"https://example.com/blog/fees/","title":"A <reply>","verified":1780000000}"#;
+
+fn site() -> tempfile::TempDir {
+    let root = site_root(FEDERATION);
+    write_post(&root, "fees", FEES);
+    write_post(&root, "jamming", JAMMING);
+    let mentions = root.path().join("content/webmentions");
+    fs::create_dir_all(&mentions).unwrap();
+    fs::write(mentions.join("0123456789abcdef.json"), MENTION).unwrap();
+    blog_gen::generate(root.path()).unwrap();
+    root
+}
+
+fn json(root: &tempfile::TempDir, path: &str) -> Value {
+    serde_json::from_str(&read(root, path)).unwrap()
+}
+
+#[test]
+fn mentions_are_listed_under_the_post_they_target() {
+    let root = site();
+    let fees = read(&root, "blog/fees/index.html");
+    assert!(fees
+        .contains(r#"<link rel="webmention" href="https://example.com/.well-known/webmention">"#));
+    assert!(fees.contains(
+        r#"<a href="https://other.example/reply" rel="nofollow ugc">A &lt;reply&gt;</a>"#
+    ));
+    let jamming = read(&root, "blog/jamming/index.html");
+    assert!(!jamming.contains("class=\"webmentions\""));
+}
+
+#[test]
+fn the_actor_publishes_every_post_in_its_outbox() {
+    let root = site();
+    let actor = json(&root, "activitypub/actor.json");
+    assert_eq!(actor["id"], "https://example.com/activitypub/actor");
+    assert_eq!(actor["preferredUsername"], "someone");
+    assert_eq!(actor["outbox"], "https://example.com/activitypub/outbox");
+
+    let outbox = json(&root, "activitypub/outbox.json");
+    assert_eq!(outbox["totalItems"], 2);
+    let first = &outbox["orderedItems"][0]["object"];
+    assert_eq!(first["id"], "https://example.com/blog/jamming/");
+    let fees = &outbox["orderedItems"][1]["object"];
+    assert_eq!(fees["tag"][0]["name"], "#lightning");
+    assert!(fees["content"]
+        .as_str()
+        .unwrap()
+        .contains(r#"href="https://example.com/blog/jamming/""#));
+
+    let webfinger = json(&root, ".well-known/webfinger.json");
+    assert_eq!(webfinger["subject"], "acct:someone@example.com");
+    assert_eq!(webfinger["links"][0]["href"], actor["id"]);
+}
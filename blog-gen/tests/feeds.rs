@@ -0,0 +1,168 @@
+//! Generates a small site against the real templates and checks that every
+//! feed parses and carries whole posts with absolute URLs.
+
+mod common;
+
+use common::{read, site_root, write_post, SITE_URL};
+
+const POST: &str = r#"---
+title: "Fuzzing & friends"
+date: 2026-04-16T09:30:00-03:00
+updated: 2026-05-01
+description: "What a fuzzer found."
+tags: [fuzzing, Rust]
+---
+
+See [the earlier post](/blog/older/) and [the notes](notes.txt).
+
+![a crash](crash.png)
+
+Upstream is at [GitHub](https://github.com/rust-fuzz).
+"#;
+
+const OLDER: &str = "---\ntitle: Older\ndate: 2026-01-02\n---\n\nHello.\n";
+
+fn site() -> tempfile::TempDir {
+    let root = site_root("");
+    write_post(&root, "fuzzing", POST);
+    write_post(&root, "older", OLDER);
+    assert_eq!(blog_gen::generate(root.path()).unwrap().posts, 2);
+    root
+}
+
+fn child<'a, 'i>(node: roxmltree::Node<'a, 'i>, name: &str) -> roxmltree::Node<'a, 'i> {
+    node.children()
+        .find(|n| n.tag_name().name() == name)
+        .unwrap_or_else(|| panic!("no <{name}>"))
+}
+
+/// The links and image of [`POST`], as a reader outside the site needs them.
+fn assert_absolute(html: &str) {
+    for url in [
+        format!("{SITE_URL}/blog/older/"),
+        format!("{SITE_URL}/blog/fuzzing/notes.txt"),
+        format!("{SITE_URL}/blog/fuzzing/crash.png"),
+        "https://github.com/rust-fuzz".to_string(),
+    ] {
+        assert!(html.contains(&format!("\"{url}\"")), "{url} in {html}");
+    }
+}
+
+#[test]
+fn atom_entries_carry_the_whole_post() {
+    let site = site();
+    let xml = read(&site, "blog/feed.xml");
+    let doc = roxmltree::Document::parse(&xml).unwrap();
+    let feed = doc.root_element();
+    assert_eq!(feed.tag_name().name(), "feed");
+
+    assert_eq!(child(child(feed, "author"), "name").text(), Some("Someone"));
+
+    let entries: Vec<_> = feed
+        .children()
+        .filter(|n| n.tag_name().name() == "entry")
+        .collect();
+    assert_eq!(entries.len(), 2);
+    let entry = entries[0];
+    assert_eq!(child(entry, "title").text(), Some("Fuzzing & friends"));
+    let url = format!("{SITE_URL}/blog/fuzzing/");
+    assert_eq!(child(entry, "link").attribute("href"), Some(url.as_str()));
+    assert_eq!(child(entry, "id").text(), Some(url.as_str()));
+    assert_eq!(
+        child(entry, "published").text(),
+        Some("2026-04-16T09:30:00-03:00")
+    );
+    assert_eq!(child(entry, "updated").text(), Some("2026-05-01T00:00:00Z"));
+    assert_eq!(child(entry, "summary").text(), Some("What a fuzzer found."));
+    let content = child(entry, "content");
+    assert_eq!(content.attribute("type"), Some("html"));
+    assert_absolute(content.text().unwrap());
+
+    let terms: Vec<_> = entry
+        .children()
+        .filter(|n| n.tag_name().name() == "category")
+        .map(|n| (n.attribute("term").unwrap(), n.attribute("label").unwrap()))
+        .collect();
+    assert_eq!(terms, [("fuzzing", "fuzzing"), ("rust", "Rust")]);
+}
+
+#[test]
+fn rss_is_valid_and_dated_per_rfc_2822() {
+    let site = site();
+    let xml = read(&site, "blog/rss.xml");
+    let doc = roxmltree::Document::parse(&xml).unwrap();
+    let rss = doc.root_element();
+    assert_eq!(rss.attribute("version"), Some("2.0"));
+
+    let channel = rss.first_element_child().unwrap();
+    assert_eq!(channel.tag_name().name(), "channel");
+    for required in ["title", "link", "description"] {
+        assert!(
+            channel.children().any(|n| n.tag_name().name() == required),
+            "channel has no <{required}>"
+        );
+    }
+
+    let item = channel
+        .children()
+        .find(|n| n.tag_name().name() == "item")
+        .unwrap();
+    let text = |name: &str| child(item, name).text().unwrap();
+    assert_eq!(text("link"), format!("{SITE_URL}/blog/fuzzing/"));
+    assert_eq!(text("guid"), format!("{SITE_URL}/blog/fuzzing/"));
+    assert_eq!(text("pubDate"), "Thu, 16 Apr 2026 09:30:00 -0300");
+    assert_absolute(text("description"));
+}
+
+#[test]
+fn json_feed_follows_version_1_1() {
+    let site = site();
+    let feed: serde_json::Value = serde_json::from_str(&read(&site, "blog/feed.json")).unwrap();
+    assert_eq!(feed["version"], "https://jsonfeed.org/version/1.1");
+    assert_eq!(feed["feed_url"], format!("{SITE_URL}/blog/feed.json"));
+    assert_eq!(feed["authors"][0]["name"], "Someone");
+
+    let items = feed["items"].as_array().unwrap();
+    assert_eq!(items.len(), 2);
+    let item = &items[0];
+    assert_eq!(item["id"], format!("{SITE_URL}/blog/fuzzing/"));
+    assert_eq!(item["title"], "Fuzzing & friends");
+    assert_eq!(item["date_published"], "2026-04-16T09:30:00-03:00");
+    assert_eq!(item["date_modified"], "2026-05-01T00:00:00Z");
+    assert_eq!(item["tags"], serde_json::json!(["fuzzing", "Rust"]));
+    assert_absolute(item["content_html"].as_str().unwrap());
+
+    // Optional members are left out rather than sent empty.
+    assert!(items[1].get("date_modified").is_none());
+    assert!(items[1].get("tags").is_none());
+}
+
+#[test]
+fn every_tag_gets_all_three_feeds() {
+    let site = site();
+    for format in ["feed.xml", "rss.xml", "feed.json"] {
+        let feed = read(&site, &format!("blog/tags/rust/{format}"));
+        assert!(feed.contains("/blog/fuzzing/"), "{format}");
+        // The untagged post's body.
+        assert!(!feed.contains("Hello."), "{format}");
+    }
+}
+
+#[test]
+fn a_staging_build_links_to_its_own_host() {
+    let site = site();
+    let options = blog_gen::BuildOptions {
+        base_url: Some("http://localhost:8080".to_string()),
+        ..blog_gen::BuildOptions::default()
+    };
+    blog_gen::generate_with(site.path(), &options).unwrap();
+
+    let feed: serde_json::Value = serde_json::from_str(&read(&site, "blog/feed.json")).unwrap();
+    assert_eq!(
+        feed["items"][0]["id"],
+        "http://localhost:8080/blog/fuzzing/"
+    );
+    let post = read(&site, "blog/fuzzing/index.html");
+    assert!(post.contains("<link rel=\"canonical\" href=\"http://localhost:8080/blog/fuzzing/\">"));
+    assert!(read(&site, "sitemap.xml").contains("<loc>http://localhost:8080/books</loc>"));
+}
@@ -0,0 +1,233 @@
+//! Rebuilds a small site after edits and checks that only the pages an edit
+//! touches are written again, and that a renamed post leaves nothing behind
+//! but a redirect.
+
+mod common;
+
+use std::{fs, path::Path};
+
+use blog_gen::BuildOptions;
+use common::{read, site_root, write_post, write_site_toml, SITE_URL};
+
+fn post(title: &str, date: &str) -> String {
+    format!("---\ntitle: {title}\ndate: {date}\n---\n\n{title} body.\n")
+}
+
+fn site() -> tempfile::TempDir {
+    let root = site_root("");
+    write_post(&root, "first", &post("First", "2026-01-02"));
+    write_post(&root, "second", &post("Second", "2026-02-03"));
+    assert_eq!(blog_gen::generate(root.path()).unwrap().posts, 2);
+    root
+}
+
+/// Overwrites an output with a marker, which survives only if the next
+/// build leaves that output alone.
+fn mark(root: &tempfile::TempDir, path: &str) {
+    fs::write(root.path().join("static").join(path), "untouched").unwrap();
+}
+
+fn is_marked(root: &tempfile::TempDir, path: &str) -> bool {
+    fs::read_to_string(root.path().join("static").join(path)).unwrap() == "untouched"
+}
+
+#[test]
+fn editing_a_post_rewrites_its_page_and_the_pages_listing_it() {
+    let root = site();
+    for page in [
+        "blog/first/index.html",
+        "blog/second/index.html",
+        "blog/index.html",
+        "404.html",
+    ] {
+        mark(&root, page);
+    }
+
+    write_post(&root, "second", &post("Second, edited", "2026-02-03"));
+    blog_gen::generate(root.path()).unwrap();
+
+    assert!(is_marked(&root, "blog/first/index.html"));
+    assert!(is_marked(&root, "404.html"));
+    assert!(!is_marked(&root, "blog/second/index.html"));
+    assert!(!is_marked(&root, "blog/index.html"));
+}
+
+#[test]
+fn a_template_edit_rewrites_every_page_made_from_it() {
+    let root = site();
+    mark(&root, "blog/first/index.html");
+    mark(&root, "blog/index.html");
+
+    edit_template(
+        &root,
+        "post.html",
+        "</article>",
+        "</article><!-- edited -->",
+    );
+    blog_gen::generate(root.path()).unwrap();
+    assert!(!is_marked(&root, "blog/first/index.html"));
+    assert!(is_marked(&root, "blog/index.html"));
+
+    // Every page includes the header, through the layout they extend.
+    edit_template(
+        &root,
+        "partials/header.html",
+        "</nav>",
+        "</nav><!-- edited -->",
+    );
+    blog_gen::generate(root.path()).unwrap();
+    assert!(!is_marked(&root, "blog/index.html"));
+}
+
+fn edit_template(root: &tempfile::TempDir, name: &str, from: &str, to: &str) {
+    let tmpl = root.path().join("templates").join(name);
+    let source = fs::read_to_string(&tmpl).unwrap();
+    assert!(source.contains(from), "{name} has no {from}");
+    fs::write(&tmpl, source.replacen(from, to, 1)).unwrap();
+}
+
+#[test]
+fn an_undefined_template_variable_fails_the_build() {
+    let root = site();
+    edit_template(&root, "post.html", "{{ post.title }}", "{{ post.titel }}");
+    let err = blog_gen::generate(root.path()).unwrap_err();
+    assert!(err.contains("undefined variable `post.titel`"), "{err}");
+}
+
+fn rename_first(root: &tempfile::TempDir) {
+    fs::rename(
+        root.path().join("content/blog/first"),
+        root.path().join("content/blog/renamed"),
+    )
+    .unwrap();
+}
+
+#[test]
+fn a_renamed_post_leaves_no_stale_output() {
+    let root = site();
+    rename_first(&root);
+    blog_gen::generate(root.path()).unwrap();
+
+    let out = root.path().join("static/blog");
+    assert!(out.join("renamed/index.html").is_file());
+    assert!(!out.join("first").exists());
+    let sitemap = fs::read_to_string(root.path().join("static/sitemap.xml")).unwrap();
+    assert!(!sitemap.contains("/blog/first/"));
+    assert!(!root.path().join("content/redirects.toml").exists());
+}
+
+#[test]
+fn a_renamed_post_leaves_a_redirect_behind() {
+    let root = site();
+    rename_first(&root);
+    let options = BuildOptions {
+        redirect_moves: true,
+        ..BuildOptions::default()
+    };
+    blog_gen::generate_with(root.path(), &options).unwrap();
+
+    let redirects = fs::read_to_string(root.path().join("content/redirects.toml")).unwrap();
+    assert!(redirects.contains("\"/blog/first/\" = \"/blog/renamed/\"\n"));
+    let page = fs::read_to_string(root.path().join("static/blog/first/index.html")).unwrap();
+    assert!(page.contains("<meta http-equiv=\"refresh\" content=\"0; url=/blog/renamed/\">"));
+    assert!(page.contains(&format!(
+        "<link rel=\"canonical\" href=\"{SITE_URL}/blog/renamed/\">"
+    )));
+
+    // The entry outlives the manifest, so the page does too.
+    fs::remove_dir_all(root.path().join(".blog-gen")).unwrap();
+    blog_gen::generate(root.path()).unwrap();
+    assert!(root.path().join("static/blog/first/index.html").is_file());
+}
+
+#[test]
+fn a_redirect_never_replaces_a_page_the_build_writes() {
+    let root = site();
+    fs::write(
+        root.path().join("content/redirects.toml"),
+        "\"/books.html\" = \"/blog/first/\"\n\"/search/index.html\" = \"/blog/first/\"\n\"/404.html\" = \"/blog/first/\"\n",
+    )
+    .unwrap();
+    blog_gen::generate(root.path()).unwrap();
+    for page in ["books.html", "search/index.html", "404.html"] {
+        assert!(
+            !read(&root, page).contains("http-equiv=\"refresh\""),
+            "{page} was replaced by a redirect"
+        );
+    }
+}
+
+#[test]
+fn stray_files_in_generated_directories_go_even_without_a_manifest() {
+    let root = site();
+    fs::remove_dir_all(root.path().join(".blog-gen")).unwrap();
+    let stray = root.path().join("static/blog/long-gone/index.html");
+    fs::create_dir_all(stray.parent().unwrap()).unwrap();
+    fs::write(&stray, "old").unwrap();
+    fs::write(root.path().join("static/robots.txt"), "User-agent: *\n").unwrap();
+
+    blog_gen::generate(root.path()).unwrap();
+
+    assert!(!root.path().join("static/blog/long-gone").exists());
+    assert!(root.path().join("static/robots.txt").is_file());
+}
+
+#[test]
+fn posts_get_a_social_card_unless_they_bring_their_own() {
+    let root = site();
+    let font = Path::new(env!("CARGO_MANIFEST_DIR")).join("../assets/fonts/DejaVuSerif-Bold.ttf");
+    let card = format!(
+        "\n[card]\nbackground = \"#121110\"\ncolor = \"#ece7df\"\nfont = {:?}\n",
+        font.display().to_string()
+    );
+    write_site_toml(&root, &card);
+    blog_gen::generate(root.path()).unwrap();
+
+    let page = |slug: &str| {
+        fs::read_to_string(root.path().join(format!("static/blog/{slug}/index.html"))).unwrap()
+    };
+    assert!(root.path().join("static/blog/first/og.png").is_file());
+    assert!(page("first").contains(&format!(
+        "<meta property=\"og:image\" content=\"{SITE_URL}/blog/first/og.png\">"
+    )));
+
+    mark(&root, "blog/first/og.png");
+    write_post(
+        &root,
+        "second",
+        "---\ntitle: Second\ndate: 2026-02-03\nog_image: hero.png\n---\n\nBody.\n",
+    );
+    blog_gen::generate(root.path()).unwrap();
+    assert!(is_marked(&root, "blog/first/og.png"));
+    assert!(!root.path().join("static/blog/second/og.png").exists());
+    assert!(page("second").contains(&format!(
+        "<meta property=\"og:image\" content=\"{SITE_URL}/blog/second/hero.png\">"
+    )));
+}
+
+#[test]
+fn a_slug_cannot_climb_out_of_the_site() {
+    let root = site();
+    write_post(
+        &root,
+        "second",
+        "---\ntitle: Second\ndate: 2026-02-03\nslug: \"../../escape\"\n---\n\nBody.\n",
+    );
+    let err = blog_gen::generate(root.path()).unwrap_err();
+    assert!(err.contains("must be one path segment"), "{err}");
+    assert!(!root.path().join("escape").exists());
+    assert!(root.path().join("static/blog/second/index.html").is_file());
+}
+
+#[test]
+fn two_posts_at_one_path_fail_the_build() {
+    let root = site();
+    write_post(
+        &root,
+        "second",
+        "---\ntitle: Second\ndate: 2026-02-03\nslug: first\n---\n\nBody.\n",
+    );
+    let err = blog_gen::generate(root.path()).unwrap_err();
+    assert!(err.contains("are both at /blog/first/"), "{err}");
+    assert!(read(&root, "blog/first/index.html").contains("First body."));
+}
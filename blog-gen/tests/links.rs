@@ -0,0 +1,53 @@
+//! Builds a site with a broken link and checks that it reaches the caller,
+//! who decides where to report it, rather than going to stderr.
+
+mod common;
+
+use blog_gen::BuildOptions;
+use common::{site_root, write_post};
+
+fn site() -> tempfile::TempDir {
+    let root = site_root("");
+    write_post(
+        &root,
+        "first",
+        "---\ntitle: First\ndate: 2026-01-02\n---\n\nSee [the missing one](/blog/missing/).\n",
+    );
+    root
+}
+
+#[test]
+fn broken_links_are_returned_with_where_they_are() {
+    let root = site();
+    let generated = blog_gen::generate(root.path()).unwrap();
+    assert_eq!(generated.posts, 1);
+    // The bare test site has no stylesheet or icons either; only the post's
+    // own link matters here.
+    let missing: Vec<_> = generated
+        .broken_links
+        .iter()
+        .filter(|link| link.url == "/blog/missing/")
+        .collect();
+    let [link] = missing[..] else {
+        panic!("one link to /blog/missing/, got {missing:?}");
+    };
+    assert!(link.file.ends_with("blog/first/index.html"));
+    assert_eq!(link.reason, "nothing is served at /blog/missing/");
+}
+
+#[test]
+fn a_strict_build_fails_naming_each_broken_link() {
+    let root = site();
+    let options = BuildOptions {
+        strict_links: true,
+        ..BuildOptions::default()
+    };
+    let err = blog_gen::generate_with(root.path(), &options).unwrap_err();
+    assert!(err.contains(" broken link(s):\n"), "{err}");
+    assert!(
+        err.lines()
+            .any(|line| line.contains("blog/first/index.html:")
+                && line.contains("broken link \"/blog/missing/\"")),
+        "{err}"
+    );
+}
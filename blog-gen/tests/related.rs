@@ -0,0 +1,118 @@
+//! Generates a site with a series and posts on shared topics, and checks the
+//! series navigation and related posts on each post's page.
+
+mod common;
+
+use std::fs;
+
+use common::{site_root, write_post};
+
+fn part(n: u32, date: &str, extra: &str) -> String {
+    format!(
+        "---\ntitle: Parsers, part {n}\ndate: {date}\nseries: Writing a parser\n{extra}---\n\n\
+         Tokens, grammars and recursive descent, step {n}.\n"
+    )
+}
+
+const FEES: &str = "---\ntitle: Routing fees\ndate: 2026-05-01\ntags: [lightning, fees]\n---\n\n\
+    Channel liquidity is priced by routing fees; a stuck payment locks liquidity in every channel \
+    along its route.\n";
+
+const JAMMING: &str = "---\ntitle: Channel jamming\ndate: 2026-06-01\ntags: [Lightning]\n---\n\n\
+    Jamming locks channel liquidity with payments that never settle, so every channel along the \
+    route pays with its liquidity.\n";
+
+const BREAD: &str = "---\ntitle: Sourdough\ndate: 2026-07-01\n---\n\n\
+    Flour, water, salt and patience make a loaf.\n";
+
+fn site() -> tempfile::TempDir {
+    let root = site_root("");
+    for (slug, body) in [
+        ("parsers-1", part(1, "2026-01-01", "")),
+        ("parsers-3", part(3, "2026-03-01", "")),
+        ("parsers-2", part(2, "2026-02-01", "")),
+        ("parsers-4", part(4, "2026-04-01", "draft: true\n")),
+        ("fees", FEES.to_string()),
+        ("jamming", JAMMING.to_string()),
+        ("bread", BREAD.to_string()),
+    ] {
+        write_post(&root, slug, &body);
+    }
+    blog_gen::generate(root.path()).unwrap();
+    root
+}
+
+fn page(root: &tempfile::TempDir, path: &str) -> String {
+    fs::read_to_string(root.path().join("static").join(path).join("index.html")).unwrap()
+}
+
+/// The paths listed under "Related posts", in order.
+fn related(page: &str) -> Vec<String> {
+    let Some(at) = page.find("<aside class=\"related\">") else {
+        return Vec::new();
+    };
+    let aside = &page[at..page[at..].find("</aside>").unwrap() + at];
+    aside
+        .split("<a href=\"")
+        .skip(1)
+        .map(|rest| rest[..rest.find('"').unwrap()].to_string())
+        .collect()
+}
+
+#[test]
+fn a_series_links_its_parts_in_date_order() {
+    let root = site();
+
+    let second = page(&root, "blog/parsers-2");
+    assert!(second.contains("Part 2 of 3 in <em>Writing a parser</em>"));
+    assert!(second.contains("<a rel=\"prev\" href=\"/blog/parsers-1/\">"));
+    assert!(second.contains("<a rel=\"next\" href=\"/blog/parsers-3/\">"));
+    assert!(second.contains("<span aria-current=\"page\">Parsers, part 2</span>"));
+
+    let first = page(&root, "blog/parsers-1");
+    assert!(!first.contains("rel=\"prev\""));
+    assert!(first.contains("<a rel=\"next\" href=\"/blog/parsers-2/\">"));
+
+    // The draft is nobody's neighbour until it is published, but its own
+    // preview shows where it will go.
+    let last = page(&root, "blog/parsers-3");
+    assert!(!last.contains("rel=\"next\""));
+    assert!(!last.contains("/drafts/"));
+    let draft = page(&root, "drafts/parsers-4");
+    assert!(draft.contains("Part 4 of 4"));
+    assert!(draft.contains("<a rel=\"prev\" href=\"/blog/parsers-3/\">"));
+}
+
+#[test]
+fn a_post_outside_a_series_has_no_series_navigation() {
+    let root = site();
+    assert!(!page(&root, "blog/fees").contains("class=\"series\""));
+}
+
+#[test]
+fn related_posts_share_tags_or_words() {
+    let root = site();
+
+    let jamming = related(&page(&root, "blog/jamming"));
+    assert_eq!(jamming.first().map(String::as_str), Some("/blog/fees/"));
+    assert!(!jamming.contains(&"/blog/bread/".to_string()));
+    assert!(!jamming.contains(&"/blog/jamming/".to_string()));
+
+    let parts = related(&page(&root, "blog/parsers-1"));
+    assert!(parts.contains(&"/blog/parsers-2/".to_string()));
+    assert!(parts.iter().all(|p| !p.starts_with("/drafts/")));
+
+    assert!(related(&page(&root, "blog/bread")).is_empty());
+}
+
+#[test]
+fn retitling_a_related_post_rewrites_the_pages_listing_it() {
+    let root = site();
+    write_post(
+        &root,
+        "fees",
+        &FEES.replace("Routing fees", "Fees, revisited"),
+    );
+    blog_gen::generate(root.path()).unwrap();
+    assert!(page(&root, "blog/jamming").contains(">Fees, revisited</a>"));
+}
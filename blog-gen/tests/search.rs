@@ -0,0 +1,75 @@
+//! Builds a site from the posts in `tests/fixtures/search` and checks how
+//! its search index ranks them.
+
+mod common;
+
+use std::{fs, path::Path};
+
+use common::{copy_dir, site_root};
+use site_search::Index;
+
+fn index() -> Index {
+    let root = site_root("");
+    copy_dir(
+        &Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/search"),
+        &root.path().join("content/blog"),
+    );
+    blog_gen::generate(root.path()).unwrap();
+    Index::parse(&fs::read(root.path().join("static/search/index.bin")).unwrap()).unwrap()
+}
+
+fn ranked(index: &Index, query: &str) -> Vec<String> {
+    index
+        .search(query, 10)
+        .iter()
+        .map(|hit| hit.path.to_string())
+        .collect()
+}
+
+#[test]
+fn a_title_outranks_a_heading_which_outranks_the_body() {
+    let index = index();
+    assert_eq!(
+        ranked(&index, "jamming"),
+        [
+            "/blog/onion-jamming/",
+            "/blog/fee-notes/",
+            "/blog/fuzzing-lightning/",
+        ]
+    );
+}
+
+#[test]
+fn queries_match_other_forms_of_a_word() {
+    let index = index();
+    assert_eq!(ranked(&index, "jammed"), ranked(&index, "jamming"));
+    assert_eq!(
+        ranked(&index, "fuzz"),
+        ["/blog/fuzzing-lightning/", "/blog/rust-parsers/"]
+    );
+}
+
+#[test]
+fn posts_matching_more_of_the_query_come_first() {
+    let index = index();
+    let ranked = ranked(&index, "lightning panic");
+    assert_eq!(ranked[0], "/blog/fuzzing-lightning/");
+    assert!(ranked.contains(&"/blog/rust-parsers/".to_string()));
+}
+
+#[test]
+fn drafts_and_common_words_are_not_indexed() {
+    let index = index();
+    assert_eq!(index.len(), 4);
+    assert!(ranked(&index, "the and of").is_empty());
+}
+
+#[test]
+fn hits_carry_their_rendered_entry() {
+    let index = index();
+    let hits = index.search("routing fees", 10);
+    assert_eq!(hits.len(), 1);
+    assert!(hits[0]
+        .html
+        .starts_with("<li><a href=\"/blog/fee-notes/\">Notes on routing fees</a>"));
+}
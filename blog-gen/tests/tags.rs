@@ -0,0 +1,103 @@
+//! Generates a site with tagged posts and checks each tag's page, its feeds
+//! and its sitemap entry, and that tags that cannot have a page of their own
+//! fail the build.
+
+mod common;
+
+use common::{read, site_root, write_post, SITE_URL};
+
+fn post(title: &str, date: &str, tags: &str) -> String {
+    format!("---\ntitle: {title}\ndate: {date}\ntags: [{tags}]\n---\n\n{title} body.\n")
+}
+
+fn site() -> tempfile::TempDir {
+    let root = site_root("");
+    write_post(
+        &root,
+        "fees",
+        &post("Routing fees", "2026-05-01", "lightning, C++"),
+    );
+    write_post(
+        &root,
+        "jamming",
+        &post("Channel jamming", "2026-06-01", "Lightning"),
+    );
+    write_post(&root, "bread", &post("Sourdough", "2026-07-01", "baking"));
+    blog_gen::generate(root.path()).unwrap();
+    root
+}
+
+#[test]
+fn each_tag_gets_a_page_listing_its_posts() {
+    let root = site();
+    // "Lightning" and "lightning" are one tag, named as the newest post has it.
+    let lightning = read(&root, "blog/tags/lightning/index.html");
+    assert!(lightning.contains("<h1>Lightning</h1>"));
+    assert!(lightning.contains("/blog/fees/") && lightning.contains("/blog/jamming/"));
+    assert!(!lightning.contains("/blog/bread/"));
+
+    let cpp = read(&root, "blog/tags/c/index.html");
+    assert!(cpp.contains("<h1>C++</h1>"));
+    let index = read(&root, "blog/tags/index.html");
+    for path in [
+        "/blog/tags/lightning/",
+        "/blog/tags/c/",
+        "/blog/tags/baking/",
+    ] {
+        assert!(index.contains(path), "{path}");
+    }
+    assert!(read(&root, "blog/fees/index.html").contains("href=\"/blog/tags/c/\""));
+}
+
+#[test]
+fn each_tag_has_feeds_of_its_own_posts() {
+    let root = site();
+    let atom = read(&root, "blog/tags/lightning/feed.xml");
+    assert!(atom.contains("<title>example.com: Lightning</title>"));
+    assert!(atom.contains(&format!(
+        "<link href=\"{SITE_URL}/blog/tags/lightning/feed.xml\" rel=\"self\"/>"
+    )));
+    assert_eq!(atom.matches("<entry>").count(), 2);
+    assert!(!atom.contains("Sourdough"));
+
+    let rss = read(&root, "blog/tags/baking/rss.xml");
+    assert_eq!(rss.matches("<item>").count(), 1);
+    let json: serde_json::Value =
+        serde_json::from_str(&read(&root, "blog/tags/baking/feed.json")).unwrap();
+    assert_eq!(json["items"][0]["id"], format!("{SITE_URL}/blog/bread/"));
+}
+
+#[test]
+fn the_sitemap_lists_every_tag_page() {
+    let root = site();
+    let sitemap = read(&root, "sitemap.xml");
+    for path in ["tags/", "tags/lightning/", "tags/c/", "tags/baking/"] {
+        assert!(
+            sitemap.contains(&format!("<loc>{SITE_URL}/blog/{path}</loc>")),
+            "{path}"
+        );
+    }
+}
+
+#[test]
+fn tags_that_would_share_a_page_fail_the_build() {
+    let root = site_root("");
+    write_post(&root, "c", &post("C", "2026-01-01", "C"));
+    write_post(&root, "cpp", &post("C++", "2026-02-01", "C++"));
+    let e = blog_gen::generate(root.path()).unwrap_err();
+    assert!(
+        e.contains("tags \"C++\" and \"C\" would share the page /blog/tags/c/"),
+        "{e}"
+    );
+}
+
+#[test]
+fn a_tag_with_nothing_to_make_a_url_from_fails_the_build() {
+    let root = site_root("");
+    write_post(&root, "tokyo", &post("Tokyo", "2026-01-01", "日本語"));
+    let e = blog_gen::generate(root.path()).unwrap_err();
+    assert!(
+        e.contains("tag \"日本語\" has no ASCII letters or digits"),
+        "{e}"
+    );
+}
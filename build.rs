@@ -7,10 +7,25 @@ fn main() {
     // Sources of the generated site; touching any of them re-runs this script.
     println!("cargo:rerun-if-changed=content");
     println!("cargo:rerun-if-changed=templates");
+    println!("cargo:rerun-if-changed=site.toml");
+    println!("cargo:rerun-if-changed=assets");
     println!("cargo:rerun-if-changed=build.rs");
+    // Set to make a broken internal link fail the build rather than warn.
+    println!("cargo:rerun-if-env-changed=BLOG_GEN_STRICT_LINKS");
 
     let root = Path::new(env!("CARGO_MANIFEST_DIR"));
-    if let Err(e) = blog_gen::generate(root) {
-        panic!("blog-gen failed: {e}");
+    let options = blog_gen::BuildOptions {
+        strict_links: std::env::var_os("BLOG_GEN_STRICT_LINKS").is_some(),
+        ..Default::default()
+    };
+    // Cargo only shows a build script's stderr when it fails, so a broken
+    // link that does not fail the build is surfaced as a warning instead.
+    match blog_gen::generate_with(root, &options) {
+        Ok(generated) => {
+            for link in generated.broken_links {
+                println!("cargo:warning={link}");
+            }
+        }
+        Err(e) => panic!("blog-gen failed: {e}"),
     }
 }
@@ -0,0 +1,135 @@
+//! The site as a read-only ActivityPub actor, so that it can be looked up
+//! from the fediverse as `@user@host` and its posts read there.
+//!
+//! blog-gen writes the documents from the post list: the actor, its outbox,
+//! and the WebFinger answer that leads to the actor. Their ids have no file
+//! extension, and ActivityPub and WebFinger each want a media type no
+//! extension maps to, so they are served from here rather than by name.
+
+use crate::{cache::FileCache, Request, Response};
+
+const ACTIVITY_JSON: &str = "application/activity+json";
+const JRD_JSON: &str = "application/jrd+json";
+const WEBFINGER: &str = "/.well-known/webfinger";
+
+/// Each document's path, the file blog-gen writes it to, and its type.
+const DOCUMENTS: [(&str, &str, &str); 3] = [
+    (
+        "/activitypub/actor",
+        "/activitypub/actor.json",
+        ACTIVITY_JSON,
+    ),
+    (
+        "/activitypub/outbox",
+        "/activitypub/outbox.json",
+        ACTIVITY_JSON,
+    ),
+    (WEBFINGER, "/.well-known/webfinger.json", JRD_JSON),
+];
+
+/// The document at `request`'s path, if it is one of them and the site has
+/// it. WebFinger only answers for the actor it describes: anything else
+/// asked for is not found.
+pub(crate) fn respond(request: &Request, cache: &FileCache) -> Option<Response> {
+    let (path, file, content_type) = DOCUMENTS
+        .into_iter()
+        .find(|(path, ..)| *path == request.path)?;
+    let cached = cache.get(file)?;
+    if path == WEBFINGER && !describes(&cached.body, request.query_param("resource")) {
+        return Some(Response::not_found(
+            b"Not Found".to_vec(),
+            "text/plain",
+            false,
+        ));
+    }
+    let response = Response::ok(cached.body.to_vec(), content_type, false);
+    Some(match cached.cache_control {
+        Some(cc) => response.with_cache_control(cc),
+        None => response,
+    })
+}
+
+/// Whether the WebFinger document `jrd` is about `resource`: its subject or
+/// one of its aliases.
+fn describes(jrd: &[u8], resource: Option<String>) -> bool {
+    let (Ok(jrd), Some(resource)) = (serde_json::from_slice::<serde_json::Value>(jrd), resource)
+    else {
+        return false;
+    };
+    let aliases = jrd["aliases"].as_array().into_iter().flatten();
+    std::iter::once(&jrd["subject"])
+        .chain(aliases)
+        .any(|name| name.as_str() == Some(resource.as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn site() -> (TempDir, FileCache) {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("activitypub")).unwrap();
+        fs::create_dir_all(dir.path().join(".well-known")).unwrap();
+        fs::write(
+            dir.path().join("activitypub/actor.json"),
+            r#"{"type":"Person"}"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join(".well-known/webfinger.json"),
+            r#"{"subject":"acct:erick@example.com","aliases":["https://example.com/activitypub/actor"]}"#,
+        )
+        .unwrap();
+        let cache = FileCache::load(dir.path().to_str().unwrap());
+        (dir, cache)
+    }
+
+    fn request(path: &str, query: Option<&str>) -> Request {
+        Request {
+            method: http::Method::GET,
+            path: path.to_string(),
+            raw_path: path.to_string(),
+            version: http::Version::HTTP_11,
+            user_agent: None,
+            accepts_gzip: false,
+            peer: "203.0.113.7:54321".parse().unwrap(),
+            client_subject: None,
+            query: query.map(String::from),
+            body: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn the_actor_is_served_as_activity_json() {
+        let (_dir, cache) = site();
+        let res = respond(&request("/activitypub/actor", None), &cache).unwrap();
+        assert_eq!(res.status, 200);
+        assert_eq!(res.content_type, ACTIVITY_JSON);
+        assert_eq!(res.body, br#"{"type":"Person"}"#);
+        // Without an outbox written there is nothing to serve.
+        assert!(respond(&request("/activitypub/outbox", None), &cache).is_none());
+        assert!(respond(&request("/activitypub/inbox", None), &cache).is_none());
+    }
+
+    #[test]
+    fn webfinger_answers_only_for_the_actor() {
+        let (_dir, cache) = site();
+        let ask = |query| respond(&request(WEBFINGER, query), &cache).unwrap();
+
+        let res = ask(Some("resource=acct%3Aerick%40example.com"));
+        assert_eq!(res.status, 200);
+        assert_eq!(res.content_type, JRD_JSON);
+        let alias = "resource=https%3A%2F%2Fexample.com%2Factivitypub%2Factor";
+        assert_eq!(ask(Some(alias)).status, 200);
+
+        assert_eq!(
+            ask(Some("resource=acct%3Asomeone%40example.com")).status,
+            404
+        );
+        assert_eq!(ask(None).status, 404);
+    }
+}
@@ -150,7 +150,8 @@ impl FileCache {
     fn cache_control(filename: &str) -> Option<&'static str> {
         match Path::new(filename).extension().and_then(|ext| ext.to_str()) {
             Some(
-                "css" | "js" | "png" | "jpg" | "jpeg" | "webp" | "ico" | "svg" | "woff" | "woff2",
+                "css" | "js" | "png" | "jpg" | "jpeg" | "webp" | "avif" | "ico" | "svg" | "woff"
+                | "woff2",
             ) => Some("public, max-age=300, must-revalidate"),
             Some("html") => Some("public, max-age=300, must-revalidate"),
             _ => None,
@@ -162,7 +163,7 @@ impl FileCache {
         self.entries.len()
     }
 
-    fn content_type(filename: &str) -> &'static str {
+    pub(crate) fn content_type(filename: &str) -> &'static str {
         match Path::new(filename).extension().and_then(|ext| ext.to_str()) {
             Some("html") => "text/html",
             Some("css") => "text/css",
@@ -172,6 +173,7 @@ impl FileCache {
             Some("png") => "image/png",
             Some("jpg" | "jpeg") => "image/jpeg",
             Some("webp") => "image/webp",
+            Some("avif") => "image/avif",
             Some("ico") => "image/x-icon",
             Some("woff") => "font/woff",
             Some("woff2") => "font/woff2",
@@ -357,6 +359,7 @@ mod tests {
             ("a.jpg", "image/jpeg"),
             ("a.jpeg", "image/jpeg"),
             ("a.webp", "image/webp"),
+            ("a.avif", "image/avif"),
             ("a.ico", "image/x-icon"),
             ("a.woff", "font/woff"),
             ("a.woff2", "font/woff2"),
@@ -0,0 +1,236 @@
+//! Canary tokens: the credentials in honeypot bait, minted fresh for every
+//! request that springs a trap.
+//!
+//! A bait body is a template. Each `{{slot}}` in it becomes a new token,
+//! random but shaped like the real thing: an AWS key ID is `AKIA` and sixteen
+//! base32 characters, a GitHub token `ghp_`, thirty alphanumerics and the
+//! checksum GitHub's own tokens end in, so one passes an offline check. The
+//! part of the slot before any `.` names the kind of token, so a body with
+//! two AWS keys writes `{{aws_key_id}}` and `{{aws_key_id.ses}}`, and a slot
+//! written twice is the same token both times.
+//!
+//! The tokens each request got go into the honeypot log with it (see
+//! `intel`), so that when one turns up later, being tried against the service
+//! it imitates, `portfolio honeypot lookup` can say which scan took it.
+
+use rustls::crypto::aws_lc_rs;
+
+const UPPER_BASE32: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+const ALNUM: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+const BASE64: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const DIGITS: &[u8] = b"0123456789";
+/// The digits of the checksums GitHub and npm put on their tokens.
+const BASE62: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+/// A piece of a token.
+enum Part {
+    Fixed(&'static str),
+    Random(&'static [u8], usize),
+    /// The CRC-32 of the random characters so far, as six base62 digits.
+    Checksum,
+}
+
+use Part::{Checksum, Fixed, Random};
+
+/// Every kind of token a slot can ask for, and its shape.
+const KINDS: &[(&str, &[Part])] = &[
+    ("aws_key_id", &[Fixed("AKIA"), Random(UPPER_BASE32, 16)]),
+    (
+        "aws_session_key_id",
+        &[Fixed("ASIA"), Random(UPPER_BASE32, 16)],
+    ),
+    ("aws_secret", &[Random(BASE64, 40)]),
+    (
+        "github_token",
+        &[Fixed("ghp_"), Random(ALNUM, 30), Checksum],
+    ),
+    ("gitlab_token", &[Fixed("glpat-"), Random(ALNUM, 20)]),
+    ("npm_token", &[Fixed("npm_"), Random(ALNUM, 30), Checksum]),
+    (
+        "pypi_token",
+        &[Fixed("pypi-AgEIcHlwaS5vcmc"), Random(ALNUM, 64)],
+    ),
+    ("stripe_secret", &[Fixed("sk_live_51"), Random(ALNUM, 54)]),
+    (
+        "slack_token",
+        &[
+            Fixed("xoxb-"),
+            Random(DIGITS, 10),
+            Fixed("-"),
+            Random(DIGITS, 10),
+            Fixed("-"),
+            Random(ALNUM, 24),
+        ],
+    ),
+    ("openai_key", &[Fixed("sk-proj-"), Random(ALNUM, 48)]),
+    (
+        "sendgrid_key",
+        &[
+            Fixed("SG."),
+            Random(ALNUM, 22),
+            Fixed("."),
+            Random(ALNUM, 43),
+        ],
+    ),
+];
+
+/// `template` with every slot filled, and the `(slot, token)` pairs it was
+/// filled with, in the order the slots first appear. A slot naming no known
+/// kind is left as written.
+pub(crate) fn plant(template: &str) -> (String, Vec<(String, String)>) {
+    let mut body = String::with_capacity(template.len());
+    let mut planted: Vec<(String, String)> = Vec::new();
+    let mut rest = template;
+    while let Some(open) = rest.find("{{") {
+        let Some(close) = rest[open..].find("}}").map(|at| open + at) else {
+            break;
+        };
+        body.push_str(&rest[..open]);
+        let slot = &rest[open + 2..close];
+        match planted.iter().find(|(s, _)| s == slot) {
+            Some((_, token)) => body.push_str(token),
+            None => match mint(slot) {
+                Some(token) => {
+                    body.push_str(&token);
+                    planted.push((slot.to_string(), token));
+                }
+                None => body.push_str(&rest[open..close + 2]),
+            },
+        }
+        rest = &rest[close + 2..];
+    }
+    body.push_str(rest);
+    (body, planted)
+}
+
+/// A new token for `slot`, if its kind is known.
+fn mint(slot: &str) -> Option<String> {
+    let kind = slot.split('.').next().unwrap_or(slot);
+    let (_, parts) = KINDS.iter().find(|(name, _)| *name == kind)?;
+    let mut token = String::new();
+    let mut random = String::new();
+    for part in *parts {
+        match part {
+            Fixed(text) => token.push_str(text),
+            Random(alphabet, len) => {
+                let chars: String = random_chars(alphabet, *len).collect();
+                token.push_str(&chars);
+                random.push_str(&chars);
+            }
+            Checksum => token.push_str(&checksum(&random)),
+        }
+    }
+    Some(token)
+}
+
+/// The CRC-32 of `text` in base62, zero-padded to six digits.
+fn checksum(text: &str) -> String {
+    let mut crc = flate2::Crc::new();
+    crc.update(text.as_bytes());
+    let mut n = crc.sum();
+    let mut digits = [b'0'; 6];
+    for digit in digits.iter_mut().rev() {
+        *digit = BASE62[(n % 62) as usize];
+        n /= 62;
+    }
+    String::from_utf8(digits.to_vec()).expect("base62 digits are ASCII")
+}
+
+/// `len` characters drawn uniformly from `alphabet`.
+fn random_chars(alphabet: &[u8], len: usize) -> impl Iterator<Item = char> + '_ {
+    // Bytes at or past the last whole multiple of the alphabet's size would
+    // favour its first letters, so they are drawn again.
+    let limit = 256 - 256 % alphabet.len();
+    let random = aws_lc_rs::default_provider().secure_random;
+    let mut drawn: Vec<u8> = Vec::with_capacity(len);
+    let mut buf = [0u8; 64];
+    while drawn.len() < len {
+        random
+            .fill(&mut buf)
+            .expect("the system random number generator failed");
+        drawn.extend(buf.iter().filter(|&&b| usize::from(b) < limit));
+    }
+    drawn.truncate(len);
+    drawn
+        .into_iter()
+        .map(move |b| char::from(alphabet[usize::from(b) % alphabet.len()]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_slot_gets_a_fresh_token_of_its_kind() {
+        let template = "id={{aws_key_id}} secret={{aws_secret}} other={{aws_key_id.ses}}";
+        let (body, planted) = plant(template);
+        let (again, _) = plant(template);
+        assert_ne!(body, again);
+
+        let slots: Vec<&str> = planted.iter().map(|(s, _)| s.as_str()).collect();
+        assert_eq!(slots, ["aws_key_id", "aws_secret", "aws_key_id.ses"]);
+        let id = &planted[0].1;
+        assert!(id.starts_with("AKIA") && id.len() == 20);
+        assert!(id[4..].bytes().all(|b| UPPER_BASE32.contains(&b)));
+        assert_eq!(planted[1].1.len(), 40);
+        assert_ne!(planted[0].1, planted[2].1);
+        assert_eq!(
+            body,
+            format!(
+                "id={} secret={} other={}",
+                planted[0].1, planted[1].1, planted[2].1
+            )
+        );
+    }
+
+    #[test]
+    fn a_slot_written_twice_is_the_same_token() {
+        let (body, planted) = plant("{{github_token}} and again {{github_token}}");
+        assert_eq!(planted.len(), 1);
+        let token = &planted[0].1;
+        assert_eq!(body, format!("{token} and again {token}"));
+    }
+
+    #[test]
+    fn tokens_take_the_shape_of_their_kind() {
+        for (kind, parts) in KINDS {
+            let token = mint(kind).unwrap();
+            let len: usize = parts
+                .iter()
+                .map(|part| match part {
+                    Fixed(text) => text.len(),
+                    Random(_, len) => *len,
+                    Checksum => 6,
+                })
+                .sum();
+            assert_eq!(token.len(), len, "{kind}");
+        }
+        let slack = mint("slack_token").unwrap();
+        let fields: Vec<&str> = slack.split('-').collect();
+        assert_eq!(fields[0], "xoxb");
+        assert!(fields[1].bytes().all(|b| b.is_ascii_digit()));
+    }
+
+    #[test]
+    fn checksums_are_the_crc32_in_six_base62_digits() {
+        assert_eq!(checksum("abcdefghijklmnopqrstuvwxyz0123"), "2LolCm");
+        assert_eq!(checksum("000000000000000000000000000000"), "2C8GjS");
+        assert_eq!(checksum(""), "000000");
+    }
+
+    #[test]
+    fn github_and_npm_tokens_end_in_the_checksum_of_their_random_part() {
+        for (kind, prefix) in [("github_token", "ghp_"), ("npm_token", "npm_")] {
+            let token = mint(kind).unwrap();
+            let random = token.strip_prefix(prefix).unwrap();
+            let (body, sum) = random.split_at(30);
+            assert_eq!(sum, checksum(body), "{token}");
+        }
+    }
+
+    #[test]
+    fn text_that_is_not_a_known_slot_is_left_alone() {
+        let template = "{\"a\": {{not_a_kind}}} {{unclosed";
+        assert_eq!(plant(template), (template.to_string(), Vec::new()));
+    }
+}
@@ -1,9 +1,34 @@
-use std::sync::Arc;
+use std::{
+    sync::{Arc, PoisonError, RwLock},
+    time::{SystemTime, UNIX_EPOCH},
+};
 
-use crate::{cache::FileCache, Request, Response};
+use tokio::sync::watch;
+
+use crate::{
+    activitypub, cache::FileCache, canary, honeypot, intel, live, preview, search::Search,
+    tarpit::Tarpit, webmention, Request, Response,
+};
 
 pub struct StaticFileHandler {
-    cache: Arc<FileCache>,
+    /// Swapped wholesale when live reload rebuilds the site; otherwise fixed.
+    cache: RwLock<Arc<FileCache>>,
+    /// Read from the cache, and swapped along with it.
+    search: RwLock<Option<Arc<Search>>>,
+    /// Checked before the cache. Swapped when the server reloads its traps.
+    traps: RwLock<Arc<honeypot::Traps>>,
+    /// Path prefixes only a client with a verified certificate may read.
+    client_auth_paths: Vec<String>,
+    /// Key preview links are signed with. Without one no draft page is served.
+    preview_key: Option<Vec<u8>>,
+    /// Set when serving a site under development; see `live`.
+    live: Option<watch::Receiver<live::Build>>,
+    /// Takes Webmentions when the server was set up to keep them.
+    webmentions: Option<webmention::Receiver>,
+    /// Keeps a record of honeypot hits, when the server was set up to.
+    intel: Option<intel::Recorder>,
+    /// Slows honeypot bait down, when the server was set up to.
+    tarpit: Option<Tarpit>,
 }
 
 /// One line per request: who asked, what they got, what they asked for.
@@ -11,42 +36,243 @@ pub struct StaticFileHandler {
 /// The outcome comes before the path so `honeypot` hits group together under
 /// `sort`, and the path is escaped because it is attacker-controlled and would
 /// otherwise be able to write control characters into the log.
+///
+/// A verified client certificate's subject, when there is one, goes last so the
+/// fields before it line up whether or not the client authenticated.
 fn log(request: &Request, outcome: &str) {
-    println!(
-        "{} {} {}",
-        request.peer,
-        outcome,
-        request.path.escape_default()
-    );
+    match &request.client_subject {
+        Some(subject) => println!(
+            "{} {} {} {}",
+            request.peer,
+            outcome,
+            request.path.escape_default(),
+            subject.escape_default()
+        ),
+        None => println!(
+            "{} {} {}",
+            request.peer,
+            outcome,
+            request.path.escape_default()
+        ),
+    }
 }
 
 impl StaticFileHandler {
     pub fn new(cache: Arc<FileCache>) -> Self {
-        Self { cache }
+        Self {
+            search: RwLock::new(Search::load(&cache).map(Arc::new)),
+            cache: RwLock::new(cache),
+            traps: RwLock::new(Arc::new(honeypot::Traps::default())),
+            client_auth_paths: Vec::new(),
+            preview_key: None,
+            live: None,
+            webmentions: None,
+            intel: None,
+            tarpit: None,
+        }
+    }
+
+    /// Require a verified client certificate for every path under these
+    /// prefixes. A prefix covers itself and everything below it, segment-wise:
+    /// `/drafts` guards `/drafts` and `/drafts/post`, never `/drafts-policy`.
+    pub fn with_client_auth_paths(mut self, prefixes: &[String]) -> Self {
+        self.client_auth_paths = prefixes
+            .iter()
+            .map(|p| p.trim_end_matches('/').to_string())
+            .collect();
+        self
+    }
+
+    /// Serve draft pages to requests carrying a link signed with `key`.
+    pub fn with_preview_key(mut self, key: Option<Vec<u8>>) -> Self {
+        self.preview_key = key;
+        self
+    }
+
+    /// Follow the builds published on `pages`: inject the reload script,
+    /// show build errors, and never let a browser cache anything.
+    pub(crate) fn with_live_reload(mut self, pages: watch::Receiver<live::Build>) -> Self {
+        self.live = Some(pages);
+        self
+    }
+
+    /// Answer `/.well-known/webmention` by queueing with `receiver`.
+    pub(crate) fn with_webmentions(mut self, receiver: webmention::Receiver) -> Self {
+        self.webmentions = Some(receiver);
+        self
+    }
+
+    /// Record every honeypot hit with `recorder`.
+    pub(crate) fn with_honeypot_log(mut self, recorder: intel::Recorder) -> Self {
+        self.intel = Some(recorder);
+        self
+    }
+
+    /// Send honeypot bait through `tarpit`.
+    pub(crate) fn with_tarpit(mut self, tarpit: Tarpit) -> Self {
+        self.tarpit = Some(tarpit);
+        self
+    }
+
+    /// Serve `cache` from now on. Requests already running finish with the
+    /// old one.
+    pub(crate) fn replace_cache(&self, cache: FileCache) {
+        let search = Search::load(&cache).map(Arc::new);
+        *self.cache.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(cache);
+        *self.search.write().unwrap_or_else(PoisonError::into_inner) = search;
+    }
+
+    /// Set `traps` from now on, in place of the built-in ones or the last
+    /// set. Requests already running finish with the old ones.
+    pub(crate) fn replace_traps(&self, traps: honeypot::Traps) {
+        *self.traps.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(traps);
+    }
+
+    fn traps(&self) -> Arc<honeypot::Traps> {
+        Arc::clone(&self.traps.read().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    fn cache(&self) -> Arc<FileCache> {
+        Arc::clone(&self.cache.read().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    fn search(&self) -> Option<Arc<Search>> {
+        self.search
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// The build notifications to stream, if `path` is the live reload
+    /// event stream and live reload is on.
+    pub(crate) fn live_events(&self, path: &str) -> Option<watch::Receiver<live::Build>> {
+        self.live.clone().filter(|_| path == live::EVENTS_PATH)
+    }
+
+    fn requires_client_cert(&self, path: &str) -> bool {
+        self.client_auth_paths.iter().any(|prefix| {
+            path.strip_prefix(prefix.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        })
     }
 
     pub fn handle(&self, request: &Request) -> Response {
+        let Some(live) = &self.live else {
+            return self.respond(request, request.accepts_gzip);
+        };
+
+        let error = live.borrow().error.clone();
+        let response = match error {
+            Some(error) if self.is_page(&request.path) => Response::build_failed(&error),
+            _ => self.respond(request, false),
+        };
+        let response = if response.content_type == "text/html" {
+            Response {
+                body: live::inject_script(response.body),
+                ..response
+            }
+        } else {
+            response
+        };
+        response.with_cache_control("no-store")
+    }
+
+    /// Whether `path` is an HTML page, or would be the 404 page.
+    fn is_page(&self, path: &str) -> bool {
+        self.cache()
+            .get(path)
+            .is_none_or(|c| c.content_type == "text/html")
+    }
+
+    fn respond(&self, request: &Request, accepts_gzip: bool) -> Response {
         // Check the honeypot first: a matching path never reaches the cache.
-        if let Some(bait) = crate::honeypot::bait_for(&request.path) {
+        let traps = self.traps();
+        if let Some(catch) = traps.catch(&request.path) {
             log(request, "honeypot");
-            return Response::honeypot(bait);
+            let (body, canaries) = canary::plant(&catch.bait.body);
+            if let Some(intel) = &self.intel {
+                intel.record(request, &catch, &canaries);
+            }
+            let mut response = Response::honeypot(catch.bait.content_type, body);
+            response.tarpit = self
+                .tarpit
+                .as_ref()
+                .and_then(|t| t.admit(response.body.len()));
+            return response;
+        }
+
+        // Refuse before the lookup, so a client without a certificate cannot
+        // tell a protected page that exists from one that does not.
+        let protected = self.requires_client_cert(&request.path);
+        if protected && request.client_subject.is_none() {
+            log(request, "403");
+            return Response::forbidden();
         }
 
-        if let Some(cached) = self.cache.get(&request.path) {
+        // The search page is a shell with slots; it is only ever served
+        // filled in.
+        if let Some(search) = self.search().filter(|_| Search::handles(&request.path)) {
             log(request, "200");
-            return Self::build_response(cached, request.accepts_gzip, true);
+            return search.respond(request);
+        }
+
+        let cache = self.cache();
+        let answered = match &self.webmentions {
+            Some(receiver) if request.path == webmention::ENDPOINT => {
+                Some(receiver.receive(request, &cache))
+            }
+            _ => activitypub::respond(request, &cache),
+        };
+        if let Some(response) = answered {
+            log(request, &response.status.to_string());
+            return response;
+        }
+        let cached = cache.get(&request.path);
+
+        // A draft's page needs a valid preview link; its assets do not (see
+        // `preview`). A missing path is treated as a page, so an unsigned
+        // request cannot probe which drafts exist. A client that already
+        // proved itself with a certificate for this path needs no link.
+        let draft_scope = preview::scope(&request.path);
+        if let (Some(scope), false) = (&draft_scope, protected) {
+            let is_page = cached.is_none_or(|c| c.content_type == "text/html");
+            if is_page && !self.preview_allows(scope, request.query.as_deref()) {
+                log(request, "403");
+                return Response::forbidden();
+            }
+        }
+
+        if let Some(cached) = cached {
+            log(request, "200");
+            let response = Self::build_response(cached, accepts_gzip, true);
+            // A shared cache must never keep a page only some clients may see.
+            return if protected || draft_scope.is_some() {
+                response.with_cache_control("private, no-store")
+            } else {
+                response
+            };
         }
 
         log(request, "404");
 
-        if let Some(not_found) = self.cache.get_not_found() {
-            return Self::build_response(not_found, request.accepts_gzip, false);
+        if let Some(not_found) = cache.get_not_found() {
+            return Self::build_response(not_found, accepts_gzip, false);
         }
 
         // Fallback if 404.html isn't cached
         Response::not_found(b"Not Found".to_vec(), "text/plain", false)
     }
 
+    fn preview_allows(&self, scope: &str, query: Option<&str>) -> bool {
+        let (Some(key), Some(query)) = (&self.preview_key, query) else {
+            return false;
+        };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        preview::verify(key, scope, query, now)
+    }
+
     fn build_response(
         cached: &crate::cache::CachedFile,
         accepts_gzip: bool,
@@ -94,12 +320,29 @@ mod tests {
 
     fn request(path: &str, accepts_gzip: bool) -> Request {
         Request {
+            method: http::Method::GET,
             path: path.to_string(),
+            raw_path: path.to_string(),
+            version: http::Version::HTTP_11,
+            user_agent: None,
             accepts_gzip,
             peer: "203.0.113.7:54321".parse().unwrap(),
+            client_subject: None,
+            query: None,
+            body: Vec::new(),
         }
     }
 
+    fn guarded(files: &[(&str, &[u8])], prefixes: &[&str]) -> (TempDir, StaticFileHandler) {
+        let (dir, handler) = handler(files);
+        let prefixes: Vec<String> = prefixes.iter().map(|p| p.to_string()).collect();
+        (dir, handler.with_client_auth_paths(&prefixes))
+    }
+
+    fn authenticated(path: &str) -> Request {
+        request(path, false).with_client_subject(Some(Arc::from("CN=erick")))
+    }
+
     #[test]
     fn serves_a_cached_file_with_status_200() {
         let (_dir, handler) = handler(&[("index.html", b"home")]);
@@ -209,4 +452,251 @@ mod tests {
         assert_eq!(res.status, 200);
         assert_eq!(res.body, b"about");
     }
+
+    #[test]
+    fn a_protected_path_without_a_client_certificate_is_forbidden() {
+        let (_dir, handler) = guarded(&[("drafts/post.html", b"secret draft")], &["/drafts"]);
+        for path in ["/drafts", "/drafts/", "/drafts/post", "/drafts/post.html"] {
+            let res = handler.handle(&request(path, false));
+            assert_eq!(res.status, 403, "for {}", path);
+            assert!(!res.body.starts_with(b"secret"));
+        }
+    }
+
+    #[test]
+    fn a_protected_path_is_served_to_a_verified_client() {
+        let (_dir, handler) = guarded(&[("drafts/post.html", b"secret draft")], &["/drafts/"]);
+        let res = handler.handle(&authenticated("/drafts/post"));
+        assert_eq!(res.status, 200);
+        assert_eq!(res.body, b"secret draft");
+    }
+
+    #[test]
+    fn a_protected_page_is_never_left_in_a_shared_cache() {
+        let (_dir, handler) = guarded(&[("drafts/post.html", b"secret draft")], &["/drafts"]);
+        let res = handler.handle(&authenticated("/drafts/post"));
+        assert_eq!(res.cache_control, Some("private, no-store"));
+    }
+
+    #[test]
+    fn a_missing_protected_page_looks_the_same_as_an_existing_one() {
+        // Without a certificate, existence is not disclosed: both are 403.
+        let (_dir, handler) = guarded(
+            &[("drafts/post.html", b"secret"), ("404.html", b"missing")],
+            &["/drafts"],
+        );
+        let real = handler.handle(&request("/drafts/post", false));
+        let absent = handler.handle(&request("/drafts/nope", false));
+        assert_eq!(real.status, absent.status);
+        assert_eq!(real.body, absent.body);
+
+        // With one, a missing page is an ordinary 404.
+        assert_eq!(handler.handle(&authenticated("/drafts/nope")).status, 404);
+    }
+
+    #[test]
+    fn a_prefix_guards_whole_segments_only() {
+        let (_dir, handler) = guarded(
+            &[("drafts-policy.html", b"public"), ("index.html", b"home")],
+            &["/drafts"],
+        );
+        assert_eq!(
+            handler.handle(&request("/drafts-policy", false)).status,
+            200
+        );
+        assert_eq!(handler.handle(&request("/", false)).status, 200);
+    }
+
+    #[test]
+    fn public_pages_need_no_certificate() {
+        let (_dir, handler) = guarded(&[("about.html", b"about")], &["/drafts"]);
+        let res = handler.handle(&request("/about", false));
+        assert_eq!(res.status, 200);
+        assert_eq!(
+            res.cache_control,
+            Some("public, max-age=300, must-revalidate")
+        );
+    }
+
+    #[test]
+    fn the_honeypot_still_runs_before_client_authentication() {
+        let (_dir, handler) = guarded(&[("index.html", b"home")], &["/drafts"]);
+        let res = handler.handle(&request("/drafts/.env", false));
+        assert_eq!(res.status, 200);
+        assert!(String::from_utf8_lossy(&res.body).contains("DATABASE_URL="));
+    }
+
+    #[test]
+    fn the_search_page_is_only_ever_served_filled_in() {
+        let index = site_search::build(&[site_search::Document {
+            path: "/blog/a/".into(),
+            title: "Fuzzing".into(),
+            headings: Vec::new(),
+            body: String::new(),
+            html: "<li>Fuzzing</li>".into(),
+        }]);
+        let (_dir, handler) = handler(&[
+            ("search/index.html", b"<ol>{{results}}</ol>"),
+            ("search/index.bin", &index),
+        ]);
+        for path in ["/search", "/search/", "/search/index.html"] {
+            let req = Request {
+                query: Some("q=fuzzing".into()),
+                ..request(path, false)
+            };
+            let res = handler.handle(&req);
+            assert_eq!(res.status, 200, "{path}");
+            assert_eq!(res.body, b"<ol><li>Fuzzing</li></ol>", "{path}");
+        }
+    }
+
+    const PREVIEW_KEY: &[u8] = b"handler test key";
+
+    fn with_drafts() -> (TempDir, StaticFileHandler) {
+        let (dir, handler) = handler(&[
+            ("drafts/wip/index.html", b"unfinished"),
+            ("drafts/wip/diagram.png", &[0x89, b'P', b'N', b'G']),
+            ("404.html", b"missing"),
+        ]);
+        (dir, handler.with_preview_key(Some(PREVIEW_KEY.to_vec())))
+    }
+
+    fn previewing(link: &str) -> Request {
+        let (path, query) = link.split_once('?').unwrap();
+        Request {
+            query: Some(query.to_string()),
+            ..request(path, false)
+        }
+    }
+
+    fn in_an_hour() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+            + 3600
+    }
+
+    #[test]
+    fn a_signed_preview_link_serves_the_draft() {
+        let (_dir, handler) = with_drafts();
+        let link = preview::link(PREVIEW_KEY, "wip", in_an_hour());
+        let res = handler.handle(&previewing(&link));
+        assert_eq!(res.status, 200);
+        assert_eq!(res.body, b"unfinished");
+        assert_eq!(res.cache_control, Some("private, no-store"));
+    }
+
+    #[test]
+    fn a_draft_without_a_valid_link_is_forbidden() {
+        let (_dir, handler) = with_drafts();
+        assert_eq!(handler.handle(&request("/drafts/wip/", false)).status, 403);
+
+        let expired = preview::link(PREVIEW_KEY, "wip", 1);
+        assert_eq!(handler.handle(&previewing(&expired)).status, 403);
+
+        let forged = preview::link(b"not the key", "wip", in_an_hour());
+        assert_eq!(handler.handle(&previewing(&forged)).status, 403);
+    }
+
+    #[test]
+    fn a_link_for_one_draft_does_not_open_another() {
+        let (_dir, handler) = with_drafts();
+        let link = preview::link(PREVIEW_KEY, "other", in_an_hour());
+        let query = link.split_once('?').unwrap().1;
+        let res = handler.handle(&previewing(&format!("/drafts/wip/?{}", query)));
+        assert_eq!(res.status, 403);
+    }
+
+    #[test]
+    fn drafts_are_never_served_without_a_preview_key() {
+        let (_dir, handler) = handler(&[("drafts/wip/index.html", b"unfinished")]);
+        let link = preview::link(PREVIEW_KEY, "wip", in_an_hour());
+        assert_eq!(handler.handle(&previewing(&link)).status, 403);
+    }
+
+    #[test]
+    fn a_missing_draft_is_indistinguishable_from_an_unsigned_one() {
+        let (_dir, handler) = with_drafts();
+        let absent = handler.handle(&request("/drafts/nope/", false));
+        let unsigned = handler.handle(&request("/drafts/wip/", false));
+        assert_eq!(absent.status, unsigned.status);
+        assert_eq!(absent.body, unsigned.body);
+    }
+
+    #[test]
+    fn a_client_certificate_for_the_drafts_stands_in_for_a_link() {
+        let (_dir, handler) = with_drafts();
+        let handler = handler.with_client_auth_paths(&["/drafts".into()]);
+        let res = handler.handle(&authenticated("/drafts/wip/"));
+        assert_eq!(res.status, 200);
+        assert_eq!(res.body, b"unfinished");
+    }
+
+    #[test]
+    fn a_drafts_assets_need_no_link() {
+        let (_dir, handler) = with_drafts();
+        let res = handler.handle(&request("/drafts/wip/diagram.png", false));
+        assert_eq!(res.status, 200);
+        assert_eq!(res.cache_control, Some("private, no-store"));
+    }
+
+    fn live(files: &[(&str, &[u8])]) -> (TempDir, StaticFileHandler, watch::Sender<live::Build>) {
+        let (dir, handler) = handler(files);
+        let (pages, pages_rx) = watch::channel(live::Build::default());
+        (dir, handler.with_live_reload(pages_rx), pages)
+    }
+
+    #[test]
+    fn live_reload_adds_its_script_to_pages_and_turns_off_caching() {
+        let (_dir, handler, _pages) = live(&[
+            ("index.html", b"<body>home</body>"),
+            ("style.css", b"body{}"),
+        ]);
+        let page = handler.handle(&request("/", true));
+        assert!(!page.gzip);
+        assert_eq!(
+            page.body,
+            live::inject_script(b"<body>home</body>".to_vec())
+        );
+        assert_eq!(page.cache_control, Some("no-store"));
+
+        let css = handler.handle(&request("/style.css", true));
+        assert_eq!(css.body, b"body{}");
+        assert_eq!(css.cache_control, Some("no-store"));
+    }
+
+    #[test]
+    fn a_failed_build_replaces_pages_but_not_assets() {
+        let (_dir, handler, pages) = live(&[
+            ("index.html", b"<body>home</body>"),
+            ("style.css", b"body{}"),
+        ]);
+        pages.send_replace(live::Build {
+            error: Some("post.md:3: unclosed note".into()),
+        });
+
+        for path in ["/", "/missing"] {
+            let res = handler.handle(&request(path, false));
+            assert_eq!(res.status, 500, "{path}");
+            assert!(String::from_utf8_lossy(&res.body).contains("post.md:3: unclosed note"));
+        }
+        assert_eq!(
+            handler.handle(&request("/style.css", false)).body,
+            b"body{}"
+        );
+
+        pages.send_replace(live::Build::default());
+        assert_eq!(handler.handle(&request("/", false)).status, 200);
+    }
+
+    #[test]
+    fn the_event_stream_exists_only_with_live_reload() {
+        let (_dir, plain) = handler(&[]);
+        assert!(plain.live_events(live::EVENTS_PATH).is_none());
+
+        let (_dir, handler, _pages) = live(&[]);
+        assert!(handler.live_events(live::EVENTS_PATH).is_some());
+        assert!(handler.live_events("/").is_none());
+    }
 }
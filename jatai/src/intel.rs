@@ -0,0 +1,590 @@
+//! Honeypot intelligence: every request a trap catches, kept so the attacks
+//! can be studied rather than only answered.
+//!
+//! The handler gives each catch to the [`Recorder`], which queues it for the
+//! [`Writer`] to append to the log. The log is one JSON object per line and
+//! is never rewritten, so it holds the whole history and a crash costs at
+//! most the line being written:
+//!
+//! ```json
+//! {"time":1760000000,"peer":"203.0.113.7","trap":"dotenv","path":"/config/.env","raw_path":"/config/%2Eenv","user_agent":"curl/8.4.0","protocol":"HTTP/1.1","canaries":{"aws_key_id":"AKIA..."}}
+//! ```
+//!
+//! `canaries` are the tokens planted in the bait that hit was sent, so
+//! [`lookup`] can find the request behind one seen in the wild.
+//!
+//! Read back, the hits from one address group into sessions: runs with no
+//! gap longer than [`SESSION_GAP`]. [`report`] summarises the attackers,
+//! traps and payload encodings seen over a window; `portfolio honeypot`
+//! prints it.
+
+use std::{
+    collections::HashMap,
+    fmt::Write as _,
+    fs,
+    io::{self, Write as _},
+    net::IpAddr,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+
+use crate::{honeypot::Catch, request::url_decode, Request};
+
+/// Hits waiting to be written. A flood past this is dropped from the log,
+/// though each hit still gets its line on stdout.
+const QUEUE: usize = 1024;
+/// Longest user agent or raw path kept, in characters. Both are the
+/// attacker's to choose, and the log should not grow at their pace.
+const MAX_FIELD: usize = 1024;
+/// Longest quiet spell, in seconds, within one session.
+pub const SESSION_GAP: u64 = 30 * 60;
+/// How many attackers, traps and encodings a report lists by default.
+pub const TOP: usize = 10;
+
+/// One request a trap caught.
+#[derive(Debug, PartialEq)]
+pub struct Hit {
+    /// Unix seconds.
+    pub time: u64,
+    pub peer: IpAddr,
+    pub trap: String,
+    /// The path as the traps saw it, every layer of encoding peeled off.
+    pub path: String,
+    /// The path as it was sent.
+    pub raw_path: String,
+    pub user_agent: Option<String>,
+    /// The HTTP version, e.g. `HTTP/2.0`.
+    pub protocol: String,
+    /// The `(slot, token)` pairs planted in the bait, by slot.
+    pub canaries: Vec<(String, String)>,
+}
+
+impl Hit {
+    fn to_json(&self) -> String {
+        json!({
+            "time": self.time,
+            "peer": self.peer.to_string(),
+            "trap": self.trap,
+            "path": self.path,
+            "raw_path": self.raw_path,
+            "user_agent": self.user_agent,
+            "protocol": self.protocol,
+            "canaries": self
+                .canaries
+                .iter()
+                .map(|(slot, token)| (slot.clone(), Value::from(token.as_str())))
+                .collect::<serde_json::Map<_, _>>(),
+        })
+        .to_string()
+    }
+
+    fn from_json(line: &str) -> Option<Self> {
+        let value: Value = serde_json::from_str(line).ok()?;
+        let text = |key: &str| value[key].as_str().map(String::from);
+        Some(Self {
+            time: value["time"].as_u64()?,
+            peer: value["peer"].as_str()?.parse().ok()?,
+            trap: text("trap")?,
+            path: text("path")?,
+            raw_path: text("raw_path")?,
+            user_agent: text("user_agent"),
+            protocol: text("protocol")?,
+            // Hits logged before canaries were planted have none.
+            canaries: value["canaries"]
+                .as_object()
+                .into_iter()
+                .flatten()
+                .filter_map(|(slot, token)| Some((slot.clone(), token.as_str()?.to_string())))
+                .collect(),
+        })
+    }
+}
+
+/// The handler's side: turns a catch into a hit and queues it.
+pub(crate) struct Recorder {
+    queue: mpsc::Sender<Hit>,
+}
+
+/// Appends queued hits to the log.
+pub(crate) struct Writer {
+    log: fs::File,
+    queue: mpsc::Receiver<Hit>,
+}
+
+/// Open the log at `path` for appending, creating it and its directory if
+/// need be, and a recorder that queues for it.
+pub(crate) fn open(path: &Path) -> io::Result<(Recorder, Writer)> {
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    let log = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    let (tx, rx) = mpsc::channel(QUEUE);
+    Ok((Recorder { queue: tx }, Writer { log, queue: rx }))
+}
+
+impl Recorder {
+    /// Queue `catch`, sent bait with `canaries` planted in it.
+    pub(crate) fn record(&self, request: &Request, catch: &Catch, canaries: &[(String, String)]) {
+        let capped = |s: &str| s.chars().take(MAX_FIELD).collect::<String>();
+        let hit = Hit {
+            time: now(),
+            peer: request.peer.ip(),
+            trap: catch.trap.to_string(),
+            path: capped(&catch.normalised),
+            raw_path: capped(&request.raw_path),
+            user_agent: request.user_agent.as_deref().map(capped),
+            protocol: format!("{:?}", request.version),
+            canaries: {
+                let mut canaries = canaries.to_vec();
+                canaries.sort();
+                canaries
+            },
+        };
+        let _ = self.queue.try_send(hit);
+    }
+}
+
+impl Writer {
+    /// Write hits as they are queued, until the recorder is dropped.
+    pub(crate) async fn run(mut self) {
+        while let Some(hit) = self.queue.recv().await {
+            // One write per line, so appends never interleave.
+            let line = hit.to_json() + "\n";
+            if let Err(e) = self.log.write_all(line.as_bytes()) {
+                eprintln!("Warning: could not record a honeypot hit: {}", e);
+            }
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Every hit in the log at `path`, oldest first. A line that does not parse,
+/// like one cut short by a crash, is skipped.
+pub fn read(path: &Path) -> io::Result<Vec<Hit>> {
+    let text = fs::read_to_string(path)?;
+    let mut hits: Vec<Hit> = text.lines().filter_map(Hit::from_json).collect();
+    hits.sort_by_key(|hit| hit.time);
+    Ok(hits)
+}
+
+/// One attacker's run of hits.
+pub struct Session<'a> {
+    pub peer: IpAddr,
+    pub first_seen: u64,
+    pub last_seen: u64,
+    pub hits: Vec<&'a Hit>,
+}
+
+impl Session<'_> {
+    /// The traps probed, in order, a trap sprung several times in a row
+    /// listed once.
+    pub fn traps(&self) -> Vec<&str> {
+        let mut traps: Vec<&str> = self.hits.iter().map(|h| h.trap.as_str()).collect();
+        traps.dedup();
+        traps
+    }
+}
+
+/// `hits`, oldest first, grouped into sessions in the order they began.
+pub fn sessions(hits: &[Hit]) -> Vec<Session<'_>> {
+    let mut sessions: Vec<Session> = Vec::new();
+    let mut open: HashMap<IpAddr, usize> = HashMap::new();
+    for hit in hits {
+        match open.get(&hit.peer) {
+            Some(&i) if hit.time.saturating_sub(sessions[i].last_seen) <= SESSION_GAP => {
+                sessions[i].last_seen = hit.time;
+                sessions[i].hits.push(hit);
+            }
+            _ => {
+                open.insert(hit.peer, sessions.len());
+                sessions.push(Session {
+                    peer: hit.peer,
+                    first_seen: hit.time,
+                    last_seen: hit.time,
+                    hits: vec![hit],
+                });
+            }
+        }
+    }
+    sessions
+}
+
+/// How `raw_path` disguises what it asks for: `percent` or `multi-percent`
+/// for one or more layers of escapes, `non-utf8` escapes, `backslash`
+/// separators, `uppercase` letters and `nul` bytes. `plain` if none.
+pub fn encodings(raw_path: &str) -> Vec<&'static str> {
+    let mut layers = 0;
+    let mut decoded = raw_path.to_string();
+    loop {
+        let next = url_decode(&decoded);
+        if next == decoded {
+            break;
+        }
+        layers += 1;
+        decoded = next;
+    }
+
+    let mut found = Vec::new();
+    match layers {
+        0 => {}
+        1 => found.push("percent"),
+        _ => found.push("multi-percent"),
+    }
+    // Escapes left after decoding are ones that are not UTF-8, like the
+    // overlong `%c0%ae` for a dot.
+    let bytes = decoded.as_bytes();
+    if bytes
+        .windows(3)
+        .any(|w| w[0] == b'%' && w[1].is_ascii_hexdigit() && w[2].is_ascii_hexdigit())
+    {
+        found.push("non-utf8");
+    }
+    if decoded.contains('\\') {
+        found.push("backslash");
+    }
+    if decoded.chars().any(|c| c.is_ascii_uppercase()) {
+        found.push("uppercase");
+    }
+    if decoded.contains('\0') {
+        found.push("nul");
+    }
+    if found.is_empty() {
+        found.push("plain");
+    }
+    found
+}
+
+/// The hits from `since` (Unix seconds) on, summarised: the `top` attackers,
+/// with their sessions and what they tried last; the `top` traps; and the
+/// `top` encodings payloads were written in.
+pub fn report(hits: &[Hit], since: u64, top: usize) -> String {
+    let start = hits.partition_point(|hit| hit.time < since);
+    let hits = &hits[start..];
+    let mut out = String::new();
+    if hits.is_empty() {
+        let _ = writeln!(out, "No honeypot hits since {}", utc(since));
+        return out;
+    }
+
+    let sessions = sessions(hits);
+    let mut attackers: Vec<(IpAddr, Vec<&Session>)> = Vec::new();
+    for session in &sessions {
+        match attackers.iter_mut().find(|(peer, _)| *peer == session.peer) {
+            Some((_, theirs)) => theirs.push(session),
+            None => attackers.push((session.peer, vec![session])),
+        }
+    }
+    let hit_count = |theirs: &[&Session]| theirs.iter().map(|s| s.hits.len()).sum::<usize>();
+    attackers.sort_by(|(a, a_sessions), (b, b_sessions)| {
+        hit_count(b_sessions)
+            .cmp(&hit_count(a_sessions))
+            .then(a.cmp(b))
+    });
+
+    let _ = writeln!(
+        out,
+        "{} honeypot hits from {} addresses in {} sessions since {}",
+        hits.len(),
+        attackers.len(),
+        sessions.len(),
+        utc(since)
+    );
+
+    let _ = writeln!(out, "\nTop attackers");
+    let _ = writeln!(
+        out,
+        "{:>8}  {:>8}  {:<16}  {:<16}  address",
+        "hits", "sessions", "first seen", "last seen"
+    );
+    for (peer, theirs) in attackers.iter().take(top) {
+        let latest = theirs.last().expect("every attacker has a session");
+        let _ = writeln!(
+            out,
+            "{:>8}  {:>8}  {:<16}  {:<16}  {}",
+            hit_count(theirs),
+            theirs.len(),
+            utc(theirs[0].first_seen),
+            utc(latest.last_seen),
+            peer
+        );
+        let _ = writeln!(out, "{:38}last session: {}", "", latest.traps().join(", "));
+        if let Some(agent) = latest
+            .hits
+            .iter()
+            .rev()
+            .find_map(|h| h.user_agent.as_deref())
+        {
+            let _ = writeln!(out, "{:38}user agent: {}", "", agent.escape_default());
+        }
+    }
+
+    let mut traps: HashMap<&str, (usize, Vec<IpAddr>)> = HashMap::new();
+    for hit in hits {
+        let (count, peers) = traps.entry(hit.trap.as_str()).or_default();
+        *count += 1;
+        if !peers.contains(&hit.peer) {
+            peers.push(hit.peer);
+        }
+    }
+    let mut traps: Vec<_> = traps.into_iter().collect();
+    traps.sort_by(|(a, (a_hits, _)), (b, (b_hits, _))| b_hits.cmp(a_hits).then(a.cmp(b)));
+    let _ = writeln!(out, "\nTop traps");
+    let _ = writeln!(out, "{:>8}  {:>9}  trap", "hits", "addresses");
+    for (trap, (count, peers)) in traps.iter().take(top) {
+        let _ = writeln!(out, "{:>8}  {:>9}  {}", count, peers.len(), trap);
+    }
+
+    let mut encoded: HashMap<&str, usize> = HashMap::new();
+    for hit in hits {
+        for encoding in encodings(&hit.raw_path) {
+            *encoded.entry(encoding).or_default() += 1;
+        }
+    }
+    let mut encoded: Vec<_> = encoded.into_iter().collect();
+    encoded.sort_by(|(a, a_hits), (b, b_hits)| b_hits.cmp(a_hits).then(a.cmp(b)));
+    let _ = writeln!(out, "\nPayload encodings");
+    let _ = writeln!(out, "{:>8}  encoding", "hits");
+    for (encoding, count) in encoded.iter().take(top) {
+        let _ = writeln!(out, "{:>8}  {}", count, encoding);
+    }
+    out
+}
+
+/// Which request in `hits` was sent `token`, and when and how it was asked
+/// for: the scan that took a canary later seen in use.
+pub fn lookup(hits: &[Hit], token: &str) -> String {
+    let mut out = String::new();
+    let found = hits.iter().find_map(|hit| {
+        let (slot, _) = hit.canaries.iter().find(|(_, t)| t == token)?;
+        Some((hit, slot))
+    });
+    let Some((hit, slot)) = found else {
+        let _ = writeln!(out, "No request in the log received {}", token);
+        return out;
+    };
+    let _ = writeln!(out, "{} was planted as {} in bait for:", token, slot);
+    let _ = writeln!(out, "  time        {} UTC", utc(hit.time));
+    let _ = writeln!(out, "  address     {}", hit.peer);
+    let _ = writeln!(out, "  trap        {}", hit.trap);
+    let _ = writeln!(out, "  path        {}", hit.raw_path.escape_default());
+    let _ = writeln!(out, "  protocol    {}", hit.protocol);
+    if let Some(agent) = &hit.user_agent {
+        let _ = writeln!(out, "  user agent  {}", agent.escape_default());
+    }
+    out
+}
+
+/// `secs` as a UTC date and time to the minute.
+fn utc(secs: u64) -> String {
+    // Days to a civil date, after Howard Hinnant's `civil_from_days`.
+    let z = secs / 86_400 + 719_468;
+    let era = z / 146_097;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    let minutes = secs % 86_400 / 60;
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        year,
+        month,
+        day,
+        minutes / 60,
+        minutes % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hit(time: u64, peer: &str, trap: &str, raw_path: &str) -> Hit {
+        Hit {
+            time,
+            peer: peer.parse().unwrap(),
+            trap: trap.to_string(),
+            path: crate::request::fully_decode(raw_path).to_lowercase(),
+            raw_path: raw_path.to_string(),
+            user_agent: Some("curl/8.4.0".to_string()),
+            protocol: "HTTP/1.1".to_string(),
+            canaries: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn a_hit_reads_back_as_it_was_written() {
+        let written = hit(1_760_000_000, "2001:db8::1", "dotenv", "/%2Eenv");
+        assert_eq!(Hit::from_json(&written.to_json()), Some(written));
+        let anonymous = Hit {
+            user_agent: None,
+            ..hit(1, "203.0.113.7", "passwd", "/etc/passwd")
+        };
+        assert_eq!(Hit::from_json(&anonymous.to_json()), Some(anonymous));
+        let planted = Hit {
+            canaries: vec![
+                ("aws_key_id".to_string(), "AKIAEXAMPLE".to_string()),
+                ("aws_secret".to_string(), "c2VjcmV0".to_string()),
+            ],
+            ..hit(2, "203.0.113.7", "aws-credentials", "/.aws/credentials")
+        };
+        assert_eq!(Hit::from_json(&planted.to_json()), Some(planted));
+    }
+
+    #[test]
+    fn a_hit_from_before_canaries_reads_with_none() {
+        let line = r#"{"time":1,"peer":"203.0.113.7","trap":"dotenv","path":"/.env","raw_path":"/.env","user_agent":null,"protocol":"HTTP/1.1"}"#;
+        assert_eq!(Hit::from_json(line).unwrap().canaries, []);
+    }
+
+    #[tokio::test]
+    async fn the_log_is_appended_to_and_a_torn_line_is_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("intel/honeypot.jsonl");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(
+            &path,
+            hit(5, "203.0.113.7", "flag", "/flag").to_json() + "\n",
+        )
+        .unwrap();
+
+        let (recorder, writer) = open(&path).unwrap();
+        let request = Request::parse_h1(
+            "GET /%2e%2e/ETC/passwd HTTP/1.1\r\nUser-Agent: zgrab/0.x\r\n\r\n",
+            "198.51.100.2:40000".parse().unwrap(),
+        )
+        .unwrap();
+        let traps = crate::honeypot::Traps::default();
+        let catch = traps.catch(&request.path).unwrap();
+        let canaries = [("aws_key_id".to_string(), "AKIAEXAMPLE".to_string())];
+        recorder.record(&request, &catch, &canaries);
+        drop(recorder);
+        writer.run().await;
+
+        let mut log = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        log.write_all(b"{\"time\":9,\"pe").unwrap();
+
+        let hits = read(&path).unwrap();
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].trap, "flag");
+        let caught = &hits[1];
+        assert_eq!(caught.peer.to_string(), "198.51.100.2");
+        assert_eq!(caught.trap, "passwd");
+        assert_eq!(caught.path, "/../etc/passwd");
+        assert_eq!(caught.raw_path, "/%2e%2e/ETC/passwd");
+        assert_eq!(caught.user_agent.as_deref(), Some("zgrab/0.x"));
+        assert_eq!(caught.protocol, "HTTP/1.1");
+        assert_eq!(caught.canaries, canaries);
+    }
+
+    #[test]
+    fn a_quiet_spell_ends_a_session() {
+        let hits = [
+            hit(100, "203.0.113.7", "dotenv", "/.env"),
+            hit(110, "198.51.100.2", "wp-login", "/wp-login.php"),
+            hit(120, "203.0.113.7", "dotenv", "/config/.env"),
+            hit(130, "203.0.113.7", "git-config", "/.git/config"),
+            hit(
+                130 + SESSION_GAP + 1,
+                "203.0.113.7",
+                "passwd",
+                "/etc/passwd",
+            ),
+        ];
+        let sessions = sessions(&hits);
+        assert_eq!(sessions.len(), 3);
+        assert_eq!(sessions[0].peer.to_string(), "203.0.113.7");
+        assert_eq!((sessions[0].first_seen, sessions[0].last_seen), (100, 130));
+        assert_eq!(sessions[0].traps(), ["dotenv", "git-config"]);
+        assert_eq!(sessions[1].traps(), ["wp-login"]);
+        assert_eq!(sessions[2].traps(), ["passwd"]);
+    }
+
+    #[test]
+    fn encodings_name_each_disguise() {
+        assert_eq!(encodings("/.env"), ["plain"]);
+        assert_eq!(encodings("/%2e%2e/etc/passwd"), ["percent"]);
+        assert_eq!(encodings("/%252e%252e/etc/passwd"), ["multi-percent"]);
+        assert_eq!(encodings("/..\\..\\etc\\passwd"), ["backslash"]);
+        assert_eq!(
+            encodings("/%2e%2e%5cETC/passwd"),
+            ["percent", "backslash", "uppercase"]
+        );
+        assert_eq!(encodings("/index.php%00.html"), ["percent", "nul"]);
+        assert_eq!(encodings("/%c0%ae%c0%ae/etc/passwd"), ["non-utf8"]);
+    }
+
+    #[test]
+    fn a_report_covers_only_its_window() {
+        let hits = [
+            hit(10, "192.0.2.1", "flag", "/flag"),
+            hit(1_760_000_000, "203.0.113.7", "dotenv", "/.env"),
+            hit(1_760_000_060, "203.0.113.7", "git-config", "/%2Egit/config"),
+            hit(1_760_000_120, "198.51.100.2", "dotenv", "/.env"),
+        ];
+        let report = report(&hits, 1_759_999_999, TOP);
+        assert!(report.starts_with(
+            "3 honeypot hits from 2 addresses in 2 sessions since 2025-10-09 08:53\n"
+        ));
+        assert!(!report.contains("192.0.2.1"));
+        assert!(!report.contains("flag"));
+
+        let attackers = &report[report.find("Top attackers").unwrap()..];
+        assert!(attackers.find("203.0.113.7").unwrap() < attackers.find("198.51.100.2").unwrap());
+        assert!(attackers.contains("2025-10-09 08:53  2025-10-09 08:54  203.0.113.7"));
+        assert!(attackers.contains("last session: dotenv, git-config"));
+        assert!(attackers.contains("user agent: curl/8.4.0"));
+
+        let traps = &report[report.find("Top traps").unwrap()..];
+        assert!(traps.contains("       2          2  dotenv\n"));
+        let encodings = &report[report.find("Payload encodings").unwrap()..];
+        assert!(encodings.contains("       2  plain\n       1  percent\n"));
+    }
+
+    #[test]
+    fn a_report_with_nothing_in_its_window_says_so() {
+        let hits = [hit(10, "192.0.2.1", "flag", "/flag")];
+        assert_eq!(
+            report(&hits, 86_400, TOP),
+            "No honeypot hits since 1970-01-02 00:00\n"
+        );
+    }
+
+    #[test]
+    fn a_canary_is_traced_to_the_request_it_was_sent_to() {
+        let hits = [
+            hit(1_760_000_000, "198.51.100.2", "dotenv", "/.env"),
+            Hit {
+                canaries: vec![
+                    ("aws_key_id".to_string(), "AKIAFIRST".to_string()),
+                    ("github_token".to_string(), "ghp_taken".to_string()),
+                ],
+                ..hit(1_760_000_060, "203.0.113.7", "dotenv", "/%2Eenv")
+            },
+        ];
+        let found = lookup(&hits, "ghp_taken");
+        assert!(found.starts_with("ghp_taken was planted as github_token in bait for:\n"));
+        assert!(found.contains("  time        2025-10-09 08:54 UTC\n"));
+        assert!(found.contains("  address     203.0.113.7\n"));
+        assert!(found.contains("  path        /%2Eenv\n"));
+        assert!(found.contains("  user agent  curl/8.4.0\n"));
+
+        assert_eq!(
+            lookup(&hits, "AKIANEVERSENT"),
+            "No request in the log received AKIANEVERSENT\n"
+        );
+    }
+}
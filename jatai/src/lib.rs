@@ -1,12 +1,21 @@
+mod activitypub;
 mod cache;
+mod canary;
 mod handler;
 mod honeypot;
+pub mod intel;
+mod live;
+pub mod preview;
 mod request;
 mod response;
+mod search;
 mod server;
+mod tarpit;
 mod tls;
+mod webmention;
 
 pub use cache::FileCache;
+pub use live::LiveReload;
 pub use request::Request;
 pub use response::Response;
 pub use server::Config;
@@ -0,0 +1,122 @@
+//! Live reload, for serving a site while it is being written.
+//!
+//! A tool that rebuilds the static directory holds a [`LiveReload`] and tells
+//! it how each build went. The server then reloads its files from disk and
+//! every open page reloads itself: each HTML response carries a small script
+//! that listens on [`EVENTS_PATH`], a server-sent event stream that fires once
+//! per build. While the last build has failed, pages show its error instead.
+//!
+//! This is a development aid. With it, nothing is compressed or cached, so
+//! keep it off the public listeners.
+
+use std::sync::Arc;
+
+use tokio::sync::watch;
+
+/// Where open pages listen for rebuilds.
+pub(crate) const EVENTS_PATH: &str = "/__jatai/live";
+
+/// Appended to every HTML page; reloads it when the server says so.
+/// `EventSource` reconnects on its own, so a restarted server picks its
+/// pages back up too.
+const CLIENT_SCRIPT: &str =
+    "<script>new EventSource(\"/__jatai/live\").onmessage = () => location.reload();</script>";
+
+/// The outcome of the latest build.
+#[derive(Clone, Default)]
+pub(crate) struct Build {
+    pub(crate) error: Option<Arc<str>>,
+}
+
+/// Tells a running server that its static directory was rebuilt.
+#[derive(Clone)]
+pub struct LiveReload {
+    builds: Arc<watch::Sender<Build>>,
+}
+
+impl LiveReload {
+    pub fn new() -> Self {
+        Self {
+            builds: Arc::new(watch::Sender::new(Build::default())),
+        }
+    }
+
+    /// The build succeeded: reload the files and refresh every open page.
+    pub fn rebuilt(&self) {
+        self.builds.send_replace(Build::default());
+    }
+
+    /// The build failed: every page shows `error` until the next success.
+    /// The files of the last good build are still served for everything else.
+    pub fn failed(&self, error: impl Into<String>) {
+        self.builds.send_replace(Build {
+            error: Some(error.into().into()),
+        });
+    }
+
+    pub(crate) fn subscribe(&self) -> watch::Receiver<Build> {
+        self.builds.subscribe()
+    }
+}
+
+impl Default for LiveReload {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `html` with the reload script added at the end of its body.
+pub(crate) fn inject_script(mut html: Vec<u8>) -> Vec<u8> {
+    let at = html
+        .windows(7)
+        .rposition(|w| w.eq_ignore_ascii_case(b"</body>"))
+        .unwrap_or(html.len());
+    html.splice(at..at, CLIENT_SCRIPT.bytes());
+    html
+}
+
+/// The page shown in place of every page while the build is broken.
+pub(crate) fn error_page(error: &str) -> Vec<u8> {
+    let mut escaped = String::with_capacity(error.len());
+    for c in error.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            c => escaped.push(c),
+        }
+    }
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head><meta charset=\"UTF-8\"><title>Build failed</title></head>\n\
+         <body style=\"font-family: monospace; padding: 1rem 2rem\">\n\
+         <h1 style=\"color: #c0392b\">Build failed</h1>\n<pre style=\"white-space: pre-wrap\">{escaped}</pre>\n</body>\n</html>\n"
+    )
+    .into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_script_goes_before_the_closing_body_tag() {
+        let html = inject_script(b"<body><p>hi</p></BODY></html>".to_vec());
+        assert_eq!(
+            String::from_utf8(html).unwrap(),
+            format!("<body><p>hi</p>{CLIENT_SCRIPT}</BODY></html>")
+        );
+        assert!(CLIENT_SCRIPT.contains(EVENTS_PATH));
+    }
+
+    #[test]
+    fn a_page_without_a_body_tag_gets_the_script_at_the_end() {
+        let html = inject_script(b"<h1>bare</h1>".to_vec());
+        assert!(html.ends_with(CLIENT_SCRIPT.as_bytes()));
+    }
+
+    #[test]
+    fn the_error_page_escapes_the_error() {
+        let page = String::from_utf8(error_page("post.md:3: <figure> needs src & alt")).unwrap();
+        assert!(page.contains("post.md:3: &lt;figure&gt; needs src &amp; alt"));
+    }
+}
@@ -1,26 +1,71 @@
-use std::net::SocketAddr;
+use std::{net::SocketAddr, sync::Arc};
+
+use http::{Method, Version};
 
 pub struct Request {
+    /// Everything but the Webmention receiver answers every method as GET.
+    pub method: Method,
     pub path: String,
+    /// The path as sent, before any decoding. Only the honeypot log keeps
+    /// it, to show how an attack was written.
+    pub raw_path: String,
+    pub version: Version,
+    pub user_agent: Option<String>,
     pub accepts_gzip: bool,
     /// Where the request came from. Carried on the request rather than read
     /// back off the socket so every protocol reports the same thing, and so
     /// the value survives into the log line and any future rate limiting.
     pub peer: SocketAddr,
+    /// Subject of the client certificate presented on this connection. Only
+    /// ever set once the TLS layer has verified the certificate against the
+    /// client CA, so its presence alone is proof of identity.
+    pub client_subject: Option<Arc<str>>,
+    /// The raw query string, without the `?`. Kept out of `path` so lookups
+    /// and the honeypot see the same thing on every protocol; only preview
+    /// links and search read it.
+    pub query: Option<String>,
+    /// The request body. Only read for a POST, and only as far as
+    /// `server::MAX_BODY`; empty otherwise.
+    pub body: Vec<u8>,
 }
 
 impl Request {
     pub fn parse_h1(buf: &str, peer: SocketAddr) -> Option<Self> {
         let mut lines = buf.lines();
-        let path = url_decode(lines.next()?.split_whitespace().nth(1)?);
-        let accepts_gzip = lines.any(|line| {
-            line.to_lowercase().starts_with("accept-encoding:")
-                && line.to_lowercase().contains("gzip")
-        });
+        let mut request_line = lines.next()?.split_whitespace();
+        let method = Method::from_bytes(request_line.next()?.as_bytes()).ok()?;
+        let target = request_line.next()?;
+        let version = match request_line.next() {
+            Some("HTTP/1.0") => Version::HTTP_10,
+            _ => Version::HTTP_11,
+        };
+        let (raw_path, query) = match target.split_once('?') {
+            Some((path, query)) => (path, Some(query.to_string())),
+            None => (target, None),
+        };
+        let mut accepts_gzip = false;
+        let mut user_agent = None;
+        for line in lines {
+            let Some((name, value)) = line.split_once(':') else {
+                continue;
+            };
+            if name.eq_ignore_ascii_case("accept-encoding") {
+                accepts_gzip |= value.to_lowercase().contains("gzip");
+            } else if name.eq_ignore_ascii_case("user-agent") {
+                user_agent = Some(value.trim().to_string());
+            }
+        }
         Some(Self {
-            path,
+            method,
+            path: url_decode(raw_path),
+            raw_path: raw_path.to_string(),
+            version,
+            user_agent,
             accepts_gzip,
             peer,
+            client_subject: None,
+            query,
+            body: Vec::new(),
         })
     }
 
@@ -32,12 +77,51 @@ impl Request {
             .and_then(|v| v.to_str().ok())
             .map(|v| v.to_lowercase().contains("gzip"))
             .unwrap_or(false);
+        let user_agent = req
+            .headers()
+            .get("user-agent")
+            .map(|v| String::from_utf8_lossy(v.as_bytes()).into_owned());
         Self {
+            method: req.method().clone(),
             path,
+            raw_path: req.uri().path().to_string(),
+            version: req.version(),
+            user_agent,
             accepts_gzip,
             peer,
+            client_subject: None,
+            query: req.uri().query().map(String::from),
+            body: Vec::new(),
         }
     }
+
+    pub fn with_client_subject(mut self, subject: Option<Arc<str>>) -> Self {
+        self.client_subject = subject;
+        self
+    }
+
+    pub fn with_body(mut self, body: Vec<u8>) -> Self {
+        self.body = body;
+        self
+    }
+
+    /// The first value of the query parameter `name`, decoded the way a
+    /// form submits it: `+` for a space, then percent-escapes.
+    pub fn query_param(&self, name: &str) -> Option<String> {
+        form_value(self.query.as_deref()?, name)
+    }
+
+    /// The first value of `name` in a form-encoded body.
+    pub fn form_param(&self, name: &str) -> Option<String> {
+        form_value(std::str::from_utf8(&self.body).ok()?, name)
+    }
+}
+
+fn form_value(encoded: &str, name: &str) -> Option<String> {
+    encoded.split('&').find_map(|pair| {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        (key == name).then(|| url_decode(&value.replace('+', " ")))
+    })
 }
 
 /// Percent-decode until the result stops changing.
@@ -59,7 +143,7 @@ pub(crate) fn fully_decode(input: &str) -> String {
     }
 }
 
-fn url_decode(input: &str) -> String {
+pub(crate) fn url_decode(input: &str) -> String {
     let bytes = input.as_bytes();
     let mut result = Vec::with_capacity(bytes.len());
     let mut i = 0;
@@ -106,6 +190,23 @@ mod tests {
         assert!(!req.accepts_gzip);
     }
 
+    #[test]
+    fn query_parameters_decode_like_a_submitted_form() {
+        let req = h1("GET /search?x=1&q=onion+message%21&q=second HTTP/1.1\r\n\r\n").unwrap();
+        assert_eq!(req.query_param("q").as_deref(), Some("onion message!"));
+        assert_eq!(req.query_param("x").as_deref(), Some("1"));
+        assert_eq!(req.query_param("y"), None);
+    }
+
+    #[test]
+    fn splits_the_query_off_the_path() {
+        let req = h1("GET /drafts/post/?expires=1&sig=ab HTTP/1.1\r\n\r\n").unwrap();
+        assert_eq!(req.path, "/drafts/post/");
+        assert_eq!(req.query.as_deref(), Some("expires=1&sig=ab"));
+
+        assert_eq!(h1("GET /about HTTP/1.1\r\n\r\n").unwrap().query, None);
+    }
+
     #[test]
     fn parses_root_path() {
         assert_eq!(h1("GET / HTTP/1.1\r\n\r\n").unwrap().path, "/");
@@ -114,7 +215,20 @@ mod tests {
     #[test]
     fn accepts_any_method() {
         assert_eq!(h1("POST /x HTTP/1.1\r\n\r\n").unwrap().path, "/x");
-        assert_eq!(h1("HEAD /x HTTP/1.1\r\n\r\n").unwrap().path, "/x");
+        assert_eq!(h1("HEAD /x HTTP/1.1\r\n\r\n").unwrap().method, Method::HEAD);
+    }
+
+    #[test]
+    fn form_bodies_decode_like_queries() {
+        let req = h1("POST /x HTTP/1.1\r\n\r\n")
+            .unwrap()
+            .with_body(b"source=https%3A%2F%2Fa.example%2Fp&target=x+y".to_vec());
+        assert_eq!(
+            req.form_param("source").as_deref(),
+            Some("https://a.example/p")
+        );
+        assert_eq!(req.form_param("target").as_deref(), Some("x y"));
+        assert_eq!(req.query_param("source"), None);
     }
 
     #[test]
@@ -218,6 +332,7 @@ mod tests {
             .unwrap();
         let parsed = Request::from_h2(&req, peer());
         assert_eq!(parsed.path, "/a b");
+        assert_eq!(parsed.query.as_deref(), Some("q=1"));
         assert!(!parsed.accepts_gzip);
     }
 
@@ -231,6 +346,29 @@ mod tests {
         assert!(Request::from_h2(&req, peer()).accepts_gzip);
     }
 
+    #[test]
+    fn keeps_the_raw_path_version_and_user_agent() {
+        let req = h1("GET /%252e%252e/x?q HTTP/1.0\r\nuser-agent:  curl/8.4.0 \r\n\r\n").unwrap();
+        assert_eq!(req.raw_path, "/%252e%252e/x");
+        assert_eq!(req.version, Version::HTTP_10);
+        assert_eq!(req.user_agent.as_deref(), Some("curl/8.4.0"));
+
+        let req = h1("GET / HTTP/1.1\r\n\r\n").unwrap();
+        assert_eq!(req.version, Version::HTTP_11);
+        assert_eq!(req.user_agent, None);
+
+        let req = http::Request::builder()
+            .uri("/a%20b")
+            .version(Version::HTTP_2)
+            .header("user-agent", "Mozilla/5.0")
+            .body(())
+            .unwrap();
+        let parsed = Request::from_h2(&req, peer());
+        assert_eq!(parsed.raw_path, "/a%20b");
+        assert_eq!(parsed.version, Version::HTTP_2);
+        assert_eq!(parsed.user_agent.as_deref(), Some("Mozilla/5.0"));
+    }
+
     #[test]
     fn h2_request_without_accept_encoding_rejects_gzip() {
         let req = http::Request::builder().uri("/").body(()).unwrap();
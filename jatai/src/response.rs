@@ -4,6 +4,8 @@ pub struct Response {
     pub body: Vec<u8>,
     pub gzip: bool,
     pub cache_control: Option<&'static str>,
+    /// Set on honeypot bait the tarpit took: send the body at this pace.
+    pub(crate) tarpit: Option<crate::tarpit::Trickle>,
 }
 
 impl Response {
@@ -14,6 +16,7 @@ impl Response {
             body,
             gzip,
             cache_control: None,
+            tarpit: None,
         }
     }
 
@@ -24,20 +27,60 @@ impl Response {
             body,
             gzip,
             cache_control: None,
+            tarpit: None,
         }
     }
 
-    /// Answer an attack with bait from the honeypot.
+    /// Refuse a path that needs a client certificate the request did not
+    /// present. Plain text, so nothing about the protected page leaks.
+    pub fn forbidden() -> Self {
+        Self {
+            status: 403,
+            content_type: "text/plain",
+            body: b"Forbidden".to_vec(),
+            gzip: false,
+            cache_control: Some("no-store"),
+            tarpit: None,
+        }
+    }
+
+    /// A short answer from an endpoint that takes input, such as the
+    /// Webmention receiver: 202 once accepted, otherwise why not.
+    pub fn plain(status: u16, message: &'static str) -> Self {
+        Self {
+            status,
+            content_type: "text/plain",
+            body: message.as_bytes().to_vec(),
+            gzip: false,
+            cache_control: Some("no-store"),
+            tarpit: None,
+        }
+    }
+
+    /// Answer an attack with bait from the honeypot, its canaries planted.
     ///
     /// Deliberately 200, not 403: a refusal tells a scanner the path is real
     /// and guarded, which is exactly the signal worth denying it.
-    pub fn honeypot(bait: crate::honeypot::Bait) -> Self {
+    pub fn honeypot(content_type: &'static str, body: String) -> Self {
         Self {
             status: 200,
-            content_type: bait.content_type,
-            body: bait.body.as_bytes().to_vec(),
+            content_type,
+            body: body.into_bytes(),
+            gzip: false,
+            cache_control: None,
+            tarpit: None,
+        }
+    }
+
+    /// Stand in for a page while live reload reports a broken build.
+    pub fn build_failed(error: &str) -> Self {
+        Self {
+            status: 500,
+            content_type: "text/html",
+            body: crate::live::error_page(error),
             gzip: false,
             cache_control: None,
+            tarpit: None,
         }
     }
 
@@ -50,7 +93,6 @@ impl Response {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::honeypot::Bait;
 
     #[test]
     fn ok_response_carries_status_and_metadata() {
@@ -69,6 +111,15 @@ mod tests {
         assert!(!res.gzip);
     }
 
+    #[test]
+    fn forbidden_response_is_403_and_never_cached() {
+        let res = Response::forbidden();
+        assert_eq!(res.status, 403);
+        assert_eq!(res.content_type, "text/plain");
+        assert_eq!(res.body, b"Forbidden");
+        assert_eq!(res.cache_control, Some("no-store"));
+    }
+
     #[test]
     fn with_cache_control_sets_the_header_value() {
         let res = Response::ok(b"x".to_vec(), "text/css", false).with_cache_control("no-store");
@@ -79,10 +130,10 @@ mod tests {
     fn honeypot_answers_200_so_the_attack_looks_successful() {
         // A 403 would tell a scanner the path exists and is guarded. Returning
         // 200 with plausible bait keeps it chasing a dead end.
-        let res = Response::honeypot(Bait {
-            body: "root:x:0:0:root:/root:/bin/bash\n",
-            content_type: "text/plain",
-        });
+        let res = Response::honeypot(
+            "text/plain",
+            "root:x:0:0:root:/root:/bin/bash\n".to_string(),
+        );
 
         assert_eq!(res.status, 200);
         assert_eq!(res.content_type, "text/plain");
@@ -92,10 +143,7 @@ mod tests {
 
     #[test]
     fn honeypot_serves_the_content_type_of_the_file_it_fakes() {
-        let res = Response::honeypot(Bait {
-            body: "<html></html>",
-            content_type: "text/html",
-        });
+        let res = Response::honeypot("text/html", "<html></html>".to_string());
         assert_eq!(res.content_type, "text/html");
     }
 
@@ -103,10 +151,7 @@ mod tests {
     fn honeypot_bait_is_never_compressed() {
         // Bait is small and sent as-is; compressing it would only add a header
         // that the fake file's own server would not have sent.
-        let res = Response::honeypot(Bait {
-            body: "x",
-            content_type: "text/plain",
-        });
+        let res = Response::honeypot("text/plain", "x".to_string());
         assert!(!res.gzip);
     }
 }
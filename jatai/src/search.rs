@@ -0,0 +1,136 @@
+//! Site search, answered on the server so it works without JavaScript.
+//!
+//! blog-gen writes the index to `/search/index.bin` and the results page,
+//! with the site's chrome and slots for the query and its hits, to
+//! `/search/index.html` (see the `site-search` crate). A request for the page
+//! runs its `q` parameter against the index and gets the page back filled in.
+
+use site_search::Index;
+
+use crate::{cache::FileCache, Request};
+
+/// Paths that reach the page; all of them are answered with results.
+const PAGE_PATHS: [&str; 3] = ["/search", "/search/", "/search/index.html"];
+const INDEX_PATH: &str = "/search/index.bin";
+const PAGE_PATH: &str = "/search/index.html";
+
+/// Most hits shown for one query.
+const LIMIT: usize = 20;
+
+pub(crate) struct Search {
+    index: Index,
+    page: String,
+    cache_control: Option<&'static str>,
+}
+
+impl Search {
+    /// The index and page in `cache`, or `None` when the site has no search.
+    /// An index that fails to parse is reported and leaves search off.
+    pub(crate) fn load(cache: &FileCache) -> Option<Self> {
+        let (index, page) = (cache.get(INDEX_PATH)?, cache.get(PAGE_PATH)?);
+        let index = Index::parse(&index.body)
+            .map_err(|e| eprintln!("Warning: {}: {}", INDEX_PATH, e))
+            .ok()?;
+        Some(Self {
+            index,
+            page: String::from_utf8_lossy(&page.body).into_owned(),
+            cache_control: page.cache_control,
+        })
+    }
+
+    pub(crate) fn handles(path: &str) -> bool {
+        PAGE_PATHS.contains(&path)
+    }
+
+    /// The results page for `request`'s query; the bare form without one.
+    pub(crate) fn respond(&self, request: &Request) -> crate::Response {
+        let query = request.query_param("q").unwrap_or_default();
+        let hits = self.index.search(&query, LIMIT);
+        let body = site_search::fill(&self.page, &query, &hits).into_bytes();
+        let response = crate::Response::ok(body, "text/html", false);
+        match self.cache_control {
+            Some(cc) => response.with_cache_control(cc),
+            None => response,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use site_search::Document;
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn site() -> (TempDir, FileCache) {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("search")).unwrap();
+        let docs = ["Fuzzing Rust", "Lightning jamming"].map(|title| Document {
+            path: format!("/blog/{}/", title.to_lowercase().replace(' ', "-")),
+            title: title.to_string(),
+            headings: Vec::new(),
+            body: String::new(),
+            html: format!("<li>{}</li>", title),
+        });
+        fs::write(
+            dir.path().join("search/index.bin"),
+            site_search::build(&docs),
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("search/index.html"),
+            "<h1>{{query}}</h1><p>{{summary}}</p><ol>{{results}}</ol>",
+        )
+        .unwrap();
+        let cache = FileCache::load(dir.path().to_str().unwrap());
+        (dir, cache)
+    }
+
+    fn request(path: &str, query: Option<&str>) -> Request {
+        Request {
+            method: http::Method::GET,
+            path: path.to_string(),
+            raw_path: path.to_string(),
+            version: http::Version::HTTP_11,
+            user_agent: None,
+            accepts_gzip: false,
+            peer: "203.0.113.7:54321".parse().unwrap(),
+            client_subject: None,
+            query: query.map(String::from),
+            body: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn a_query_is_answered_with_the_page_filled_in() {
+        let (_dir, cache) = site();
+        let search = Search::load(&cache).unwrap();
+        let res = search.respond(&request("/search", Some("q=jammed+lightning")));
+        assert_eq!(res.status, 200);
+        assert_eq!(
+            String::from_utf8(res.body).unwrap(),
+            "<h1>jammed lightning</h1><p>1 post matches.</p><ol><li>Lightning jamming</li></ol>"
+        );
+    }
+
+    #[test]
+    fn without_a_query_the_form_is_empty() {
+        let (_dir, cache) = site();
+        let res = Search::load(&cache)
+            .unwrap()
+            .respond(&request("/search/", None));
+        assert_eq!(
+            String::from_utf8(res.body).unwrap(),
+            "<h1></h1><p></p><ol></ol>"
+        );
+    }
+
+    #[test]
+    fn a_site_without_an_index_has_no_search() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("index.html"), "home").unwrap();
+        assert!(Search::load(&FileCache::load(dir.path().to_str().unwrap())).is_none());
+    }
+}
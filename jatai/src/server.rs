@@ -1,13 +1,18 @@
 use std::{env, io, net::SocketAddr, sync::Arc};
 
-use bytes::Bytes;
+use bytes::{Buf, Bytes};
 use h2::server;
+use rustls::pki_types::CertificateDer;
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
 use tokio::time::{timeout, Duration, Instant};
 use tokio_rustls::TlsAcceptor;
 
-use crate::{cache::FileCache, handler::StaticFileHandler, Request};
+use crate::{
+    cache::FileCache, handler::StaticFileHandler, honeypot::Traps, intel, live, tarpit::Tarpit,
+    webmention, LiveReload, Request,
+};
 
 const READ_TIMEOUT: Duration = Duration::from_secs(30);
 // Total time budget to receive the complete request line and headers. Unlike a
@@ -15,9 +20,22 @@ const READ_TIMEOUT: Duration = Duration::from_secs(30);
 // trickles bytes cannot hold the connection open indefinitely.
 const HEADER_TIMEOUT: Duration = Duration::from_secs(10);
 const H1_MAX_HEADER_SIZE: usize = 8192;
+/// Most of a POST body read, on any protocol. Nothing jatai takes in comes
+/// close; a longer body drops the request.
+pub(crate) const MAX_BODY: usize = 8192;
+// An idle live reload stream sends a comment this often; a failed write is how
+// the server learns the page was closed.
+const LIVE_KEEPALIVE: Duration = Duration::from_secs(15);
 
 const SERVER_AGENT: &str = "jatai";
 
+/// Where Webmention sources served over HTTPS are verified against, unless
+/// `WEBMENTION_CA_PATH` says otherwise.
+const SYSTEM_ROOTS: &str = "/etc/ssl/certs/ca-certificates.crt";
+/// Most honeypot responses tarpitted at once, unless `TARPIT_MAX` says
+/// otherwise.
+const TARPIT_MAX: usize = 32;
+
 const SECURITY_HEADERS: &str = "\
 X-Content-Type-Options: nosniff\r\n\
 X-Frame-Options: DENY\r\n\
@@ -27,6 +45,12 @@ pub struct Config {
     static_dir: String,
     http_bind: String,
     https: Option<HttpsConfig>,
+    client_auth: Option<ClientAuthConfig>,
+    preview_key: Option<String>,
+    webmentions: Option<WebmentionConfig>,
+    honeypot_log: Option<String>,
+    honeypot_traps: Option<String>,
+    tarpit: Option<TarpitConfig>,
 }
 
 struct HttpsConfig {
@@ -36,16 +60,72 @@ struct HttpsConfig {
     enable_h3: bool,
 }
 
+struct WebmentionConfig {
+    inbox: String,
+    site_url: String,
+    roots_path: String,
+}
+
+struct TarpitConfig {
+    duration: Duration,
+    max: usize,
+}
+
+struct ClientAuthConfig {
+    ca_path: String,
+    paths: Vec<String>,
+}
+
 impl Config {
     pub fn from_env() -> Self {
         dotenvy::dotenv().ok();
+        let https = Self::parse_https_config();
+        let static_dir = env_var("STATIC_DIR");
         Self {
-            static_dir: env_var("STATIC_DIR"),
+            webmentions: Self::parse_webmention_config(&static_dir),
+            static_dir,
             http_bind: env_var("HTTP_BIND"),
-            https: Self::parse_https_config(),
+            client_auth: Self::parse_client_auth_config(https.is_some()),
+            https,
+            // Optional: without it drafts exist on disk but are never served.
+            preview_key: env::var("PREVIEW_KEY").ok().filter(|k| !k.is_empty()),
+            // Optional: without it honeypot hits are only logged to stdout.
+            honeypot_log: env::var("HONEYPOT_LOG").ok().filter(|p| !p.is_empty()),
+            // Optional: without it the built-in traps are set.
+            honeypot_traps: env::var("HONEYPOT_TRAPS").ok().filter(|d| !d.is_empty()),
+            tarpit: Self::parse_tarpit_config(),
         }
     }
 
+    /// The tarpit is opt-in: `TARPIT_SECS` is how long each bait body takes
+    /// to send, and unset, zero or unparseable leaves it off.
+    fn parse_tarpit_config() -> Option<TarpitConfig> {
+        let secs = env::var("TARPIT_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .filter(|&secs| secs > 0)?;
+        let max = env::var("TARPIT_MAX")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(TARPIT_MAX);
+        Some(TarpitConfig {
+            duration: Duration::from_secs(secs),
+            max,
+        })
+    }
+
+    /// Webmentions are opt-in: only with `WEBMENTION_DIR` set is there an
+    /// endpoint. Which targets are ours is the `base_url` of the site being
+    /// served, read from the `site.toml` blog-gen keeps beside `static_dir`.
+    fn parse_webmention_config(static_dir: &str) -> Option<WebmentionConfig> {
+        let inbox = env::var("WEBMENTION_DIR").ok().filter(|d| !d.is_empty())?;
+        Some(WebmentionConfig {
+            inbox,
+            site_url: site_base_url(static_dir),
+            roots_path: env::var("WEBMENTION_CA_PATH").unwrap_or_else(|_| SYSTEM_ROOTS.into()),
+        })
+    }
+
     fn parse_https_config() -> Option<HttpsConfig> {
         let enable_https = env::var("ENABLE_HTTPS")
             .ok()
@@ -68,6 +148,48 @@ impl Config {
             None
         }
     }
+
+    /// Client certificates are opt-in: without `CLIENT_CA_PATH` the TLS layer
+    /// never asks for one. The protected paths are read whether or not HTTPS
+    /// is on, since plain HTTP has to refuse them too; but naming them
+    /// without HTTPS and a CA to verify against would lock them for everyone,
+    /// so that refuses to start.
+    fn parse_client_auth_config(https: bool) -> Option<ClientAuthConfig> {
+        let paths: Vec<String> = env::var("CLIENT_AUTH_PATHS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+            .map(String::from)
+            .collect();
+
+        if !paths.is_empty() && !https {
+            panic!("CLIENT_AUTH_PATHS is set but ENABLE_HTTPS is not");
+        }
+        match env::var("CLIENT_CA_PATH") {
+            Ok(ca_path) => Some(ClientAuthConfig { ca_path, paths }),
+            Err(_) if paths.is_empty() => None,
+            Err(_) => panic!("CLIENT_AUTH_PATHS is set but CLIENT_CA_PATH is not"),
+        }
+    }
+}
+
+/// `base_url` from the `site.toml` beside `static_dir`. Webmentions are on
+/// when this is called, so a missing or broken file refuses to start.
+fn site_base_url(static_dir: &str) -> String {
+    #[derive(serde::Deserialize)]
+    struct SiteToml {
+        base_url: String,
+    }
+    let path = std::path::Path::new(static_dir)
+        .parent()
+        .unwrap_or(std::path::Path::new(""))
+        .join("site.toml");
+    let text = std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("WEBMENTION_DIR is set but {}: {}", path.display(), e));
+    toml::from_str::<SiteToml>(&text)
+        .unwrap_or_else(|e| panic!("{}: {}", path.display(), e))
+        .base_url
 }
 
 fn env_var(key: &str) -> String {
@@ -94,6 +216,13 @@ pub struct Jatai {
     quic_endpoint: Option<quinn::Endpoint>,
     h3_port: Option<u16>,
     static_dir: String,
+    client_auth_paths: Vec<String>,
+    preview_key: Option<Vec<u8>>,
+    live_reload: Option<LiveReload>,
+    webmentions: Option<(webmention::Receiver, webmention::Verifier)>,
+    intel: Option<(intel::Recorder, intel::Writer)>,
+    traps: Option<(String, Traps)>, // (dir, traps loaded from it)
+    tarpit: Option<Tarpit>,
 }
 
 pub struct JataiBuilder {
@@ -101,6 +230,15 @@ pub struct JataiBuilder {
     http_bind: Option<String>,
     https: Option<(String, String, String)>, // (bind, cert_path, key_path)
     enable_h3: bool,
+    client_ca_path: Option<String>,
+    client_auth_paths: Vec<String>,
+    preview_key: Option<Vec<u8>>,
+    live_reload: Option<LiveReload>,
+    webmentions: Option<(String, String)>, // (inbox, site_url)
+    webmention_roots: String,
+    honeypot_log: Option<String>,
+    honeypot_traps: Option<String>,
+    tarpit: Option<(Duration, usize)>, // (duration, max)
 }
 
 impl JataiBuilder {
@@ -110,6 +248,15 @@ impl JataiBuilder {
             http_bind: None,
             https: None,
             enable_h3: false,
+            client_ca_path: None,
+            client_auth_paths: Vec::new(),
+            preview_key: None,
+            live_reload: None,
+            webmentions: None,
+            webmention_roots: SYSTEM_ROOTS.to_string(),
+            honeypot_log: None,
+            honeypot_traps: None,
+            tarpit: None,
         }
     }
 
@@ -138,6 +285,69 @@ impl JataiBuilder {
         self
     }
 
+    /// Accept client certificates signed by the CA at `ca_path`, and require
+    /// one for every path under `paths`. Everything else stays public, so the
+    /// certificate is optional at the handshake and enforced per request.
+    pub fn client_auth<P: Into<String>>(
+        mut self,
+        ca_path: impl Into<String>,
+        paths: impl IntoIterator<Item = P>,
+    ) -> Self {
+        self.client_ca_path = Some(ca_path.into());
+        self.client_auth_paths = paths.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Serve pages under `/drafts/` to requests carrying a preview link signed
+    /// with `key`, as printed by `blog-gen preview`.
+    pub fn preview_key(mut self, key: impl Into<Vec<u8>>) -> Self {
+        self.preview_key = Some(key.into());
+        self
+    }
+
+    /// Reload the static directory whenever `live` reports a build, and make
+    /// open pages reload with it (see `LiveReload`). For local development
+    /// over plain HTTP/1.1 only.
+    pub fn live_reload(mut self, live: LiveReload) -> Self {
+        self.live_reload = Some(live);
+        self
+    }
+
+    /// Take Webmentions for pages of the site at `site_url` (its origin, as
+    /// in blog-gen's `base_url`), and keep the ones that check out in `inbox`
+    /// for blog-gen to render.
+    pub fn webmentions(mut self, inbox: impl Into<String>, site_url: impl Into<String>) -> Self {
+        self.webmentions = Some((inbox.into(), site_url.into()));
+        self
+    }
+
+    /// Verify Webmention sources served over HTTPS against the certificates
+    /// in this PEM bundle rather than the system's.
+    pub fn webmention_roots(mut self, path: impl Into<String>) -> Self {
+        self.webmention_roots = path.into();
+        self
+    }
+
+    /// Append every honeypot hit to the log at `path`, for `intel::report`.
+    pub fn honeypot_log(mut self, path: impl Into<String>) -> Self {
+        self.honeypot_log = Some(path.into());
+        self
+    }
+
+    /// Set the honeypot traps in `dir/traps.toml` instead of the built-in
+    /// ones, and set them again from there on `SIGHUP`.
+    pub fn honeypot_traps(mut self, dir: impl Into<String>) -> Self {
+        self.honeypot_traps = Some(dir.into());
+        self
+    }
+
+    /// Send each honeypot bait body slowly, spread over `duration`, to at
+    /// most `max` clients at once; the rest get theirs at full speed.
+    pub fn tarpit(mut self, duration: Duration, max: usize) -> Self {
+        self.tarpit = Some((duration, max));
+        self
+    }
+
     pub async fn build(self) -> io::Result<Jatai> {
         let mut listeners = Vec::new();
         let mut quic_endpoint = None;
@@ -151,14 +361,15 @@ impl JataiBuilder {
         }
 
         if let Some((addr, cert_path, key_path)) = self.https {
-            let config = crate::tls::load_config(&cert_path, &key_path)?;
+            let client_ca = self.client_ca_path.as_deref();
+            let config = crate::tls::load_config(&cert_path, &key_path, client_ca)?;
             listeners.push(Listener {
                 tcp: TcpListener::bind(&addr).await?,
                 tls_acceptor: Some(TlsAcceptor::from(Arc::new(config))),
             });
 
             if self.enable_h3 {
-                let quic_config = crate::tls::load_quic_config(&cert_path, &key_path)?;
+                let quic_config = crate::tls::load_quic_config(&cert_path, &key_path, client_ca)?;
                 let socket_addr: std::net::SocketAddr =
                     addr.parse().map_err(|e: std::net::AddrParseError| {
                         io::Error::new(io::ErrorKind::InvalidInput, e)
@@ -172,11 +383,42 @@ impl JataiBuilder {
             }
         }
 
+        let webmentions = match self.webmentions {
+            Some((inbox, site_url)) => {
+                std::fs::create_dir_all(&inbox)?;
+                let tls = crate::tls::client_config(&self.webmention_roots)?;
+                Some(webmention::queue(&site_url, inbox.into(), tls))
+            }
+            None => None,
+        };
+
+        let intel = match self.honeypot_log {
+            Some(path) => Some(intel::open(std::path::Path::new(&path))?),
+            None => None,
+        };
+
+        let traps = match self.honeypot_traps {
+            Some(dir) => {
+                let traps = Traps::load(std::path::Path::new(&dir))?;
+                Some((dir, traps))
+            }
+            None => None,
+        };
+
         Ok(Jatai {
             listeners,
             quic_endpoint,
             h3_port,
             static_dir: self.static_dir,
+            client_auth_paths: self.client_auth_paths,
+            preview_key: self.preview_key,
+            live_reload: self.live_reload,
+            webmentions,
+            intel,
+            traps,
+            tarpit: self
+                .tarpit
+                .map(|(duration, max)| Tarpit::new(duration, max)),
         })
     }
 }
@@ -218,6 +460,40 @@ impl Jatai {
         }
 
         let cache = Arc::new(FileCache::load(&self.static_dir));
+        let mut handler = StaticFileHandler::new(cache)
+            .with_client_auth_paths(&self.client_auth_paths)
+            .with_preview_key(self.preview_key);
+        // Pages hear of a build only once the cache holds it, so a reload
+        // never fetches the previous one.
+        let (pages, pages_rx) = watch::channel(live::Build::default());
+        if self.live_reload.is_some() {
+            handler = handler.with_live_reload(pages_rx);
+        }
+        let verifier = match self.webmentions {
+            Some((receiver, verifier)) => {
+                handler = handler.with_webmentions(receiver);
+                Some(verifier)
+            }
+            None => None,
+        };
+        let writer = match self.intel {
+            Some((recorder, writer)) => {
+                handler = handler.with_honeypot_log(recorder);
+                Some(writer)
+            }
+            None => None,
+        };
+        if let Some(tarpit) = self.tarpit {
+            handler = handler.with_tarpit(tarpit);
+        }
+        let traps_dir = match self.traps {
+            Some((dir, traps)) => {
+                handler.replace_traps(traps);
+                Some(dir)
+            }
+            None => None,
+        };
+        let handler = Arc::new(handler);
 
         let alt_svc: Option<Arc<str>> = self
             .h3_port
@@ -237,18 +513,42 @@ impl Jatai {
 
         let mut handles = Vec::new();
 
+        if let Some(live) = self.live_reload {
+            let handler = Arc::clone(&handler);
+            let static_dir = self.static_dir.clone();
+            handles.push(tokio::spawn(async move {
+                Self::follow_builds(live, static_dir, handler, pages).await;
+            }));
+        }
+
+        if let Some(verifier) = verifier {
+            handles.push(tokio::spawn(verifier.run()));
+        }
+
+        if let Some(writer) = writer {
+            handles.push(tokio::spawn(writer.run()));
+        }
+
+        #[cfg(unix)]
+        if let Some(dir) = traps_dir {
+            let handler = Arc::clone(&handler);
+            handles.push(tokio::spawn(async move {
+                Self::reload_traps_on_hangup(dir, handler).await;
+            }));
+        }
+
         for listener in self.listeners {
-            let cache = Arc::clone(&cache);
+            let handler = Arc::clone(&handler);
             let alt_svc = alt_svc.clone();
             handles.push(tokio::spawn(async move {
-                Self::accept_loop(listener, cache, alt_svc).await;
+                Self::accept_loop(listener, handler, alt_svc).await;
             }));
         }
 
         if let Some(endpoint) = self.quic_endpoint {
-            let cache = Arc::clone(&cache);
+            let handler = Arc::clone(&handler);
             handles.push(tokio::spawn(async move {
-                Self::accept_quic(endpoint, cache).await;
+                Self::accept_quic(endpoint, handler).await;
             }));
         }
 
@@ -257,15 +557,101 @@ impl Jatai {
         }
     }
 
-    async fn accept_loop(listener: Listener, cache: Arc<FileCache>, alt_svc: Option<Arc<str>>) {
+    async fn follow_builds(
+        live: LiveReload,
+        static_dir: String,
+        handler: Arc<StaticFileHandler>,
+        pages: watch::Sender<live::Build>,
+    ) {
+        let mut builds = live.subscribe();
+        // Only the handle's clones are left to report builds; once the last
+        // is gone this stops and pages keep the last build.
+        drop(live);
+        while builds.changed().await.is_ok() {
+            let build = builds.borrow_and_update().clone();
+            if build.error.is_none() {
+                let dir = static_dir.clone();
+                match tokio::task::spawn_blocking(move || FileCache::load(&dir)).await {
+                    Ok(cache) => handler.replace_cache(cache),
+                    Err(e) => eprintln!("Reloading {} failed: {}", static_dir, e),
+                }
+            }
+            pages.send_replace(build);
+        }
+    }
+
+    /// Set the traps in `dir` again each time the process gets `SIGHUP`. A
+    /// table that no longer loads leaves the one in use in place.
+    #[cfg(unix)]
+    async fn reload_traps_on_hangup(dir: String, handler: Arc<StaticFileHandler>) {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut hangups = match signal(SignalKind::hangup()) {
+            Ok(hangups) => hangups,
+            Err(e) => {
+                eprintln!("Warning: honeypot traps will not reload: {}", e);
+                return;
+            }
+        };
+        while hangups.recv().await.is_some() {
+            Self::reload_traps(&dir, &handler).await;
+        }
+    }
+
+    async fn reload_traps(dir: &str, handler: &StaticFileHandler) {
+        let path = std::path::PathBuf::from(dir);
+        let loaded = tokio::task::spawn_blocking(move || Traps::load(&path)).await;
+        match loaded.map_err(io::Error::other).and_then(|traps| traps) {
+            Ok(traps) => {
+                handler.replace_traps(traps);
+                println!("Reloaded honeypot traps from {}", dir);
+            }
+            Err(e) => eprintln!("Warning: keeping the honeypot traps in use: {}", e),
+        }
+    }
+
+    /// Hold the connection open as a server-sent event stream, sending one
+    /// event per build until the client goes away.
+    async fn stream_live_events<S>(stream: &mut S, mut builds: watch::Receiver<live::Build>)
+    where
+        S: AsyncWriteExt + Unpin,
+    {
+        let header = format!(
+            "HTTP/1.1 200 OK\r\nServer: {}\r\nConnection: close\r\nContent-Type: text/event-stream\r\nCache-Control: no-store\r\n{}\r\n",
+            SERVER_AGENT, SECURITY_HEADERS,
+        );
+        if stream.write_all(header.as_bytes()).await.is_err() {
+            return;
+        }
+        builds.mark_unchanged();
+        loop {
+            let message: &[u8] = match timeout(LIVE_KEEPALIVE, builds.changed()).await {
+                Ok(Ok(())) => {
+                    builds.mark_unchanged();
+                    b"data: reload\n\n"
+                }
+                Ok(Err(_)) => return,
+                Err(_) => b": keepalive\n\n",
+            };
+            if stream.write_all(message).await.is_err() || stream.flush().await.is_err() {
+                return;
+            }
+        }
+    }
+
+    async fn accept_loop(
+        listener: Listener,
+        handler: Arc<StaticFileHandler>,
+        alt_svc: Option<Arc<str>>,
+    ) {
         loop {
             match listener.tcp.accept().await {
                 Ok((stream, peer)) => {
-                    let cache = Arc::clone(&cache);
+                    let handler = Arc::clone(&handler);
                     let tls_acceptor = listener.tls_acceptor.clone();
                     let alt_svc = alt_svc.clone();
                     tokio::spawn(async move {
-                        Self::handle_connection(stream, peer, tls_acceptor, cache, alt_svc).await;
+                        Self::handle_connection(stream, peer, tls_acceptor, handler, alt_svc).await;
                     });
                 }
                 Err(e) => eprintln!("Connection failed: {}", e),
@@ -273,9 +659,9 @@ impl Jatai {
         }
     }
 
-    async fn accept_quic(endpoint: quinn::Endpoint, cache: Arc<FileCache>) {
+    async fn accept_quic(endpoint: quinn::Endpoint, handler: Arc<StaticFileHandler>) {
         while let Some(incoming) = endpoint.accept().await {
-            let cache = Arc::clone(&cache);
+            let handler = Arc::clone(&handler);
             tokio::spawn(async move {
                 let connection = match incoming.await {
                     Ok(c) => c,
@@ -285,7 +671,12 @@ impl Jatai {
                     }
                 };
                 let peer = connection.remote_address();
-                Self::serve_h3(connection, cache, peer).await;
+                let client_subject = connection
+                    .peer_identity()
+                    .and_then(|identity| identity.downcast::<Vec<CertificateDer>>().ok())
+                    .and_then(|chain| crate::tls::client_subject(&chain))
+                    .map(Arc::from);
+                Self::serve_h3(connection, handler, peer, client_subject).await;
             });
         }
     }
@@ -294,26 +685,35 @@ impl Jatai {
         stream: TcpStream,
         peer: SocketAddr,
         tls_acceptor: Option<TlsAcceptor>,
-        cache: Arc<FileCache>,
+        handler: Arc<StaticFileHandler>,
         alt_svc: Option<Arc<str>>,
     ) {
         let _ = stream.set_nodelay(true);
 
         if let Some(acceptor) = tls_acceptor {
             if let Ok(Ok(tls_stream)) = timeout(READ_TIMEOUT, acceptor.accept(stream)).await {
+                let session = tls_stream.get_ref().1;
+                // Any chain rustls hands back here has already been verified
+                // against the client CA; an unverifiable one fails the
+                // handshake above and never reaches this point.
+                let client_subject = session
+                    .peer_certificates()
+                    .and_then(crate::tls::client_subject)
+                    .map(Arc::from);
+
                 // Dispatch on the ALPN protocol negotiated during the TLS
                 // handshake. Clients that don't negotiate "h2" (e.g. plain
                 // HTTP/1.1 fetchers) must be served over HTTP/1.1, otherwise
                 // they receive HTTP/2 framing they can't parse.
-                let is_h2 = tls_stream.get_ref().1.alpn_protocol() == Some(b"h2");
+                let is_h2 = session.alpn_protocol() == Some(b"h2");
                 if is_h2 {
-                    Self::serve_h2(tls_stream, cache, peer, alt_svc).await;
+                    Self::serve_h2(tls_stream, handler, peer, client_subject, alt_svc).await;
                 } else {
-                    Self::serve_h1(tls_stream, cache, peer, alt_svc).await;
+                    Self::serve_h1(tls_stream, handler, peer, client_subject, alt_svc).await;
                 }
             }
         } else {
-            Self::serve_h1(stream, cache, peer, alt_svc).await;
+            Self::serve_h1(stream, handler, peer, None, alt_svc).await;
         }
     }
 
@@ -352,10 +752,39 @@ impl Jatai {
         }
     }
 
+    /// The body of a request with headers `head`, `started` being the part
+    /// that arrived with them. Only as long as `Content-Length` says, which
+    /// has to be within `MAX_BODY`; `None` for a longer or stalled body.
+    async fn read_h1_body<S: AsyncReadExt + Unpin>(
+        stream: &mut S,
+        head: &str,
+        mut started: Vec<u8>,
+    ) -> Option<Vec<u8>> {
+        let length = head.lines().find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name.eq_ignore_ascii_case("content-length")
+                .then(|| value.trim().parse::<usize>())
+        });
+        let length = match length {
+            None => 0,
+            Some(Ok(length)) if length <= MAX_BODY => length,
+            Some(_) => return None,
+        };
+        started.truncate(length);
+        let mut rest = vec![0; length - started.len()];
+        timeout(READ_TIMEOUT, stream.read_exact(&mut rest))
+            .await
+            .ok()?
+            .ok()?;
+        started.extend_from_slice(&rest);
+        Some(started)
+    }
+
     async fn serve_h1<S>(
         mut stream: S,
-        cache: Arc<FileCache>,
+        handler: Arc<StaticFileHandler>,
         peer: SocketAddr,
+        client_subject: Option<Arc<str>>,
         alt_svc: Option<Arc<str>>,
     ) where
         S: AsyncReadExt + AsyncWriteExt + Unpin,
@@ -365,7 +794,12 @@ impl Jatai {
             None => return,
         };
 
-        let request_str = match std::str::from_utf8(&buf) {
+        // Whatever came in after the blank line is the start of a body.
+        let head_len = buf
+            .windows(4)
+            .position(|w| w == b"\r\n\r\n")
+            .map_or(buf.len(), |end| end + 4);
+        let request_str = match std::str::from_utf8(&buf[..head_len]) {
             Ok(s) => s,
             Err(_) => return,
         };
@@ -373,8 +807,20 @@ impl Jatai {
         let Some(request) = Request::parse_h1(request_str, peer) else {
             return;
         };
+        let mut request = request.with_client_subject(client_subject);
+        if request.method == http::Method::POST {
+            let started = buf[head_len..].to_vec();
+            let Some(body) = Self::read_h1_body(&mut stream, request_str, started).await else {
+                return;
+            };
+            request = request.with_body(body);
+        }
+
+        if let Some(builds) = handler.live_events(&request.path) {
+            Self::stream_live_events(&mut stream, builds).await;
+            return;
+        }
 
-        let handler = StaticFileHandler::new(Arc::clone(&cache));
         let response = handler.handle(&request);
 
         let encoding_header = if response.gzip {
@@ -395,7 +841,13 @@ impl Jatai {
 
         let status_text = match response.status {
             200 => "200 OK",
+            202 => "202 ACCEPTED",
+            400 => "400 BAD REQUEST",
+            403 => "403 FORBIDDEN",
             404 => "404 NOT FOUND",
+            405 => "405 METHOD NOT ALLOWED",
+            500 => "500 INTERNAL SERVER ERROR",
+            503 => "503 SERVICE UNAVAILABLE",
             _ => "200 OK",
         };
 
@@ -412,15 +864,39 @@ impl Jatai {
         );
 
         let _ = stream.write_all(header.as_bytes()).await;
-        let _ = stream.write_all(&response.body).await;
+        match &response.tarpit {
+            Some(trickle) => {
+                let _ = stream.flush().await;
+                let sent = trickle.within(async {
+                    for piece in trickle.pieces(&response.body) {
+                        trickle.wait().await;
+                        if stream.write_all(piece).await.is_err() || stream.flush().await.is_err() {
+                            return false;
+                        }
+                    }
+                    true
+                });
+                if sent.await != Some(true) {
+                    return;
+                }
+            }
+            None => {
+                let _ = stream.write_all(&response.body).await;
+            }
+        }
         // Close the write half explicitly. Over TLS this emits close_notify;
         // without it strict clients report the response as truncated instead of
         // complete, even though every declared byte arrived.
         let _ = stream.shutdown().await;
     }
 
-    async fn serve_h2<S>(io: S, cache: Arc<FileCache>, peer: SocketAddr, alt_svc: Option<Arc<str>>)
-    where
+    async fn serve_h2<S>(
+        io: S,
+        handler: Arc<StaticFileHandler>,
+        peer: SocketAddr,
+        client_subject: Option<Arc<str>>,
+        alt_svc: Option<Arc<str>>,
+    ) where
         S: AsyncRead + AsyncWrite + Unpin,
     {
         let mut connection = match server::handshake(io).await {
@@ -440,23 +916,46 @@ impl Jatai {
                 }
             };
 
-            let cache = Arc::clone(&cache);
+            let handler = Arc::clone(&handler);
+            let client_subject = client_subject.clone();
             let alt_svc = alt_svc.clone();
             tokio::spawn(async move {
-                Self::handle_h2_request(request, respond, cache, peer, alt_svc);
+                let mut req = Request::from_h2(&request, peer).with_client_subject(client_subject);
+                if req.method == http::Method::POST {
+                    let Some(body) = Self::read_h2_body(request.into_body()).await else {
+                        return;
+                    };
+                    req = req.with_body(body);
+                }
+                Self::handle_h2_request(req, respond, handler, alt_svc).await;
             });
         }
     }
 
-    fn handle_h2_request(
-        request: http::Request<h2::RecvStream>,
+    /// A request body of at most `MAX_BODY`; `None` for a longer or stalled
+    /// one, which drops the stream.
+    async fn read_h2_body(mut body: h2::RecvStream) -> Option<Vec<u8>> {
+        let read = async {
+            let mut out = Vec::new();
+            while let Some(chunk) = body.data().await {
+                let chunk = chunk.ok()?;
+                let _ = body.flow_control().release_capacity(chunk.len());
+                if out.len() + chunk.len() > MAX_BODY {
+                    return None;
+                }
+                out.extend_from_slice(&chunk);
+            }
+            Some(out)
+        };
+        timeout(READ_TIMEOUT, read).await.ok()?
+    }
+
+    async fn handle_h2_request(
+        req: Request,
         mut respond: server::SendResponse<Bytes>,
-        cache: Arc<FileCache>,
-        peer: SocketAddr,
+        handler: Arc<StaticFileHandler>,
         alt_svc: Option<Arc<str>>,
     ) {
-        let req = Request::from_h2(&request, peer);
-        let handler = StaticFileHandler::new(cache);
         let response = handler.handle(&req);
 
         let mut builder = http::Response::builder().status(response.status);
@@ -488,12 +987,54 @@ impl Jatai {
             Err(_) => return,
         };
 
-        if !end_of_stream {
-            let _ = send.send_data(Bytes::from(response.body), true);
+        match (&response.tarpit, end_of_stream) {
+            (_, true) => {}
+            (Some(trickle), false) => {
+                let sent = trickle.within(async {
+                    let mut pieces = trickle.pieces(&response.body).peekable();
+                    while let Some(piece) = pieces.next() {
+                        trickle.wait().await;
+                        let last = pieces.peek().is_none();
+                        if !Self::send_h2_data(&mut send, Bytes::copy_from_slice(piece), last).await
+                        {
+                            return false;
+                        }
+                    }
+                    true
+                });
+                if sent.await.is_none() {
+                    send.send_reset(h2::Reason::CANCEL);
+                }
+            }
+            (None, false) => {
+                let _ = send.send_data(Bytes::from(response.body), true);
+            }
         }
     }
 
-    async fn serve_h3(conn: quinn::Connection, cache: Arc<FileCache>, peer: SocketAddr) {
+    /// Send `data` as the peer's flow-control window allows, waiting for it
+    /// to open rather than buffering past it. False once the stream is gone.
+    async fn send_h2_data(send: &mut h2::SendStream<Bytes>, mut data: Bytes, end: bool) -> bool {
+        while !data.is_empty() {
+            send.reserve_capacity(data.len());
+            let granted = match std::future::poll_fn(|cx| send.poll_capacity(cx)).await {
+                Some(Ok(granted)) => granted,
+                _ => return false,
+            };
+            let chunk = data.split_to(granted.min(data.len()));
+            if send.send_data(chunk, end && data.is_empty()).is_err() {
+                return false;
+            }
+        }
+        true
+    }
+
+    async fn serve_h3(
+        conn: quinn::Connection,
+        handler: Arc<StaticFileHandler>,
+        peer: SocketAddr,
+        client_subject: Option<Arc<str>>,
+    ) {
         let mut h3_conn: h3::server::Connection<h3_quinn::Connection, Bytes> =
             match h3::server::Connection::new(h3_quinn::Connection::new(conn)).await {
                 Ok(c) => c,
@@ -506,11 +1047,20 @@ impl Jatai {
         loop {
             match h3_conn.accept().await {
                 Ok(Some(resolver)) => {
-                    let cache = Arc::clone(&cache);
+                    let handler = Arc::clone(&handler);
+                    let client_subject = client_subject.clone();
                     tokio::spawn(async move {
                         match resolver.resolve_request().await {
-                            Ok((req, stream)) => {
-                                Self::handle_h3_request(req, stream, cache, peer).await;
+                            Ok((req, mut stream)) => {
+                                let mut req = Request::from_h2(&req, peer)
+                                    .with_client_subject(client_subject);
+                                if req.method == http::Method::POST {
+                                    let Some(body) = Self::read_h3_body(&mut stream).await else {
+                                        return;
+                                    };
+                                    req = req.with_body(body);
+                                }
+                                Self::handle_h3_request(req, stream, handler).await;
                             }
                             Err(e) => eprintln!("H3 request error: {}", e),
                         }
@@ -522,14 +1072,28 @@ impl Jatai {
         }
     }
 
+    /// As `read_h2_body`, over HTTP/3.
+    async fn read_h3_body(
+        stream: &mut h3::server::RequestStream<h3_quinn::BidiStream<Bytes>, Bytes>,
+    ) -> Option<Vec<u8>> {
+        let read = async {
+            let mut out = Vec::new();
+            while let Some(mut chunk) = stream.recv_data().await.ok()? {
+                if out.len() + chunk.remaining() > MAX_BODY {
+                    return None;
+                }
+                out.extend_from_slice(&chunk.copy_to_bytes(chunk.remaining()));
+            }
+            Some(out)
+        };
+        timeout(READ_TIMEOUT, read).await.ok()?
+    }
+
     async fn handle_h3_request(
-        request: http::Request<()>,
+        req: Request,
         mut stream: h3::server::RequestStream<h3_quinn::BidiStream<Bytes>, Bytes>,
-        cache: Arc<FileCache>,
-        peer: SocketAddr,
+        handler: Arc<StaticFileHandler>,
     ) {
-        let req = Request::from_h2(&request, peer);
-        let handler = StaticFileHandler::new(cache);
         let response = handler.handle(&req);
 
         let mut builder = http::Response::builder().status(response.status);
@@ -555,8 +1119,35 @@ impl Jatai {
             return;
         }
 
-        if !response.body.is_empty() {
-            let _ = stream.send_data(Bytes::from(response.body)).await;
+        match &response.tarpit {
+            Some(trickle) => {
+                let sent = trickle.within(async {
+                    for piece in trickle.pieces(&response.body) {
+                        trickle.wait().await;
+                        // Waits for QUIC's flow control to admit the piece.
+                        if stream
+                            .send_data(Bytes::copy_from_slice(piece))
+                            .await
+                            .is_err()
+                        {
+                            return false;
+                        }
+                    }
+                    true
+                });
+                match sent.await {
+                    Some(true) => {}
+                    Some(false) => return,
+                    None => {
+                        stream.stop_stream(h3::error::Code::H3_REQUEST_CANCELLED);
+                        return;
+                    }
+                }
+            }
+            None if !response.body.is_empty() => {
+                let _ = stream.send_data(Bytes::from(response.body)).await;
+            }
+            None => {}
         }
 
         let _ = stream.finish().await;
@@ -568,6 +1159,28 @@ impl From<Config> for JataiBuilder {
         let mut builder = JataiBuilder::new().with_static_dir(&config.static_dir);
         builder = builder.bind_http(&config.http_bind);
 
+        if let Some(key) = config.preview_key {
+            builder = builder.preview_key(key);
+        }
+
+        if let Some(webmentions) = config.webmentions {
+            builder = builder
+                .webmentions(webmentions.inbox, webmentions.site_url)
+                .webmention_roots(webmentions.roots_path);
+        }
+
+        if let Some(path) = config.honeypot_log {
+            builder = builder.honeypot_log(path);
+        }
+
+        if let Some(dir) = config.honeypot_traps {
+            builder = builder.honeypot_traps(dir);
+        }
+
+        if let Some(tarpit) = config.tarpit {
+            builder = builder.tarpit(tarpit.duration, tarpit.max);
+        }
+
         if let Some(https) = config.https {
             builder = builder.bind_https(&https.bind, &https.cert_path, &https.key_path);
             if https.enable_h3 {
@@ -575,6 +1188,10 @@ impl From<Config> for JataiBuilder {
             }
         }
 
+        if let Some(client_auth) = config.client_auth {
+            builder = builder.client_auth(client_auth.ca_path, client_auth.paths);
+        }
+
         builder
     }
 }
@@ -593,13 +1210,13 @@ mod tests {
         "203.0.113.7:54321".parse().unwrap()
     }
 
-    fn cache_of(files: &[(&str, &[u8])]) -> (TempDir, Arc<FileCache>) {
+    fn handler_of(files: &[(&str, &[u8])]) -> (TempDir, Arc<StaticFileHandler>) {
         let dir = TempDir::new().unwrap();
         for (rel, contents) in files {
             fs::write(dir.path().join(rel), contents).unwrap();
         }
         let cache = FileCache::load(dir.path().to_str().unwrap());
-        (dir, Arc::new(cache))
+        (dir, Arc::new(StaticFileHandler::new(Arc::new(cache))))
     }
 
     /// Feed `request` through `serve_h1` over an in-memory pipe and return the
@@ -609,10 +1226,10 @@ mod tests {
         request: &str,
         alt_svc: Option<Arc<str>>,
     ) -> Vec<u8> {
-        let (_dir, cache) = cache_of(files);
+        let (_dir, handler) = handler_of(files);
         let (mut client, server) = duplex(64 * 1024);
 
-        let serving = tokio::spawn(Jatai::serve_h1(server, cache, test_peer(), alt_svc));
+        let serving = tokio::spawn(Jatai::serve_h1(server, handler, test_peer(), None, alt_svc));
 
         client.write_all(request.as_bytes()).await.unwrap();
         serving.await.unwrap();
@@ -754,6 +1371,167 @@ mod tests {
         assert!(String::from_utf8_lossy(&body).contains("DATABASE_URL="));
     }
 
+    /// A handler whose honeypot bait takes `secs` to send, to `max` clients
+    /// at once.
+    fn tarpitted(secs: u64, max: usize) -> (TempDir, Arc<StaticFileHandler>) {
+        let dir = TempDir::new().unwrap();
+        let cache = FileCache::load(dir.path().to_str().unwrap());
+        let handler = StaticFileHandler::new(Arc::new(cache))
+            .with_tarpit(Tarpit::new(Duration::from_secs(secs), max));
+        (dir, Arc::new(handler))
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn h1_trickles_bait_out_over_the_tarpit_duration() {
+        let (_dir, handler) = tarpitted(10, 4);
+        let (mut client, server) = duplex(64 * 1024);
+        let started = Instant::now();
+        tokio::spawn(Jatai::serve_h1(server, handler, test_peer(), None, None));
+        client
+            .write_all(b"GET /.env HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+
+        // The headers come at once; the body does not.
+        let mut head = vec![0; 4096];
+        let n = client.read(&mut head).await.unwrap();
+        assert!(head[..n].ends_with(b"\r\n\r\n"));
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        let mut body = Vec::new();
+        client.read_to_end(&mut body).await.unwrap();
+        assert!(body.starts_with(b"APP_NAME=acme-payments"));
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_secs(10), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_secs(11), "{:?}", elapsed);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_full_tarpit_sends_bait_at_full_speed() {
+        let (_dir, handler) = tarpitted(10, 1);
+        let (mut held, server) = duplex(64 * 1024);
+        let first = tokio::spawn(Jatai::serve_h1(
+            server,
+            Arc::clone(&handler),
+            test_peer(),
+            None,
+            None,
+        ));
+        held.write_all(b"GET /.env HTTP/1.1\r\n\r\n").await.unwrap();
+        // Once the headers are back, the first response holds the only slot.
+        let mut head = vec![0; 4096];
+        let n = held.read(&mut head).await.unwrap();
+        assert!(head[..n].ends_with(b"\r\n\r\n"));
+
+        let started = Instant::now();
+        let (mut client, server) = duplex(64 * 1024);
+        tokio::spawn(Jatai::serve_h1(server, handler, test_peer(), None, None));
+        client
+            .write_all(b"GET /etc/passwd HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+        let mut raw = Vec::new();
+        client.read_to_end(&mut raw).await.unwrap();
+        assert!(split_response(&raw).1.starts_with(b"root:x:0:0:"));
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert!(!first.is_finished());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn h2_trickles_bait_within_the_clients_flow_control_window() {
+        let (_dir, handler) = tarpitted(10, 4);
+        let (client, server) = duplex(64 * 1024);
+        tokio::spawn(Jatai::serve_h2(server, handler, test_peer(), None, None));
+
+        // A window smaller than a piece: each has to wait for the client to
+        // read the one before.
+        let (send_request, connection) = h2::client::Builder::new()
+            .initial_window_size(16)
+            .handshake::<_, Bytes>(client)
+            .await
+            .unwrap();
+        tokio::spawn(async move {
+            let _ = connection.await;
+        });
+        let started = Instant::now();
+        let request = http::Request::get("https://localhost/.env")
+            .body(())
+            .unwrap();
+        let (response, _) = send_request
+            .ready()
+            .await
+            .unwrap()
+            .send_request(request, true)
+            .unwrap();
+        let response = response.await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        let mut stream = response.into_body();
+        let mut body = Vec::new();
+        while let Some(chunk) = stream.data().await {
+            let chunk = chunk.unwrap();
+            assert!(chunk.len() <= 16);
+            stream.flow_control().release_capacity(chunk.len()).unwrap();
+            body.extend_from_slice(&chunk);
+        }
+        assert!(body.starts_with(b"APP_NAME=acme-payments"));
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_secs(10), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_secs(11), "{:?}", elapsed);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_client_that_never_opens_its_window_loses_its_tarpit_slot() {
+        let (_dir, handler) = tarpitted(10, 1);
+        let (client, server) = duplex(64 * 1024);
+        tokio::spawn(Jatai::serve_h2(
+            server,
+            Arc::clone(&handler),
+            test_peer(),
+            None,
+            None,
+        ));
+
+        // No credit at all: not one byte of the body can ever be sent.
+        let (send_request, connection) = h2::client::Builder::new()
+            .initial_window_size(0)
+            .handshake::<_, Bytes>(client)
+            .await
+            .unwrap();
+        tokio::spawn(async move {
+            let _ = connection.await;
+        });
+        let started = Instant::now();
+        let request = http::Request::get("https://localhost/.env")
+            .body(())
+            .unwrap();
+        let (response, _) = send_request
+            .ready()
+            .await
+            .unwrap()
+            .send_request(request, true)
+            .unwrap();
+        let mut stream = response.await.unwrap().into_body();
+        let reset = stream.data().await.unwrap().unwrap_err();
+        assert_eq!(reset.reason(), Some(h2::Reason::CANCEL));
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_secs(10), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_secs(30), "{:?}", elapsed);
+
+        // The only slot is free again: the next scanner is tarpitted.
+        let (mut client, server) = duplex(64 * 1024);
+        tokio::spawn(Jatai::serve_h1(server, handler, test_peer(), None, None));
+        let started = Instant::now();
+        client
+            .write_all(b"GET /etc/passwd HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+        let mut raw = Vec::new();
+        client.read_to_end(&mut raw).await.unwrap();
+        assert!(started.elapsed() >= Duration::from_secs(10));
+    }
+
     #[tokio::test]
     async fn h1_closes_without_replying_to_a_malformed_request_line() {
         let raw = h1_exchange(&[("index.html", b"home")], "\r\n\r\n", None).await;
@@ -762,10 +1540,10 @@ mod tests {
 
     #[tokio::test]
     async fn h1_closes_without_replying_to_non_utf8_bytes() {
-        let (_dir, cache) = cache_of(&[("index.html", b"home")]);
+        let (_dir, handler) = handler_of(&[("index.html", b"home")]);
         let (mut client, server) = duplex(1024);
 
-        let serving = tokio::spawn(Jatai::serve_h1(server, cache, test_peer(), None));
+        let serving = tokio::spawn(Jatai::serve_h1(server, handler, test_peer(), None, None));
         client
             .write_all(b"GET /\xff\xfe HTTP/1.1\r\n\r\n")
             .await
@@ -823,6 +1601,34 @@ mod tests {
         assert!(Jatai::read_h1_headers(&mut input).await.is_none());
     }
 
+    #[tokio::test]
+    async fn a_post_body_is_read_as_far_as_its_content_length() {
+        let head = "POST /x HTTP/1.1\r\nContent-Length: 11\r\n\r\n";
+        let (mut client, mut server) = duplex(64);
+        tokio::spawn(async move {
+            client.write_all(b"o world and more").await.unwrap();
+        });
+        let body = Jatai::read_h1_body(&mut server, head, b"hell".to_vec()).await;
+        assert_eq!(body.unwrap(), b"hello world");
+
+        let mut nothing = &b""[..];
+        let no_length = "POST /x HTTP/1.1\r\n\r\n";
+        let body = Jatai::read_h1_body(&mut nothing, no_length, Vec::new()).await;
+        assert_eq!(body.unwrap(), b"");
+    }
+
+    #[tokio::test]
+    async fn a_post_body_over_the_limit_is_refused() {
+        let head = format!(
+            "POST /x HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            MAX_BODY + 1
+        );
+        let mut input = &b""[..];
+        assert!(Jatai::read_h1_body(&mut input, &head, Vec::new())
+            .await
+            .is_none());
+    }
+
     #[tokio::test]
     async fn a_builder_without_binds_reports_no_addresses() {
         let server = JataiBuilder::new().build().await.unwrap();
@@ -899,7 +1705,8 @@ mod tests {
                 tcp: TcpListener::bind("127.0.0.1:0").await.unwrap(),
                 tls_acceptor: None,
             };
-            let config = crate::tls::load_config(super::tests::CERT, super::tests::KEY).unwrap();
+            let config =
+                crate::tls::load_config(super::tests::CERT, super::tests::KEY, None).unwrap();
             let tls = Listener {
                 tcp: TcpListener::bind("127.0.0.1:0").await.unwrap(),
                 tls_acceptor: Some(TlsAcceptor::from(Arc::new(config))),
@@ -919,7 +1726,7 @@ mod tests {
     /// developer's local `.env` from leaking into the result.
     static ENV_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
 
-    const ENV_VARS: [&str; 7] = [
+    const ENV_VARS: [&str; 16] = [
         "STATIC_DIR",
         "HTTP_BIND",
         "ENABLE_HTTPS",
@@ -927,6 +1734,15 @@ mod tests {
         "HTTPS_BIND",
         "CERT_PATH",
         "KEY_PATH",
+        "CLIENT_CA_PATH",
+        "CLIENT_AUTH_PATHS",
+        "PREVIEW_KEY",
+        "WEBMENTION_DIR",
+        "WEBMENTION_CA_PATH",
+        "HONEYPOT_LOG",
+        "HONEYPOT_TRAPS",
+        "TARPIT_SECS",
+        "TARPIT_MAX",
     ];
 
     fn with_env<T>(vars: &[(&str, &str)], f: impl FnOnce() -> T) -> T {
@@ -973,6 +1789,20 @@ mod tests {
         assert_eq!(config.static_dir, "pages");
         assert_eq!(config.http_bind, "0.0.0.0:8080");
         assert!(config.https.is_none());
+        assert!(config.preview_key.is_none());
+    }
+
+    #[test]
+    fn config_reads_the_preview_key() {
+        let config = with_env(
+            &[
+                ("STATIC_DIR", "pages"),
+                ("HTTP_BIND", "0.0.0.0:8080"),
+                ("PREVIEW_KEY", "s3cret"),
+            ],
+            Config::from_env,
+        );
+        assert_eq!(config.preview_key.as_deref(), Some("s3cret"));
     }
 
     #[test]
@@ -1026,6 +1856,209 @@ mod tests {
         assert!(!config.https.unwrap().enable_h3);
     }
 
+    const HTTPS_ENV: [(&str, &str); 6] = [
+        ("STATIC_DIR", "pages"),
+        ("HTTP_BIND", "0.0.0.0:80"),
+        ("ENABLE_HTTPS", "true"),
+        ("HTTPS_BIND", "0.0.0.0:443"),
+        ("CERT_PATH", "/etc/cert.pem"),
+        ("KEY_PATH", "/etc/key.pem"),
+    ];
+
+    #[test]
+    fn client_auth_is_off_unless_a_client_ca_is_configured() {
+        let config = with_env(&HTTPS_ENV, Config::from_env);
+        assert!(config.client_auth.is_none());
+    }
+
+    #[test]
+    fn client_auth_reads_the_ca_and_the_protected_paths() {
+        let mut vars = HTTPS_ENV.to_vec();
+        vars.push(("CLIENT_CA_PATH", "/etc/client-ca.pem"));
+        vars.push(("CLIENT_AUTH_PATHS", "/drafts, /internal/,,"));
+        let config = with_env(&vars, Config::from_env);
+
+        let client_auth = config.client_auth.unwrap();
+        assert_eq!(client_auth.ca_path, "/etc/client-ca.pem");
+        assert_eq!(client_auth.paths, ["/drafts", "/internal/"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "CLIENT_AUTH_PATHS is set but CLIENT_CA_PATH is not")]
+    fn protected_paths_without_a_client_ca_fail_at_startup() {
+        // No client could ever present a certificate, so the paths would be
+        // locked for everyone: a misconfiguration, not a policy.
+        let mut vars = HTTPS_ENV.to_vec();
+        vars.push(("CLIENT_AUTH_PATHS", "/drafts"));
+        with_env(&vars, Config::from_env);
+    }
+
+    #[test]
+    #[should_panic(expected = "CLIENT_AUTH_PATHS is set but ENABLE_HTTPS is not")]
+    fn protected_paths_without_https_fail_at_startup() {
+        // Over plain HTTP no client can present a certificate either; reading
+        // the paths only with HTTPS on used to serve them to everyone.
+        with_env(
+            &[
+                ("STATIC_DIR", "pages"),
+                ("HTTP_BIND", "0.0.0.0:80"),
+                ("ENABLE_HTTPS", "false"),
+                ("CLIENT_CA_PATH", "/etc/client-ca.pem"),
+                ("CLIENT_AUTH_PATHS", "/drafts"),
+            ],
+            Config::from_env,
+        );
+    }
+
+    #[tokio::test]
+    async fn h1_answers_403_for_a_protected_path() {
+        let (_dir, cache) = {
+            let dir = TempDir::new().unwrap();
+            fs::create_dir(dir.path().join("drafts")).unwrap();
+            fs::write(dir.path().join("drafts/index.html"), b"secret").unwrap();
+            let cache = FileCache::load(dir.path().to_str().unwrap());
+            (dir, Arc::new(cache))
+        };
+        let handler =
+            Arc::new(StaticFileHandler::new(cache).with_client_auth_paths(&["/drafts".into()]));
+
+        let exchange = |subject: Option<Arc<str>>| {
+            let handler = Arc::clone(&handler);
+            async move {
+                let (mut client, server) = duplex(64 * 1024);
+                let serving =
+                    tokio::spawn(Jatai::serve_h1(server, handler, test_peer(), subject, None));
+                client
+                    .write_all(b"GET /drafts/ HTTP/1.1\r\n\r\n")
+                    .await
+                    .unwrap();
+                serving.await.unwrap();
+                let mut raw = Vec::new();
+                client.read_to_end(&mut raw).await.unwrap();
+                split_response(&raw)
+            }
+        };
+
+        let (head, body) = exchange(None).await;
+        assert!(head.starts_with("HTTP/1.1 403 FORBIDDEN\r\n"));
+        assert_eq!(body, b"Forbidden");
+
+        let (head, body) = exchange(Some(Arc::from("CN=erick"))).await;
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert_eq!(body, b"secret");
+    }
+
+    #[test]
+    fn webmentions_are_off_unless_an_inbox_is_configured() {
+        let config = with_env(&HTTPS_ENV, Config::from_env);
+        assert!(config.webmentions.is_none());
+
+        let site = TempDir::new().unwrap();
+        fs::write(
+            site.path().join("site.toml"),
+            "base_url = \"https://example.com\"\ntitle = \"example.com\"\n",
+        )
+        .unwrap();
+        let static_dir = site.path().join("static");
+        let mut vars = HTTPS_ENV.to_vec();
+        vars.push(("STATIC_DIR", static_dir.to_str().unwrap()));
+        vars.push(("WEBMENTION_DIR", "/var/lib/jatai/webmentions"));
+        let webmentions = with_env(&vars, Config::from_env).webmentions.unwrap();
+        assert_eq!(webmentions.inbox, "/var/lib/jatai/webmentions");
+        assert_eq!(webmentions.site_url, "https://example.com");
+        assert_eq!(webmentions.roots_path, SYSTEM_ROOTS);
+    }
+
+    #[test]
+    #[should_panic(expected = "WEBMENTION_DIR is set but site.toml")]
+    fn an_inbox_without_a_site_toml_fails_at_startup() {
+        // Without its base_url no target could be told to be ours.
+        let mut vars = HTTPS_ENV.to_vec();
+        vars.push(("WEBMENTION_DIR", "/var/lib/jatai/webmentions"));
+        with_env(&vars, Config::from_env);
+    }
+
+    #[test]
+    fn the_honeypot_log_is_kept_only_where_configured() {
+        assert!(with_env(&HTTPS_ENV, Config::from_env)
+            .honeypot_log
+            .is_none());
+        let mut vars = HTTPS_ENV.to_vec();
+        vars.push(("HONEYPOT_LOG", "/var/lib/jatai/honeypot.jsonl"));
+        let config = with_env(&vars, Config::from_env);
+        assert_eq!(
+            config.honeypot_log.as_deref(),
+            Some("/var/lib/jatai/honeypot.jsonl")
+        );
+    }
+
+    #[test]
+    fn the_built_in_traps_are_set_unless_a_directory_is_given() {
+        assert!(with_env(&HTTPS_ENV, Config::from_env)
+            .honeypot_traps
+            .is_none());
+        let mut vars = HTTPS_ENV.to_vec();
+        vars.push(("HONEYPOT_TRAPS", "/etc/jatai/traps"));
+        let config = with_env(&vars, Config::from_env);
+        assert_eq!(config.honeypot_traps.as_deref(), Some("/etc/jatai/traps"));
+    }
+
+    #[tokio::test]
+    async fn reloading_traps_swaps_them_in_unless_they_no_longer_load() {
+        let (_static_dir, handler) = handler_of(&[]);
+        let traps = TempDir::new().unwrap();
+        let dir = traps.path().to_str().unwrap();
+        let get = |path: &str| {
+            let request = format!("GET {} HTTP/1.1\r\n\r\n", path);
+            let request = Request::parse_h1(&request, test_peer()).unwrap();
+            String::from_utf8(handler.handle(&request).body).unwrap()
+        };
+        assert!(get("/etc/passwd").starts_with("root:x:0:0:"));
+
+        fs::write(
+            traps.path().join("traps.toml"),
+            "[[trap]]\nname = \"vault\"\nmatch = [{ name = \"vault\" }]\nbait = \"vault.txt\"\n",
+        )
+        .unwrap();
+        fs::write(traps.path().join("vault.txt"), "root_token = s.fake\n").unwrap();
+        Jatai::reload_traps(dir, &handler).await;
+        assert_eq!(get("/vault"), "root_token = s.fake\n");
+        assert!(!get("/etc/passwd").starts_with("root:x:0:0:"));
+
+        // Broken on disk: the traps in use stay.
+        fs::write(traps.path().join("vault.txt"), "").unwrap();
+        Jatai::reload_traps(dir, &handler).await;
+        assert_eq!(get("/vault"), "root_token = s.fake\n");
+    }
+
+    #[tokio::test]
+    async fn traps_that_do_not_load_fail_the_build() {
+        let traps = TempDir::new().unwrap();
+        let built = JataiBuilder::new()
+            .honeypot_traps(traps.path().to_str().unwrap())
+            .build()
+            .await;
+        assert!(built.is_err());
+    }
+
+    #[test]
+    fn the_tarpit_is_off_unless_given_a_duration() {
+        assert!(with_env(&HTTPS_ENV, Config::from_env).tarpit.is_none());
+        let tarpit = |extra: &[(&'static str, &'static str)]| {
+            let mut vars = HTTPS_ENV.to_vec();
+            vars.extend_from_slice(extra);
+            with_env(&vars, Config::from_env).tarpit
+        };
+        assert!(tarpit(&[("TARPIT_SECS", "0")]).is_none());
+        assert!(tarpit(&[("TARPIT_SECS", "soon")]).is_none());
+
+        let config = tarpit(&[("TARPIT_SECS", "30")]).unwrap();
+        assert_eq!(config.duration, Duration::from_secs(30));
+        assert_eq!(config.max, TARPIT_MAX);
+        let config = tarpit(&[("TARPIT_SECS", "30"), ("TARPIT_MAX", "4")]).unwrap();
+        assert_eq!(config.max, 4);
+    }
+
     #[test]
     #[should_panic(expected = "HTTP_BIND environment variable not set")]
     fn a_missing_required_variable_fails_loudly_at_startup() {
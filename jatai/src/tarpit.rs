@@ -0,0 +1,125 @@
+//! The tarpit: honeypot bait sent a few bytes at a time, so a scanner that
+//! waits for the whole answer spends its time here instead of on its next
+//! target.
+//!
+//! The headers go out at once and the body follows in pieces spread evenly
+//! over the configured time, the connection held open throughout. Each
+//! tarpitted response holds a slot until its last piece is sent; with every
+//! slot taken, bait goes out at full speed again, so however many scanners
+//! arrive at once the tarpit never holds more than its share of connections.
+//! A client that stops reading, or never grants flow-control credit, would
+//! hold its slot for good; so each send has a deadline, and past it the
+//! stream is dropped.
+
+use std::{sync::Arc, time::Duration};
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// The longest wait between two pieces of a body.
+const TICK: Duration = Duration::from_secs(1);
+/// How long past the configured duration a send may run, for a client that
+/// reads slowly but does read.
+const GRACE: Duration = Duration::from_secs(10);
+
+pub(crate) struct Tarpit {
+    duration: Duration,
+    slots: Arc<Semaphore>,
+}
+
+/// One response's slot in the tarpit, and the pace to send its body at.
+pub(crate) struct Trickle {
+    _slot: OwnedSemaphorePermit,
+    piece: usize,
+    interval: Duration,
+    deadline: Duration,
+}
+
+impl Tarpit {
+    /// Spread each body over `duration`, for at most `max` responses at once.
+    pub(crate) fn new(duration: Duration, max: usize) -> Self {
+        Self {
+            duration,
+            slots: Arc::new(Semaphore::new(max)),
+        }
+    }
+
+    /// The pace for a body of `len` bytes: a piece every `TICK` or so, or
+    /// a byte at a time when the body is shorter than that. `None` when
+    /// every slot is taken, or there is nothing to send.
+    pub(crate) fn admit(&self, len: usize) -> Option<Trickle> {
+        if len == 0 {
+            return None;
+        }
+        let slot = Arc::clone(&self.slots).try_acquire_owned().ok()?;
+        let ticks = self.duration.as_nanos().div_ceil(TICK.as_nanos()).max(1);
+        let pieces = usize::try_from(ticks).unwrap_or(usize::MAX).min(len);
+        let piece = len.div_ceil(pieces);
+        let pieces = len.div_ceil(piece);
+        Some(Trickle {
+            _slot: slot,
+            piece,
+            interval: self.duration / u32::try_from(pieces).unwrap_or(u32::MAX),
+            deadline: self.duration + GRACE,
+        })
+    }
+}
+
+impl Trickle {
+    /// `body` in the pieces to send, each after a `wait`.
+    pub(crate) fn pieces<'a>(&self, body: &'a [u8]) -> std::slice::Chunks<'a, u8> {
+        body.chunks(self.piece)
+    }
+
+    pub(crate) async fn wait(&self) {
+        tokio::time::sleep(self.interval).await;
+    }
+
+    /// `send` if it finishes within the duration and `GRACE`, else `None`,
+    /// so a stalled client gives its slot back.
+    pub(crate) async fn within<T>(&self, send: impl std::future::Future<Output = T>) -> Option<T> {
+        tokio::time::timeout(self.deadline, send).await.ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_body_is_spread_over_the_whole_duration() {
+        let tarpit = Tarpit::new(Duration::from_secs(10), 4);
+
+        let trickle = tarpit.admit(1000).unwrap();
+        assert_eq!(trickle.pieces(&[0; 1000]).count(), 10);
+        assert_eq!(trickle.interval, Duration::from_secs(1));
+
+        // Shorter than the number of ticks: a byte at a time, further apart.
+        let trickle = tarpit.admit(4).unwrap();
+        assert_eq!(trickle.pieces(b"root").count(), 4);
+        assert_eq!(trickle.interval, Duration::from_millis(2500));
+
+        // Uneven: the pieces still add up to the body.
+        let trickle = tarpit.admit(1001).unwrap();
+        let body = [7; 1001];
+        assert_eq!(trickle.pieces(&body).map(<[u8]>::len).sum::<usize>(), 1001);
+        assert!(trickle.interval * trickle.pieces(&body).count() as u32 <= tarpit.duration);
+    }
+
+    #[test]
+    fn no_more_than_max_responses_are_held_at_once() {
+        let tarpit = Tarpit::new(Duration::from_secs(10), 2);
+        let first = tarpit.admit(10).unwrap();
+        let _second = tarpit.admit(10).unwrap();
+        assert!(tarpit.admit(10).is_none());
+
+        drop(first);
+        assert!(tarpit.admit(10).is_some());
+    }
+
+    #[test]
+    fn an_empty_body_is_not_held() {
+        let tarpit = Tarpit::new(Duration::from_secs(10), 1);
+        assert!(tarpit.admit(0).is_none());
+        assert!(tarpit.admit(1).is_some());
+    }
+}
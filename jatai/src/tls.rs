@@ -4,21 +4,19 @@ use std::{
     sync::Arc,
 };
 
-use rustls::ServerConfig;
+use rustls::{
+    pki_types::CertificateDer,
+    server::{danger::ClientCertVerifier, WebPkiClientVerifier},
+    ClientConfig, RootCertStore, ServerConfig,
+};
 use rustls_pemfile::{certs, private_key};
 
-pub fn load_config(cert_path: &str, key_path: &str) -> io::Result<ServerConfig> {
-    let cert_file = File::open(cert_path)?;
-    let key_file = File::open(key_path)?;
-
-    let certs: Vec<_> = certs(&mut BufReader::new(cert_file)).collect::<Result<_, _>>()?;
-    let key = private_key(&mut BufReader::new(key_file))?
-        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "No private key found"))?;
-
-    let mut config = ServerConfig::builder()
-        .with_no_client_auth()
-        .with_single_cert(certs, key)
-        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+pub fn load_config(
+    cert_path: &str,
+    key_path: &str,
+    client_ca_path: Option<&str>,
+) -> io::Result<ServerConfig> {
+    let mut config = server_config(cert_path, key_path, client_ca_path)?;
 
     // Offer HTTP/1.1 as well as h2: many clients (e.g. RSS fetchers using
     // undici/node-fetch) connect over TLS speaking HTTP/1.1 and never offer
@@ -28,7 +26,49 @@ pub fn load_config(cert_path: &str, key_path: &str) -> io::Result<ServerConfig>
     Ok(config)
 }
 
-pub fn load_quic_config(cert_path: &str, key_path: &str) -> io::Result<quinn::ServerConfig> {
+pub fn load_quic_config(
+    cert_path: &str,
+    key_path: &str,
+    client_ca_path: Option<&str>,
+) -> io::Result<quinn::ServerConfig> {
+    let mut tls_config = server_config(cert_path, key_path, client_ca_path)?;
+    tls_config.alpn_protocols = vec![b"h3".to_vec()];
+
+    let quic_config = quinn::crypto::rustls::QuicServerConfig::try_from(tls_config)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+
+    Ok(quinn::ServerConfig::with_crypto(Arc::new(quic_config)))
+}
+
+/// A TLS client, for fetching Webmention sources, that trusts the
+/// certificates in the PEM bundle at `roots_path`, such as the system's
+/// `/etc/ssl/certs/ca-certificates.crt`. Entries rustls cannot use are
+/// skipped, as system bundles carry a few.
+pub fn client_config(roots_path: &str) -> io::Result<ClientConfig> {
+    let file = File::open(roots_path)?;
+    let certs: Vec<_> = certs(&mut BufReader::new(file)).collect::<Result<_, _>>()?;
+
+    let mut roots = RootCertStore::empty();
+    roots.add_parsable_certificates(certs);
+    if roots.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "No CA certificate found",
+        ));
+    }
+
+    let mut config = ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(config)
+}
+
+fn server_config(
+    cert_path: &str,
+    key_path: &str,
+    client_ca_path: Option<&str>,
+) -> io::Result<ServerConfig> {
     let cert_file = File::open(cert_path)?;
     let key_file = File::open(key_path)?;
 
@@ -36,17 +76,172 @@ pub fn load_quic_config(cert_path: &str, key_path: &str) -> io::Result<quinn::Se
     let key = private_key(&mut BufReader::new(key_file))?
         .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "No private key found"))?;
 
-    let mut tls_config = ServerConfig::builder()
-        .with_no_client_auth()
+    let builder = ServerConfig::builder();
+    let builder = match client_ca_path {
+        Some(path) => builder.with_client_cert_verifier(client_verifier(path)?),
+        None => builder.with_no_client_auth(),
+    };
+
+    builder
         .with_single_cert(certs, key)
-        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
 
-    tls_config.alpn_protocols = vec![b"h3".to_vec()];
+/// Verifies client certificates against the CA at `ca_path`, without making
+/// one mandatory.
+///
+/// The handshake only decides whether a certificate is genuine; whether a
+/// request needs one at all is decided per path by the handler. A client that
+/// presents nothing still gets the public pages, while one that presents a
+/// certificate the CA did not sign is refused at the handshake.
+fn client_verifier(ca_path: &str) -> io::Result<Arc<dyn ClientCertVerifier>> {
+    let ca_file = File::open(ca_path)?;
 
-    let quic_config = quinn::crypto::rustls::QuicServerConfig::try_from(tls_config)
-        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+    let mut roots = RootCertStore::empty();
+    for cert in certs(&mut BufReader::new(ca_file)) {
+        roots
+            .add(cert?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    }
+    if roots.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "No CA certificate found",
+        ));
+    }
 
-    Ok(quinn::ServerConfig::with_crypto(Arc::new(quic_config)))
+    WebPkiClientVerifier::builder(Arc::new(roots))
+        .allow_unauthenticated()
+        .build()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// The subject of a verified client certificate, as `C=BR, O=acme, CN=erick`.
+///
+/// Only ever called on a chain rustls has already verified, so this reads the
+/// one field it needs instead of validating the certificate a second time. An
+/// attribute it has no short name for is shown by its dotted OID.
+pub fn client_subject(chain: &[CertificateDer<'_>]) -> Option<String> {
+    let mut cert = Der::new(chain.first()?.as_ref()).sequence()?;
+    let mut tbs = cert.sequence()?;
+
+    // Optional explicit [0] version, then serial, signature algorithm, issuer
+    // and validity: everything before the subject.
+    if tbs.peek() == Some(0xa0) {
+        tbs.any()?;
+    }
+    for _ in 0..4 {
+        tbs.any()?;
+    }
+    let mut subject = tbs.sequence()?;
+
+    let mut parts = Vec::new();
+    while !subject.is_empty() {
+        let mut rdn = subject.tagged(0x31)?;
+        while !rdn.is_empty() {
+            let mut attribute = rdn.sequence()?;
+            let oid = attribute.tagged(0x06)?.rest;
+            let value = String::from_utf8_lossy(attribute.any()?.rest);
+            parts.push(format!("{}={}", attribute_name(oid), value));
+        }
+    }
+    Some(parts.join(", "))
+}
+
+fn attribute_name(oid: &[u8]) -> String {
+    // 2.5.4.x, the X.520 attribute types a certificate subject is built from.
+    match oid {
+        [0x55, 0x04, 0x03] => "CN".into(),
+        [0x55, 0x04, 0x06] => "C".into(),
+        [0x55, 0x04, 0x07] => "L".into(),
+        [0x55, 0x04, 0x08] => "ST".into(),
+        [0x55, 0x04, 0x0a] => "O".into(),
+        [0x55, 0x04, 0x0b] => "OU".into(),
+        _ => dotted_oid(oid),
+    }
+}
+
+fn dotted_oid(oid: &[u8]) -> String {
+    let mut arcs: Vec<u64> = Vec::new();
+    let mut value: u64 = 0;
+    for byte in oid {
+        value = value << 7 | u64::from(byte & 0x7f);
+        if byte & 0x80 != 0 {
+            continue;
+        }
+        if arcs.is_empty() {
+            // The first subidentifier packs the first two arcs as 40x + y.
+            let first = (value / 40).min(2);
+            arcs.push(first);
+            arcs.push(value - first * 40);
+        } else {
+            arcs.push(value);
+        }
+        value = 0;
+    }
+    arcs.iter()
+        .map(|arc| arc.to_string())
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Just enough DER to walk to a certificate's subject: definite lengths only,
+/// which is all DER allows.
+struct Der<'a> {
+    rest: &'a [u8],
+}
+
+impl<'a> Der<'a> {
+    fn new(rest: &'a [u8]) -> Self {
+        Self { rest }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.rest.is_empty()
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.rest.first().copied()
+    }
+
+    /// The next element, whatever its tag, as a reader over its contents.
+    fn any(&mut self) -> Option<Der<'a>> {
+        let (_tag, rest) = self.rest.split_first()?;
+        let (&len_byte, mut rest) = rest.split_first()?;
+
+        let len = if len_byte & 0x80 == 0 {
+            usize::from(len_byte)
+        } else {
+            let count = usize::from(len_byte & 0x7f);
+            if count == 0 || count > std::mem::size_of::<usize>() || rest.len() < count {
+                return None;
+            }
+            let (len_bytes, after) = rest.split_at(count);
+            rest = after;
+            len_bytes
+                .iter()
+                .fold(0usize, |len, &b| len << 8 | usize::from(b))
+        };
+
+        if rest.len() < len {
+            return None;
+        }
+        let (contents, after) = rest.split_at(len);
+        self.rest = after;
+        Some(Der::new(contents))
+    }
+
+    /// The next element, which must carry `tag`.
+    fn tagged(&mut self, tag: u8) -> Option<Der<'a>> {
+        if self.peek()? != tag {
+            return None;
+        }
+        self.any()
+    }
+
+    fn sequence(&mut self) -> Option<Der<'a>> {
+        self.tagged(0x30)
+    }
 }
 
 #[cfg(test)]
@@ -55,12 +250,20 @@ mod tests {
 
     const CERT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../example/cert.pem");
     const KEY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../example/key.pem");
+    const CLIENT_CA: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../example/client-ca.pem");
+    const CLIENT_CERT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../example/client.pem");
+
+    fn pem_chain(path: &str) -> Vec<CertificateDer<'static>> {
+        certs(&mut BufReader::new(File::open(path).unwrap()))
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
 
     #[test]
     fn tcp_config_offers_h2_first_then_http1() {
         // Order encodes preference: clients that offer both get HTTP/2, and
         // clients that only speak HTTP/1.1 still find a match.
-        let config = load_config(CERT, KEY).unwrap();
+        let config = load_config(CERT, KEY, None).unwrap();
         assert_eq!(
             config.alpn_protocols,
             vec![b"h2".to_vec(), b"http/1.1".to_vec()]
@@ -69,18 +272,28 @@ mod tests {
 
     #[test]
     fn quic_config_offers_only_h3() {
-        assert!(load_quic_config(CERT, KEY).is_ok());
+        assert!(load_quic_config(CERT, KEY, None).is_ok());
+    }
+
+    #[test]
+    fn a_client_trusts_a_bundle_and_speaks_http1() {
+        let config = client_config(CERT).unwrap();
+        assert_eq!(config.alpn_protocols, vec![b"http/1.1".to_vec()]);
+
+        let empty = tempfile::NamedTempFile::new().unwrap();
+        let err = client_config(empty.path().to_str().unwrap()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
     }
 
     #[test]
     fn missing_certificate_file_is_an_error() {
-        let err = load_config("/nonexistent/cert.pem", KEY).unwrap_err();
+        let err = load_config("/nonexistent/cert.pem", KEY, None).unwrap_err();
         assert_eq!(err.kind(), io::ErrorKind::NotFound);
     }
 
     #[test]
     fn missing_key_file_is_an_error() {
-        let err = load_config(CERT, "/nonexistent/key.pem").unwrap_err();
+        let err = load_config(CERT, "/nonexistent/key.pem", None).unwrap_err();
         assert_eq!(err.kind(), io::ErrorKind::NotFound);
     }
 
@@ -88,7 +301,7 @@ mod tests {
     fn a_key_file_without_a_private_key_is_rejected() {
         let mut file = tempfile::NamedTempFile::new().unwrap();
         std::io::Write::write_all(&mut file, b"not a pem file\n").unwrap();
-        let err = load_config(CERT, file.path().to_str().unwrap()).unwrap_err();
+        let err = load_config(CERT, file.path().to_str().unwrap(), None).unwrap_err();
         assert_eq!(err.kind(), io::ErrorKind::InvalidData);
         assert!(err.to_string().contains("No private key"));
     }
@@ -97,7 +310,63 @@ mod tests {
     fn an_empty_certificate_chain_is_rejected() {
         let mut file = tempfile::NamedTempFile::new().unwrap();
         std::io::Write::write_all(&mut file, b"").unwrap();
-        let err = load_config(file.path().to_str().unwrap(), KEY).unwrap_err();
+        let err = load_config(file.path().to_str().unwrap(), KEY, None).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn a_client_ca_is_accepted_for_both_transports() {
+        assert!(load_config(CERT, KEY, Some(CLIENT_CA)).is_ok());
+        assert!(load_quic_config(CERT, KEY, Some(CLIENT_CA)).is_ok());
+    }
+
+    #[test]
+    fn a_client_ca_file_without_certificates_is_rejected() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(&mut file, b"").unwrap();
+        let err = load_config(CERT, KEY, Some(file.path().to_str().unwrap())).unwrap_err();
         assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("No CA certificate"));
+    }
+
+    #[test]
+    fn a_missing_client_ca_file_is_an_error() {
+        let err = load_config(CERT, KEY, Some("/nonexistent/ca.pem")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn the_subject_is_read_in_certificate_order() {
+        assert_eq!(
+            client_subject(&pem_chain(CLIENT_CERT)).as_deref(),
+            Some("C=BR, O=erickcestari.dev, CN=erick")
+        );
+        assert_eq!(
+            client_subject(&pem_chain(CERT)).as_deref(),
+            Some("CN=localhost")
+        );
+    }
+
+    #[test]
+    fn no_certificate_means_no_subject() {
+        assert_eq!(client_subject(&[]), None);
+    }
+
+    #[test]
+    fn a_truncated_certificate_yields_no_subject_instead_of_panicking() {
+        let chain = pem_chain(CLIENT_CERT);
+        let der = chain[0].as_ref();
+        for len in [0, 1, 2, 10, der.len() / 2] {
+            let cut = CertificateDer::from(der[..len].to_vec());
+            assert_eq!(client_subject(&[cut]), None, "cut at {}", len);
+        }
+    }
+
+    #[test]
+    fn unknown_attributes_are_named_by_their_dotted_oid() {
+        // 1.2.840.113549.1.9.1, emailAddress: no short name in the table.
+        let oid = [0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x01];
+        assert_eq!(attribute_name(&oid), "1.2.840.113549.1.9.1");
+        assert_eq!(attribute_name(&[0x55, 0x04, 0x03]), "CN");
     }
 }
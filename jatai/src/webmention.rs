@@ -0,0 +1,782 @@
+//! Receiving Webmentions (<https://www.w3.org/TR/webmention/>): another site
+//! telling this one that a page of theirs links to a post here.
+//!
+//! A POST to `/.well-known/webmention` names the linking page, `source`, and
+//! the post, `target`. The handler checks what it can without the network:
+//! both are http(s) URLs, and the target is a page this site serves. It then
+//! answers 202 and queues the mention for the [`Verifier`], which fetches the
+//! source, one mention at a time, and keeps the mention only if the source
+//! really links to the target.
+//!
+//! A kept mention is a file in the inbox, named for its source and target so
+//! that sending it again replaces it. blog-gen renders it under the post on
+//! its next build:
+//!
+//! ```json
+//! {
+//!   "source": "https://example.com/a-reply",
+//!   "target": "https://erickcestari.dev/blog/a-post/",
+//!   "title": "A reply",
+//!   "verified": 1760000000
+//! }
+//! ```
+//!
+//! A mention whose source is gone, or no longer links to the target, is
+//! taken back out.
+
+use std::{
+    fs, io,
+    net::{IpAddr, SocketAddr},
+    path::PathBuf,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use http::Method;
+use rustls::pki_types::ServerName;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio::time::{timeout, Duration};
+use tokio_rustls::TlsConnector;
+
+use crate::{cache::FileCache, preview, Request, Response};
+
+pub(crate) const ENDPOINT: &str = "/.well-known/webmention";
+
+/// Mentions waiting to be verified; past this, senders are asked to come
+/// back later.
+const QUEUE: usize = 64;
+/// Time to fetch a source, redirects included.
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+/// Most of a source that is read. A link past this goes unseen.
+const MAX_SOURCE: usize = 1 << 20;
+const MAX_REDIRECTS: usize = 3;
+/// Longest title kept for a mention, in characters.
+const MAX_TITLE: usize = 200;
+
+const USER_AGENT: &str = "jatai (Webmention verifier)";
+
+struct Mention {
+    source: String,
+    target: String,
+}
+
+/// The handler's side: checks a mention and queues it.
+pub(crate) struct Receiver {
+    /// The site's origin, which every target starts with.
+    site_url: String,
+    queue: mpsc::Sender<Mention>,
+}
+
+/// Verifies queued mentions, keeping the good ones in `inbox`.
+pub(crate) struct Verifier {
+    inbox: PathBuf,
+    queue: mpsc::Receiver<Mention>,
+    tls: TlsConnector,
+    /// Fetch sources on loopback and private addresses too. Only tests turn
+    /// it on: otherwise a mention could make the server probe its own network.
+    allow_local: bool,
+}
+
+/// A receiver for the site at `site_url`, and the verifier it queues for,
+/// which keeps mentions in `inbox` and fetches HTTPS sources with `tls`.
+pub(crate) fn queue(
+    site_url: &str,
+    inbox: PathBuf,
+    tls: rustls::ClientConfig,
+) -> (Receiver, Verifier) {
+    let (tx, rx) = mpsc::channel(QUEUE);
+    let receiver = Receiver {
+        site_url: site_url.trim_end_matches('/').to_string(),
+        queue: tx,
+    };
+    let verifier = Verifier {
+        inbox,
+        queue: rx,
+        tls: TlsConnector::from(Arc::new(tls)),
+        allow_local: false,
+    };
+    (receiver, verifier)
+}
+
+impl Receiver {
+    /// Answer a request to the endpoint: 202 once the mention is queued.
+    pub(crate) fn receive(&self, request: &Request, cache: &FileCache) -> Response {
+        if request.method != Method::POST {
+            return Response::plain(405, "Webmentions are sent with POST");
+        }
+        let (Some(source), Some(target)) =
+            (request.form_param("source"), request.form_param("target"))
+        else {
+            return Response::plain(400, "source and target are both required");
+        };
+        if Url::parse(&source).is_none() {
+            return Response::plain(400, "source is not an http(s) URL");
+        }
+        if source == target {
+            return Response::plain(400, "source and target are the same page");
+        }
+        if !self.is_page(&target, cache) {
+            return Response::plain(400, "target is not a page on this site");
+        }
+        match self.queue.try_send(Mention { source, target }) {
+            Ok(()) => Response::plain(202, "Accepted"),
+            Err(_) => Response::plain(503, "Too many Webmentions waiting; try again later"),
+        }
+    }
+
+    /// Whether `target` is a published page of this site. Drafts are left
+    /// out, so the endpoint cannot be used to find out which ones exist.
+    fn is_page(&self, target: &str, cache: &FileCache) -> bool {
+        let Some(rest) = target.strip_prefix(&self.site_url) else {
+            return false;
+        };
+        let path = rest.split(['?', '#']).next().unwrap_or_default();
+        let path = if path.is_empty() { "/" } else { path };
+        path.starts_with('/')
+            && preview::scope(path).is_none()
+            && cache
+                .get(path)
+                .is_some_and(|c| c.content_type == "text/html")
+    }
+}
+
+/// What a source turned out to be.
+enum Source {
+    Page(String),
+    Gone,
+}
+
+impl Verifier {
+    /// Verify mentions as they are queued, until the receiver is dropped.
+    pub(crate) async fn run(mut self) {
+        while let Some(mention) = self.queue.recv().await {
+            let outcome = match timeout(FETCH_TIMEOUT, self.verify(&mention)).await {
+                Ok(Ok(outcome)) => outcome.to_string(),
+                Ok(Err(e)) => format!("unverified ({})", e),
+                Err(_) => "unverified (timed out)".to_string(),
+            };
+            println!(
+                "webmention {} -> {}: {}",
+                mention.source.escape_default(),
+                mention.target.escape_default(),
+                outcome
+            );
+        }
+    }
+
+    async fn verify(&self, mention: &Mention) -> io::Result<&'static str> {
+        let name = file_name(mention);
+        let file = self.inbox.join(&name);
+        let html = match self.fetch(&mention.source).await? {
+            Source::Page(html) => html,
+            Source::Gone => {
+                remove(&file)?;
+                return Ok("source is gone");
+            }
+        };
+        if !links_to(&html, &mention.target) {
+            remove(&file)?;
+            return Ok("source does not link to target");
+        }
+
+        let verified = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let kept = json!({
+            "source": mention.source,
+            "target": mention.target,
+            "title": title(&html),
+            "verified": verified,
+        });
+        // Written aside and renamed, so a build never reads half a file.
+        let partial = self.inbox.join(format!(".{}.tmp", name));
+        fs::write(&partial, serde_json::to_vec_pretty(&kept)?)?;
+        fs::rename(&partial, &file)?;
+        Ok("verified")
+    }
+
+    /// The page at `url`, after any redirects.
+    async fn fetch(&self, url: &str) -> io::Result<Source> {
+        let mut url = Url::parse(url).ok_or_else(|| invalid("not an http(s) URL"))?;
+        for _ in 0..=MAX_REDIRECTS {
+            let response = self.get(&url).await?;
+            match response.status {
+                200 => {
+                    let html = String::from_utf8_lossy(&response.body).into_owned();
+                    return Ok(Source::Page(html));
+                }
+                404 | 410 => return Ok(Source::Gone),
+                301 | 302 | 303 | 307 | 308 => {
+                    let location = response
+                        .location
+                        .ok_or_else(|| invalid("redirect without a Location"))?;
+                    url = url
+                        .join(&location)
+                        .ok_or_else(|| invalid("redirect to a URL that is not http(s)"))?;
+                }
+                status => return Err(invalid(&format!("source answered {}", status))),
+            }
+        }
+        Err(invalid("too many redirects"))
+    }
+
+    async fn get(&self, url: &Url) -> io::Result<Fetched> {
+        let addr = self.resolve(url).await?;
+        let tcp = TcpStream::connect(addr).await?;
+        let request = format!(
+            "GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: {}\r\nAccept: text/html\r\nConnection: close\r\n\r\n",
+            url.path,
+            url.authority(),
+            USER_AGENT
+        );
+        let raw = if url.https {
+            let name = ServerName::try_from(url.host.clone())
+                .map_err(|e| invalid(&format!("host {}: {}", url.host, e)))?;
+            let mut tls = self.tls.connect(name, tcp).await?;
+            tls.write_all(request.as_bytes()).await?;
+            read_capped(&mut tls).await?
+        } else {
+            let mut tcp = tcp;
+            tcp.write_all(request.as_bytes()).await?;
+            read_capped(&mut tcp).await?
+        };
+        parse_response(&raw)
+    }
+
+    /// The address to fetch `url` from: the first the host resolves to, and
+    /// only when that is a public one.
+    async fn resolve(&self, url: &Url) -> io::Result<SocketAddr> {
+        let addr = tokio::net::lookup_host((url.host.as_str(), url.port))
+            .await?
+            .next()
+            .ok_or_else(|| invalid("host has no address"))?;
+        if !self.allow_local && !is_public(addr.ip()) {
+            return Err(invalid("source is on a private address"));
+        }
+        Ok(addr)
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn remove(file: &std::path::Path) -> io::Result<()> {
+    match fs::remove_file(file) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// One file per source and target, so a mention sent again replaces the last.
+fn file_name(mention: &Mention) -> String {
+    let digest = Sha256::new()
+        .chain_update(&mention.source)
+        .chain_update(b"\n")
+        .chain_update(&mention.target)
+        .finalize();
+    let hex: String = digest[..16].iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}.json", hex)
+}
+
+/// Anything but loopback, private, link-local and shared (CGNAT) addresses.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            !(v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                || (a == 100 && b & 0xc0 == 64))
+        }
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => is_public(IpAddr::V4(v4)),
+            None => {
+                let first = v6.segments()[0];
+                !(v6.is_loopback()
+                    || v6.is_unspecified()
+                    || first & 0xfe00 == 0xfc00
+                    || first & 0xffc0 == 0xfe80)
+            }
+        },
+    }
+}
+
+/// An absolute http(s) URL, as far as fetching one needs.
+#[derive(Debug, Clone, PartialEq)]
+struct Url {
+    https: bool,
+    /// Without the brackets of an IPv6 literal.
+    host: String,
+    port: u16,
+    /// Path and query, starting with `/`.
+    path: String,
+}
+
+impl Url {
+    fn parse(url: &str) -> Option<Self> {
+        if url.chars().any(|c| c.is_whitespace() || c.is_control()) {
+            return None;
+        }
+        let (https, rest) = match url.strip_prefix("https://") {
+            Some(rest) => (true, rest),
+            None => (false, url.strip_prefix("http://")?),
+        };
+        let rest = rest.split('#').next().unwrap_or_default();
+        let (authority, path) = rest.split_at(rest.find(['/', '?']).unwrap_or(rest.len()));
+        if authority.contains('@') {
+            return None;
+        }
+        let (host, port) = match authority.strip_prefix('[') {
+            Some(literal) => {
+                let (host, after) = literal.split_once(']')?;
+                let port = match after {
+                    "" => None,
+                    _ => Some(after.strip_prefix(':')?),
+                };
+                (host, port)
+            }
+            None => match authority.split_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (authority, None),
+            },
+        };
+        if host.is_empty() {
+            return None;
+        }
+        let port = match port {
+            Some(port) => port.parse().ok()?,
+            None if https => 443,
+            None => 80,
+        };
+        let path = match path {
+            "" => "/".to_string(),
+            query if query.starts_with('?') => format!("/{}", query),
+            path => path.to_string(),
+        };
+        Some(Self {
+            https,
+            host: host.to_string(),
+            port,
+            path,
+        })
+    }
+
+    /// `host[:port]`, as the `Host` header wants it.
+    fn authority(&self) -> String {
+        let host = if self.host.contains(':') {
+            format!("[{}]", self.host)
+        } else {
+            self.host.clone()
+        };
+        let default = if self.https { 443 } else { 80 };
+        if self.port == default {
+            host
+        } else {
+            format!("{}:{}", host, self.port)
+        }
+    }
+
+    /// A redirect's `location`, resolved against this URL.
+    fn join(&self, location: &str) -> Option<Self> {
+        if location.starts_with("//") {
+            let scheme = if self.https { "https:" } else { "http:" };
+            Self::parse(&format!("{}{}", scheme, location))
+        } else if location.starts_with('/') {
+            Some(Self {
+                path: location.split('#').next().unwrap_or_default().to_string(),
+                ..self.clone()
+            })
+        } else {
+            Self::parse(location)
+        }
+    }
+}
+
+/// Everything the source sends, up to `MAX_SOURCE` of body.
+async fn read_capped<S: AsyncRead + Unpin>(stream: &mut S) -> io::Result<Vec<u8>> {
+    let mut raw = Vec::new();
+    let limit = (MAX_SOURCE + 16 * 1024) as u64;
+    match stream.take(limit).read_to_end(&mut raw).await {
+        Ok(_) => Ok(raw),
+        // Plenty of servers close without TLS's close_notify; what they sent
+        // before that still arrived.
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof && !raw.is_empty() => Ok(raw),
+        Err(e) => Err(e),
+    }
+}
+
+struct Fetched {
+    status: u16,
+    location: Option<String>,
+    body: Vec<u8>,
+}
+
+fn parse_response(raw: &[u8]) -> io::Result<Fetched> {
+    let end = raw
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .ok_or_else(|| invalid("source sent no complete response"))?;
+    let head = std::str::from_utf8(&raw[..end]).map_err(|_| invalid("headers are not UTF-8"))?;
+    let mut lines = head.split("\r\n");
+    let status = lines
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| invalid("malformed status line"))?;
+
+    let mut location = None;
+    let mut chunked = false;
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        if name.eq_ignore_ascii_case("location") {
+            location = Some(value.trim().to_string());
+        } else if name.eq_ignore_ascii_case("transfer-encoding") {
+            chunked = value.to_ascii_lowercase().contains("chunked");
+        }
+    }
+
+    let body = &raw[end + 4..];
+    let body = if chunked {
+        dechunk(body)
+    } else {
+        body.to_vec()
+    };
+    Ok(Fetched {
+        status,
+        location,
+        body,
+    })
+}
+
+/// A chunked body put back together. One cut short, by the size cap or the
+/// peer, keeps what arrived.
+fn dechunk(mut rest: &[u8]) -> Vec<u8> {
+    let mut body = Vec::new();
+    loop {
+        let Some(line_end) = rest.windows(2).position(|w| w == b"\r\n") else {
+            return body;
+        };
+        let size = std::str::from_utf8(&rest[..line_end])
+            .ok()
+            .and_then(|line| line.split(';').next())
+            .and_then(|size| usize::from_str_radix(size.trim(), 16).ok());
+        let Some(size) = size.filter(|&size| size > 0) else {
+            return body;
+        };
+        rest = &rest[line_end + 2..];
+        if rest.len() < size {
+            body.extend_from_slice(rest);
+            return body;
+        }
+        body.extend_from_slice(&rest[..size]);
+        rest = rest.get(size + 2..).unwrap_or_default();
+    }
+}
+
+/// Whether `html` has an `href` of exactly `target`.
+fn links_to(html: &str, target: &str) -> bool {
+    // Lowercasing ASCII keeps every byte where it was.
+    let lower = html.to_ascii_lowercase();
+    let mut from = 0;
+    while let Some(at) = lower[from..].find("href") {
+        from += at + "href".len();
+        let Some(rest) = html[from..].trim_start().strip_prefix('=') else {
+            continue;
+        };
+        let rest = rest.trim_start();
+        let value = match rest.chars().next() {
+            Some(quote @ ('"' | '\'')) => rest[1..].split(quote).next(),
+            _ => rest.split(|c: char| c.is_whitespace() || c == '>').next(),
+        };
+        if value.is_some_and(|value| decode_entities(value) == target) {
+            return true;
+        }
+    }
+    false
+}
+
+/// The source's `<title>`, whitespace collapsed, cut to `MAX_TITLE`.
+fn title(html: &str) -> Option<String> {
+    let lower = html.to_ascii_lowercase();
+    let start = lower.find("<title")?;
+    let open_end = start + lower[start..].find('>')? + 1;
+    let close = open_end + lower[open_end..].find("</title")?;
+    let text = decode_entities(&html[open_end..close]);
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let title: String = words.join(" ").chars().take(MAX_TITLE).collect();
+    (!title.is_empty()).then_some(title)
+}
+
+fn decode_entities(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    const SITE: &str = "https://example.com";
+    const TARGET: &str = "https://example.com/blog/post/";
+
+    struct Fixture {
+        _site: TempDir,
+        inbox: TempDir,
+        cache: FileCache,
+        receiver: Receiver,
+        verifier: Verifier,
+    }
+
+    fn fixture() -> Fixture {
+        let site = TempDir::new().unwrap();
+        for (rel, contents) in [
+            ("blog/post/index.html", "post"),
+            ("drafts/wip/index.html", "draft"),
+            ("style.css", "body{}"),
+        ] {
+            let path = site.path().join(rel);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+        let cache = FileCache::load(site.path().to_str().unwrap());
+        let inbox = TempDir::new().unwrap();
+        let tls = rustls::ClientConfig::builder()
+            .with_root_certificates(rustls::RootCertStore::empty())
+            .with_no_client_auth();
+        let (receiver, verifier) = queue(SITE, inbox.path().to_path_buf(), tls);
+        Fixture {
+            _site: site,
+            inbox,
+            cache,
+            receiver,
+            verifier,
+        }
+    }
+
+    fn post(body: &str) -> Request {
+        Request::parse_h1(
+            "POST /.well-known/webmention HTTP/1.1\r\n\r\n",
+            "203.0.113.7:54321".parse().unwrap(),
+        )
+        .unwrap()
+        .with_body(body.as_bytes().to_vec())
+    }
+
+    fn form(source: &str, target: &str) -> String {
+        let encode = |s: &str| s.replace(':', "%3A").replace('/', "%2F");
+        format!("source={}&target={}", encode(source), encode(target))
+    }
+
+    /// A stand-in for the source's server: answers each connection with the
+    /// next of `responses`.
+    async fn stand_in(responses: Vec<String>) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            for response in responses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0u8; 1024];
+                while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                }
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        addr
+    }
+
+    fn inbox_files(f: &Fixture) -> Vec<serde_json::Value> {
+        fs::read_dir(f.inbox.path())
+            .unwrap()
+            .map(|entry| serde_json::from_slice(&fs::read(entry.unwrap().path()).unwrap()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn only_a_post_naming_a_page_of_this_site_is_queued() {
+        let mut f = fixture();
+        let source = "https://elsewhere.example/reply";
+        let status = |request: Request| f.receiver.receive(&request, &f.cache).status;
+
+        let get = Request::parse_h1(
+            "GET /.well-known/webmention HTTP/1.1\r\n\r\n",
+            "203.0.113.7:54321".parse().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(status(get), 405);
+        assert_eq!(status(post(&format!("source={}", source))), 400);
+        assert_eq!(status(post(&form("ftp://elsewhere.example/", TARGET))), 400);
+        assert_eq!(status(post(&form(TARGET, TARGET))), 400);
+        for target in [
+            "https://elsewhere.example/blog/post/",
+            "https://example.com.evil/blog/post/",
+            "https://example.com/style.css",
+            "https://example.com/drafts/wip/",
+            "https://example.com/missing/",
+        ] {
+            assert_eq!(status(post(&form(source, target))), 400, "{target}");
+        }
+        assert!(f.verifier.queue.try_recv().is_err());
+
+        assert_eq!(status(post(&form(source, TARGET))), 202);
+        let queued = f.verifier.queue.try_recv().unwrap();
+        assert_eq!(
+            (queued.source.as_str(), queued.target.as_str()),
+            (source, TARGET)
+        );
+    }
+
+    #[test]
+    fn a_full_queue_asks_senders_to_come_back() {
+        let f = fixture();
+        let request = post(&form("https://elsewhere.example/reply", TARGET));
+        for _ in 0..QUEUE {
+            assert_eq!(f.receiver.receive(&request, &f.cache).status, 202);
+        }
+        assert_eq!(f.receiver.receive(&request, &f.cache).status, 503);
+    }
+
+    #[tokio::test]
+    async fn a_source_that_links_to_the_target_is_kept_until_it_stops() {
+        let linking = format!(
+            "<html><head><title>A  reply &amp; more</title></head>\
+             <body><a class=u-in-reply-to HREF='{}'>post</a></body></html>",
+            TARGET
+        );
+        let chunked: String = format!(
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n{:x}\r\n{}\r\n0\r\n\r\n",
+            linking.len(),
+            linking
+        );
+        let source = stand_in(vec![
+            chunked,
+            "HTTP/1.1 301 Moved\r\nLocation: /moved\r\n\r\n".to_string(),
+            "HTTP/1.1 200 OK\r\n\r\n<p>Edited, no link any more.</p>".to_string(),
+        ])
+        .await;
+        let source = format!("http://{}/reply", source);
+
+        let mut f = fixture();
+        f.verifier.allow_local = true;
+        let mention = Mention {
+            source: source.clone(),
+            target: TARGET.to_string(),
+        };
+
+        assert_eq!(f.verifier.verify(&mention).await.unwrap(), "verified");
+        let kept = inbox_files(&f);
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0]["source"], source);
+        assert_eq!(kept[0]["target"], TARGET);
+        assert_eq!(kept[0]["title"], "A reply & more");
+
+        assert_eq!(
+            f.verifier.verify(&mention).await.unwrap(),
+            "source does not link to target"
+        );
+        assert!(inbox_files(&f).is_empty());
+    }
+
+    #[tokio::test]
+    async fn queued_mentions_are_verified_in_the_background() {
+        let page = format!("<a href=\"{}\">post</a>", TARGET);
+        let addr = stand_in(vec![format!("HTTP/1.1 200 OK\r\n\r\n{}", page)]).await;
+        let mut f = fixture();
+        f.verifier.allow_local = true;
+
+        let request = post(&form(&format!("http://{}/reply", addr), TARGET));
+        assert_eq!(f.receiver.receive(&request, &f.cache).status, 202);
+        // With the receiver gone the verifier stops once the queue is empty.
+        drop(f.receiver);
+        let verifier = f.verifier;
+        verifier.run().await;
+
+        let kept: Vec<_> = fs::read_dir(f.inbox.path()).unwrap().collect();
+        assert_eq!(kept.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn sources_on_private_addresses_are_not_fetched() {
+        let addr = stand_in(vec![]).await;
+        let f = fixture();
+        let mention = Mention {
+            source: format!("http://{}/reply", addr),
+            target: TARGET.to_string(),
+        };
+        let err = f.verifier.verify(&mention).await.unwrap_err();
+        assert!(err.to_string().contains("private address"));
+    }
+
+    #[test]
+    fn urls_are_split_for_fetching_and_redirects_resolved() {
+        let url = Url::parse("https://[::1]:8443?x=1#frag").unwrap();
+        assert_eq!((url.host.as_str(), url.port), ("::1", 8443));
+        assert_eq!(url.path, "/?x=1");
+        assert_eq!(url.authority(), "[::1]:8443");
+
+        let url = Url::parse("http://example.com/a/b").unwrap();
+        assert_eq!(url.authority(), "example.com");
+        assert_eq!(url.join("/c#x").unwrap().path, "/c");
+        assert!(url.join("//other.example/d").unwrap().host == "other.example");
+
+        for bad in [
+            "ftp://x/",
+            "https://",
+            "https://user@x/",
+            "https://x:port/",
+            "https://x/a b",
+        ] {
+            assert!(Url::parse(bad).is_none(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn only_an_exact_href_counts_as_a_link() {
+        let target = "https://example.com/p/?a=1&b=2";
+        assert!(links_to(
+            "<a href=\"https://example.com/p/?a=1&amp;b=2\">",
+            target
+        ));
+        assert!(links_to(
+            "<a href = https://example.com/p/?a=1&b=2>",
+            target
+        ));
+        assert!(!links_to("<a href=\"https://example.com/p/\">", target));
+        assert!(!links_to(&format!("<p>{}</p>", target), target));
+    }
+
+    #[test]
+    fn local_addresses_are_not_public() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "192.168.0.1",
+            "100.64.0.1",
+            "::1",
+            "fd00::1",
+            "::ffff:10.0.0.1",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{ip}");
+        }
+        assert!(is_public("93.184.215.14".parse().unwrap()));
+        assert!(is_public("2606:2800:21f:cb07::1".parse().unwrap()));
+    }
+}
@@ -2,10 +2,15 @@
 //! real clients over TCP, TLS and QUIC. These cover the wire format and the
 //! protocol dispatch, which the unit tests deliberately stub out.
 
-use std::{fs, net::SocketAddr, sync::Arc, time::Duration};
+use std::{
+    fs,
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 use bytes::Buf;
-use jatai::JataiBuilder;
+use jatai::{preview, JataiBuilder, LiveReload};
 use tempfile::TempDir;
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt},
@@ -15,6 +20,9 @@ use tokio::{
 
 const CERT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../example/cert.pem");
 const KEY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../example/key.pem");
+const CLIENT_CA: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../example/client-ca.pem");
+const CLIENT_CERT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../example/client.pem");
+const CLIENT_KEY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../example/client-key.pem");
 
 /// Every client operation is bounded, so a protocol-level stall fails the test
 /// instead of hanging the suite.
@@ -35,11 +43,14 @@ fn site() -> Vec<(&'static str, &'static [u8])> {
         ("404.html", b"<h1>not found</h1>"),
         ("style.css", b"body{margin:0}"),
         ("logo.png", &[0x89, b'P', b'N', b'G', 0x0d]),
+        ("private.html", b"<h1>private</h1>"),
+        ("drafts/wip/index.html", b"<h1>draft</h1>"),
+        ("activitypub/actor.json", br#"{"type":"Person"}"#),
     ]
 }
 
 struct TestServer {
-    _dir: TempDir,
+    dir: TempDir,
     http: SocketAddr,
     https: Option<SocketAddr>,
     quic: Option<SocketAddr>,
@@ -47,9 +58,28 @@ struct TestServer {
 
 impl TestServer {
     async fn start(tls: bool, h3: bool) -> Self {
+        Self::start_with(tls, h3, |builder| builder).await
+    }
+
+    /// TLS and h3, with `/private` reserved for clients holding a certificate
+    /// from the test client CA.
+    async fn with_client_auth() -> Self {
+        Self::start_with(true, true, |builder| {
+            builder.client_auth(CLIENT_CA, ["/private"])
+        })
+        .await
+    }
+
+    async fn start_with(
+        tls: bool,
+        h3: bool,
+        configure: impl FnOnce(JataiBuilder) -> JataiBuilder,
+    ) -> Self {
         let dir = TempDir::new().unwrap();
         for (name, contents) in site() {
-            fs::write(dir.path().join(name), contents).unwrap();
+            let path = dir.path().join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
         }
 
         let mut builder = JataiBuilder::new()
@@ -62,6 +92,7 @@ impl TestServer {
                 builder = builder.enable_h3();
             }
         }
+        builder = configure(builder);
 
         let server = builder.build().await.expect("server should bind");
         let addrs = server.tcp_addrs();
@@ -72,7 +103,7 @@ impl TestServer {
         tokio::spawn(server.run());
 
         Self {
-            _dir: dir,
+            dir,
             http,
             https,
             quic,
@@ -197,16 +228,61 @@ fn client_config(alpn: &[&[u8]]) -> rustls::ClientConfig {
     config
 }
 
+/// A client that presents the certificate in `cert_path` when asked.
+fn client_config_with_cert(
+    alpn: &[&[u8]],
+    cert_path: &str,
+    key_path: &str,
+) -> rustls::ClientConfig {
+    let chain = rustls_pemfile::certs(&mut std::io::BufReader::new(
+        fs::File::open(cert_path).unwrap(),
+    ))
+    .collect::<Result<Vec<_>, _>>()
+    .unwrap();
+    let key = rustls_pemfile::private_key(&mut std::io::BufReader::new(
+        fs::File::open(key_path).unwrap(),
+    ))
+    .unwrap()
+    .unwrap();
+
+    let mut config = rustls::ClientConfig::builder()
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(TrustTheTestCert))
+        .with_client_auth_cert(chain, key)
+        .unwrap();
+    config.alpn_protocols = alpn.iter().map(|p| p.to_vec()).collect();
+    config
+}
+
 async fn tls_connect(
     addr: SocketAddr,
     alpn: &[&[u8]],
 ) -> tokio_rustls::client::TlsStream<TcpStream> {
-    let connector = tokio_rustls::TlsConnector::from(Arc::new(client_config(alpn)));
+    tls_connect_with(addr, client_config(alpn))
+        .await
+        .expect("TLS handshake should succeed")
+}
+
+async fn tls_connect_with(
+    addr: SocketAddr,
+    config: rustls::ClientConfig,
+) -> std::io::Result<tokio_rustls::client::TlsStream<TcpStream>> {
+    let connector = tokio_rustls::TlsConnector::from(Arc::new(config));
     let tcp = TcpStream::connect(addr).await.unwrap();
     let name = rustls::pki_types::ServerName::try_from("localhost").unwrap();
-    bounded("tls handshake", connector.connect(name, tcp))
+    bounded("tls handshake", connector.connect(name, tcp)).await
+}
+
+/// One HTTP/1.1 request over an established TLS stream, read to the close.
+async fn tls_get(mut tls: tokio_rustls::client::TlsStream<TcpStream>, path: &str) -> Reply {
+    let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path);
+    tls.write_all(request.as_bytes()).await.unwrap();
+
+    let mut raw = Vec::new();
+    bounded("tls read", tls.read_to_end(&mut raw))
         .await
-        .expect("TLS handshake should succeed")
+        .unwrap();
+    Reply::parse(&raw)
 }
 
 // -- plaintext HTTP/1.1 -----------------------------------------------------
@@ -489,7 +565,18 @@ struct H2Reply {
 }
 
 async fn h2_get(addr: SocketAddr, path: &str, accept_gzip: bool) -> H2Reply {
-    let tls = tls_connect(addr, &[b"h2"]).await;
+    h2_get_with(client_config(&[b"h2"]), addr, path, accept_gzip).await
+}
+
+async fn h2_get_with(
+    config: rustls::ClientConfig,
+    addr: SocketAddr,
+    path: &str,
+    accept_gzip: bool,
+) -> H2Reply {
+    let tls = tls_connect_with(addr, config)
+        .await
+        .expect("TLS handshake should succeed");
     assert_eq!(
         tls.get_ref().1.alpn_protocol(),
         Some(&b"h2"[..]),
@@ -742,3 +829,458 @@ async fn the_client_address_reaches_the_request() {
     assert_eq!(local.ip().to_string(), "127.0.0.1");
     assert_ne!(local.port(), 0);
 }
+
+// -- draft previews ---------------------------------------------------------
+
+const PREVIEW_KEY: &[u8] = b"e2e preview key";
+
+fn preview_link(slug: &str) -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    preview::link(PREVIEW_KEY, slug, now.as_secs() + 600)
+}
+
+#[tokio::test]
+async fn a_draft_is_served_only_through_a_signed_preview_link() {
+    let server = TestServer::start_with(true, false, |b| b.preview_key(PREVIEW_KEY)).await;
+    let link = preview_link("wip");
+
+    let h1 = get(server.http, &link).await;
+    assert_eq!(h1.status_line(), "HTTP/1.1 200 OK");
+    assert_eq!(h1.body, b"<h1>draft</h1>");
+    assert_eq!(
+        h1.header("cache-control").as_deref(),
+        Some("private, no-store")
+    );
+
+    let h2 = h2_get(server.https(), &link, false).await;
+    assert_eq!(h2.parts.status, 200);
+    assert_eq!(h2.body, b"<h1>draft</h1>");
+
+    let unsigned = get(server.http, "/drafts/wip/").await;
+    assert_eq!(unsigned.status_line(), "HTTP/1.1 403 FORBIDDEN");
+    assert_eq!(
+        h2_get(server.https(), "/drafts/wip/", false)
+            .await
+            .parts
+            .status,
+        403
+    );
+}
+
+#[tokio::test]
+async fn a_query_string_does_not_change_which_page_is_served() {
+    let server = TestServer::plain().await;
+    let reply = get(server.http, "/about?utm_source=feed").await;
+    assert_eq!(reply.status_line(), "HTTP/1.1 200 OK");
+    assert_eq!(reply.body, b"<h1>about</h1>");
+}
+
+// -- live reload ------------------------------------------------------------
+
+/// Read from an open event stream until `needle` arrives.
+async fn read_until(stream: &mut TcpStream, needle: &str) -> String {
+    let mut seen = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !String::from_utf8_lossy(&seen).contains(needle) {
+        let n = bounded("event stream read", stream.read(&mut buf))
+            .await
+            .unwrap();
+        assert!(n > 0, "stream closed before {:?}", needle);
+        seen.extend_from_slice(&buf[..n]);
+    }
+    String::from_utf8(seen).unwrap()
+}
+
+#[tokio::test]
+async fn live_reload_serves_each_build_and_tells_open_pages() {
+    let live = LiveReload::new();
+    let server = TestServer::start_with(false, false, |b| b.live_reload(live.clone())).await;
+
+    let mut events = TcpStream::connect(server.http).await.unwrap();
+    events
+        .write_all(b"GET /__jatai/live HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .await
+        .unwrap();
+    let head = read_until(&mut events, "\r\n\r\n").await;
+    assert!(head.contains("Content-Type: text/event-stream\r\n"));
+
+    let home = get(server.http, "/").await;
+    assert!(String::from_utf8_lossy(&home.body).contains("/__jatai/live"));
+
+    fs::write(server.dir.path().join("new.html"), b"<h1>new</h1>").unwrap();
+    live.rebuilt();
+    read_until(&mut events, "data: reload\n\n").await;
+    assert_eq!(
+        get(server.http, "/new").await.status_line(),
+        "HTTP/1.1 200 OK"
+    );
+
+    live.failed("content/blog/post/index.md:4: unclosed note");
+    read_until(&mut events, "data: reload\n\n").await;
+    let broken = get(server.http, "/new").await;
+    assert_eq!(broken.status_line(), "HTTP/1.1 500 INTERNAL SERVER ERROR");
+    assert!(String::from_utf8_lossy(&broken.body).contains("index.md:4: unclosed note"));
+}
+
+#[tokio::test]
+async fn there_is_no_event_stream_without_live_reload() {
+    let server = TestServer::plain().await;
+    let reply = get(server.http, "/__jatai/live").await;
+    assert_eq!(reply.status_line(), "HTTP/1.1 404 NOT FOUND");
+    assert!(!String::from_utf8_lossy(&get(server.http, "/").await.body).contains("__jatai"));
+}
+
+// -- mutual TLS -------------------------------------------------------------
+
+#[tokio::test]
+async fn public_pages_need_no_client_certificate() {
+    let server = TestServer::with_client_auth().await;
+    let reply = tls_get(tls_connect(server.https(), &[b"http/1.1"]).await, "/about").await;
+    assert_eq!(reply.status_line(), "HTTP/1.1 200 OK");
+    assert_eq!(reply.body, b"<h1>about</h1>");
+}
+
+#[tokio::test]
+async fn a_protected_path_is_forbidden_without_a_client_certificate() {
+    let server = TestServer::with_client_auth().await;
+
+    let h1 = tls_get(
+        tls_connect(server.https(), &[b"http/1.1"]).await,
+        "/private",
+    )
+    .await;
+    assert_eq!(h1.status_line(), "HTTP/1.1 403 FORBIDDEN");
+    assert_eq!(h1.body, b"Forbidden");
+
+    let h2 = h2_get(server.https(), "/private", false).await;
+    assert_eq!(h2.parts.status, 403);
+
+    // Plaintext can never carry a certificate, so the path is closed there.
+    assert_eq!(
+        get(server.http, "/private").await.status_line(),
+        "HTTP/1.1 403 FORBIDDEN"
+    );
+}
+
+#[tokio::test]
+async fn a_protected_path_is_served_to_a_client_with_a_trusted_certificate() {
+    let server = TestServer::with_client_auth().await;
+
+    let config = client_config_with_cert(&[b"http/1.1"], CLIENT_CERT, CLIENT_KEY);
+    let tls = tls_connect_with(server.https(), config).await.unwrap();
+    let h1 = tls_get(tls, "/private").await;
+    assert_eq!(h1.status_line(), "HTTP/1.1 200 OK");
+    assert_eq!(h1.body, b"<h1>private</h1>");
+    assert_eq!(
+        h1.header("cache-control").as_deref(),
+        Some("private, no-store")
+    );
+
+    let config = client_config_with_cert(&[b"h2"], CLIENT_CERT, CLIENT_KEY);
+    let h2 = h2_get_with(config, server.https(), "/private", false).await;
+    assert_eq!(h2.parts.status, 200);
+    assert_eq!(h2.body, b"<h1>private</h1>");
+}
+
+#[tokio::test]
+async fn a_certificate_from_another_ca_fails_the_handshake() {
+    // The server certificate is self-signed, not issued by the client CA: a
+    // client presenting it is refused outright rather than treated as
+    // anonymous, so a forged identity never reaches the handler.
+    let server = TestServer::with_client_auth().await;
+    let config = client_config_with_cert(&[b"http/1.1"], CERT, KEY);
+
+    let refused = match tls_connect_with(server.https(), config).await {
+        Err(_) => true,
+        // TLS 1.3 finishes the client side before the server has judged the
+        // certificate; the refusal then arrives instead of a response.
+        Ok(mut tls) => {
+            let _ = tls
+                .write_all(b"GET /private HTTP/1.1\r\nHost: localhost\r\n\r\n")
+                .await;
+            let mut raw = Vec::new();
+            let read = bounded("tls read", tls.read_to_end(&mut raw)).await;
+            read.is_err() || raw.is_empty()
+        }
+    };
+    assert!(refused, "an untrusted client certificate must be refused");
+}
+
+#[tokio::test]
+async fn http3_carries_the_client_certificate_too() {
+    let server = TestServer::with_client_auth().await;
+    let addr = server.quic.expect("QUIC endpoint");
+
+    for (config, expected) in [
+        (client_config(&[b"h3"]), 403),
+        (
+            client_config_with_cert(&[b"h3"], CLIENT_CERT, CLIENT_KEY),
+            200,
+        ),
+    ] {
+        let mut endpoint = quinn::Endpoint::client("127.0.0.1:0".parse().unwrap()).unwrap();
+        endpoint.set_default_client_config(quinn::ClientConfig::new(Arc::new(
+            quinn::crypto::rustls::QuicClientConfig::try_from(config).unwrap(),
+        )));
+        let connection = bounded("quic connect", endpoint.connect(addr, "localhost").unwrap())
+            .await
+            .expect("QUIC handshake should succeed");
+
+        let (mut driver, mut send_request) = bounded(
+            "h3 handshake",
+            h3::client::new(h3_quinn::Connection::new(connection)),
+        )
+        .await
+        .unwrap();
+        let driving =
+            tokio::spawn(async move { std::future::poll_fn(|cx| driver.poll_close(cx)).await });
+
+        let request = http::Request::builder()
+            .uri("https://localhost/private")
+            .body(())
+            .unwrap();
+        let mut stream = bounded("h3 request", send_request.send_request(request))
+            .await
+            .unwrap();
+        stream.finish().await.unwrap();
+        let response = bounded("h3 response", stream.recv_response())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), expected);
+
+        drop(stream);
+        drop(send_request);
+        endpoint.wait_idle().await;
+        let _ = driving.await;
+    }
+}
+
+// -- Webmention and ActivityPub ---------------------------------------------
+
+const WEBMENTION: &str = "source=https%3A%2F%2Felsewhere.example%2Freply\
+                          &target=https%3A%2F%2Fexample.com%2Fabout";
+
+async fn with_webmentions(inbox: &TempDir) -> TestServer {
+    let inbox = inbox.path().to_str().unwrap().to_string();
+    TestServer::start_with(true, false, |builder| {
+        builder
+            .webmentions(inbox, "https://example.com")
+            .webmention_roots(CERT)
+    })
+    .await
+}
+
+#[tokio::test]
+async fn takes_webmentions_over_http1() {
+    let inbox = TempDir::new().unwrap();
+    let server = with_webmentions(&inbox).await;
+
+    let request = format!(
+        "POST /.well-known/webmention HTTP/1.1\r\nHost: localhost\r\n\
+         Content-Type: application/x-www-form-urlencoded\r\nContent-Length: {}\r\n\r\n{}",
+        WEBMENTION.len(),
+        WEBMENTION
+    );
+    let reply = Reply::parse(&tcp_exchange(server.http, &request).await);
+    assert_eq!(reply.status_line(), "HTTP/1.1 202 ACCEPTED");
+    assert_eq!(reply.body, b"Accepted");
+
+    let reply = get(server.http, "/.well-known/webmention").await;
+    assert_eq!(reply.status_line(), "HTTP/1.1 405 METHOD NOT ALLOWED");
+}
+
+#[tokio::test]
+async fn takes_webmentions_over_http2() {
+    let inbox = TempDir::new().unwrap();
+    let server = with_webmentions(&inbox).await;
+    let tls = tls_connect_with(server.https(), client_config(&[b"h2"]))
+        .await
+        .expect("TLS handshake should succeed");
+    let (send_request, connection) = bounded("h2 handshake", h2::client::handshake(tls))
+        .await
+        .unwrap();
+    tokio::spawn(async move {
+        let _ = connection.await;
+    });
+
+    let mut send_request = send_request.ready().await.unwrap();
+    let request = http::Request::builder()
+        .method("POST")
+        .uri("https://localhost/.well-known/webmention")
+        .header("content-type", "application/x-www-form-urlencoded")
+        .body(())
+        .unwrap();
+    let (response, mut body) = send_request.send_request(request, false).unwrap();
+    body.send_data(bytes::Bytes::from_static(WEBMENTION.as_bytes()), true)
+        .unwrap();
+    let response = bounded("h2 response", response).await.unwrap();
+    assert_eq!(response.status(), 202);
+}
+
+#[tokio::test]
+async fn there_is_no_webmention_endpoint_unless_configured() {
+    let server = TestServer::plain().await;
+    let request = format!(
+        "POST /.well-known/webmention HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}",
+        WEBMENTION.len(),
+        WEBMENTION
+    );
+    let reply = Reply::parse(&tcp_exchange(server.http, &request).await);
+    assert_eq!(reply.status_line(), "HTTP/1.1 404 NOT FOUND");
+}
+
+#[tokio::test]
+async fn serves_the_activitypub_actor_as_activity_json() {
+    let server = TestServer::plain().await;
+    let reply = get(server.http, "/activitypub/actor").await;
+    assert_eq!(reply.status_line(), "HTTP/1.1 200 OK");
+    assert_eq!(
+        reply.header("content-type").as_deref(),
+        Some("application/activity+json")
+    );
+    assert_eq!(reply.body, br#"{"type":"Person"}"#);
+}
+
+#[tokio::test]
+async fn records_every_honeypot_hit_with_its_protocol_and_user_agent() {
+    let logs = TempDir::new().unwrap();
+    let log = logs.path().join("honeypot.jsonl");
+    let path = log.to_str().unwrap().to_string();
+    let server = TestServer::start_with(true, false, |builder| builder.honeypot_log(path)).await;
+
+    let request = "GET /%252e%252e/etc/passwd HTTP/1.1\r\nHost: localhost\r\n\
+                   User-Agent: zgrab/0.x\r\n\r\n";
+    Reply::parse(&tcp_exchange(server.http, request).await);
+    h2_get(server.https(), "/config/.env", false).await;
+
+    // The hits are written in the background; give the writer a moment.
+    let hits = bounded("honeypot log", async {
+        loop {
+            let hits = jatai::intel::read(&log).unwrap();
+            if hits.len() == 2 {
+                return hits;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await;
+    let h1 = hits.iter().find(|h| h.trap == "passwd").unwrap();
+    assert_eq!(h1.raw_path, "/%252e%252e/etc/passwd");
+    assert_eq!(h1.path, "/../etc/passwd");
+    assert_eq!(h1.user_agent.as_deref(), Some("zgrab/0.x"));
+    assert_eq!(h1.protocol, "HTTP/1.1");
+    let h2 = hits.iter().find(|h| h.trap == "dotenv").unwrap();
+    assert_eq!(h2.protocol, "HTTP/2.0");
+    assert!(h2.peer.is_loopback());
+    assert_eq!(jatai::intel::sessions(&hits).len(), 1);
+}
+
+#[tokio::test]
+async fn each_catch_gets_fresh_canaries_and_the_log_keeps_them() {
+    let logs = TempDir::new().unwrap();
+    let log = logs.path().join("honeypot.jsonl");
+    let path = log.to_str().unwrap().to_string();
+    let server = TestServer::start_with(true, false, |builder| builder.honeypot_log(path)).await;
+
+    let request = "GET /.aws/credentials HTTP/1.1\r\nHost: localhost\r\n\r\n";
+    let first = Reply::parse(&tcp_exchange(server.http, request).await);
+    let second = Reply::parse(&tcp_exchange(server.http, request).await);
+    assert_eq!(first.status_line(), "HTTP/1.1 200 OK");
+    assert_ne!(first.body, second.body);
+
+    let hits = bounded("honeypot log", async {
+        loop {
+            let hits = jatai::intel::read(&log).unwrap();
+            if hits.len() == 2 {
+                return hits;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await;
+    let body = String::from_utf8(first.body).unwrap();
+    let (_, key_id) = hits
+        .iter()
+        .flat_map(|hit| &hit.canaries)
+        .find(|(slot, token)| slot == "aws_key_id" && body.contains(token.as_str()))
+        .expect("the key ID sent should be in the log");
+    assert!(key_id.starts_with("AKIA"));
+    assert!(jatai::intel::lookup(&hits, key_id).contains("trap        aws-credentials"));
+}
+
+#[tokio::test]
+async fn serves_the_traps_loaded_from_a_directory() {
+    let traps = TempDir::new().unwrap();
+    std::fs::write(
+        traps.path().join("traps.toml"),
+        "[[trap]]\nname = \"grafana\"\nmatch = [{ path = [\"api\", \"datasources\"] }]\n\
+         bait = \"datasources.json\"\n",
+    )
+    .unwrap();
+    std::fs::write(
+        traps.path().join("datasources.json"),
+        "[{\"name\": \"prod\", \"basicAuthPassword\": \"{{github_token}}\"}]\n",
+    )
+    .unwrap();
+    let dir = traps.path().to_str().unwrap().to_string();
+    let server = TestServer::start_with(true, false, |builder| builder.honeypot_traps(dir)).await;
+
+    let reply = h2_get(server.https(), "/API/%64atasources", false).await;
+    assert_eq!(reply.parts.status, 200);
+    assert_eq!(reply.parts.headers["content-type"], "application/json");
+    let body = String::from_utf8(reply.body).unwrap();
+    assert!(body.contains("\"basicAuthPassword\": \"ghp_"));
+
+    // The built-in traps are no longer set.
+    let reply = h2_get(server.https(), "/.env", false).await;
+    assert_eq!(reply.parts.status, 404);
+}
+
+#[tokio::test(start_paused = true)]
+async fn tarpits_bait_over_http3() {
+    let server = TestServer::start_with(true, true, |builder| {
+        builder.tarpit(Duration::from_secs(5), 4)
+    })
+    .await;
+    let addr = server.quic.expect("QUIC endpoint");
+
+    let mut endpoint = quinn::Endpoint::client("127.0.0.1:0".parse().unwrap()).unwrap();
+    let tls = client_config(&[b"h3"]);
+    endpoint.set_default_client_config(quinn::ClientConfig::new(Arc::new(
+        quinn::crypto::rustls::QuicClientConfig::try_from(tls).unwrap(),
+    )));
+    let connection = bounded("quic connect", endpoint.connect(addr, "localhost").unwrap())
+        .await
+        .expect("QUIC handshake should succeed");
+    let (mut driver, mut send_request) = bounded(
+        "h3 handshake",
+        h3::client::new(h3_quinn::Connection::new(connection)),
+    )
+    .await
+    .unwrap();
+    tokio::spawn(async move { std::future::poll_fn(|cx| driver.poll_close(cx)).await });
+
+    let started = tokio::time::Instant::now();
+    let request = http::Request::builder()
+        .uri("https://localhost/.git/config")
+        .body(())
+        .unwrap();
+    let mut stream = bounded("h3 request", send_request.send_request(request))
+        .await
+        .unwrap();
+    stream.finish().await.unwrap();
+    let response = bounded("h3 response", stream.recv_response())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(started.elapsed() < Duration::from_secs(1));
+
+    let mut body = Vec::new();
+    while let Some(chunk) = bounded("h3 body", stream.recv_data()).await.unwrap() {
+        body.extend_from_slice(chunk.chunk());
+    }
+    assert!(body.starts_with(b"[core]"));
+    let elapsed = started.elapsed();
+    assert!(elapsed >= Duration::from_secs(5), "{:?}", elapsed);
+    assert!(elapsed < Duration::from_secs(6), "{:?}", elapsed);
+}
@@ -0,0 +1,344 @@
+//! The index file, and ranking against it.
+//!
+//! Little-endian, lengths and counts as LEB128 varints, strings as a length
+//! and UTF-8:
+//!
+//! ```text
+//! "SRCH" version
+//! documents: count, then per document: path, html, title/headings/body length in terms
+//! terms: count, then per term (sorted): term, postings count, then per
+//!        posting: document number (delta from the previous), title/headings/body count
+//! ```
+//!
+//! Ranking is BM25F: a term counts for more in the title than in a heading,
+//! and more in a heading than in the body, and for less in a long post than
+//! a short one.
+
+use std::collections::BTreeMap;
+
+use crate::{terms, Document};
+
+const MAGIC: &[u8; 4] = b"SRCH";
+const VERSION: u8 = 1;
+
+/// How much one occurrence in each field is worth: title, headings, body.
+const WEIGHTS: [f32; FIELDS] = [4.0, 2.0, 1.0];
+const FIELDS: usize = 3;
+/// BM25's saturation and length normalization.
+const K1: f32 = 1.2;
+const B: f32 = 0.75;
+
+/// Where a post is found, and what to show for it.
+struct Entry {
+    path: String,
+    html: String,
+    /// Terms in each field.
+    lengths: [u32; FIELDS],
+}
+
+struct Posting {
+    doc: u32,
+    counts: [u32; FIELDS],
+}
+
+pub struct Index {
+    docs: Vec<Entry>,
+    terms: BTreeMap<String, Vec<Posting>>,
+    /// Mean of each field's length over every document.
+    average: [f32; FIELDS],
+}
+
+/// A post that matched a query.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hit<'a> {
+    pub path: &'a str,
+    pub html: &'a str,
+    pub score: f32,
+}
+
+/// The index file for `docs`.
+pub fn build(docs: &[Document]) -> Vec<u8> {
+    let mut postings: BTreeMap<String, Vec<Posting>> = BTreeMap::new();
+    let mut out = MAGIC.to_vec();
+    out.push(VERSION);
+    write_uint(&mut out, docs.len() as u64);
+
+    for (doc_number, doc) in docs.iter().enumerate() {
+        let fields = [
+            terms(&doc.title),
+            terms(&doc.headings.join("\n")),
+            terms(&doc.body),
+        ];
+        write_str(&mut out, &doc.path);
+        write_str(&mut out, &doc.html);
+        for field in &fields {
+            write_uint(&mut out, field.len() as u64);
+        }
+
+        for (i, field) in fields.iter().enumerate() {
+            for term in field {
+                let list = postings.entry(term.clone()).or_default();
+                if list.last().is_none_or(|p| p.doc != doc_number as u32) {
+                    list.push(Posting {
+                        doc: doc_number as u32,
+                        counts: [0; FIELDS],
+                    });
+                }
+                list.last_mut().expect("just pushed").counts[i] += 1;
+            }
+        }
+    }
+
+    write_uint(&mut out, postings.len() as u64);
+    for (term, list) in &postings {
+        write_str(&mut out, term);
+        write_uint(&mut out, list.len() as u64);
+        let mut previous = 0;
+        for posting in list {
+            write_uint(&mut out, u64::from(posting.doc - previous));
+            previous = posting.doc;
+            for count in posting.counts {
+                write_uint(&mut out, u64::from(count));
+            }
+        }
+    }
+    out
+}
+
+impl Index {
+    /// Reads an index [`build`] wrote.
+    pub fn parse(bytes: &[u8]) -> Result<Self, String> {
+        let mut r = Reader { bytes, at: 0 };
+        if r.take(4)? != MAGIC {
+            return Err("not a search index".to_string());
+        }
+        let version = r.take(1)?[0];
+        if version != VERSION {
+            return Err(format!(
+                "search index version {version}, expected {VERSION}"
+            ));
+        }
+
+        let mut docs = Vec::new();
+        for _ in 0..r.uint()? {
+            let path = r.str()?;
+            let html = r.str()?;
+            let mut lengths = [0; FIELDS];
+            for length in &mut lengths {
+                *length = r.u32()?;
+            }
+            docs.push(Entry {
+                path,
+                html,
+                lengths,
+            });
+        }
+
+        let mut terms = BTreeMap::new();
+        for _ in 0..r.uint()? {
+            let term = r.str()?;
+            let mut list = Vec::new();
+            let mut doc = 0u32;
+            for _ in 0..r.uint()? {
+                doc = doc
+                    .checked_add(r.u32()?)
+                    .filter(|&d| (d as usize) < docs.len())
+                    .ok_or("posting for a document not in the index")?;
+                let mut counts = [0; FIELDS];
+                for count in &mut counts {
+                    *count = r.u32()?;
+                }
+                list.push(Posting { doc, counts });
+            }
+            terms.insert(term, list);
+        }
+        if r.at != bytes.len() {
+            return Err("trailing bytes after the search index".to_string());
+        }
+
+        let mut average = [0.0; FIELDS];
+        for (i, mean) in average.iter_mut().enumerate() {
+            let total: f32 = docs.iter().map(|d| d.lengths[i] as f32).sum();
+            *mean = total / docs.len().max(1) as f32;
+        }
+        Ok(Index {
+            docs,
+            terms,
+            average,
+        })
+    }
+
+    /// How many posts are indexed.
+    pub fn len(&self) -> usize {
+        self.docs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.docs.is_empty()
+    }
+
+    /// The `limit` posts that best match `query`, best first; posts that
+    /// score the same keep the order they were indexed in.
+    pub fn search(&self, query: &str, limit: usize) -> Vec<Hit<'_>> {
+        let mut query = terms(query);
+        query.sort();
+        query.dedup();
+
+        let mut scores = vec![0.0f32; self.docs.len()];
+        let n = self.docs.len() as f32;
+        for term in &query {
+            let Some(list) = self.terms.get(term) else {
+                continue;
+            };
+            let df = list.len() as f32;
+            let idf = (1.0 + (n - df + 0.5) / (df + 0.5)).ln();
+            for posting in list {
+                let entry = &self.docs[posting.doc as usize];
+                let tf: f32 = (0..FIELDS)
+                    .map(|i| {
+                        let relative = if self.average[i] > 0.0 {
+                            entry.lengths[i] as f32 / self.average[i]
+                        } else {
+                            1.0
+                        };
+                        WEIGHTS[i] * posting.counts[i] as f32 / (1.0 - B + B * relative)
+                    })
+                    .sum();
+                scores[posting.doc as usize] += idf * tf / (K1 + tf);
+            }
+        }
+
+        let mut hits: Vec<Hit> = scores
+            .into_iter()
+            .zip(&self.docs)
+            .filter(|(score, _)| *score > 0.0)
+            .map(|(score, entry)| Hit {
+                path: &entry.path,
+                html: &entry.html,
+                score,
+            })
+            .collect();
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+        hits.truncate(limit);
+        hits
+    }
+}
+
+fn write_uint(out: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn write_str(out: &mut Vec<u8>, s: &str) {
+    write_uint(out, s.len() as u64);
+    out.extend_from_slice(s.as_bytes());
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, n: usize) -> Result<&[u8], String> {
+        let end = self
+            .at
+            .checked_add(n)
+            .filter(|&end| end <= self.bytes.len());
+        let end = end.ok_or("search index is truncated")?;
+        let taken = &self.bytes[self.at..end];
+        self.at = end;
+        Ok(taken)
+    }
+
+    fn uint(&mut self) -> Result<u64, String> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.take(1)?[0];
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err("search index has an overlong number".to_string())
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        u32::try_from(self.uint()?).map_err(|_| "search index number out of range".to_string())
+    }
+
+    fn str(&mut self) -> Result<String, String> {
+        let len = usize::try_from(self.uint()?).map_err(|_| "search index string too long")?;
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| "search index text is not UTF-8".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn doc(path: &str, title: &str, headings: &[&str], body: &str) -> Document {
+        Document {
+            path: path.to_string(),
+            title: title.to_string(),
+            headings: headings.iter().map(|h| h.to_string()).collect(),
+            body: body.to_string(),
+            html: format!("<li>{title}</li>"),
+        }
+    }
+
+    fn paths(index: &Index, query: &str) -> Vec<String> {
+        index
+            .search(query, 10)
+            .iter()
+            .map(|hit| hit.path.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn an_index_reads_back_what_was_written() {
+        let bytes = build(&[doc("/a/", "Fuzzing", &["Setup"], "Coverage guided.")]);
+        let index = Index::parse(&bytes).unwrap();
+        assert_eq!(index.len(), 1);
+        let hits = index.search("fuzzing coverage", 10);
+        assert_eq!(hits[0].path, "/a/");
+        assert_eq!(hits[0].html, "<li>Fuzzing</li>");
+    }
+
+    #[test]
+    fn damaged_files_are_refused() {
+        let bytes = build(&[doc("/a/", "A title", &[], "Body.")]);
+        assert!(Index::parse(b"JUNK").is_err());
+        assert!(Index::parse(&bytes[..bytes.len() - 1]).is_err());
+        let mut longer = bytes.clone();
+        longer.push(0);
+        assert!(Index::parse(&longer).is_err());
+    }
+
+    #[test]
+    fn a_query_of_only_common_words_or_unknown_ones_finds_nothing() {
+        let index = Index::parse(&build(&[doc("/a/", "The post", &[], "Words.")])).unwrap();
+        assert!(index.search("the and of", 10).is_empty());
+        assert!(index.search("zebra", 10).is_empty());
+        assert!(index.search("", 10).is_empty());
+    }
+
+    #[test]
+    fn limit_caps_the_hits() {
+        let docs: Vec<_> = (0..5)
+            .map(|i| doc(&format!("/{i}/"), "Rust", &[], "Rust."))
+            .collect();
+        let index = Index::parse(&build(&docs)).unwrap();
+        assert_eq!(index.search("rust", 3).len(), 3);
+        // Equal scores keep index order.
+        assert_eq!(paths(&index, "rust")[0], "/0/");
+    }
+}
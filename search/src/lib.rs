@@ -0,0 +1,124 @@
+//! Site search without client-side code.
+//!
+//! blog-gen cuts each post into terms at build time and writes them, with a
+//! result entry already rendered for each post, to one compact file
+//! ([`build`]); jatai loads it ([`Index::parse`]), answers `?q=` queries
+//! against it, and puts the hits into a page rendered around slots
+//! ([`fill`]). A query and a post meet only through [`terms`], which both
+//! sides share: lowercased words, common words dropped, the rest stemmed.
+
+mod index;
+mod stem;
+
+pub use index::{build, Hit, Index};
+
+/// Marks in the search page that [`fill`] replaces. Nothing a template
+/// escapes is in them, so they come through rendering as written.
+pub const QUERY_SLOT: &str = "{{query}}";
+pub const SUMMARY_SLOT: &str = "{{summary}}";
+pub const RESULTS_SLOT: &str = "{{results}}";
+
+/// A post as it goes into the index.
+pub struct Document {
+    /// Where the post is served.
+    pub path: String,
+    pub title: String,
+    pub headings: Vec<String>,
+    /// The text of the post, markup removed.
+    pub body: String,
+    /// Its entry in a list of results, as HTML.
+    pub html: String,
+}
+
+/// Words too common to tell posts apart.
+const STOP_WORDS: [&str; 96] = [
+    "a", "about", "after", "all", "also", "am", "an", "and", "any", "are", "as", "at", "be",
+    "because", "been", "before", "being", "but", "by", "can", "could", "did", "do", "does",
+    "doing", "for", "from", "had", "has", "have", "he", "her", "here", "him", "his", "how", "i",
+    "if", "in", "into", "is", "it", "its", "just", "me", "more", "most", "my", "no", "not", "now",
+    "of", "on", "one", "only", "or", "other", "our", "out", "over", "own", "she", "so", "some",
+    "such", "than", "that", "the", "their", "them", "then", "there", "these", "they", "this",
+    "those", "through", "to", "too", "up", "very", "was", "we", "were", "what", "when", "where",
+    "which", "while", "who", "why", "will", "with", "would", "you", "your",
+];
+
+/// The terms `text` is searched by, in order, repeats kept.
+pub fn terms(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .filter(|word| !STOP_WORDS.contains(&word.as_str()))
+        .filter(|word| word.chars().count() > 1 || word.chars().all(|c| c.is_numeric()))
+        .map(|word| stem::stem(&word))
+        .collect()
+}
+
+/// `page` with its slots filled for `query` and the `hits` found for it.
+/// Each slot is replaced where the page has it, never inside the query.
+pub fn fill(page: &str, query: &str, hits: &[Hit]) -> String {
+    let query = query.trim();
+    let summary = match (query.is_empty(), hits.len()) {
+        (true, _) => String::new(),
+        (false, 0) => "No posts match.".to_string(),
+        (false, 1) => "1 post matches.".to_string(),
+        (false, n) => format!("{n} posts match."),
+    };
+    let results: String = hits.iter().map(|hit| hit.html).collect();
+
+    let mut out = String::with_capacity(page.len() + results.len());
+    let mut rest = page;
+    loop {
+        let next = [
+            (QUERY_SLOT, escape_html(query)),
+            (SUMMARY_SLOT, summary.clone()),
+            (RESULTS_SLOT, results.clone()),
+        ]
+        .into_iter()
+        .filter_map(|(slot, value)| rest.find(slot).map(|at| (at, slot, value)))
+        .min_by_key(|(at, ..)| *at);
+        let Some((at, slot, value)) = next else {
+            out.push_str(rest);
+            return out;
+        };
+        out.push_str(&rest[..at]);
+        out.push_str(&value);
+        rest = &rest[at + slot.len()..];
+    }
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn terms_are_lowercased_stemmed_words_without_the_common_ones() {
+        assert_eq!(
+            terms("Jamming the Lightning Network's onion messages, BOLT 12!"),
+            ["jam", "lightn", "network", "onion", "messag", "bolt", "12"]
+        );
+    }
+
+    #[test]
+    fn slots_are_filled_once_and_the_query_is_escaped() {
+        let page = "<input value=\"{{query}}\"><p>{{summary}}</p><ol>{{results}}</ol>";
+        let hits = [Hit {
+            path: "/blog/a/",
+            html: "<li>A</li>",
+            score: 1.0,
+        }];
+        assert_eq!(
+            fill(page, "<b>{{results}}</b>", &hits),
+            "<input value=\"&lt;b&gt;{{results}}&lt;/b&gt;\"><p>1 post matches.</p>\
+             <ol><li>A</li></ol>"
+        );
+        assert_eq!(fill(page, "", &[]), "<input value=\"\"><p></p><ol></ol>");
+    }
+}
@@ -0,0 +1,258 @@
+//! The Porter stemmer (M. F. Porter, "An algorithm for suffix stripping",
+//! 1980), as in his reference implementation: "jamming", "jammed" and "jams"
+//! all become "jam". Words that aren't plain ASCII are left as they are.
+
+pub(crate) fn stem(word: &str) -> String {
+    if word.len() <= 2 || !word.is_ascii() {
+        return word.to_string();
+    }
+    let mut w = Word(word.as_bytes().to_vec());
+    w.step1ab();
+    w.step1c();
+    w.step2();
+    w.step3();
+    w.step4();
+    w.step5();
+    String::from_utf8(w.0).expect("stemming ASCII leaves ASCII")
+}
+
+struct Word(Vec<u8>);
+
+impl Word {
+    fn is_consonant(&self, i: usize) -> bool {
+        match self.0[i] {
+            b'a' | b'e' | b'i' | b'o' | b'u' => false,
+            b'y' => i == 0 || !self.is_consonant(i - 1),
+            _ => true,
+        }
+    }
+
+    /// How many vowel-consonant runs the first `len` letters have: the m of
+    /// Porter's [C](VC){m}[V].
+    fn measure(&self, len: usize) -> usize {
+        let mut m = 0;
+        let mut i = 0;
+        while i < len && self.is_consonant(i) {
+            i += 1;
+        }
+        loop {
+            while i < len && !self.is_consonant(i) {
+                i += 1;
+            }
+            if i == len {
+                return m;
+            }
+            while i < len && self.is_consonant(i) {
+                i += 1;
+            }
+            m += 1;
+        }
+    }
+
+    fn has_vowel(&self, len: usize) -> bool {
+        (0..len).any(|i| !self.is_consonant(i))
+    }
+
+    /// Whether the first `len` letters end in a doubled consonant.
+    fn ends_double(&self, len: usize) -> bool {
+        len >= 2 && self.0[len - 1] == self.0[len - 2] && self.is_consonant(len - 1)
+    }
+
+    /// Whether the first `len` letters end consonant-vowel-consonant, the
+    /// last not w, x or y: "hop", not "hoop" or "how".
+    fn ends_cvc(&self, len: usize) -> bool {
+        len >= 3
+            && self.is_consonant(len - 3)
+            && !self.is_consonant(len - 2)
+            && self.is_consonant(len - 1)
+            && !matches!(self.0[len - 1], b'w' | b'x' | b'y')
+    }
+
+    /// The length of what's left without `suffix`, if the word ends in it.
+    fn without(&self, suffix: &str) -> Option<usize> {
+        self.0
+            .ends_with(suffix.as_bytes())
+            .then(|| self.0.len() - suffix.len())
+    }
+
+    fn set_end(&mut self, len: usize, end: &str) {
+        self.0.truncate(len);
+        self.0.extend_from_slice(end.as_bytes());
+    }
+
+    /// Replaces the first of `rules`' suffixes the word ends in, when what's
+    /// left measures over `min_measure`. Only the first match is tried.
+    fn replace_first(&mut self, rules: &[(&str, &str)], min_measure: usize) {
+        for (suffix, replacement) in rules {
+            if let Some(len) = self.without(suffix) {
+                if self.measure(len) > min_measure {
+                    self.set_end(len, replacement);
+                }
+                return;
+            }
+        }
+    }
+
+    /// Plurals and -ed or -ing.
+    fn step1ab(&mut self) {
+        if self.0.ends_with(b"sses") || self.0.ends_with(b"ies") {
+            self.0.truncate(self.0.len() - 2);
+        } else if self.0.ends_with(b"s") && !self.0.ends_with(b"ss") {
+            self.0.pop();
+        }
+
+        if let Some(len) = self.without("eed") {
+            if self.measure(len) > 0 {
+                self.0.pop();
+            }
+            return;
+        }
+        let Some(len) = self.without("ed").or_else(|| self.without("ing")) else {
+            return;
+        };
+        if !self.has_vowel(len) {
+            return;
+        }
+        self.0.truncate(len);
+        if self.0.ends_with(b"at") || self.0.ends_with(b"bl") || self.0.ends_with(b"iz") {
+            self.0.push(b'e');
+        } else if self.ends_double(len) && !matches!(self.0[len - 1], b'l' | b's' | b'z') {
+            self.0.pop();
+        } else if self.measure(len) == 1 && self.ends_cvc(len) {
+            self.0.push(b'e');
+        }
+    }
+
+    /// A final y becomes i when there is a vowel before it.
+    fn step1c(&mut self) {
+        if let Some(len) = self.without("y") {
+            if self.has_vowel(len) {
+                self.0[len] = b'i';
+            }
+        }
+    }
+
+    /// Double suffixes down to single ones.
+    fn step2(&mut self) {
+        self.replace_first(
+            &[
+                ("ational", "ate"),
+                ("tional", "tion"),
+                ("enci", "ence"),
+                ("anci", "ance"),
+                ("izer", "ize"),
+                ("bli", "ble"),
+                ("alli", "al"),
+                ("entli", "ent"),
+                ("eli", "e"),
+                ("ousli", "ous"),
+                ("ization", "ize"),
+                ("ation", "ate"),
+                ("ator", "ate"),
+                ("alism", "al"),
+                ("iveness", "ive"),
+                ("fulness", "ful"),
+                ("ousness", "ous"),
+                ("aliti", "al"),
+                ("iviti", "ive"),
+                ("biliti", "ble"),
+                ("logi", "log"),
+            ],
+            0,
+        );
+    }
+
+    fn step3(&mut self) {
+        self.replace_first(
+            &[
+                ("icate", "ic"),
+                ("ative", ""),
+                ("alize", "al"),
+                ("iciti", "ic"),
+                ("ical", "ic"),
+                ("ful", ""),
+                ("ness", ""),
+            ],
+            0,
+        );
+    }
+
+    /// Single suffixes off words long enough to keep meaning without them.
+    fn step4(&mut self) {
+        const SUFFIXES: [&str; 19] = [
+            "al", "ance", "ence", "er", "ic", "able", "ible", "ant", "ement", "ment", "ent", "ion",
+            "ou", "ism", "ate", "iti", "ous", "ive", "ize",
+        ];
+        for suffix in SUFFIXES {
+            let Some(len) = self.without(suffix) else {
+                continue;
+            };
+            if suffix == "ion" && (len == 0 || !matches!(self.0[len - 1], b's' | b't')) {
+                continue;
+            }
+            if self.measure(len) > 1 {
+                self.0.truncate(len);
+            }
+            return;
+        }
+    }
+
+    /// A final e, and a final double l.
+    fn step5(&mut self) {
+        if let Some(len) = self.without("e") {
+            let m = self.measure(len);
+            if m > 1 || (m == 1 && !self.ends_cvc(len)) {
+                self.0.pop();
+            }
+        }
+        let len = self.0.len();
+        if self.0.ends_with(b"ll") && self.measure(len) > 1 {
+            self.0.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_the_reference_vocabulary() {
+        // From the sample vocabulary published with the algorithm.
+        for (word, stemmed) in [
+            ("caresses", "caress"),
+            ("ponies", "poni"),
+            ("cats", "cat"),
+            ("feed", "feed"),
+            ("agreed", "agre"),
+            ("plastered", "plaster"),
+            ("motoring", "motor"),
+            ("sing", "sing"),
+            ("conflated", "conflat"),
+            ("hopping", "hop"),
+            ("falling", "fall"),
+            ("filing", "file"),
+            ("happy", "happi"),
+            ("relational", "relat"),
+            ("conditional", "condit"),
+            ("generalization", "gener"),
+            ("electricity", "electr"),
+            ("hopefulness", "hope"),
+            ("revival", "reviv"),
+            ("adjustment", "adjust"),
+            ("adoption", "adopt"),
+            ("controll", "control"),
+            ("rate", "rate"),
+            ("cease", "ceas"),
+        ] {
+            assert_eq!(stem(word), stemmed, "{word}");
+        }
+    }
+
+    #[test]
+    fn inflections_share_a_stem() {
+        for word in ["jam", "jams", "jammed", "jamming"] {
+            assert_eq!(stem(word), "jam", "{word}");
+        }
+    }
+}
@@ -1,10 +1,113 @@
-use jatai::{Config, JataiBuilder};
+use std::{
+    path::Path,
+    process::ExitCode,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use jatai::{intel, Config, JataiBuilder};
+
+const USAGE: &str = "usage: portfolio
+       portfolio honeypot [--log <path>] [--since <n>(m|h|d)] [--top <n>]
+       portfolio honeypot lookup <token> [--log <path>]";
+
+/// How far back `honeypot` looks when `--since` is not given.
+const DEFAULT_WINDOW_SECS: u64 = 7 * 24 * 60 * 60;
 
 #[tokio::main]
-async fn main() {
+async fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        None => {}
+        Some("honeypot") => {
+            return match honeypot(&args[1..]) {
+                Ok(report) => {
+                    print!("{report}");
+                    ExitCode::SUCCESS
+                }
+                Err(e) => {
+                    eprintln!("portfolio: {e}");
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        Some(_) => {
+            eprintln!("{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    }
+
     let config = Config::from_env();
     let builder = JataiBuilder::from(config);
     let server = builder.build().await.expect("Failed to build server");
 
     server.run().await;
+    ExitCode::SUCCESS
+}
+
+/// Summarises the honeypot log the server keeps at `HONEYPOT_LOG` (from the
+/// environment or `.env`), or at `--log`; or, given `lookup <token>`, finds
+/// the request a canary token was sent to.
+fn honeypot(args: &[String]) -> Result<String, String> {
+    if args.first().map(String::as_str) == Some("lookup") {
+        let token = args.get(1).ok_or(USAGE)?;
+        let log = match &args[2..] {
+            [] => None,
+            [flag, value] if flag == "--log" => Some(value.clone()),
+            _ => return Err(USAGE.to_string()),
+        };
+        let hits = read_log(log)?;
+        return Ok(intel::lookup(&hits, token));
+    }
+
+    let mut log = None;
+    let mut window = DEFAULT_WINDOW_SECS;
+    let mut top = intel::TOP;
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        let value = args.next().ok_or(USAGE)?;
+        match flag.as_str() {
+            "--log" => log = Some(value.clone()),
+            "--since" => window = parse_window(value)?,
+            "--top" => top = value.parse().map_err(|_| USAGE)?,
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+
+    let hits = read_log(log)?;
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| e.to_string())?
+        .as_secs();
+    Ok(intel::report(&hits, now.saturating_sub(window), top))
+}
+
+/// The hits in the log at `log`, or at `HONEYPOT_LOG` if not given.
+fn read_log(log: Option<String>) -> Result<Vec<intel::Hit>, String> {
+    let log = match log {
+        Some(log) => log,
+        None => {
+            dotenvy::dotenv().ok();
+            std::env::var("HONEYPOT_LOG")
+                .ok()
+                .filter(|p| !p.is_empty())
+                .ok_or("HONEYPOT_LOG environment variable not set; pass --log")?
+        }
+    };
+    intel::read(Path::new(&log)).map_err(|e| format!("read {log}: {e}"))
+}
+
+/// `30m`, `12h`, `7d`: how far back to look, in seconds.
+fn parse_window(s: &str) -> Result<u64, String> {
+    let invalid = || format!("invalid --since {s:?}: expected a number followed by m, h or d");
+    let split = s.len().checked_sub(1).ok_or_else(invalid)?;
+    let (n, unit) = s.split_at_checked(split).ok_or_else(invalid)?;
+    let n: u64 = n.parse().map_err(|_| invalid())?;
+    let scale = match unit {
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+    n.checked_mul(scale).filter(|&t| t > 0).ok_or_else(invalid)
 }